@@ -0,0 +1,1039 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use git2::{Oid, Repository};
+
+use crate::blame::{self, Backend};
+use crate::cache::{Cache, Lookups};
+use crate::date;
+use crate::error::Error;
+use crate::git::{self, output};
+use crate::mailmap::Mailmap;
+use crate::platform;
+use crate::regex::Regex;
+use crate::trace::{self, Level};
+use crate::ownership::{normalize_email, normalize_name, owner_key};
+use crate::{FileReport, RepoReport};
+
+/// How files are analyzed
+#[derive(Clone)]
+pub struct Options {
+    pub blame: blame::Options,
+    pub backend: Backend,
+    /// Read and update the blame cache in `.git/whoknows-cache`
+    pub cache: bool,
+    /// Keep the entries of the cache here instead, shared with other
+    /// clones and worktrees of the project
+    pub cache_dir: Option<PathBuf>,
+    /// Merge identities through the `.mailmap` of the work tree
+    pub mailmap: bool,
+    /// Further identity merges, applied after `.mailmap`
+    pub identities: Mailmap,
+    /// Leave out lines attributed to merge commits, which only get any
+    /// when the merge changed them itself, resolving conflicts for instance
+    pub no_merges: bool,
+    /// Leave out authors whose name or email matches any of these, after
+    /// identities have been merged
+    pub exclude_authors: Vec<Regex>,
+    /// Keep only authors with an email at one of these domains or below
+    /// them, unless empty
+    pub only_domains: Vec<String>,
+    /// Leave out authors with fewer commits than this in the whole history
+    pub min_commits: Option<usize>,
+    /// Half-life in days with which old lines count less towards the score
+    pub decay: Option<f64>,
+    /// Only count lines of commits authored at or after this time, in
+    /// seconds since the epoch
+    pub since: Option<i64>,
+    /// Only count lines of commits authored at or before this time
+    pub until: Option<i64>,
+    /// Files analyzed at the same time, defaults to the number of cores
+    pub jobs: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            blame: blame::Options::default(),
+            backend: Backend::Libgit2,
+            cache: true,
+            cache_dir: None,
+            mailmap: true,
+            identities: Mailmap::default(),
+            no_merges: false,
+            exclude_authors: Vec::new(),
+            only_domains: Vec::new(),
+            min_commits: None,
+            decay: None,
+            since: None,
+            until: None,
+            jobs: None,
+        }
+    }
+}
+
+/// Analysis of a repository, configured step by step
+///
+/// ```no_run
+/// # fn main() -> Result<(), whoknows::Error> {
+/// let repo = git2::Repository::open(".")?;
+/// let report = whoknows::Analyzer::new(&repo)
+///     .rev("v1.0")
+///     .decay(180.0)
+///     .repo()?;
+/// println!("bus factor {}", report.bus_factor());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Analyzer<'r> {
+    repo: &'r Repository,
+    opts: Options,
+}
+
+impl<'r> Analyzer<'r> {
+    pub fn new (repo: &'r Repository) -> Analyzer<'r>
+    {
+        Analyzer {
+            repo,
+            opts: Options::default(),
+        }
+    }
+
+    pub fn options (mut self, opts: Options) -> Analyzer<'r>
+    {
+        self.opts = opts;
+        self
+    }
+
+    /// Blame as of `rev` instead of HEAD
+    pub fn rev (mut self, rev: &str) -> Analyzer<'r>
+    {
+        self.opts.blame.rev = Some(rev.to_string());
+        self
+    }
+
+    /// Attribute the changes of the commits listed in `file` to their
+    /// parents, as `git blame --ignore-revs-file`; needs the git backend
+    pub fn ignore_revs (mut self, file: &Path) -> Analyzer<'r>
+    {
+        self.opts.blame.ignore_revs = Some(file.to_path_buf());
+        self
+    }
+
+    /// Halve the weight of a line every `half_life` days of age
+    pub fn decay (mut self, half_life: f64) -> Analyzer<'r>
+    {
+        self.opts.decay = Some(half_life);
+        self
+    }
+
+    /// Leave out lines attributed to merge commits
+    pub fn no_merges (mut self, no_merges: bool) -> Analyzer<'r>
+    {
+        self.opts.no_merges = no_merges;
+        self
+    }
+
+    /// Leave out authors whose name or email matches `pattern`, bots for
+    /// instance
+    pub fn exclude_author (mut self, pattern: Regex) -> Analyzer<'r>
+    {
+        self.opts.exclude_authors.push(pattern);
+        self
+    }
+
+    /// Keep only authors with an email at `domain` or a subdomain of it;
+    /// may be given several times
+    pub fn only_domain (mut self, domain: &str) -> Analyzer<'r>
+    {
+        self.opts.only_domains.push(domain.to_string());
+        self
+    }
+
+    /// Leave out authors with fewer than `commits` commits in the history
+    /// of the revision that is blamed, drive-by contributors for instance
+    pub fn min_commits (mut self, commits: usize) -> Analyzer<'r>
+    {
+        self.opts.min_commits = Some(commits);
+        self
+    }
+
+    /// Leave out lines of commits authored before `time`
+    pub fn since (mut self, time: i64) -> Analyzer<'r>
+    {
+        self.opts.since = Some(time);
+        self
+    }
+
+    /// Leave out lines of commits authored after `time`
+    pub fn until (mut self, time: i64) -> Analyzer<'r>
+    {
+        self.opts.until = Some(time);
+        self
+    }
+
+    pub fn mailmap (mut self, mailmap: bool) -> Analyzer<'r>
+    {
+        self.opts.mailmap = mailmap;
+        self
+    }
+
+    pub fn backend (mut self, backend: Backend) -> Analyzer<'r>
+    {
+        self.opts.backend = backend;
+        self
+    }
+
+    pub fn moves (mut self, moves: bool) -> Analyzer<'r>
+    {
+        self.opts.blame.moves = moves;
+        self
+    }
+
+    /// Look for copied lines as hard as `level` times `-C`, see
+    /// `blame::Options::copies`
+    pub fn copies (mut self, level: u8) -> Analyzer<'r>
+    {
+        self.opts.blame.copies = level.min(3);
+        self
+    }
+
+    pub fn first_parent (mut self, first_parent: bool) -> Analyzer<'r>
+    {
+        self.opts.blame.first_parent = first_parent;
+        self
+    }
+
+    pub fn no_follow (mut self, no_follow: bool) -> Analyzer<'r>
+    {
+        self.opts.blame.no_follow = no_follow;
+        self
+    }
+
+    pub fn cache (mut self, cache: bool) -> Analyzer<'r>
+    {
+        self.opts.cache = cache;
+        self
+    }
+
+    /// Share the entries of the cache in `dir`
+    pub fn cache_dir (mut self, dir: &Path) -> Analyzer<'r>
+    {
+        self.opts.cache_dir = Some(dir.to_path_buf());
+        self
+    }
+
+    /// Analyze at most `jobs` files at a time
+    pub fn jobs (mut self, jobs: usize) -> Analyzer<'r>
+    {
+        self.opts.jobs = Some(jobs);
+        self
+    }
+
+    /// Ownership of one file, given relative to the work tree
+    pub fn file (&self, path: &Path) -> Result<FileReport, Error>
+    {
+        analyze_file(self.repo, path, &self.opts)
+    }
+
+    /// Ownership of `paths`, telling `observer` about the progress
+    pub fn files (&self, paths: &[PathBuf], observer: &dyn Observer) -> Result<RepoReport, Error>
+    {
+        let files = analyze_files(self.repo, paths, &self.opts, observer)?;
+        Ok(RepoReport {
+            revision: self.revision(),
+            partial: files.len() < paths.len(),
+            files,
+        })
+    }
+
+    /// Ownership of every tracked file
+    pub fn repo (&self) -> Result<RepoReport, Error>
+    {
+        let workdir = self.repo.workdir()
+            .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+        let paths = tracked_files(self.repo, &[workdir.to_path_buf()])?;
+        self.files(&paths, &Silent)
+    }
+
+    /// Ownership of every tracked file, one report at a time as they are
+    /// finished, see `analyze_files_iter`
+    pub fn repo_iter (&self) -> Result<FileReports, Error>
+    {
+        analyze_repo_iter(self.repo, &self.opts)
+    }
+
+    /// The commit that gets blamed
+    fn revision (&self) -> Option<Oid>
+    {
+        let rev = self.opts.blame.rev.as_deref().unwrap_or("HEAD");
+        self.repo.revparse_single(rev).ok()?.peel_to_commit().ok().map(|c| c.id())
+    }
+}
+
+/// Told about the progress of a multi-file analysis, from any worker thread
+pub trait Observer: Sync {
+    fn started(&self, _path: &Path) {}
+    fn finished(&self, _path: &Path) {}
+
+    /// A file was analyzed successfully, called before `finished`
+    fn completed(&self, _report: &FileReport) {}
+
+    /// Analyzing a file failed, called before `finished`
+    fn failed(&self, _path: &Path, _error: &Error) {}
+
+    /// The blame of a file was looked up in the cache and found there or not
+    fn cached(&self, _path: &Path, _hit: bool) {}
+
+    /// Whether the analysis should stop early with what it has
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// An observer that ignores everything
+pub struct Silent;
+
+impl Observer for Silent {}
+
+/// The repository `start` is in, as git finds it: `GIT_DIR` and
+/// `GIT_WORK_TREE` take precedence when set, and a linked worktree is
+/// found through its `.git` file like any other
+pub fn discover (start: &Path) -> Result<Repository, git2::Error>
+{
+    let repo = match std::env::var_os("GIT_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Repository::open(dir)?,
+        None => Repository::discover(start)?,
+    };
+    if let Some(workdir) = std::env::var_os("GIT_WORK_TREE").filter(|dir| !dir.is_empty()) {
+        repo.set_workdir(Path::new(&workdir), false)?;
+    }
+    Ok(repo)
+}
+
+/// Open the repository at `git_dir` again, for another thread, with the
+/// work tree of the first handle even where that is not the one the git
+/// directory implies
+pub fn reopen (git_dir: &Path, workdir: Option<&Path>) -> Result<Repository, git2::Error>
+{
+    let repo = Repository::open(git_dir)?;
+    if let Some(workdir) = workdir {
+        if repo.workdir() != Some(workdir) {
+            repo.set_workdir(workdir, false)?;
+        }
+    }
+    Ok(repo)
+}
+
+/// Path of `path` relative to the work tree of `repo`
+pub fn repo_relative (repo: &Repository, path: &Path) -> Result<PathBuf, Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+    let workdir = platform::canonicalize(workdir)?;
+    let path = platform::canonicalize(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Error::Path(format!("{} does not exist", path.display())),
+        _ => e.into(),
+    })?;
+    match path.strip_prefix(&workdir) {
+        Ok(relative) => Ok(relative.to_path_buf()),
+        Err(_) => {
+            let mut message = format!("{} is outside repository at {}", path.display(), workdir.display());
+            // Paths of one run have to be in one repository
+            if let Some(other) = Repository::discover(&path).ok().as_ref().and_then(Repository::workdir) {
+                message.push_str(&format!(", in the one at {}", other.components().as_path().display()));
+            }
+            Err(Error::Path(message))
+        }
+    }
+}
+
+/// Path of `path` relative to the work tree of `repo` like `repo_relative`,
+/// for a path that need not exist any more
+fn relative_to_workdir (repo: &Repository, path: &Path) -> Result<PathBuf, Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+    let workdir = platform::canonicalize(workdir)?;
+    let absolute = std::env::current_dir()?.join(path);
+    // Whatever of it still exists is resolved as by `repo_relative`
+    let mut missing = Vec::new();
+    let mut existing = absolute.as_path();
+    while !existing.exists() {
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return Err(Error::Path(format!("{} does not exist", path.display()))),
+        }
+    }
+    let mut resolved = platform::canonicalize(existing)?;
+    resolved.extend(missing.iter().rev());
+    resolved.strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .map_err(|_| Error::Path(format!("{} is outside repository at {}", path.display(), workdir.display())))
+}
+
+/// The path in the tree of `rev` of the file `path` names, which need not
+/// exist in the work tree any more: `path` itself if the tree has it, or
+/// else the path the file had there when it was renamed to `path` or from
+/// `path` later or earlier; `None` if there is no such file
+pub fn path_at (repo: &Repository, path: &Path, rev: &str) -> Result<Option<PathBuf>, Error>
+{
+    let relative = relative_to_workdir(repo, path)?;
+    let tree = repo.revparse_single(rev)?.peel_to_tree()?;
+    if tree.get_path(&relative).is_ok() {
+        return Ok(Some(relative));
+    }
+
+    // The newest commit with the path, from which the rename shows in a
+    // diff to `rev` in whichever direction it went
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+    let mut other = None;
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        if commit.tree()?.get_path(&relative).is_ok() {
+            other = Some(commit.tree()?);
+            break;
+        }
+    }
+    let other = match other {
+        Some(other) => other,
+        None => return Ok(None),
+    };
+    let mut diff = repo.diff_tree_to_tree(Some(&other), Some(&tree), None)?;
+    diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))?;
+    let renamed = diff.deltas()
+        .find(|delta| delta.status() == git2::Delta::Renamed && delta.old_file().path() == Some(&relative))
+        .and_then(|delta| delta.new_file().path().map(Path::to_path_buf));
+    Ok(renamed)
+}
+
+/// Fail with [`Error::Untracked`] unless `path`, relative to the work tree,
+/// is in the index, as for `git ls-files --error-unmatch`
+pub fn ensure_tracked (repo: &Repository, path: &Path) -> Result<(), Error>
+{
+    match repo.index()?.get_path(path, 0) {
+        Some(_) => Ok(()),
+        None => Err(Error::Untracked(path.display().to_string())),
+    }
+}
+
+/// Expand the requested paths into the repository relative paths of the
+/// tracked files they name, in index order
+///
+/// The index is sorted by path, so the files below a directory are found
+/// by a binary search for the first of them rather than by going through
+/// every entry, and each file is listed once however many of the paths
+/// name it.
+pub fn tracked_files (repo: &Repository, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error>
+{
+    let index = repo.index()?;
+    let mut files = Vec::new();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for path in paths
+    {
+        let relative = repo_relative(repo, path)?;
+        if !path.is_dir() {
+            if index.get_path(&relative, 0).is_none() {
+                return Err(Error::Untracked(relative.display().to_string()));
+            }
+            if seen.insert(relative.clone()) {
+                files.push(relative);
+            }
+            continue;
+        }
+        // A submodule is a directory with an entry of its own
+        if index.get_path(&relative, 0).is_some() && seen.insert(relative.clone()) {
+            files.push(relative.clone());
+        }
+        let mut prefix = relative.to_string_lossy().replace('\\', "/").into_bytes();
+        if !prefix.is_empty() {
+            prefix.push(b'/');
+        }
+        for n in first_at_or_after(&index, &prefix)..index.len()
+        {
+            let entry = match index.get(n) {
+                Some(entry) if entry.path.starts_with(&prefix) => entry,
+                _ => break,
+            };
+            let entry_path = PathBuf::from(String::from_utf8_lossy(&entry.path).as_ref());
+            if seen.insert(entry_path.clone()) {
+                files.push(entry_path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The position of the first entry of `index` whose path sorts at or after
+/// `path`
+fn first_at_or_after (index: &git2::Index, path: &[u8]) -> usize
+{
+    let (mut low, mut high) = (0, index.len());
+    while low < high {
+        let middle = (low + high) / 2;
+        match index.get(middle) {
+            Some(entry) if entry.path.as_slice() < path => low = middle + 1,
+            _ => high = middle,
+        }
+    }
+    low
+}
+
+/// Ownership of one file, given relative to the work tree
+pub fn analyze_file (repo: &Repository, path: &Path, opts: &Options) -> Result<FileReport, Error>
+{
+    let shared = Shared::new(repo, opts)?;
+    let report = analyze_with(repo, path, opts, &shared, &Silent);
+    let _ = Cache::new(repo).add_stats(&shared.lookups);
+    report
+}
+
+/// Lines of a file that came from one commit
+pub struct Region {
+    /// First line, counting from 1
+    pub line: usize,
+    pub lines: usize,
+    pub commit: Oid,
+    pub name: String,
+    /// Normalized, as the keys of `FileReport::owners`
+    pub email: String,
+    /// Author time of the commit, seconds since the epoch
+    pub time: i64,
+}
+
+/// Where the lines of `path` came from, top to bottom, with identities
+/// merged as for `analyze_file`; nothing else of `opts` is applied and
+/// nothing is cached
+pub fn blame_regions (repo: &Repository, path: &Path, opts: &Options) -> Result<Vec<Region>, Error>
+{
+    let mailmap = load_mailmap(repo, opts)?;
+    let mut regions = Vec::new();
+    let mut line = 1;
+    // Both backends hand out hunks in the order of their lines
+    blame::blame(repo, path, opts.backend, &opts.blame, &mut |hunk| {
+        let (name, email) = mailmap.resolve(hunk.name, hunk.email);
+        regions.push(Region {
+            line,
+            lines: hunk.lines,
+            commit: hunk.commit,
+            name: normalize_name(name),
+            email: normalize_email(email).into_owned(),
+            time: hunk.time,
+        });
+        line += hunk.lines;
+    })?;
+    Ok(regions)
+}
+
+/// The text of `path` that blame with `opts` looks at: its contents if
+/// given, the file at `opts.blame.rev`, or in the work tree without one
+pub(crate) fn blamed_text (repo: &Repository, path: &Path, opts: &Options) -> Result<String, Error>
+{
+    if let Some(contents) = &opts.blame.contents {
+        return Ok(String::from_utf8_lossy(contents).into_owned());
+    }
+    match &opts.blame.rev {
+        Some(rev) => {
+            let tree = repo.revparse_single(rev)?.peel_to_tree()?;
+            let blob = tree.get_path(path)?.to_object(repo)?.peel_to_blob()?;
+            Ok(String::from_utf8_lossy(blob.content()).into_owned())
+        }
+        None => {
+            let workdir = repo.workdir()
+                .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+            Ok(fs::read_to_string(workdir.join(path))?)
+        }
+    }
+}
+
+/// The mailmap to apply: `.mailmap` unless disabled, then `opts.identities`
+pub fn load_mailmap (repo: &Repository, opts: &Options) -> Result<Mailmap, Error>
+{
+    let mut mailmap = match repo.workdir() {
+        Some(workdir) if opts.mailmap => Mailmap::from_file(&workdir.join(".mailmap"))?,
+        _ => Mailmap::default(),
+    };
+    mailmap.extend(&opts.identities);
+    Ok(mailmap)
+}
+
+/// Whether `email` is at `domain` or one of its subdomains
+fn in_domain (email: &str, domain: &str) -> bool
+{
+    let host = match email.rsplit_once('@') {
+        Some((_, host)) => host.to_ascii_lowercase(),
+        None => return false,
+    };
+    let domain = domain.trim_start_matches('@').to_ascii_lowercase();
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Which commits are merges, remembered across files and workers
+#[derive(Default)]
+struct Merges(Mutex<HashMap<Oid, bool>>);
+
+impl Merges {
+    /// Uncommitted lines, attributed to the zero id, are no merge
+    fn is_merge (&self, repo: &Repository, oid: Oid) -> bool
+    {
+        if let Some(merge) = self.0.lock().unwrap().get(&oid) {
+            return *merge;
+        }
+        let merge = repo.find_commit(oid).map(|c| c.parent_count() > 1).unwrap_or(false);
+        self.0.lock().unwrap().insert(oid, merge);
+        merge
+    }
+}
+
+/// Commits per author email in the history of the blamed revision, after
+/// merging identities
+fn commit_counts (repo: &Repository, opts: &Options, mailmap: &Mailmap) -> Result<HashMap<String, usize>, Error>
+{
+    let mut counts = HashMap::new();
+    let rev = opts.blame.rev.as_deref().unwrap_or("HEAD");
+    let start = match repo.revparse_single(rev) {
+        Ok(object) => object.peel_to_commit()?.id(),
+        // Nothing committed yet
+        Err(_) if opts.blame.rev.is_none() => return Ok(counts),
+        Err(e) => return Err(e.into()),
+    };
+    let mut walk = repo.revwalk()?;
+    walk.push(start)?;
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        let author = commit.author();
+        let name = String::from_utf8_lossy(author.name_bytes());
+        let email = String::from_utf8_lossy(author.email_bytes());
+        let (name, email) = mailmap.resolve(&name, &email);
+        *counts.entry(owner_key(name, email).into_owned()).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
+
+/// What the files of one analysis share, from any number of threads
+struct Shared {
+    mailmap: Mailmap,
+    lookups: Lookups,
+    merges: Merges,
+    /// Only looked up for `Options::min_commits`
+    commit_counts: HashMap<String, usize>,
+}
+
+impl Shared {
+    fn new (repo: &Repository, opts: &Options) -> Result<Shared, Error>
+    {
+        let mailmap = load_mailmap(repo, opts)?;
+        let commit_counts = match opts.min_commits {
+            Some(_) => commit_counts(repo, opts, &mailmap)?,
+            None => HashMap::new(),
+        };
+        Ok(Shared {
+            mailmap,
+            lookups: Lookups::default(),
+            merges: Merges::default(),
+            commit_counts,
+        })
+    }
+}
+
+/// Blame or load from the cache, then drop merges, apply the mailmap,
+/// author filters, the date window and decay, which are cheap enough to
+/// not be worth caching
+fn analyze_with (
+    repo: &Repository,
+    path: &Path,
+    opts: &Options,
+    shared: &Shared,
+    observer: &dyn Observer
+) -> Result<FileReport, Error>
+{
+    let mut report = blame_cached(repo, path, opts, &shared.lookups, observer)?;
+    let mut span = trace::span(Level::Debug, "aggregate");
+    span.record("path", path.display());
+    if opts.no_merges {
+        report.retain_commits(|oid, _| !shared.merges.is_merge(repo, *oid));
+    }
+    if !shared.mailmap.is_empty() {
+        report = report.remap(&shared.mailmap);
+    }
+    if !opts.exclude_authors.is_empty() {
+        report.retain_owners(|owner| !opts.exclude_authors.iter()
+            .any(|re| re.is_match(&owner.name) || re.is_match(&owner.email)));
+    }
+    if !opts.only_domains.is_empty() {
+        report.retain_owners(|owner| opts.only_domains.iter().any(|d| in_domain(&owner.email, d)));
+    }
+    if let Some(min) = opts.min_commits {
+        // Uncommitted lines belong to no commit of the history
+        report.retain_owners(|owner| shared.commit_counts.get(owner_key(&owner.name, &owner.email).as_ref()).is_some_and(|&n| n >= min));
+    }
+    if opts.since.is_some() || opts.until.is_some() {
+        report.window(opts.since, opts.until);
+    }
+    if let Some(half_life) = opts.decay {
+        report.decay(half_life, date::now());
+    }
+    span.record("owners", report.owners.len());
+    Ok(report)
+}
+
+fn blame_cached (
+    repo: &Repository,
+    path: &Path,
+    opts: &Options,
+    lookups: &Lookups,
+    observer: &dyn Observer
+) -> Result<FileReport, Error>
+{
+    let cache_key = if opts.cache { cache_key(repo, path, opts) } else { None };
+    let cache = Cache::open(repo, opts.cache_dir.as_deref());
+    // Held until the entry is stored
+    let _lock = match &cache_key {
+        Some(key) => {
+            let mut span = trace::span(Level::Trace, "cache");
+            span.record("path", path.display());
+            let mut report = cache.load(key, path);
+            // Another run may be blaming the same blob; wait for it and
+            // take its entry rather than blame the blob again
+            let lock = if report.is_none() { cache.lock(key) } else { None };
+            if lock.is_some() {
+                report = cache.load(key, path);
+            }
+            if let Some(report) = report {
+                span.record("result", "hit");
+                lookups.hit();
+                observer.cached(path, true);
+                return Ok(report);
+            }
+            span.record("result", "miss");
+            lookups.miss();
+            observer.cached(path, false);
+            lock
+        }
+        None => None,
+    };
+
+    let mut span = trace::span(Level::Debug, "blame");
+    span.record("path", path.display()).record("backend", opts.backend);
+    let mut report = FileReport::new(&path.display().to_string());
+    blame::blame(repo, path, opts.backend, &opts.blame, &mut |hunk| report.add_blamed(hunk))?;
+    drop(span);
+
+    if let Some(key) = &cache_key {
+        // A cache that cannot be written only costs time
+        let _ = cache.store(key, &report);
+    }
+    Ok(report)
+}
+
+/// Cache entry for `path`, unless what gets blamed is not a committed blob
+///
+/// Besides the blob, the key holds the path and the last commit to change
+/// the file as of the revision, which is where blame starts to look back
+/// from: an identical file elsewhere, or a blob brought back by a revert,
+/// has other owners. Commits that leave the file alone keep its entry.
+fn cache_key (repo: &Repository, path: &Path, opts: &Options) -> Option<String>
+{
+    if opts.blame.contents.is_some() {
+        return None;
+    }
+    let rev = opts.blame.rev.as_deref().unwrap_or("HEAD");
+    let commit = repo.revparse_single(rev).ok()?.peel_to_commit().ok()?.id();
+    let blob = repo.find_commit(commit).ok()?.tree().ok()?.get_path(path).ok()?.id();
+
+    // Without a revision git blame looks at the work tree, which may have
+    // moved on from HEAD
+    if opts.backend == Backend::Git && opts.blame.rev.is_none()
+        && repo.status_file(path).ok()? != git2::Status::CURRENT {
+        return None;
+    }
+    let changed = Oid::from_str(output(last_change(repo, path, commit, opts).ok()?).ok()?.trim()).ok()?;
+    Some(Cache::key(blob, changed, path, opts.backend, &opts.blame))
+}
+
+/// The `git rev-list` finding the last commit to change `path` as of
+/// `commit`, which its cache entry is named after
+fn last_change (repo: &Repository, path: &Path, commit: Oid, opts: &Options) -> Result<Command, Error>
+{
+    let commit = commit.to_string();
+    let mut args: Vec<&OsStr> = vec!["--literal-pathspecs".as_ref(), "rev-list".as_ref(), "-1".as_ref()];
+    if opts.blame.first_parent {
+        args.push("--first-parent".as_ref());
+    }
+    args.extend(&[commit.as_ref(), "--".as_ref(), path.as_os_str()]);
+    git::command(repo, &args)
+}
+
+/// The commands that analyzing `path` with `opts` runs, unless its cache
+/// entry has it: the one looking for the entry when the cache is used,
+/// then the blame, which is only an illustration with libgit2
+pub fn commands (repo: &Repository, path: &Path, opts: &Options) -> Result<Vec<Command>, Error>
+{
+    let mut commands = Vec::new();
+    if opts.cache && opts.blame.contents.is_none() {
+        let rev = opts.blame.rev.as_deref().unwrap_or("HEAD");
+        let commit = repo.revparse_single(rev)?.peel_to_commit()?.id();
+        commands.push(last_change(repo, path, commit, opts)?);
+    }
+    commands.push(blame::git_command(repo, path, &opts.blame)?);
+    Ok(commands)
+}
+
+/// Ownership of every tracked file in the work tree
+pub fn analyze_repo (repo: &Repository, opts: &Options) -> Result<Vec<FileReport>, Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+    let paths = tracked_files(repo, &[workdir.to_path_buf()])?;
+    analyze_files(repo, &paths, opts, &Silent)
+}
+
+/// Analyze `paths` on a pool of threads, one per core unless `opts.jobs`
+/// says otherwise, each with its own handle on the repository; reports come
+/// back in the order of `paths`
+///
+/// Once `observer` asks to cancel, no further files are started and the
+/// reports of the files finished so far are returned; files that fail
+/// because they were interrupted are left out.
+pub fn analyze_files (
+    repo: &Repository,
+    paths: &[PathBuf],
+    opts: &Options,
+    observer: &dyn Observer
+) -> Result<Vec<FileReport>, Error>
+{
+    let shared = Shared::new(repo, opts)?;
+    let analyze_one = |repo: &Repository, path: &Path| {
+        observer.started(path);
+        let result = analyze_with(repo, path, opts, &shared, observer)
+            .map_err(|e| Error::File(path.display().to_string(), Box::new(e)));
+        match &result {
+            Ok(report) => observer.completed(report),
+            Err(e) => observer.failed(path, e),
+        }
+        observer.finished(path);
+        result
+    };
+    let reports = run_pool(repo, paths, opts, observer, &analyze_one);
+    let _ = Cache::new(repo).add_stats(&shared.lookups);
+    reports
+}
+
+/// Ownership of every tracked file in the work tree, see
+/// `analyze_files_iter`
+pub fn analyze_repo_iter (repo: &Repository, opts: &Options) -> Result<FileReports, Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+    let paths = tracked_files(repo, &[workdir.to_path_buf()])?;
+    analyze_files_iter(repo, paths, opts)
+}
+
+/// Analyze `paths` on a pool of threads like `analyze_files`, handing out
+/// every report as soon as it is finished rather than all of them at the
+/// end, so in the order they are finished
+///
+/// Workers stay at most a report each ahead of whoever pulls them, so a
+/// slow consumer slows the analysis down instead of piling reports up.
+/// A file that fails is an error among the reports, after which the others
+/// keep coming; dropping the iterator stops the analysis.
+pub fn analyze_files_iter (repo: &Repository, paths: Vec<PathBuf>, opts: &Options) -> Result<FileReports, Error>
+{
+    let jobs = opts.jobs
+        .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+        .clamp(1, paths.len().max(1));
+    let pool = Arc::new(Pool {
+        git_dir: repo.path().to_path_buf(),
+        workdir: repo.workdir().map(Path::to_path_buf),
+        paths,
+        opts: opts.clone(),
+        shared: Shared::new(repo, opts)?,
+        next: AtomicUsize::new(0),
+        running: AtomicUsize::new(jobs),
+        stop: AtomicBool::new(false),
+    });
+    let (sender, receiver) = mpsc::sync_channel(jobs);
+    let workers = (0..jobs)
+        .map(|_| {
+            let (pool, sender) = (Arc::clone(&pool), sender.clone());
+            thread::spawn(move || pool.work(&sender))
+        })
+        .collect();
+    Ok(FileReports { receiver: Some(receiver), pool, workers })
+}
+
+/// What the workers of `analyze_files_iter` share
+struct Pool {
+    git_dir: PathBuf,
+    workdir: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+    opts: Options,
+    shared: Shared,
+    /// The index of the next path to analyze
+    next: AtomicUsize,
+    /// Workers yet to finish, the last of which records the cache lookups
+    running: AtomicUsize,
+    stop: AtomicBool,
+}
+
+impl Pool {
+    /// Analyze paths and send their reports until there are none left or
+    /// nobody listens any more
+    fn work (&self, sender: &SyncSender<Result<FileReport, Error>>)
+    {
+        let repo = match reopen(&self.git_dir, self.workdir.as_deref()) {
+            Ok(repo) => Some(repo),
+            Err(e) => {
+                let _ = sender.send(Err(e.into()));
+                None
+            }
+        };
+        while let Some(repo) = &repo {
+            let i = self.next.fetch_add(1, Ordering::SeqCst);
+            if i >= self.paths.len() || self.stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let path = &self.paths[i];
+            let result = analyze_with(repo, path, &self.opts, &self.shared, &Silent)
+                .map_err(|e| Error::File(path.display().to_string(), Box::new(e)));
+            if sender.send(result).is_err() {
+                break;
+            }
+        }
+        if self.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            if let Some(repo) = &repo {
+                let _ = Cache::new(repo).add_stats(&self.shared.lookups);
+            }
+        }
+    }
+}
+
+/// The reports of an analysis in progress, see `analyze_files_iter`
+pub struct FileReports {
+    receiver: Option<Receiver<Result<FileReport, Error>>>,
+    pool: Arc<Pool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl FileReports {
+    /// How many files the analysis covers
+    pub fn total (&self) -> usize
+    {
+        self.pool.paths.len()
+    }
+}
+
+impl Iterator for FileReports {
+    type Item = Result<FileReport, Error>;
+
+    fn next (&mut self) -> Option<Result<FileReport, Error>>
+    {
+        self.receiver.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for FileReports {
+    fn drop (&mut self)
+    {
+        self.pool.stop.store(true, Ordering::SeqCst);
+        // Workers waiting to send find nobody listening and stop
+        drop(self.receiver.take());
+        for worker in self.workers.drain(..)
+        {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Run `analyze_one` over `paths` on `opts.jobs` threads
+fn run_pool (
+    repo: &Repository,
+    paths: &[PathBuf],
+    opts: &Options,
+    observer: &dyn Observer,
+    analyze_one: &(dyn Fn(&Repository, &Path) -> Result<FileReport, Error> + Sync)
+) -> Result<Vec<FileReport>, Error>
+{
+    let jobs = opts.jobs
+        .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+        .min(paths.len());
+    if jobs <= 1 {
+        let mut reports = Vec::new();
+        for path in paths
+        {
+            if observer.cancelled() {
+                break;
+            }
+            match analyze_one(repo, path) {
+                Ok(report) => reports.push(report),
+                Err(_) if observer.cancelled() => break,
+                Err(e) => return Err(e),
+            }
+        }
+        return Ok(reports);
+    }
+
+    let (git_dir, workdir) = (repo.path(), repo.workdir());
+    let next = AtomicUsize::new(0);
+    // Set by the first file that fails, after which nothing more is blamed
+    let failed = AtomicBool::new(false);
+    let slots: Mutex<Vec<Option<Result<FileReport, Error>>>> =
+        Mutex::new(paths.iter().map(|_| None).collect());
+
+    thread::scope(|scope| -> Result<(), Error> {
+        let workers: Vec<_> = (0..jobs).map(|_| scope.spawn(|| -> Result<(), Error> {
+            let repo = reopen(git_dir, workdir)?;
+            loop {
+                if failed.load(Ordering::SeqCst) || observer.cancelled() {
+                    return Ok(());
+                }
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= paths.len() {
+                    return Ok(());
+                }
+                let result = analyze_one(&repo, &paths[i]);
+                if result.is_err() {
+                    failed.store(true, Ordering::SeqCst);
+                }
+                slots.lock().unwrap()[i] = Some(result);
+            }
+        })).collect();
+
+        for worker in workers
+        {
+            worker.join().expect("blame worker panicked")?;
+        }
+        Ok(())
+    })?;
+
+    let cancelled = observer.cancelled();
+    let mut reports = Vec::new();
+    let mut skipped = None;
+    for (path, slot) in paths.iter().zip(slots.into_inner().unwrap())
+    {
+        match slot {
+            Some(Ok(report)) => reports.push(report),
+            Some(Err(_)) | None if cancelled => {}
+            Some(Err(e)) => return Err(e),
+            None => skipped = skipped.or(Some(path)),
+        }
+    }
+    // Files are only skipped once one failed, and that one is returned above
+    if let Some(path) = skipped {
+        return Err(Error::Path(format!("{} was not analyzed", path.display())));
+    }
+    Ok(reports)
+}
@@ -0,0 +1,62 @@
+//! Reports that name nobody
+//!
+//! How concentrated the knowledge of a code base is can be shared outside
+//! the team without showing what each person did. Every author becomes a
+//! pseudonym hashed from their identity and a salt: the same in every run
+//! with the same salt, and not to be guessed from without it. Where names
+//! may be shown but addresses may not, emails can be redacted instead.
+
+use git2::{ObjectType, Oid};
+
+use crate::blame::Hunk;
+use crate::ownership::owner_key;
+use crate::FileReport;
+
+/// The pseudonym, as a name and an email, of the owner with `key`, see
+/// `owner_key`
+pub fn pseudonym (key: &str, salt: &str) -> (String, String)
+{
+    let text = format!("{}\0{}", salt, key);
+    let hash = Oid::hash_object(ObjectType::Blob, text.as_bytes())
+        .map(|oid| oid.to_string()[..10].to_string())
+        .unwrap_or_default();
+    (format!("author-{}", hash), format!("{}@anonymized.invalid", hash))
+}
+
+/// `file` with every owner replaced by their pseudonym, keeping their
+/// lines and score
+pub fn anonymize (file: &FileReport, salt: &str) -> FileReport
+{
+    let mut anonymous = FileReport::new(&file.path);
+    anonymous.renamed_from = file.renamed_from.clone();
+    anonymous.spans = file.spans.clone();
+    for (key, owner) in &file.owners
+    {
+        let (name, email) = pseudonym(key, salt);
+        for (commit, contribution) in &owner.commits
+        {
+            anonymous.add_hunk(&Hunk {
+                commit: *commit,
+                name: &name,
+                email: &email,
+                time: contribution.time,
+                lines: contribution.lines,
+                origin: None,
+            });
+        }
+        if let Some(pseudonymous) = anonymous.owners.get_mut(owner_key(&name, &email).as_ref()) {
+            pseudonymous.score = owner.score;
+        }
+    }
+    anonymous
+}
+
+/// `email` with everything before the domain masked
+pub fn redact_email (email: &str) -> String
+{
+    match email.rsplit_once('@') {
+        Some((_, domain)) => format!("***@{}", domain),
+        None if email.is_empty() => String::new(),
+        None => "***".to_string(),
+    }
+}
@@ -0,0 +1,934 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+#[cfg(feature = "git")]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(feature = "git")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "git")]
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(feature = "git")]
+use std::sync::OnceLock;
+#[cfg(feature = "git")]
+use std::thread;
+
+#[cfg(feature = "git")]
+use git2::{BlameOptions, Repository};
+
+use crate::error::Error;
+use crate::oid::Oid;
+#[cfg(feature = "git")]
+use crate::platform;
+#[cfg(feature = "git")]
+use crate::trace::{self, Level};
+use crate::FileReport;
+
+/// Lines blamed by one `git blame` unless `Options::chunk` says otherwise
+pub const CHUNK_LINES: usize = 50_000;
+
+/// A run of consecutive lines last changed by the same commit
+///
+/// The identity is borrowed from whoever produced the hunk, so handing out
+/// hunks does not allocate; `owned` makes one that can be kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hunk<'a> {
+    pub commit: Oid,
+    pub name: &'a str,
+    pub email: &'a str,
+    /// Author time of the commit, seconds since the epoch
+    pub time: i64,
+    pub lines: usize,
+    /// Path of the file in the commit, if the backend tells
+    pub origin: Option<&'a str>,
+}
+
+impl Hunk<'_> {
+    pub fn owned (&self) -> BlameHunk
+    {
+        BlameHunk {
+            commit: self.commit,
+            name: self.name.to_string(),
+            email: self.email.to_string(),
+            time: self.time,
+            lines: self.lines,
+            origin: self.origin.map(String::from),
+        }
+    }
+}
+
+/// A `Hunk` owning its identity and path
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlameHunk {
+    pub commit: Oid,
+    pub name: String,
+    pub email: String,
+    /// Author time of the commit, seconds since the epoch
+    pub time: i64,
+    pub lines: usize,
+    /// Path of the file in the commit, if the backend tells
+    pub origin: Option<String>,
+}
+
+impl BlameHunk {
+    pub fn hunk (&self) -> Hunk<'_>
+    {
+        Hunk {
+            commit: self.commit,
+            name: &self.name,
+            email: &self.email,
+            time: self.time,
+            lines: self.lines,
+            origin: self.origin.as_deref(),
+        }
+    }
+}
+
+/// How blame is computed
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// In process through libgit2
+    Libgit2,
+    /// By running `git blame --porcelain` and parsing its output
+    Git,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Backend, String> {
+        match s {
+            "libgit2" => Ok(Backend::Libgit2),
+            "git" => Ok(Backend::Git),
+            _ => Err(format!("unknown backend '{}'", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Backend::Libgit2 => "libgit2",
+            Backend::Git => "git",
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Options {
+    /// find line moves within and across files
+    pub moves: bool,
+    /// How hard to look for lines copied from other files, as many times
+    /// as `-C` is given to git blame: 1 for files changed in the same
+    /// commit, 2 also for files of the commit that created the file, 3 for
+    /// every file of every commit
+    pub copies: u8,
+    /// follow only the first parent commits
+    pub first_parent: bool,
+    /// Blame the file as of this revision instead of HEAD or the work tree
+    pub rev: Option<String>,
+    /// File listing commits whose changes are attributed to their parents
+    pub ignore_revs: Option<PathBuf>,
+    /// Attribute lines from before the file got its path to the commit that
+    /// gave it that path instead of following the file through renames
+    pub no_follow: bool,
+    /// With the git backend, blame longer files this many lines at a time,
+    /// so that neither git nor the parser holds what it knows about more;
+    /// `CHUNK_LINES` if not given, 0 for whole files
+    pub chunk: Option<usize>,
+    /// Blame these contents as the file instead of what is in the work
+    /// tree, such as the unsaved buffer of an editor; lines that differ
+    /// from the file at `rev`, or HEAD, are not committed yet. Needs the
+    /// git backend
+    pub contents: Option<Arc<[u8]>>,
+}
+
+/// Blame `path`, handing every hunk to `sink` as soon as it is known, in
+/// the order of their lines
+#[cfg(feature = "git")]
+pub fn blame (
+    repo: &Repository,
+    path: &Path,
+    backend: Backend,
+    opts: &Options,
+    sink: &mut dyn FnMut(&Hunk)
+) -> Result<(), Error>
+{
+    if !opts.no_follow {
+        return match backend {
+            Backend::Libgit2 => blame_libgit2(repo, path, opts, sink),
+            Backend::Git => blame_git(repo, path, opts, sink),
+        };
+    }
+
+    // Only looked up once lines from another path turn up
+    let mut created: Option<(Oid, String, String, i64)> = None;
+    let mut failed = None;
+    let current = path.to_string_lossy();
+    let mut redirect = |hunk: &Hunk| {
+        if hunk.origin.is_none_or(|origin| origin == current) {
+            return sink(hunk);
+        }
+        if created.is_none() {
+            match created_at(repo, path, opts) {
+                Ok(commit) => created = Some(commit),
+                Err(e) => {
+                    failed.get_or_insert(e);
+                    return sink(hunk);
+                }
+            }
+        }
+        if let Some((commit, name, email, time)) = &created {
+            sink(&Hunk { commit: *commit, name, email, time: *time, lines: hunk.lines, origin: None });
+        }
+    };
+    match backend {
+        Backend::Libgit2 => blame_libgit2(repo, path, opts, &mut redirect)?,
+        Backend::Git => blame_git(repo, path, opts, &mut redirect)?,
+    }
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// The commit that gave `path` its name, the newest one from the blamed
+/// revision that has the path while none of its parents do, with the name,
+/// email and time of its author
+#[cfg(feature = "git")]
+fn created_at (repo: &Repository, path: &Path, opts: &Options) -> Result<(Oid, String, String, i64), Error>
+{
+    let mut walk = repo.revwalk()?;
+    match &opts.rev {
+        Some(rev) => walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?,
+        None => walk.push_head()?,
+    }
+    if opts.first_parent {
+        walk.simplify_first_parent()?;
+    }
+    let has_path = |commit: &git2::Commit| commit.tree().is_ok_and(|tree| tree.get_path(path).is_ok());
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        if has_path(&commit) && !commit.parents().any(|parent| has_path(&parent)) {
+            let author = commit.author();
+            return Ok((commit.id(), String::from_utf8_lossy(author.name_bytes()).into_owned(),
+                       String::from_utf8_lossy(author.email_bytes()).into_owned(), author.when().seconds()));
+        }
+    }
+    Err(Error::Blame(format!("no commit added {}", path.display())))
+}
+
+#[cfg(feature = "git")]
+fn blame_libgit2 (
+    repo: &Repository,
+    path: &Path,
+    opts: &Options,
+    sink: &mut dyn FnMut(&Hunk)
+) -> Result<(), Error>
+{
+    if opts.ignore_revs.is_some() {
+        return Err(Error::Blame("ignoring revisions needs the git backend".to_string()));
+    }
+    if opts.contents.is_some() {
+        return Err(Error::Blame("blaming other contents needs the git backend".to_string()));
+    }
+
+    // Prepare our blame options
+    let mut blame_opts = BlameOptions::new();
+    blame_opts.track_copies_same_commit_moves(opts.moves)
+        .track_copies_same_commit_copies(opts.copies >= 1)
+        .track_copies_any_commit_copies(opts.copies >= 2)
+        .first_parent(opts.first_parent);
+    if let Some(rev) = &opts.rev {
+        blame_opts.newest_commit(repo.revparse_single(rev)?.peel_to_commit()?.id());
+    }
+
+    let blame = repo.blame_file(path, Some(&mut blame_opts))?;
+
+    // An empty file has a hunk of no lines
+    for hunk in blame.iter().filter(|hunk| hunk.lines_in_hunk() > 0)
+    {
+        let sig = hunk.final_signature();
+        let origin = hunk.path().map(Path::to_string_lossy);
+        sink(&Hunk {
+            commit: hunk.final_commit_id(),
+            name: &String::from_utf8_lossy(sig.name_bytes()),
+            email: &String::from_utf8_lossy(sig.email_bytes()),
+            time: sig.when().seconds(),
+            lines: hunk.lines_in_hunk(),
+            origin: origin.as_deref(),
+        });
+    }
+    Ok(())
+}
+
+/// The version of the git on the PATH, looked up once
+#[cfg(feature = "git")]
+static GIT_VERSION: OnceLock<Option<(u32, u32, u32)>> = OnceLock::new();
+
+/// The version of the git on the PATH, if it runs and tells one
+#[cfg(feature = "git")]
+pub fn git_version () -> Option<(u32, u32, u32)>
+{
+    *GIT_VERSION.get_or_init(|| {
+        let output = Command::new(platform::git()).arg("--version").stdin(Stdio::null()).output().ok()?;
+        parse_version(&String::from_utf8_lossy(&output.stdout))
+    })
+}
+
+/// `(major, minor, patch)` of `git version 2.39.2`, ignoring what vendors
+/// add such as `.windows.1` or ` (Apple Git-143)`
+#[cfg(feature = "git")]
+fn parse_version (text: &str) -> Option<(u32, u32, u32)>
+{
+    let version = text.trim().strip_prefix("git version ")?.split(' ').next()?;
+    let mut numbers = version.split('.').map(|n| n.parse::<u32>().ok());
+    let major = numbers.next()??;
+    let minor = numbers.next()??;
+    let patch = numbers.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+/// Fail unless the git on the PATH is at least `major.minor`, which
+/// `feature` needs
+#[cfg(feature = "git")]
+pub fn require_git (feature: &str, major: u32, minor: u32) -> Result<(), Error>
+{
+    match git_version() {
+        Some((found_major, found_minor, found_patch)) if (found_major, found_minor) < (major, minor) => {
+            Err(Error::Usage(format!("{} requires git >= {}.{}, found {}.{}.{}",
+                                     feature, major, minor, found_major, found_minor, found_patch)))
+        }
+        // Whatever fails with a git we cannot tell the version of is
+        // reported by the blame itself
+        _ => Ok(()),
+    }
+}
+
+/// Fail unless the git on the PATH can do everything `opts` asks of it
+#[cfg(feature = "git")]
+pub fn check_git (opts: &Options) -> Result<(), Error>
+{
+    if opts.ignore_revs.is_some() {
+        require_git("--ignore-revs-file", 2, 23)?;
+    }
+    Ok(())
+}
+
+/// The `git blame --porcelain` that the git backend runs for `path`
+#[cfg(feature = "git")]
+pub fn git_command (repo: &Repository, path: &Path, opts: &Options) -> Result<Command, Error>
+{
+    command(repo, path, opts, None)
+}
+
+/// `git_command`, only for the lines from the first to the last of `lines`
+/// if given
+#[cfg(feature = "git")]
+fn command (repo: &Repository, path: &Path, opts: &Options, lines: Option<(usize, usize)>) -> Result<Command, Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+
+    // Named explicitly, git would otherwise go by GIT_DIR and GIT_WORK_TREE,
+    // which may be relative to another directory than the work tree
+    let mut cmd = Command::new(platform::git());
+    cmd.current_dir(workdir)
+        .arg("--git-dir").arg(repo.path())
+        .arg("--work-tree").arg(workdir)
+        .arg("blame").arg("--porcelain");
+    if opts.moves {
+        cmd.arg("-M");
+    }
+    for _ in 0..opts.copies
+    {
+        cmd.arg("-C");
+    }
+    if opts.first_parent {
+        cmd.arg("--first-parent");
+    }
+    if let Some(file) = &opts.ignore_revs {
+        cmd.arg("--ignore-revs-file").arg(file);
+    }
+    if opts.contents.is_some() {
+        cmd.arg("--contents").arg("-");
+    }
+    if let Some((first, last)) = lines {
+        cmd.arg("-L").arg(format!("{},{}", first, last));
+    }
+    if let Some(rev) = &opts.rev {
+        cmd.arg(rev);
+    }
+    cmd.arg("--").arg(path);
+    Ok(cmd)
+}
+
+/// `cmd` as it would be typed into a POSIX shell
+#[cfg(feature = "git")]
+pub fn command_line (cmd: &Command) -> String
+{
+    let quote = |word: &std::ffi::OsStr| {
+        let word = word.to_string_lossy();
+        if !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:@+,".contains(c)) {
+            word.into_owned()
+        } else {
+            format!("'{}'", word.replace('\'', "'\\''"))
+        }
+    };
+    std::iter::once(cmd.get_program()).chain(cmd.get_args()).map(quote).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(feature = "git")]
+fn blame_git (
+    repo: &Repository,
+    path: &Path,
+    opts: &Options,
+    sink: &mut dyn FnMut(&Hunk)
+) -> Result<(), Error>
+{
+    check_git(opts)?;
+    let chunk = opts.chunk.unwrap_or(CHUNK_LINES);
+    let total = match chunk {
+        // Contents come from an editor and are no larger than it can hold
+        _ if opts.contents.is_some() => None,
+        0 => None,
+        _ => blamed_lines(repo, path, opts, chunk)?,
+    };
+    let total = match total {
+        Some(total) => total,
+        None => return run_git(git_command(repo, path, opts)?, path, opts.contents.clone(), sink),
+    };
+    let mut first = 1;
+    while first <= total
+    {
+        let last = total.min(first + chunk - 1);
+        run_git(command(repo, path, opts, Some((first, last)))?, path, None, sink)?;
+        first = last + 1;
+    }
+    Ok(())
+}
+
+/// Lines of `path` as git blames it, at `opts.rev` or else in the work
+/// tree, if there are more than `chunk`; files too small to have that many
+/// are not read
+#[cfg(feature = "git")]
+fn blamed_lines (repo: &Repository, path: &Path, opts: &Options, chunk: usize) -> Result<Option<usize>, Error>
+{
+    let count = |content: &mut dyn Read| -> Result<usize, Error> {
+        let mut reader = BufReader::new(content);
+        let (mut lines, mut last) = (0, b'\n');
+        loop
+        {
+            let buffer = reader.fill_buf()?;
+            let read = buffer.len();
+            if read == 0 {
+                break;
+            }
+            lines += buffer.iter().filter(|&&b| b == b'\n').count();
+            last = buffer[read - 1];
+            reader.consume(read);
+        }
+        Ok(lines + (last != b'\n') as usize)
+    };
+    let lines = match &opts.rev {
+        Some(rev) => {
+            let id = repo.revparse_single(rev)?.peel_to_tree()?.get_path(path)?.id();
+            let (size, _) = repo.odb()?.read_header(id)?;
+            if size <= chunk {
+                return Ok(None);
+            }
+            count(&mut repo.find_blob(id)?.content())?
+        }
+        None => {
+            let workdir = repo.workdir()
+                .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+            let mut file = std::fs::File::open(workdir.join(path))?;
+            if file.metadata()?.len() <= chunk as u64 {
+                return Ok(None);
+            }
+            count(&mut file)?
+        }
+    };
+    Ok(Some(lines).filter(|&lines| lines > chunk))
+}
+
+/// Run `cmd`, a `git blame --porcelain` of `path` reading `input` if
+/// given, handing its hunks to `sink`
+#[cfg(feature = "git")]
+fn run_git (
+    mut cmd: Command,
+    path: &Path,
+    input: Option<Arc<[u8]>>,
+    sink: &mut dyn FnMut(&Hunk)
+) -> Result<(), Error>
+{
+    let mut child = cmd
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Blame(format!("could not run git: {}", e)))?;
+
+    // Fed on the side, as git may stop reading it to report an error;
+    // stdin closing once it is written tells git where the contents end
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        thread::spawn(move || stdin.write_all(&input));
+    }
+
+    // Drain stderr on the side so a chatty git cannot fill the pipe and
+    // stall while we are still reading its stdout
+    let mut stderr = child.stderr.take().unwrap();
+    let errors = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+
+    let mut span = trace::span(Level::Trace, "parse");
+    span.record("path", path.display());
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let hunks = match read_porcelain(stdout, sink) {
+        Ok(hunks) => hunks,
+        Err(e) => {
+            // Not left blocked on a pipe nobody reads, nor as a zombie
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    };
+    span.record("hunks", hunks);
+    drop(span);
+    let status = child.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        return Err(Error::Blame(errors.trim().to_string()));
+    }
+    Ok(())
+}
+
+/// Parse porcelain output from `stdout` to its end, handing its hunks to
+/// `sink`; how many there were
+#[cfg(feature = "git")]
+fn read_porcelain (mut stdout: impl BufRead, sink: &mut dyn FnMut(&Hunk)) -> Result<usize, Error>
+{
+    let mut hunks = 0;
+    let mut parser = Parser::new();
+    let mut line = Vec::new();
+    while let Some(&next) = stdout.fill_buf()?.first() {
+        // The file contents are only counted, so skip them in place rather
+        // than copying every line out of the read buffer
+        if next == b'\t' {
+            stdout.skip_until(b'\n')?;
+            if let Some(hunk) = parser.content_line() {
+                hunks += 1;
+                sink(&hunk);
+            }
+        } else {
+            line.clear();
+            stdout.read_until(b'\n', &mut line)?;
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            parser.feed(&line)?;
+        }
+    }
+    Ok(hunks)
+}
+
+/// The hunks of the whole output of `git blame --porcelain` or
+/// `--line-porcelain`, in the order of their lines
+pub fn parse (output: &str) -> Result<Vec<BlameHunk>, Error>
+{
+    let mut parser = Parser::new();
+    let mut hunks = Vec::new();
+    for line in output.lines()
+    {
+        if line.starts_with('\t') {
+            if let Some(hunk) = parser.content_line() {
+                hunks.push(hunk.owned());
+            }
+        } else {
+            parser.feed(line.as_bytes())?;
+        }
+    }
+    Ok(hunks)
+}
+
+/// Ownership of `path` from the whole output of `git blame --porcelain`
+/// for it, produced wherever git is at hand
+pub fn report (path: &str, output: &str) -> Result<FileReport, Error>
+{
+    let mut report = FileReport::new(path);
+    for hunk in parse(output)?
+    {
+        report.add_blamed(&hunk.hunk());
+    }
+    Ok(report)
+}
+
+/// What porcelain output tells us about a commit the first time it appears
+#[derive(Default)]
+struct CommitInfo {
+    name: String,
+    email: String,
+    time: i64,
+    /// Path of the file in the commit, which git only tells with the
+    /// first group of lines from it
+    filename: Option<String>,
+}
+
+/// The group of lines currently being read
+struct Group {
+    commit: Oid,
+    lines: usize,
+    remaining: usize,
+}
+
+/// Incremental parser for `git blame --porcelain`, fed one line at a time
+///
+/// Nothing but the metadata of the commits seen so far is retained, hunks
+/// are handed on as soon as their last line has been read, so memory does
+/// not grow with the size of the blamed file. Lines of file contents, those
+/// starting with a tab, go to `content_line` and all others to `feed`.
+#[derive(Default)]
+pub struct Parser {
+    commits: HashMap<Oid, CommitInfo>,
+    group: Option<Group>,
+}
+
+impl Parser {
+    pub fn new () -> Parser
+    {
+        Parser {
+            commits: HashMap::new(),
+            group: None,
+        }
+    }
+
+    /// Consume one header line of output (without the newline); content
+    /// lines go to `content_line` instead
+    pub fn feed (&mut self, line: &[u8]) -> Result<(), Error>
+    {
+        // Nothing to read from a blank line, whether or not a group has
+        // started yet
+        if line.is_empty() {
+            return Ok(());
+        }
+        // git for Windows may end lines with CRLF
+        let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line));
+        let (key, value) = match line.find(' ') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (&line[..], ""),
+        };
+
+        // Whatever length the hashes of the repository have; no other key
+        // is that long and all hex digits
+        if key.len() >= 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return self.header(key, value);
+        }
+
+        let commit = match &self.group {
+            Some(group) => group.commit,
+            None => return Err(malformed(&line)),
+        };
+        let info = self.commits.entry(commit).or_default();
+        match key {
+            "filename" => info.filename = Some(unquote(value).into_owned()),
+            "author" => info.name = value.to_string(),
+            "author-mail" => info.email = value.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .trim()
+                .to_string(),
+            "author-time" => info.time = value.parse().map_err(|_| malformed(&line))?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `<sha> <orig line> <final line> [<lines in group>]`
+    fn header (&mut self, sha: &str, rest: &str) -> Result<(), Error>
+    {
+        let fields: Vec<&str> = rest.split(' ').collect();
+        if fields.len() < 3 {
+            // Continuation of the current group
+            return Ok(());
+        }
+
+        let lines = fields[2].parse::<usize>().map_err(|_| malformed(rest))?;
+        self.group = Some(Group {
+            commit: Oid::from_str(sha)?,
+            lines,
+            remaining: lines,
+        });
+        Ok(())
+    }
+
+    /// Count one line of file contents, returning the hunk once its last
+    /// line has been read
+    pub fn content_line (&mut self) -> Option<Hunk<'_>>
+    {
+        let group = self.group.as_mut()?;
+        group.remaining = group.remaining.saturating_sub(1);
+        if group.remaining > 0 {
+            return None;
+        }
+
+        let info = self.commits.get(&group.commit);
+        Some(Hunk {
+            commit: group.commit,
+            name: info.map(|i| i.name.as_str()).unwrap_or(""),
+            email: info.map(|i| i.email.as_str()).unwrap_or(""),
+            time: info.map(|i| i.time).unwrap_or(0),
+            lines: group.lines,
+            origin: info.and_then(|i| i.filename.as_deref()),
+        })
+    }
+}
+
+/// A path as git writes it, without the quotes and escapes it gets when it
+/// has a quote, a backslash, a control character or, unless
+/// `core.quotePath` is off, anything outside of ASCII
+pub fn unquote (path: &str) -> Cow<'_, str>
+{
+    let quoted = match path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+        Some(quoted) => quoted.as_bytes(),
+        None => return Cow::Borrowed(path),
+    };
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut i = 0;
+    while i < quoted.len() {
+        if quoted[i] != b'\\' || i + 1 == quoted.len() {
+            bytes.push(quoted[i]);
+            i += 1;
+            continue;
+        }
+        let octal = quoted.get(i + 1..i + 4)
+            .filter(|digits| digits.iter().all(|d| (b'0'..=b'7').contains(d)));
+        if let Some(digits) = octal {
+            bytes.push(digits.iter().fold(0u8, |byte, d| byte.wrapping_mul(8) + (d - b'0')));
+            i += 4;
+            continue;
+        }
+        bytes.push(match quoted[i + 1] {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0b,
+            b'f' => 0x0c,
+            b'r' => b'\r',
+            other => other,
+        });
+        i += 2;
+    }
+    Cow::Owned(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// `text` as git quotes paths, if it has to be; `unquote` undoes it
+pub fn quote (text: &str) -> String
+{
+    if !text.chars().any(|c| c == '"' || c == '\\' || c.is_control()) {
+        return text.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in text.chars()
+    {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes()
+                {
+                    quoted.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn malformed (line: &str) -> Error
+{
+    Error::Blame(format!("unexpected blame output: {}", line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "1111111111111111111111111111111111111111";
+    const BOB: &str = "2222222222222222222222222222222222222222";
+
+    #[test]
+    fn empty_file ()
+    {
+        let report = report("empty.txt", "").unwrap();
+        assert!(report.owners.is_empty());
+        assert_eq!(report.lines(), 0);
+        assert_eq!(report.bus_factor(), 0);
+    }
+
+    #[test]
+    fn single_line_without_newline ()
+    {
+        let output = format!("{} 1 1 1\nauthor Alice\nauthor-mail <alice@example.com>\nauthor-time 1700000000\n\
+                              filename one.txt\n\tlast line", ALICE);
+        let report = report("one.txt", &output).unwrap();
+        assert_eq!(report.lines(), 1);
+        assert_eq!(report.bus_factor(), 1);
+        assert_eq!(report.owners["alice@example.com"].name, "Alice");
+    }
+
+    #[test]
+    fn filename_of_every_group ()
+    {
+        // git tells the name of the file in a commit with its first group
+        // only, here a commit from before a rename with two
+        let output = format!("{alice} 1 1 1\nauthor Alice\nauthor-mail <alice@example.com>\nauthor-time 1\n\
+                              filename old.txt\n\tone\n\
+                              {bob} 2 2 1\nauthor Bob\nauthor-mail <bob@example.com>\nauthor-time 2\n\
+                              filename new.txt\n\ttwo\n\
+                              {alice} 3 3 2\n\tthree\n{alice} 4 4\n\tfour\n",
+                             alice = ALICE, bob = BOB);
+        let hunks = parse(&output).unwrap();
+        let origins: Vec<(&str, usize, Option<&str>)> = hunks.iter()
+            .map(|hunk| (hunk.name.as_str(), hunk.lines, hunk.origin.as_deref()))
+            .collect();
+        assert_eq!(origins, [("Alice", 1, Some("old.txt")), ("Bob", 1, Some("new.txt")), ("Alice", 2, Some("old.txt"))]);
+    }
+
+    #[test]
+    fn porcelain_details ()
+    {
+        // CRLF line ends, other keys and a boundary commit are of no
+        // consequence, and blank lines are skipped
+        let output = format!("{} 1 1 2\r\nauthor Alice\r\nauthor-mail <alice@example.com>\r\n\
+                              author-time 1\r\nauthor-tz +0100\r\nsummary first\r\nboundary\r\n\
+                              filename a.txt\r\n\tone\r\n\n{} 2 2\r\n\ttwo\r\n", ALICE, ALICE);
+        let hunks = parse(&output).unwrap();
+        assert_eq!(hunks.len(), 1);
+        assert_eq!((hunks[0].name.as_str(), hunks[0].email.as_str(), hunks[0].time, hunks[0].lines),
+                   ("Alice", "alice@example.com", 1, 2));
+        assert_eq!(hunks[0].origin.as_deref(), Some("a.txt"));
+    }
+
+    #[test]
+    fn malformed_porcelain ()
+    {
+        assert!(parse("author Alice\n\tone\n").is_err());
+        assert!(parse(&format!("{} 1 1 1\nauthor-time soon\n\tone\n", ALICE)).is_err());
+    }
+
+    /// A new repository named after `name` with one commit by Alice adding
+    /// every file of `files`, of two lines each
+    #[cfg(feature = "git")]
+    fn repository (name: &str, files: &[&str]) -> Repository
+    {
+        let dir = std::env::temp_dir().join(format!("git-whoknows-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").current_dir(&dir)
+                .args(["-c", "user.name=Alice", "-c", "user.email=alice@example.com", "-c", "commit.gpgsign=false"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        for file in files
+        {
+            std::fs::write(dir.join(file), "one\ntwo\n").unwrap();
+        }
+        git(&["add", "--", "."]);
+        git(&["commit", "-q", "-m", "files"]);
+        Repository::open(&dir).unwrap()
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn unusual_paths ()
+    {
+        let files = ["with space.txt", "n\u{f6}n-\u{e4}scii.txt", "-dash.txt"];
+        let repo = repository("paths", &files);
+        for &backend in &[Backend::Libgit2, Backend::Git]
+        {
+            for file in &files
+            {
+                let mut blamed = Vec::new();
+                blame(&repo, Path::new(file), backend, &Options::default(), &mut |hunk| {
+                    blamed.push((hunk.name.to_string(), hunk.lines));
+                }).unwrap();
+                assert_eq!(blamed, [("Alice".to_string(), 2)], "{} with {}", file, backend);
+            }
+        }
+        std::fs::remove_dir_all(repo.workdir().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unquoted_paths ()
+    {
+        assert_eq!(unquote("plain.txt"), "plain.txt");
+        assert_eq!(unquote("with space.txt"), "with space.txt");
+        assert_eq!(unquote("-dash.txt"), "-dash.txt");
+        assert_eq!(unquote("\"n\\303\\266n.txt\""), "n\u{f6}n.txt");
+        assert_eq!(unquote("\"tab\\there\""), "tab\there");
+        assert_eq!(unquote("\"say \\\"hi\\\"\""), "say \"hi\"");
+        assert_eq!(unquote("\"back\\\\slash\""), "back\\slash");
+        // Only quoted paths have escapes
+        assert_eq!(unquote("n\\303\\266n.txt"), "n\\303\\266n.txt");
+    }
+
+    #[test]
+    fn quoted_paths_round_trip ()
+    {
+        for path in vec!["plain.txt", "with space.txt", "-dash.txt", "n\u{f6}n-\u{e4}scii.txt",
+                         "tab\there", "line\nbreak", "say \"hi\"", "back\\slash", "bell\u{7}"]
+        {
+            assert_eq!(unquote(&quote(path)), path);
+        }
+        assert_eq!(quote("with space.txt"), "with space.txt");
+        assert_eq!(quote("tab\there"), "\"tab\\there\"");
+    }
+
+    #[test]
+    fn quoted_filename ()
+    {
+        let output = format!("{} 1 1 2\nauthor Alice\nauthor-mail <alice@example.com>\nauthor-time 1\n\
+                              filename \"caf\\303\\251 menu.txt\"\n\tone\n{} 2 2\n\ttwo\n", ALICE, ALICE);
+        let hunks = parse(&output).unwrap();
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].lines, 2);
+        assert_eq!(hunks[0].origin.as_deref(), Some("caf\u{e9} menu.txt"));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn quoted_paths_are_not_renames ()
+    {
+        // git quotes these in its porcelain output; were they taken for
+        // other paths, --no-follow would give their lines to whoever
+        // created them under those
+        let files = ["n\u{f6}n-\u{e4}scii.txt", "tab\there.txt", "say \"hi\".txt"];
+        let repo = repository("quoted", &files);
+        let opts = Options { no_follow: true, ..Options::default() };
+        for file in &files
+        {
+            let mut origins = Vec::new();
+            blame(&repo, Path::new(file), Backend::Git, &opts, &mut |hunk| {
+                origins.push(hunk.origin.map(str::to_string));
+            }).unwrap();
+            assert_eq!(origins, [Some(file.to_string())]);
+        }
+        std::fs::remove_dir_all(repo.workdir().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,475 @@
+//! Blame results by file, last change and blob
+//!
+//! Entries live in `.git/whoknows-cache` unless `--cache-dir` names a
+//! directory shared by every clone and worktree of a project on the host,
+//! under a subdirectory named after the project's root commit. Runs blaming
+//! the same blob at the same time take turns through a lock file, so that
+//! only the first one blames it and the others read its entry.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+
+use crate::blame::{quote, unquote, Backend, Hunk, Options};
+use crate::error::Error;
+use crate::{FileReport, Span};
+
+/// First line of every cache entry, bumped whenever the format changes
+const HEADER: &str = "git-whoknows cache 6";
+
+/// Entry holding the tree analyzed by the previous incremental run
+const LAST_RUN: &str = "last-run";
+
+/// Entry counting lookups over all runs, `<hits> <misses>`
+const STATS: &str = "stats";
+
+/// Entry remembering the root commit that names the project in a shared
+/// cache
+const PROJECT: &str = "project";
+
+/// Directory of the lock files of a shared cache
+const LOCKS: &str = "locks";
+
+/// A cached report
+pub struct Entry {
+    pub key: String,
+    /// The blob that was blamed
+    pub blob: Oid,
+    /// Bytes on disk
+    pub size: u64,
+}
+
+/// How often the cache could answer
+#[derive(Default)]
+pub struct Stats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Lookups counted during one run, from any number of threads
+#[derive(Default)]
+pub struct Lookups {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Lookups {
+    pub fn hit (&self)
+    {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss (&self)
+    {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Blame results, kept in `.git/whoknows-cache`
+///
+/// The same blob has other owners under another path, or in a history
+/// that reached it differently, say by a revert, so entries are keyed by
+/// the file and the last commit to change it as well as by the blob.
+pub struct Cache {
+    dir: PathBuf,
+    /// Where the entries are, `dir` unless shared
+    entries: PathBuf,
+}
+
+/// The right to blame a blob and store its entry, held until dropped
+pub struct Lock {
+    _file: fs::File,
+}
+
+impl Cache {
+    pub fn new (repo: &Repository) -> Cache
+    {
+        let dir = repo.path().join("whoknows-cache");
+        Cache {
+            entries: dir.clone(),
+            dir,
+        }
+    }
+
+    /// The cache of `repo` with its entries in `shared`, if given, where
+    /// clones and worktrees of the same project find them; statistics and
+    /// the rest of the bookkeeping stay with the repository
+    pub fn open (repo: &Repository, shared: Option<&Path>) -> Cache
+    {
+        let mut cache = Cache::new(repo);
+        if let Some(shared) = shared {
+            let project = cache.project(repo).map_or_else(|| "unborn".to_string(), |oid| oid.to_string());
+            cache.entries = shared.join(project);
+        }
+        cache
+    }
+
+    /// The root commit of the history of HEAD, the oldest one if there are
+    /// several; found once, then remembered
+    fn project (&self, repo: &Repository) -> Option<Oid>
+    {
+        let path = self.dir.join(PROJECT);
+        if let Some(oid) = fs::read_to_string(&path).ok().and_then(|text| Oid::from_str(text.trim()).ok()) {
+            return Some(oid);
+        }
+        let mut walk = repo.revwalk().ok()?;
+        walk.push_head().ok()?;
+        let mut roots = Vec::new();
+        for oid in walk.flatten()
+        {
+            let commit = repo.find_commit(oid).ok()?;
+            if commit.parent_count() == 0 {
+                roots.push((commit.time().seconds(), oid));
+            }
+        }
+        let (_, root) = roots.into_iter().min()?;
+        let _ = fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, format!("{}\n", root)));
+        Some(root)
+    }
+
+    /// Whether the entries are shared with other repositories
+    pub fn is_shared (&self) -> bool
+    {
+        self.entries != self.dir
+    }
+
+    /// Wait until no other run holds the entry `key` of a shared cache;
+    /// a private cache, or one where locks cannot be taken, needs no lock
+    pub fn lock (&self, key: &str) -> Option<Lock>
+    {
+        if !self.is_shared() {
+            return None;
+        }
+        let dir = self.entries.join(LOCKS);
+        fs::create_dir_all(&dir).ok()?;
+        let file = fs::OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(key)).ok()?;
+        file.lock().ok()?;
+        Some(Lock { _file: file })
+    }
+
+    /// Name of the entry for `blob` at `path`, last changed by `commit`,
+    /// blamed by `backend` with `opts`, as the backends need not agree on
+    /// every line; the path and the list of ignored revisions are
+    /// represented by a hash of their contents
+    pub fn key (blob: Oid, commit: Oid, path: &Path, backend: Backend, opts: &Options) -> String
+    {
+        let path = Oid::hash_object(ObjectType::Blob, path.to_string_lossy().as_bytes())
+            .map_or_else(|_| String::new(), |oid| oid.to_string()[..12].to_string());
+        let mut key = format!("{}-{}-{}-{}-m{}c{}f{}", blob, commit, path, backend,
+                              opts.moves as u8, opts.copies, opts.first_parent as u8);
+        if opts.no_follow {
+            key.push_str("n1");
+        }
+        if let Some(file) = &opts.ignore_revs {
+            let contents = fs::read(file).unwrap_or_default();
+            if let Ok(oid) = Oid::hash_object(git2::ObjectType::Blob, &contents) {
+                key.push_str(&format!("i{}", &oid.to_string()[..12]));
+            }
+        }
+        key
+    }
+
+    /// Tree of HEAD when `--incremental` last ran
+    pub fn last_run (&self) -> Option<Oid>
+    {
+        let text = fs::read_to_string(self.dir.join(LAST_RUN)).ok()?;
+        Oid::from_str(text.trim()).ok()
+    }
+
+    pub fn set_last_run (&self, tree: Oid) -> io::Result<()>
+    {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(LAST_RUN), format!("{}\n", tree))
+    }
+
+    fn entry (&self, key: &str) -> PathBuf
+    {
+        self.entries.join(key)
+    }
+
+    pub fn dir (&self) -> &Path
+    {
+        &self.dir
+    }
+
+    /// Where the entries are
+    pub fn entries_dir (&self) -> &Path
+    {
+        &self.entries
+    }
+
+    /// Every cached report, in no particular order
+    pub fn entries (&self) -> io::Result<Vec<Entry>>
+    {
+        let dir = match fs::read_dir(&self.entries) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for file in dir
+        {
+            let file = file?;
+            let key = file.file_name().to_string_lossy().into_owned();
+            // Entries are named <blob>-<commit>-<path>-<options>, anything
+            // else is bookkeeping
+            let blob = match key.split('-').next().and_then(|b| Oid::from_str(b).ok()) {
+                Some(blob) if key.len() > 40 && !key.ends_with(".tmp") => blob,
+                _ => continue,
+            };
+            entries.push(Entry { key, blob, size: file.metadata()?.len() });
+        }
+        Ok(entries)
+    }
+
+    /// How many entries were written in another format, by another
+    /// version, and are never read
+    pub fn outdated (&self) -> io::Result<usize>
+    {
+        let mut outdated = 0;
+        for entry in self.entries()?
+        {
+            let mut header = [0; HEADER.len() + 1];
+            let read = fs::File::open(self.entry(&entry.key))
+                .and_then(|mut file| io::Read::read(&mut file, &mut header));
+            if read.ok().map(|n| &header[..n]) != Some(format!("{}\n", HEADER).as_bytes()) {
+                outdated += 1;
+            }
+        }
+        Ok(outdated)
+    }
+
+    pub fn stats (&self) -> Stats
+    {
+        let text = fs::read_to_string(self.dir.join(STATS)).unwrap_or_default();
+        let mut numbers = text.split_whitespace().map(|n| n.parse().unwrap_or(0));
+        Stats {
+            hits: numbers.next().unwrap_or(0),
+            misses: numbers.next().unwrap_or(0),
+        }
+    }
+
+    /// Add the lookups of a run to the totals; runs finishing at the same
+    /// moment may lose each other's counts, which is fine for statistics
+    pub fn add_stats (&self, lookups: &Lookups) -> io::Result<()>
+    {
+        let hits = lookups.hits.load(Ordering::Relaxed);
+        let misses = lookups.misses.load(Ordering::Relaxed);
+        if hits + misses == 0 {
+            return Ok(());
+        }
+        let stats = self.stats();
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(STATS),
+                  format!("{} {}\n", stats.hits + hits, stats.misses + misses))
+    }
+
+    /// Remove the whole cache, statistics included, and the entries of
+    /// the project in a shared cache
+    pub fn clear (&self) -> io::Result<()>
+    {
+        for dir in &[&self.entries, &self.dir]
+        {
+            match fs::remove_dir_all(dir) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the entries of blobs that are not part of HEAD or of the tip
+    /// of any branch or tag, returning how many were removed and their size
+    pub fn prune (&self, repo: &Repository) -> Result<(usize, u64), Error>
+    {
+        let mut tips = vec![repo.head().ok().and_then(|head| head.peel_to_tree().ok())];
+        for reference in repo.references()?
+        {
+            tips.push(reference.ok().and_then(|r| r.peel_to_tree().ok()));
+        }
+
+        let mut trees = HashSet::new();
+        let mut blobs = HashSet::new();
+        for tree in tips.into_iter().flatten()
+        {
+            if !trees.insert(tree.id()) {
+                continue;
+            }
+            tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+                if entry.kind() == Some(ObjectType::Blob) {
+                    blobs.insert(entry.id());
+                }
+                TreeWalkResult::Ok
+            })?;
+        }
+
+        let mut removed = 0;
+        let mut bytes = 0;
+        for entry in self.entries()?
+        {
+            if !blobs.contains(&entry.blob) {
+                fs::remove_file(self.entry(&entry.key))?;
+                removed += 1;
+                bytes += entry.size;
+            }
+        }
+        // Lock files no run is holding
+        if let Ok(locks) = fs::read_dir(self.entries.join(LOCKS)) {
+            for lock in locks.flatten()
+            {
+                let idle = fs::File::open(lock.path()).is_ok_and(|file| file.try_lock().is_ok());
+                if idle {
+                    let _ = fs::remove_file(lock.path());
+                }
+            }
+        }
+        Ok((removed, bytes))
+    }
+
+    /// The cached report for `path`, if there is one and it can be read
+    pub fn load (&self, key: &str, path: &Path) -> Option<FileReport>
+    {
+        let text = fs::read_to_string(self.entry(key)).ok()?;
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return None;
+        }
+        decode(lines, path)
+    }
+
+    pub fn store (&self, key: &str, report: &FileReport) -> io::Result<()>
+    {
+        fs::create_dir_all(&self.entries)?;
+
+        let mut text = String::new();
+        text.push_str(HEADER);
+        text.push('\n');
+        text.push_str(&encode(report));
+
+        // Write aside and rename so concurrent runs never see half an entry;
+        // the time tells apart processes of other hosts or containers that
+        // share the directory and happen to have the same id
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let tmp = self.entries.join(format!("{}.{}.{}.tmp", key, std::process::id(), nanos));
+        fs::File::create(&tmp)?.write_all(text.as_bytes())?;
+        fs::rename(&tmp, self.entry(key))
+    }
+}
+
+/// The contributions of `report`, one line each
+pub fn encode (report: &FileReport) -> String
+{
+    let mut text = String::new();
+    for path in &report.renamed_from
+    {
+        text.push_str(&format!("renamed\t{}\n", quote(path)));
+    }
+    for span in &report.spans
+    {
+        text.push_str(&format!("span\t{}\t{}\n", span.commit, span.lines));
+    }
+    for owner in report.ranked_owners()
+    {
+        for (commit, c) in &owner.commits
+        {
+            text.push_str(&format!("{}\t{}\t{}\t{}\t{}\n",
+                                   commit, c.lines, c.time, quote(&owner.email), quote(&owner.name)));
+        }
+    }
+    text
+}
+
+/// The report for `path` from lines written by `encode`
+pub fn decode<'a, I> (lines: I, path: &Path) -> Option<FileReport>
+    where I: Iterator<Item = &'a str>
+{
+    let mut report = FileReport::new(&path.display().to_string());
+    for line in lines
+    {
+        // <commit> <lines> <time> <email> <name>, tab separated, after the
+        // earlier paths as renamed <path> and the lines in order as
+        // span <commit> <lines>; paths, emails and names with a tab, a
+        // newline or a quote are quoted as git quotes paths
+        let mut fields = line.splitn(5, '\t');
+        let first = fields.next()?;
+        if first == "renamed" {
+            // The same blob blamed under its earlier name
+            let origin = unquote(fields.next()?);
+            if origin != report.path {
+                report.renamed_from.push(origin.into_owned());
+            }
+            continue;
+        }
+        if first == "span" {
+            let commit = Oid::from_str(fields.next()?).ok()?;
+            let lines = fields.next()?.parse().ok()?;
+            let start = report.spans.last().map_or(1, |span| span.start + span.lines);
+            report.spans.push(Span { start, lines, commit });
+            continue;
+        }
+        let commit = Oid::from_str(first).ok()?;
+        let lines = fields.next()?.parse().ok()?;
+        let time = fields.next()?.parse().ok()?;
+        let email = unquote(fields.next()?);
+        let name = unquote(fields.next()?);
+        report.add_hunk(&Hunk { commit, name: &name, email: &email, time, lines, origin: None });
+    }
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid (n: u8) -> Oid
+    {
+        Oid::from_str(&format!("{:02x}", n).repeat(20)).unwrap()
+    }
+
+    #[test]
+    fn round_trip ()
+    {
+        let mut report = FileReport::new("src/lib.rs");
+        report.renamed_from.push("src/old\tname.rs".to_string());
+        for &(commit, name, email, time, lines) in &[
+            (oid(1), "Alice", "alice@example.com", 100, 3),
+            (oid(2), "Bob \"the builder\"", "bob@example.com", 200, 2),
+            (oid(1), "Alice", "alice@example.com", 100, 1),
+            (Oid::zero(), "Not Committed Yet", "not.committed.yet", 300, 1),
+        ]
+        {
+            report.add_blamed(&Hunk { commit, name, email, time, lines, origin: None });
+        }
+
+        let text = encode(&report);
+        let decoded = decode(text.lines(), Path::new("src/lib.rs")).unwrap();
+        assert_eq!(decoded.path, report.path);
+        assert_eq!(decoded.renamed_from, report.renamed_from);
+        assert_eq!(decoded.lines(), 7);
+        let spans = |report: &FileReport| -> Vec<(usize, usize, Oid)> {
+            report.spans.iter().map(|span| (span.start, span.lines, span.commit)).collect()
+        };
+        assert_eq!(spans(&decoded), spans(&report));
+        for (key, owner) in &report.owners
+        {
+            let other = &decoded.owners[key];
+            assert_eq!((&other.name, &other.email, other.lines()), (&owner.name, &owner.email, owner.lines()));
+            assert_eq!(other.commits.len(), owner.commits.len());
+        }
+    }
+
+    #[test]
+    fn unreadable_entries ()
+    {
+        assert!(decode("not a commit\t1\t1\ta@example.com\tA".lines(), Path::new("a")).is_none());
+        assert!(decode(format!("{}\tmany\t1\ta@example.com\tA", oid(1)).lines(), Path::new("a")).is_none());
+        assert!(decode(format!("{}\t1\t1", oid(1)).lines(), Path::new("a")).is_none());
+        assert_eq!(decode("".lines(), Path::new("a")).unwrap().lines(), 0);
+    }
+}
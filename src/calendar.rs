@@ -0,0 +1,227 @@
+//! When each author changed a part of the repository, day by day
+//!
+//! The commits touching some paths are counted per author and day and laid
+//! out like the contribution calendar of GitHub: a column per week, Sunday
+//! at the top, darker the more commits a day has. Side by side, the
+//! calendars of the authors of a directory show who works on it all year,
+//! who only at the end of every quarter and who stopped months ago.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use git2::{DiffOptions, Repository, Sort};
+
+use crate::date;
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::owner_key;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Shades of a day, from no commits to the most
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Colors of the same in HTML, those of GitHub
+const COLORS: [&str; 5] = ["#ebedf0", "#9be9a8", "#40c463", "#30a14e", "#216e39"];
+
+/// The commits of one author by day
+pub struct Activity {
+    pub name: String,
+    pub email: String,
+    /// Commits by day, counted in days since the epoch
+    pub days: BTreeMap<i64, usize>,
+}
+
+impl Activity {
+    pub fn commits (&self) -> usize
+    {
+        self.days.values().sum()
+    }
+}
+
+/// The calendar of `weeks` weeks up to and including the week of `now`
+pub struct Calendar {
+    /// The Sunday of the first week, in days since the epoch
+    pub first: i64,
+    pub weeks: usize,
+}
+
+impl Calendar {
+    pub fn new (now: i64, weeks: usize) -> Calendar
+    {
+        let today = now.div_euclid(DAY);
+        // Day 0 was a Thursday
+        let sunday = today - (today + 4).rem_euclid(7);
+        Calendar { first: sunday - 7 * (weeks.max(1) as i64 - 1), weeks: weeks.max(1) }
+    }
+
+    /// Seconds since the epoch of the first day
+    pub fn start (&self) -> i64
+    {
+        self.first * DAY
+    }
+
+    /// The day of `weekday`, 0 for Sunday, in `week`, 0 for the first
+    fn day (&self, week: usize, weekday: usize) -> i64
+    {
+        self.first + (week * 7 + weekday) as i64
+    }
+}
+
+/// Every author of a non-merge commit reachable from `rev`, HEAD if not
+/// given, that changed any of `paths` since the start of `calendar`, most
+/// commits first; every path when `paths` is empty
+pub fn activity (
+    repo: &Repository,
+    rev: Option<&str>,
+    paths: &[PathBuf],
+    mailmap: &Mailmap,
+    calendar: &Calendar
+) -> Result<Vec<Activity>, Error>
+{
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME)?;
+    match rev {
+        Some(rev) => walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?,
+        None => walk.push_head()?,
+    }
+    let mut opts = DiffOptions::new();
+    for path in paths
+    {
+        opts.pathspec(path);
+    }
+
+    let mut authors: HashMap<String, Activity> = HashMap::new();
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        let time = commit.time().seconds();
+        if time < calendar.start() {
+            break;
+        }
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        if !paths.is_empty() {
+            let parent = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+            let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), Some(&mut opts))?;
+            if diff.deltas().len() == 0 {
+                continue;
+            }
+        }
+        let author = commit.author();
+        let name = String::from_utf8_lossy(author.name_bytes());
+        let email = String::from_utf8_lossy(author.email_bytes());
+        let (name, email) = mailmap.resolve(&name, &email);
+        let activity = authors.entry(owner_key(name, email).into_owned()).or_insert_with(|| Activity {
+            name: name.to_string(),
+            email: email.to_string(),
+            days: BTreeMap::new(),
+        });
+        *activity.days.entry(time.div_euclid(DAY)).or_default() += 1;
+    }
+
+    let mut authors: Vec<Activity> = authors.into_values().collect();
+    authors.sort_by(|a, b| b.commits().cmp(&a.commits()).then_with(|| a.email.cmp(&b.email)));
+    Ok(authors)
+}
+
+/// The shade of a day with `commits` out of `most` on any day
+fn shade (commits: usize, most: usize) -> usize
+{
+    match commits {
+        0 => 0,
+        _ => (commits * (SHADES.len() - 1)).div_ceil(most.max(1)).clamp(1, SHADES.len() - 1),
+    }
+}
+
+/// Most commits on one day of any author
+fn most (authors: &[Activity]) -> usize
+{
+    authors.iter().flat_map(|author| author.days.values()).max().copied().unwrap_or(1)
+}
+
+/// The calendars of `authors` drawn with block characters, the months
+/// above them and Monday, Wednesday and Friday to their left
+pub fn text (authors: &[Activity], calendar: &Calendar) -> String
+{
+    let most = most(authors);
+    // Each month named above the week it starts in, where there is room
+    let mut months = vec![' '; calendar.weeks + 3];
+    let mut free = 0;
+    for week in 0..calendar.weeks
+    {
+        let month = date::format_month(calendar.day(week, 0) * DAY);
+        let started = week == 0 || month != date::format_month(calendar.day(week - 1, 0) * DAY);
+        if started && week >= free {
+            months.splice(week..week + 3, month.chars());
+            free = week + 4;
+        }
+    }
+    let months: String = months.into_iter().collect();
+
+    let mut out = String::new();
+    for author in authors
+    {
+        out.push_str(&format!("{} <{}>: {} commits\n", author.name, author.email, author.commits()));
+        out.push_str(&format!("    {}\n", months.trim_end()));
+        for weekday in 0..7
+        {
+            out.push_str(match weekday {
+                1 => "Mon ",
+                3 => "Wed ",
+                5 => "Fri ",
+                _ => "    ",
+            });
+            for week in 0..calendar.weeks
+            {
+                let commits = author.days.get(&calendar.day(week, weekday)).copied().unwrap_or(0);
+                out.push(SHADES[shade(commits, most)]);
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The calendars of `authors` as a page of HTML, each day telling its
+/// date and commits when pointed at
+pub fn html (authors: &[Activity], calendar: &Calendar, title: &str) -> String
+{
+    let most = most(authors);
+    let mut out = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+                           <style>\n\
+                           body {{ font-family: sans-serif; }}\n\
+                           .calendar {{ display: grid; grid-template-rows: repeat(7, 11px); grid-auto-flow: column; \
+                           grid-auto-columns: 11px; gap: 2px; margin-bottom: 24px; }}\n\
+                           .calendar div {{ border-radius: 2px; }}\n\
+                           </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+                          escape(title), escape(title));
+    for author in authors
+    {
+        out.push_str(&format!("<h2>{} &lt;{}&gt;: {} commits</h2>\n<div class=\"calendar\">\n",
+                              escape(&author.name), escape(&author.email), author.commits()));
+        for week in 0..calendar.weeks
+        {
+            for weekday in 0..7
+            {
+                let day = calendar.day(week, weekday);
+                let commits = author.days.get(&day).copied().unwrap_or(0);
+                out.push_str(&format!("<div style=\"background: {}\" title=\"{}: {} commits\"></div>\n",
+                                      COLORS[shade(commits, most)], date::format_date(day * DAY), commits));
+            }
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape (text: &str) -> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,144 @@
+//! Remembering finished files while a long run is going
+//!
+//! Every report is appended to `.git/whoknows-cache/checkpoint` as soon as
+//! its file is done, so a run that crashes or gets interrupted can be
+//! resumed with `--resume`. The first line identifies the run; a checkpoint
+//! left by a run with other options, paths or a different HEAD is ignored.
+//! Records only count once their closing line has been written, which
+//! makes a record cut short by a crash harmless.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use whoknows::blame::{quote, unquote};
+use whoknows::cache::{self, Cache};
+use whoknows::{Error, FileReport, Observer};
+
+const HEADER: &str = "git-whoknows checkpoint 1";
+const FILE: &str = "file\t";
+const END: &str = "end";
+
+pub struct Checkpoint {
+    path: PathBuf,
+    run: String,
+    out: Mutex<Option<File>>,
+}
+
+impl Checkpoint {
+    /// The checkpoint of the run identified by `run`
+    pub fn new (cache: &Cache, run: &str) -> Checkpoint
+    {
+        Checkpoint {
+            path: cache.dir().join("checkpoint"),
+            run: run.to_string(),
+            out: Mutex::new(None),
+        }
+    }
+
+    /// Reports of the files a previous attempt at this run finished
+    pub fn load (&self) -> HashMap<PathBuf, FileReport>
+    {
+        let mut done = HashMap::new();
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(_) => return done,
+        };
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) || lines.next() != Some(self.run.as_str()) {
+            return done;
+        }
+
+        while let Some(line) = lines.next() {
+            let path = match line.strip_prefix(FILE) {
+                Some(path) => PathBuf::from(unquote(path).as_ref()),
+                None => break,
+            };
+            let mut records = Vec::new();
+            let mut complete = false;
+            for line in lines.by_ref()
+            {
+                if line == END {
+                    complete = true;
+                    break;
+                }
+                records.push(line);
+            }
+            // Only the last record can have been cut short
+            if !complete {
+                break;
+            }
+            if let Some(report) = cache::decode(records.into_iter(), &path) {
+                done.insert(path, report);
+            }
+        }
+        done
+    }
+
+    /// Start a new checkpoint holding `done`, replacing any older one
+    pub fn start (&self, done: &[&FileReport]) -> io::Result<()>
+    {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        writeln!(out, "{}\n{}", HEADER, self.run)?;
+        for report in done
+        {
+            write_record(&mut out, report)?;
+        }
+        *self.out.lock().unwrap() = Some(out);
+        Ok(())
+    }
+
+    /// The run is complete, nothing is left to resume
+    pub fn remove (&self)
+    {
+        *self.out.lock().unwrap() = None;
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn write_record (out: &mut File, report: &FileReport) -> io::Result<()>
+{
+    write!(out, "{}{}\n{}{}\n", FILE, quote(&report.path), cache::encode(report), END)
+}
+
+/// Passes progress on to `inner` and appends every finished file to the
+/// checkpoint
+pub struct Checkpointing<'a> {
+    pub inner: &'a dyn Observer,
+    pub checkpoint: &'a Checkpoint,
+}
+
+impl<'a> Observer for Checkpointing<'a> {
+    fn started(&self, path: &Path) {
+        self.inner.started(path);
+    }
+
+    fn finished(&self, path: &Path) {
+        self.inner.finished(path);
+    }
+
+    fn completed(&self, report: &FileReport) {
+        if let Some(out) = self.checkpoint.out.lock().unwrap().as_mut() {
+            // Only resuming suffers from a checkpoint that cannot be written
+            let _ = write_record(out, report);
+        }
+        self.inner.completed(report);
+    }
+
+    fn failed(&self, path: &Path, error: &Error) {
+        self.inner.failed(path, error);
+    }
+
+    fn cached(&self, path: &Path, hit: bool) {
+        self.inner.cached(path, hit);
+    }
+
+    fn cancelled(&self) -> bool {
+        self.inner.cancelled()
+    }
+}
@@ -0,0 +1,133 @@
+//! Reports on the clipboard as well as on stdout
+//!
+//! While copying, the output keeps a copy of everything written to it, so
+//! the report still appears as it is written. In the end that copy, with
+//! its tables in Markdown unless plain text was asked for, is handed to the
+//! clipboard tool of the system: pbcopy, wl-copy, xclip, xsel or clip.exe.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use whoknows::Error;
+
+use crate::output::Output;
+use crate::table;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CopyFormat {
+    Markdown,
+    Text,
+}
+
+impl FromStr for CopyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CopyFormat, String> {
+        match s {
+            "markdown" => Ok(CopyFormat::Markdown),
+            "text" => Ok(CopyFormat::Text),
+            _ => Err(format!("unknown copy format '{}'", s)),
+        }
+    }
+}
+
+/// Keep everything written to `out` from now on, to be copied by `copy`
+pub fn start (out: &mut Output)
+{
+    table::record();
+    out.keep();
+}
+
+/// Put `kept`, what was written since `start`, on the clipboard in
+/// `format`
+pub fn copy (kept: &[u8], format: CopyFormat, quiet: bool) -> Result<(), Error>
+{
+    let tables = table::recorded();
+    let text = String::from_utf8_lossy(kept);
+    let text = match format {
+        CopyFormat::Markdown => markdown(&text, &tables),
+        CopyFormat::Text => text.into_owned(),
+    };
+    let tool = put(&text)?;
+    if !quiet {
+        eprintln!("copied the report to the clipboard with {}", tool);
+    }
+    Ok(())
+}
+
+/// `text` with each of `tables`, given as rendered and in Markdown,
+/// replaced by its Markdown
+fn markdown (text: &str, tables: &[(String, String)]) -> String
+{
+    let mut out = String::new();
+    let mut rest = text;
+    for (rendered, markdown) in tables
+    {
+        if let Some(at) = rest.find(rendered.as_str()) {
+            prose(&mut out, &rest[..at]);
+            out.push_str(markdown);
+            out.push('\n');
+            rest = &rest[at + rendered.len()..];
+        }
+    }
+    prose(&mut out, rest);
+    format!("{}\n", out.trim_end())
+}
+
+/// The lines of `text`, which holds no table, kept apart by hard line
+/// breaks and ending the paragraph
+fn prose (out: &mut String, text: &str)
+{
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if !lines.is_empty() {
+        out.push_str(&lines.join("  \n"));
+        out.push_str("\n\n");
+    }
+}
+
+/// Clipboard tools to try, in order, with their arguments
+fn tools () -> Vec<(&'static str, &'static [&'static str])>
+{
+    let mut tools: Vec<(&str, &[&str])> = Vec::new();
+    if cfg!(target_os = "macos") {
+        tools.push(("pbcopy", &[]));
+    }
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(("wl-copy", &[]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        tools.push(("xclip", &["-selection", "clipboard"]));
+        tools.push(("xsel", &["--clipboard", "--input"]));
+    }
+    // Windows, also from WSL
+    tools.push(("clip.exe", &[]));
+    tools
+}
+
+/// Put `text` on the clipboard, with the first tool there is; the name
+/// of that tool
+fn put (text: &str) -> Result<&'static str, Error>
+{
+    for (tool, args) in tools()
+    {
+        let mut child = match Command::new(tool).args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::Export(format!("cannot run {}: {}", tool, e))),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())
+                .map_err(|e| Error::Export(format!("cannot write to {}: {}", tool, e)))?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(Error::Export(format!("{} failed with {}", tool, status)));
+        }
+        return Ok(tool);
+    }
+    Err(Error::Export("no clipboard tool found, such as pbcopy, wl-copy, xclip or xsel".to_string()))
+}
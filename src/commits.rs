@@ -0,0 +1,217 @@
+//! Commit metadata beyond what blame reports
+//!
+//! With the git backend the objects are read from one long-lived
+//! `git cat-file --batch` process rather than a `git show` per commit, which
+//! would dominate the runtime of repository wide analyses. Every commit is
+//! only looked up once per `Commits`.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use git2::{Oid, Repository};
+
+use crate::blame::Backend;
+use crate::error::Error;
+use crate::platform;
+
+pub struct Commit {
+    pub oid: Oid,
+    pub parents: Vec<Oid>,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author time, seconds since the epoch
+    pub time: i64,
+    /// Offset of the author's timezone from UTC, in minutes
+    pub tz: i32,
+    pub message: String,
+}
+
+impl Commit {
+    /// First line of the message
+    pub fn summary (&self) -> &str
+    {
+        self.message.lines().next().unwrap_or("").trim()
+    }
+
+    pub fn is_merge (&self) -> bool
+    {
+        self.parents.len() > 1
+    }
+
+    /// `Key: value` lines of the last paragraph of the message
+    pub fn trailers (&self) -> Vec<(&str, &str)>
+    {
+        let body = self.message.trim_end();
+        let paragraph = match body.rfind("\n\n") {
+            Some(i) => &body[i + 2..],
+            None => return Vec::new(),
+        };
+        let mut trailers = Vec::new();
+        for line in paragraph.lines()
+        {
+            match line.split_once(": ") {
+                Some((key, value)) if !key.is_empty() && !key.contains(' ') => {
+                    trailers.push((key, value.trim()));
+                }
+                _ => return Vec::new(),
+            }
+        }
+        trailers
+    }
+}
+
+enum Source<'r> {
+    Libgit2(&'r Repository),
+    CatFile(CatFile),
+}
+
+/// Looks up commits through the same means blame is computed with
+pub struct Commits<'r> {
+    source: Source<'r>,
+    seen: HashMap<Oid, Commit>,
+}
+
+impl<'r> Commits<'r> {
+    pub fn new (repo: &'r Repository, backend: Backend) -> Result<Commits<'r>, Error>
+    {
+        let source = match backend {
+            Backend::Libgit2 => Source::Libgit2(repo),
+            Backend::Git => Source::CatFile(CatFile::spawn(repo)?),
+        };
+        Ok(Commits {
+            source,
+            seen: HashMap::new(),
+        })
+    }
+
+    pub fn get (&mut self, oid: Oid) -> Result<&Commit, Error>
+    {
+        if !self.seen.contains_key(&oid) {
+            let commit = match &mut self.source {
+                Source::Libgit2(repo) => from_libgit2(&repo.find_commit(oid)?),
+                Source::CatFile(cat) => cat.commit(oid)?,
+            };
+            self.seen.insert(oid, commit);
+        }
+        Ok(&self.seen[&oid])
+    }
+}
+
+fn from_libgit2 (commit: &git2::Commit) -> Commit
+{
+    let author = commit.author();
+    Commit {
+        oid: commit.id(),
+        parents: commit.parent_ids().collect(),
+        author_name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+        author_email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+        time: author.when().seconds(),
+        tz: author.when().offset_minutes(),
+        message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+    }
+}
+
+/// A `git cat-file --batch` process answering one object at a time
+struct CatFile {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+/// The `git cat-file --batch` that the git backend looks up commits with
+pub fn command (repo: &Repository) -> Command
+{
+    let mut cmd = Command::new(platform::git());
+    cmd.arg("--git-dir").arg(repo.path())
+        .arg("cat-file").arg("--batch");
+    cmd
+}
+
+impl CatFile {
+    fn spawn (repo: &Repository) -> Result<CatFile, Error>
+    {
+        let mut child = command(repo)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::Blame(format!("could not run git: {}", e)))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(CatFile { child, stdin, stdout })
+    }
+
+    fn commit (&mut self, oid: Oid) -> Result<Commit, Error>
+    {
+        writeln!(self.stdin, "{}", oid)?;
+        self.stdin.flush()?;
+
+        // <oid> <type> <size>, or <oid> missing
+        let mut header = String::new();
+        self.stdout.read_line(&mut header)?;
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let size = match fields.as_slice() {
+            [_, "commit", size] => size.parse::<usize>()
+                .map_err(|_| Error::Blame(format!("unexpected cat-file output: {}", header.trim())))?,
+            _ => return Err(Error::Blame(format!("{} is not a commit", oid))),
+        };
+
+        // The object is followed by a newline
+        let mut body = vec![0; size + 1];
+        self.stdout.read_exact(&mut body)?;
+        body.pop();
+        Ok(parse_commit(oid, &String::from_utf8_lossy(&body)))
+    }
+}
+
+impl Drop for CatFile {
+    fn drop(&mut self) {
+        // It is only ever waiting for the next request
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A commit object: headers, a blank line, then the message
+fn parse_commit (oid: Oid, text: &str) -> Commit
+{
+    let (headers, message) = text.split_once("\n\n").unwrap_or((text, ""));
+    let mut commit = Commit {
+        oid,
+        parents: Vec::new(),
+        author_name: String::new(),
+        author_email: String::new(),
+        time: 0,
+        tz: 0,
+        message: message.to_string(),
+    };
+    for line in headers.lines()
+    {
+        if let Some(parent) = line.strip_prefix("parent ") {
+            commit.parents.extend(Oid::from_str(parent.trim()).ok());
+        } else if let Some(author) = line.strip_prefix("author ") {
+            // Name <email> <time> <tz>
+            if let (Some(open), Some(close)) = (author.find('<'), author.rfind('>')) {
+                commit.author_name = author[..open].trim().to_string();
+                commit.author_email = author[open + 1..close].to_string();
+                let mut when = author[close + 1..].split_whitespace();
+                commit.time = when.next().and_then(|t| t.parse().ok()).unwrap_or(0);
+                commit.tz = when.next().and_then(parse_tz).unwrap_or(0);
+            }
+        }
+    }
+    commit
+}
+
+/// Minutes east of UTC of a `+hhmm` or `-hhmm` timezone
+fn parse_tz (tz: &str) -> Option<i32>
+{
+    let sign = match tz.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits: i32 = tz.get(1..5)?.parse().ok()?;
+    Some(sign * (digits / 100 * 60 + digits % 100))
+}
@@ -0,0 +1,113 @@
+//! Weighing lines by how much logic they hold
+//!
+//! Two hundred lines of nested conditions take more knowing than two
+//! hundred lines of struct fields. Every line is worth one plus half its
+//! nesting depth plus one for each branch it takes, a proxy that needs no
+//! parser: depth comes from indentation and branches from keywords.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use git2::{Oid, Repository};
+
+use crate::analyze::{blame_regions, blamed_text, Options};
+use crate::error::Error;
+use crate::FileReport;
+
+/// Seconds in a day
+const DAY: f64 = 86_400.0;
+
+/// Words that branch in common languages
+const BRANCHES: &[&str] = &[
+    "if", "else", "elif", "elsif", "unless", "for", "foreach", "while", "until", "loop",
+    "match", "switch", "case", "when", "catch", "except", "rescue",
+];
+
+/// The worth of every line of `source`; blank lines are worth nothing
+pub fn line_weights (source: &str) -> Vec<f64>
+{
+    // The indentation of one level is the smallest one in the file
+    let unit = source.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .filter(|&spaces| spaces > 0)
+        .min()
+        .unwrap_or(4);
+    source.lines()
+        .map(|line| {
+            let code = line.trim_start();
+            if code.is_empty() {
+                return 0.0;
+            }
+            let indent = &line[..line.len() - code.len()];
+            let tabs = indent.chars().filter(|&c| c == '\t').count();
+            let depth = tabs + (indent.len() - tabs) / unit;
+            1.0 + depth as f64 / 2.0 + branches(code) as f64
+        })
+        .collect()
+}
+
+/// Branching keywords and short-circuiting operators in one line
+fn branches (code: &str) -> usize
+{
+    let words = code.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| BRANCHES.contains(word))
+        .count();
+    words + code.matches("&&").count() + code.matches("||").count()
+}
+
+/// How much one line of every commit of a file is worth on average
+pub struct Weights {
+    commits: HashMap<Oid, f64>,
+}
+
+impl Weights {
+    /// The weights of the lines of `path` as blamed with `opts`
+    pub fn of (repo: &Repository, path: &Path, opts: &Options) -> Result<Weights, Error>
+    {
+        let weights = line_weights(&blamed_text(repo, path, opts)?);
+        let mut sums: HashMap<Oid, (f64, usize)> = HashMap::new();
+        for region in blame_regions(repo, path, opts)?
+        {
+            let first = region.line - 1;
+            let worth: f64 = weights.iter().skip(first).take(region.lines).sum();
+            let sum = sums.entry(region.commit).or_insert((0.0, 0));
+            sum.0 += worth;
+            sum.1 += region.lines;
+        }
+        let commits = sums.into_iter()
+            .filter(|&(_, (_, lines))| lines > 0)
+            .map(|(oid, (worth, lines))| (oid, worth / lines as f64))
+            .collect();
+        Ok(Weights { commits })
+    }
+
+    /// What one line of `commit` is worth, 1 for a commit without lines in
+    /// the file
+    pub fn of_commit (&self, commit: Oid) -> f64
+    {
+        self.commits.get(&commit).copied().unwrap_or(1.0)
+    }
+
+    /// Score the owners of `file`, the file these are the weights of or a
+    /// part of it, by the worth of their lines, which halves every
+    /// `half_life` days of age as of `now` if given
+    pub fn apply (&self, file: &mut FileReport, half_life: Option<f64>, now: i64)
+    {
+        for owner in file.owners.values_mut()
+        {
+            owner.score = owner.commits.iter()
+                .map(|(oid, c)| {
+                    let worth = c.lines as f64 * self.of_commit(*oid);
+                    match half_life {
+                        Some(half_life) => {
+                            let age = (now - c.time).max(0) as f64 / DAY;
+                            worth * 0.5f64.powf(age / half_life)
+                        }
+                        None => worth,
+                    }
+                })
+                .sum();
+        }
+    }
+}
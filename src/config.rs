@@ -0,0 +1,1055 @@
+//! Shared defaults from configuration files
+//!
+//! Read from `~/.config/git-whoknows/config.toml`, the `[whoknows]` section
+//! of the system and global git config, `.git-whoknows.toml` at the root of
+//! the work tree and the `[whoknows]` section of the repository's own git
+//! config, in that order, later values winning. `WHOKNOWS_MAILMAP` and
+//! `WHOKNOWS_EXCLUDE`, a comma separated list, win over those, and options
+//! given on the command line or their `WHOKNOWS_*` variables win over all
+//! of them.
+//!
+//! ```toml
+//! format = "table"
+//! backend = "git"
+//! decay = 365
+//! jobs = 4
+//! # Blame results shared by every clone and worktree on this host
+//! cache_dir = "/var/cache/git-whoknows"
+//! mailmap = true
+//! hide_emails = false
+//! exclude = ["vendor/", "*.lock"]
+//! exclude_authors = ["\\[bot\\]", "^renovate"]
+//! tests = ["integration/", "*_check.sh"]
+//!
+//! [identities]
+//! "alice@old-corp.com" = "Alice Smith <alice@corp.com>"
+//!
+//! [aliases]
+//! "Alice Smith <alice@corp.com>" = [
+//!     "12345+alice@users.noreply.github.com",
+//!     "Alice Jones <alice@gmail.com>",
+//! ]
+//!
+//! [teams]
+//! core = ["alice@corp.com", "bob@corp.com"]
+//!
+//! [thresholds]
+//! min_bus_factor = 2
+//! max_single_owner = 90
+//! # Only warned about by `check`
+//! warn_min_bus_factor = 3
+//!
+//! # What it takes to be shown as a maintainer or regular by --roles
+//! [roles.maintainer]
+//! min_commits = 5
+//! min_share = 25
+//! min_span_days = 90
+//!
+//! # Labels by the least share of a file, as shown by --expertise
+//! [expertise]
+//! expert = 40
+//! contributor = 10
+//! visitor = 0
+//!
+//! # Settings for the files below one path, later rules winning
+//! [rules."src/crypto/**".thresholds]
+//! min_bus_factor = 3
+//! # Fails `check` unless someone on the --roster owns at least 20%
+//! require_owner = 20
+//!
+//! [rules."docs/**"]
+//! exclude = ["*.svg"]
+//!
+//! # Repositories that `workspace` combines, by name, relative to the file
+//! [workspace]
+//! api = "../api"
+//! web = "../web"
+//!
+//! # Where `report --email` sends to, the password being in
+//! # WHOKNOWS_SMTP_PASSWORD
+//! [email]
+//! smtp = "smtps://smtp.corp.com:465"
+//! user = "whoknows@corp.com"
+//! from = "Knowledge report <whoknows@corp.com>"
+//! to = ["engineering@corp.com"]
+//!
+//! # Options for --profile review, keyed by their long names
+//! [profile.review]
+//! since = "6 months ago"
+//! no-merges = true
+//! exclude-author = ["\\[bot\\]"]
+//! F = true
+//! ```
+//!
+//! In git config the scalar settings and `exclude`, which may be given
+//! several times, are spelled `whoknows.format`, `whoknows.backend`,
+//! `whoknows.decayDays`, `whoknows.jobs`, `whoknows.cacheDir`, `whoknows.mailmap`,
+//! `whoknows.hideEmails`, `whoknows.exclude`, `whoknows.excludeAuthor` and
+//! `whoknows.tests`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use git2::{ConfigLevel, Repository};
+
+use crate::blame::Backend;
+use crate::{FileReport, Owner};
+use crate::error::Error;
+use crate::glob::{Ignore, Pattern};
+use crate::mailmap::Mailmap;
+use crate::regex::Regex;
+use crate::ownership::percent;
+use crate::toml::{self, Table, Value};
+
+/// Name of the configuration file in the work tree
+pub const REPO_FILE: &str = ".git-whoknows.toml";
+
+/// Name of the file in the work tree listing paths to leave out, in
+/// `.gitignore` syntax
+pub const IGNORE_FILE: &str = ".whoknowsignore";
+
+/// Limits beyond which a file counts as a knowledge risk
+#[derive(Clone, Default)]
+pub struct Thresholds {
+    pub min_bus_factor: Option<usize>,
+    /// Percentage of the lines of a file owned by a single author
+    pub max_single_owner: Option<f64>,
+    /// Limits that only warn, for rolling out a policy before it fails
+    /// anything
+    pub warn_min_bus_factor: Option<usize>,
+    pub warn_max_single_owner: Option<f64>,
+    /// Percentage of the lines of a file that at least one owner on the
+    /// roster has to own, so that someone still around knows it
+    pub require_owner: Option<f64>,
+}
+
+impl Thresholds {
+    pub fn is_empty (&self) -> bool
+    {
+        self.min_bus_factor.is_none() && self.max_single_owner.is_none()
+            && self.warn_min_bus_factor.is_none() && self.warn_max_single_owner.is_none()
+            && self.require_owner.is_none()
+    }
+
+    /// Take every limit `other` sets
+    pub fn override_with (&mut self, other: &Thresholds)
+    {
+        self.min_bus_factor = other.min_bus_factor.or(self.min_bus_factor);
+        self.max_single_owner = other.max_single_owner.or(self.max_single_owner);
+        self.warn_min_bus_factor = other.warn_min_bus_factor.or(self.warn_min_bus_factor);
+        self.warn_max_single_owner = other.warn_max_single_owner.or(self.warn_max_single_owner);
+        self.require_owner = other.require_owner.or(self.require_owner);
+    }
+
+    /// The limits that warn, as thresholds of their own
+    pub fn warnings (&self) -> Thresholds
+    {
+        Thresholds {
+            min_bus_factor: self.warn_min_bus_factor,
+            max_single_owner: self.warn_max_single_owner,
+            ..Thresholds::default()
+        }
+    }
+
+    /// Whether too few people own `file`; an empty file has nobody to
+    /// lose
+    pub fn bus_factor_too_low (&self, file: &FileReport) -> bool
+    {
+        self.min_bus_factor.is_some_and(|min| file.lines() > 0 && file.bus_factor() < min)
+    }
+
+    pub fn single_owner_too_high (&self, file: &FileReport) -> bool
+    {
+        self.max_single_owner.is_some_and(|max| {
+            file.ranked_owners().iter().any(|o| percent(o.lines(), file.lines()) > max)
+        })
+    }
+}
+
+/// The part an author plays in a file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Maintainer,
+    Regular,
+    Visitor,
+}
+
+impl fmt::Display for Role {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        f.write_str(match self {
+            Role::Maintainer => "maintainer",
+            Role::Regular => "regular",
+            Role::Visitor => "visitor",
+        })
+    }
+}
+
+/// What it takes to have a role, every limit set having to be met
+#[derive(Clone, Default)]
+pub struct RoleRule {
+    /// Commits the author's lines of the file survive from
+    pub min_commits: Option<usize>,
+    /// Percentage of the lines of the file
+    pub min_share: Option<f64>,
+    /// Days between the first and the last of those commits
+    pub min_span_days: Option<f64>,
+}
+
+impl RoleRule {
+    fn override_with (&mut self, other: &RoleRule)
+    {
+        self.min_commits = other.min_commits.or(self.min_commits);
+        self.min_share = other.min_share.or(self.min_share);
+        self.min_span_days = other.min_span_days.or(self.min_span_days);
+    }
+
+    /// Whether `owner` of `file` meets every limit, those not set taken
+    /// from `default`
+    fn admits (&self, default: &RoleRule, owner: &Owner, file: &FileReport) -> bool
+    {
+        let times = || owner.commits.values().map(|c| c.time);
+        let span = times().max().unwrap_or(0) - times().min().unwrap_or(0);
+        self.min_commits.or(default.min_commits).is_none_or(|min| owner.commits.len() >= min)
+            && self.min_share.or(default.min_share).is_none_or(|min| percent(owner.lines(), file.lines()) >= min)
+            && self.min_span_days.or(default.min_span_days).is_none_or(|min| span as f64 / 86_400.0 >= min)
+    }
+}
+
+/// Limits of a maintainer unless configured; zero lifts one
+const MAINTAINER: RoleRule = RoleRule { min_commits: Some(3), min_share: Some(20.0), min_span_days: Some(30.0) };
+
+/// Limits of a regular contributor unless configured
+const REGULAR: RoleRule = RoleRule { min_commits: Some(2), min_share: Some(5.0), min_span_days: None };
+
+/// The rules by which authors are told apart; whoever meets neither is a
+/// visitor
+#[derive(Clone, Default)]
+pub struct Roles {
+    pub maintainer: RoleRule,
+    pub regular: RoleRule,
+}
+
+impl Roles {
+    fn override_with (&mut self, other: &Roles)
+    {
+        self.maintainer.override_with(&other.maintainer);
+        self.regular.override_with(&other.regular);
+    }
+
+    pub fn role (&self, owner: &Owner, file: &FileReport) -> Role
+    {
+        if self.maintainer.admits(&MAINTAINER, owner, file) {
+            Role::Maintainer
+        } else if self.regular.admits(&REGULAR, owner, file) {
+            Role::Regular
+        } else {
+            Role::Visitor
+        }
+    }
+}
+
+/// Levels of expertise unless configured, by the least share of a file
+const EXPERTISE: [(&str, f64); 3] = [("expert", 40.0), ("contributor", 10.0), ("visitor", 0.0)];
+
+/// Names for how much of a file someone owns, for readers of a report
+/// who would rather not interpret percentages
+#[derive(Clone, Default)]
+pub struct Expertise {
+    /// Label and least percentage of the lines, most first; the defaults
+    /// if empty
+    pub levels: Vec<(String, f64)>,
+}
+
+impl Expertise {
+    /// The label of the highest level `share` percent of a file reaches,
+    /// if any
+    pub fn label (&self, share: f64) -> Option<&str>
+    {
+        if self.levels.is_empty() {
+            return EXPERTISE.iter().find(|(_, min)| share >= *min).map(|(label, _)| *label);
+        }
+        self.levels.iter().find(|(_, min)| share >= *min).map(|(label, _)| label.as_str())
+    }
+
+    /// The label of `owner` of `file`, empty if no level is reached
+    pub fn of (&self, owner: &Owner, file: &FileReport) -> &str
+    {
+        self.label(percent(owner.lines(), file.lines())).unwrap_or("")
+    }
+}
+
+/// Settings for the files a pattern matches
+#[derive(Clone)]
+pub struct Rule {
+    pub pattern: Pattern,
+    /// Left out when directories are expanded, if also matched by `pattern`
+    pub exclude: Vec<Pattern>,
+    /// Override the repository wide thresholds
+    pub thresholds: Thresholds,
+}
+
+/// Where a code hosting service is and what its users are called
+#[derive(Clone, Default)]
+pub struct Forge {
+    /// Base URL of its API
+    pub url: Option<String>,
+    /// User names by email
+    pub users: BTreeMap<String, String>,
+}
+
+impl Forge {
+    fn override_with (&mut self, other: Forge)
+    {
+        self.url = other.url.or(self.url.take());
+        self.users.extend(other.users);
+    }
+
+    /// The user name configured for `email`, if any
+    pub fn user (&self, email: &str) -> Option<&str>
+    {
+        self.users.iter()
+            .find(|(e, _)| e.eq_ignore_ascii_case(email))
+            .map(|(_, user)| user.as_str())
+    }
+}
+
+/// How reports are mailed
+#[derive(Clone, Default)]
+pub struct Email {
+    /// `smtp://` or `smtps://` URL of the server
+    pub smtp: Option<String>,
+    /// User to log in as, with the password in `WHOKNOWS_SMTP_PASSWORD`
+    pub user: Option<String>,
+    pub from: Option<String>,
+    pub to: Vec<String>,
+}
+
+impl Email {
+    fn override_with (&mut self, other: Email)
+    {
+        self.smtp = other.smtp.or(self.smtp.take());
+        self.user = other.user.or(self.user.take());
+        self.from = other.from.or(self.from.take());
+        if !other.to.is_empty() {
+            self.to = other.to;
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Config {
+    pub format: Option<String>,
+    pub backend: Option<Backend>,
+    pub decay: Option<f64>,
+    pub jobs: Option<usize>,
+    /// Where the entries of the blame cache are shared
+    pub cache_dir: Option<PathBuf>,
+    pub mailmap: Option<bool>,
+    /// Leave the emails of authors out of every report
+    pub hide_emails: Option<bool>,
+    /// Paths left out when directories are expanded
+    pub exclude: Vec<Pattern>,
+    /// The patterns of `.whoknowsignore`, which does the same
+    pub ignore: Ignore,
+    /// Authors left out of every report, by name or email
+    pub exclude_authors: Vec<Regex>,
+    /// Paths of tests, besides those that follow the usual conventions
+    pub tests: Vec<Pattern>,
+    pub identities: Mailmap,
+    /// Member emails by team name
+    pub teams: BTreeMap<String, Vec<String>>,
+    pub thresholds: Thresholds,
+    pub roles: Roles,
+    pub expertise: Expertise,
+    /// In order, later rules overriding earlier ones
+    pub rules: Vec<Rule>,
+    /// Command line options by profile name, see `profile_args`
+    pub profiles: BTreeMap<String, Table>,
+    pub github: Forge,
+    pub gitlab: Forge,
+    pub gerrit: Forge,
+    pub email: Email,
+    /// Paths of the repositories of a workspace by their names
+    pub workspace: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// The user configuration overridden by that of the repository
+    pub fn load (repo: &Repository) -> Result<Config, Error>
+    {
+        let mut config = Config::default();
+        if let Some(path) = user_file() {
+            config.merge(Config::from_file(&path)?);
+        }
+        config.merge(Config::from_git(repo, false)?);
+        if let Some(workdir) = repo.workdir() {
+            config.merge(Config::from_file(&workdir.join(REPO_FILE))?);
+            config.ignore = Ignore::from_file(&workdir.join(IGNORE_FILE))?;
+        }
+        config.merge(Config::from_git(repo, true)?);
+        config.merge(Config::from_env()?);
+        Ok(config)
+    }
+
+    /// The settings of `WHOKNOWS_*` variables that have no command line
+    /// option
+    pub fn from_env () -> Result<Config, Error>
+    {
+        let mut config = Config::default();
+        if let Ok(value) = std::env::var("WHOKNOWS_MAILMAP") {
+            config.set_git("mailmap", &value)
+                .map_err(|e| Error::Config(format!("WHOKNOWS_MAILMAP: {}", e)))?;
+        }
+        if let Ok(value) = std::env::var("WHOKNOWS_EXCLUDE") {
+            config.exclude = value.split(',')
+                .filter(|p| !p.trim().is_empty())
+                .map(Pattern::new)
+                .collect();
+        }
+        Ok(config)
+    }
+
+    /// The `[whoknows]` section of the repository's own git config when
+    /// `local`, otherwise that of the levels below it
+    pub fn from_git (repo: &Repository, local: bool) -> Result<Config, Error>
+    {
+        let mut config = Config::default();
+        for (name, value) in git_settings(repo, local)?
+        {
+            config.set_git(&name["whoknows.".len()..], &value)
+                .map_err(|e| Error::Config(format!("git config {}: {}", name, e)))?;
+        }
+        Ok(config)
+    }
+
+    /// Apply one git config setting; git has already lowercased `key`, and
+    /// values are read the way git reads them
+    fn set_git (&mut self, key: &str, value: &str) -> Result<(), String>
+    {
+        let number = || value.trim().parse::<f64>().ok();
+        match key {
+            "format" => self.format = Some(value.to_string()),
+            "backend" => self.backend = Some(value.parse()?),
+            "decaydays" => match number() {
+                Some(n) if n > 0.0 => self.decay = Some(n),
+                _ => return Err("must be a positive number".to_string()),
+            },
+            "jobs" => match value.trim().parse::<usize>() {
+                Ok(n) if n > 0 => self.jobs = Some(n),
+                _ => return Err("must be a positive integer".to_string()),
+            },
+            "cachedir" => self.cache_dir = Some(PathBuf::from(value)),
+            "mailmap" => self.mailmap = Some(
+                git2::Config::parse_bool(value).map_err(|e| e.message().to_string())?),
+            "hideemails" => self.hide_emails = Some(
+                git2::Config::parse_bool(value).map_err(|e| e.message().to_string())?),
+            "exclude" => self.exclude.push(Pattern::new(value)),
+            "excludeauthor" => self.exclude_authors.push(Regex::new(value)?),
+            "tests" => self.tests.push(Pattern::new(value)),
+            _ => return Err("unknown setting".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Read `path`; a missing file is an empty configuration
+    pub fn from_file (path: &Path) -> Result<Config, Error>
+    {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text)
+                .map_err(|e| Error::Config(format!("{}: {}", path.display(), e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse (text: &str) -> Result<Config, String>
+    {
+        let table = toml::parse(text)?;
+        let mut config = Config::default();
+        for (key, value) in &table
+        {
+            match key.as_str() {
+                "format" => config.format = Some(string(key, value)?.to_string()),
+                "backend" => config.backend = Some(string(key, value)?.parse()?),
+                "decay" => config.decay = Some(positive(key, value)?),
+                "jobs" => config.jobs = Some(count(key, value)?),
+                "cache_dir" => config.cache_dir = Some(PathBuf::from(string(key, value)?)),
+                "mailmap" => config.mailmap = Some(boolean(key, value)?),
+                "hide_emails" => config.hide_emails = Some(boolean(key, value)?),
+                "exclude" => config.exclude = strings(key, value)?.iter()
+                    .map(|p| Pattern::new(p))
+                    .collect(),
+                "exclude_authors" => config.exclude_authors = strings(key, value)?.iter()
+                    .map(|p| Regex::new(p))
+                    .collect::<Result<_, _>>()?,
+                "tests" => config.tests = strings(key, value)?.iter()
+                    .map(|p| Pattern::new(p))
+                    .collect(),
+                "identities" => config.identities.extend(&identities(table_of(key, value)?)?),
+                "aliases" => config.identities.extend(&aliases(table_of(key, value)?)?),
+                "teams" => {
+                    for (team, members) in table_of(key, value)?
+                    {
+                        config.teams.insert(team.clone(), strings(team, members)?);
+                    }
+                }
+                "thresholds" => config.thresholds = thresholds(table_of(key, value)?)?,
+                "roles" => config.roles = roles(table_of(key, value)?)?,
+                "expertise" => config.expertise = expertise(table_of(key, value)?)?,
+                "profile" => {
+                    for (name, options) in table_of(key, value)?
+                    {
+                        let options = table_of(name, options)?;
+                        profile_args(name, options)?;
+                        config.profiles.insert(name.clone(), options.clone());
+                    }
+                }
+                "github" => config.github = forge(key, table_of(key, value)?)?,
+                "gitlab" => config.gitlab = forge(key, table_of(key, value)?)?,
+                "gerrit" => config.gerrit = forge(key, table_of(key, value)?)?,
+                "email" => config.email = email(table_of(key, value)?)?,
+                "workspace" => {
+                    for (name, path) in table_of(key, value)?
+                    {
+                        config.workspace.insert(name.clone(), string(name, path)?.to_string());
+                    }
+                }
+                "rules" => {
+                    for (pattern, rule) in table_of(key, value)?
+                    {
+                        config.rules.push(parse_rule(pattern, table_of(pattern, rule)?)?);
+                    }
+                }
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Take every value `other` sets
+    pub fn merge (&mut self, other: Config)
+    {
+        self.format = other.format.or(self.format.take());
+        self.backend = other.backend.or(self.backend);
+        self.decay = other.decay.or(self.decay);
+        self.jobs = other.jobs.or(self.jobs);
+        self.cache_dir = other.cache_dir.or(self.cache_dir.take());
+        self.mailmap = other.mailmap.or(self.mailmap);
+        self.hide_emails = other.hide_emails.or(self.hide_emails);
+        self.exclude.extend(other.exclude);
+        self.ignore.extend(other.ignore);
+        self.exclude_authors.extend(other.exclude_authors);
+        self.tests.extend(other.tests);
+        self.identities.extend(&other.identities);
+        self.teams.extend(other.teams);
+        self.thresholds.override_with(&other.thresholds);
+        self.roles.override_with(&other.roles);
+        if !other.expertise.levels.is_empty() {
+            self.expertise = other.expertise;
+        }
+        self.rules.extend(other.rules);
+        self.profiles.extend(other.profiles);
+        self.github.override_with(other.github);
+        self.gitlab.override_with(other.gitlab);
+        self.gerrit.override_with(other.gerrit);
+        self.email.override_with(other.email);
+        self.workspace.extend(other.workspace);
+    }
+
+    pub fn excluded (&self, path: &Path) -> bool
+    {
+        let path = path.to_string_lossy();
+        self.exclude.iter().any(|p| p.matches(&path))
+            || self.ignore.is_ignored(&path)
+            || self.rules.iter()
+                .filter(|rule| rule.pattern.matches(&path))
+                .any(|rule| rule.exclude.iter().any(|p| p.matches(&path)))
+    }
+
+    /// Whether `path` holds tests, by convention or by `tests`
+    pub fn is_test (&self, path: &Path) -> bool
+    {
+        crate::testcode::is_test(&path.to_string_lossy(), &self.tests)
+    }
+
+    /// The thresholds that apply to `path`, after the rules matching it
+    pub fn thresholds_for (&self, path: &Path) -> Thresholds
+    {
+        let path = path.to_string_lossy();
+        let mut thresholds = self.thresholds.clone();
+        for rule in self.rules.iter().filter(|rule| rule.pattern.matches(&path))
+        {
+            thresholds.override_with(&rule.thresholds);
+        }
+        thresholds
+    }
+
+    /// Whether any file has a threshold to meet
+    pub fn has_thresholds (&self) -> bool
+    {
+        !self.thresholds.is_empty() || self.rules.iter().any(|rule| !rule.thresholds.is_empty())
+    }
+
+    /// The team `email` belongs to, if any
+    pub fn team (&self, email: &str) -> Option<&str>
+    {
+        self.teams.iter()
+            .find(|(_, members)| members.iter().any(|m| m.eq_ignore_ascii_case(email)))
+            .map(|(team, _)| team.as_str())
+    }
+}
+
+/// The `whoknows.*` settings of the repository's own git config when
+/// `local`, otherwise those of the levels below it, lower levels first
+fn git_settings (repo: &Repository, local: bool) -> Result<Vec<(String, String)>, Error>
+{
+    let git = repo.config()?.snapshot()?;
+    let entries = git.entries(Some("^whoknows\\."))?;
+    let mut settings = Vec::new();
+    for entry in &entries
+    {
+        let entry = entry?;
+        let level = entry.level() as i32;
+        if (level >= ConfigLevel::Local as i32) != local {
+            continue;
+        }
+        let name = entry.name().unwrap_or("").to_string();
+        // A key without a value is true, as for git's own booleans
+        let value = if entry.has_value() { entry.value().unwrap_or("") } else { "true" };
+        let value = value.to_string();
+        settings.push((level, name, value));
+    }
+    // Lower levels first so that higher ones override them
+    settings.sort_by_key(|(level, _, _)| *level);
+    Ok(settings.into_iter().map(|(_, name, value)| (name, value)).collect())
+}
+
+/// One setting in effect and where it comes from
+pub struct Setting {
+    /// Dotted key as in a configuration file
+    pub key: String,
+    /// The value as it would be written in a configuration file
+    pub value: String,
+    pub source: String,
+}
+
+/// Every setting in effect for `repo`, in the order they are read; a
+/// scalar only where no later source overrides it, list entries from all
+/// sources as they add up
+pub fn settings (repo: &Repository) -> Result<Vec<Setting>, Error>
+{
+    let mut all: Vec<Setting> = Vec::new();
+    let mut add = |key: String, value: String, source: &str| {
+        all.push(Setting { key, value, source: source.to_string() });
+    };
+    let file = |path: &Path, add: &mut dyn FnMut(String, String, &str)| -> Result<(), Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let table = toml::parse(&text).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        let source = path.display().to_string();
+        flatten("", &table, &mut |key, value| add(key, value, &source));
+        Ok(())
+    };
+    let git = |local: bool, add: &mut dyn FnMut(String, String, &str)| -> Result<(), Error> {
+        let source = if local { "git config (repository)" } else { "git config (global)" };
+        for (name, value) in git_settings(repo, local)?
+        {
+            let key = match &name["whoknows.".len()..] {
+                "decaydays" => "decay",
+                "cachedir" => "cache_dir",
+                "excludeauthor" => "exclude_authors",
+                "hideemails" => "hide_emails",
+                key => key,
+            };
+            add(key.to_string(), git_value(&value), source);
+        }
+        Ok(())
+    };
+
+    if let Some(path) = user_file() {
+        file(&path, &mut add)?;
+    }
+    git(false, &mut add)?;
+    if let Some(workdir) = repo.workdir() {
+        file(&workdir.join(REPO_FILE), &mut add)?;
+        let source = workdir.join(IGNORE_FILE).display().to_string();
+        if let Ok(text) = fs::read_to_string(workdir.join(IGNORE_FILE)) {
+            for line in text.lines().map(str::trim_end).filter(|l| !l.is_empty() && !l.starts_with('#'))
+            {
+                add("ignore".to_string(), Value::String(line.to_string()).to_string(), &source);
+            }
+        }
+    }
+    git(true, &mut add)?;
+    if let Ok(value) = std::env::var("WHOKNOWS_MAILMAP") {
+        add("mailmap".to_string(), git_value(&value), "WHOKNOWS_MAILMAP");
+    }
+    if let Ok(value) = std::env::var("WHOKNOWS_EXCLUDE") {
+        // Replaces the patterns of the files rather than adding to them
+        all.retain(|setting| setting.key != "exclude");
+        for pattern in value.split(',').filter(|p| !p.trim().is_empty())
+        {
+            all.push(Setting {
+                key: "exclude".to_string(),
+                value: Value::String(pattern.to_string()).to_string(),
+                source: "WHOKNOWS_EXCLUDE".to_string(),
+            });
+        }
+    }
+
+    // Lists add up, everything else is overridden by later sources
+    let adds_up = |key: &str| {
+        ["exclude", "exclude_authors", "ignore", "tests"].contains(&key) || key.starts_with("rules.")
+    };
+    let mut effective: Vec<Setting> = Vec::new();
+    for setting in all.into_iter().rev()
+    {
+        if adds_up(&setting.key) || !effective.iter().any(|s| s.key == setting.key) {
+            effective.push(setting);
+        }
+    }
+    effective.reverse();
+    Ok(effective)
+}
+
+/// Every value of `table` by its dotted key below `prefix`; arrays of
+/// patterns count as one setting per entry
+fn flatten (prefix: &str, table: &Table, out: &mut dyn FnMut(String, String))
+{
+    for (key, value) in table
+    {
+        let key = if prefix.is_empty() { toml::quote_key(key) } else { format!("{}.{}", prefix, toml::quote_key(key)) };
+        match value {
+            Value::Table(table) => flatten(&key, table, out),
+            Value::Array(items) if ["exclude", "exclude_authors", "tests"].contains(&key.as_str()) => {
+                for item in items
+                {
+                    out(key.clone(), item.to_string());
+                }
+            }
+            value => out(key, value.to_string()),
+        }
+    }
+}
+
+/// A git config value as it would be written in TOML: numbers and
+/// booleans bare, everything else as a string
+fn git_value (value: &str) -> String
+{
+    let value = value.trim();
+    if value.parse::<f64>().is_ok() || value == "true" || value == "false" {
+        value.to_string()
+    } else {
+        Value::String(value.to_string()).to_string()
+    }
+}
+
+/// Set `key`, dotted as in `thresholds.min_bus_factor`, to `value` in the
+/// configuration file at `path`, keeping everything else of it as it is.
+/// `value` is written as given if it is a number, boolean, array or quoted
+/// string and as a string otherwise. Nothing is written unless the result
+/// is a valid configuration.
+pub fn set (path: &Path, key: &str, value: &str) -> Result<(), Error>
+{
+    let invalid = |e: String| Error::Config(format!("{}: {}", path.display(), e));
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut path_keys = toml::key_path(&format!("{}=", key), '=').map_err(|_| invalid(format!("invalid key '{}'", key)))?;
+    let leaf = path_keys.pop().unwrap_or_default();
+    let raw = value.trim();
+    let bare = raw.starts_with('[') || raw.starts_with('"') || raw.starts_with('\'')
+        || raw.parse::<f64>().is_ok() || raw == "true" || raw == "false";
+    let value = if bare { raw.to_string() } else { Value::String(value.to_string()).to_string() };
+    let line = format!("{} = {}", toml::quote_key(&leaf), value);
+
+    // Find the key, or else the end of its table, line by line
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut table: Vec<String> = Vec::new();
+    let mut end_of_table = if path_keys.is_empty() { Some(0) } else { None };
+    let mut found = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            table = toml::key_path(header, ']').unwrap_or_default();
+        } else if table == path_keys && !trimmed.starts_with('#')
+            && toml::key_path(trimmed, '=').ok() == Some(vec![leaf.clone()])
+        {
+            // A value may go on over several lines
+            let mut last = i;
+            while toml::parse(&lines[i..=last].join("\n")).is_err() && last + 1 < lines.len() {
+                last += 1;
+            }
+            found = Some((i, last));
+            break;
+        }
+        if table == path_keys && !trimmed.is_empty() {
+            end_of_table = Some(i + 1);
+        }
+        i += 1;
+    }
+
+    match (found, end_of_table) {
+        (Some((first, last)), _) => {
+            lines.splice(first..=last, std::iter::once(line));
+        }
+        (None, Some(end)) => lines.insert(end, line),
+        (None, None) => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            let header: Vec<String> = path_keys.iter().map(|k| toml::quote_key(k)).collect();
+            lines.push(format!("[{}]", header.join(".")));
+            lines.push(line);
+        }
+    }
+    let text = lines.join("\n") + "\n";
+    Config::parse(&text).map_err(invalid)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// `$XDG_CONFIG_HOME/git-whoknows/config.toml`, by default below `~/.config`
+pub fn user_file () -> Option<PathBuf>
+{
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("git-whoknows").join("config.toml"))
+}
+
+fn string<'v> (key: &str, value: &'v Value) -> Result<&'v str, String>
+{
+    value.as_str().ok_or_else(|| format!("'{}' must be a string", key))
+}
+
+fn strings (key: &str, value: &Value) -> Result<Vec<String>, String>
+{
+    let items = value.as_array().ok_or_else(|| format!("'{}' must be an array of strings", key))?;
+    items.iter().map(|item| string(key, item).map(str::to_string)).collect()
+}
+
+fn boolean (key: &str, value: &Value) -> Result<bool, String>
+{
+    value.as_bool().ok_or_else(|| format!("'{}' must be true or false", key))
+}
+
+fn positive (key: &str, value: &Value) -> Result<f64, String>
+{
+    match value.as_float() {
+        Some(n) if n > 0.0 => Ok(n),
+        _ => Err(format!("'{}' must be a positive number", key)),
+    }
+}
+
+fn not_negative (key: &str, value: &Value) -> Result<f64, String>
+{
+    match value.as_float() {
+        Some(n) if n >= 0.0 => Ok(n),
+        _ => Err(format!("'{}' must be a number of at least 0", key)),
+    }
+}
+
+fn count (key: &str, value: &Value) -> Result<usize, String>
+{
+    match value.as_integer() {
+        Some(n) if n > 0 => Ok(n as usize),
+        _ => Err(format!("'{}' must be a positive integer", key)),
+    }
+}
+
+fn table_of<'v> (key: &str, value: &'v Value) -> Result<&'v Table, String>
+{
+    value.as_table().ok_or_else(|| format!("'{}' must be a table", key))
+}
+
+/// `"commit@email" = "Proper Name <proper@email>"`, as a mailmap
+fn identities (table: &Table) -> Result<Mailmap, String>
+{
+    let mut lines = String::new();
+    for (email, proper) in table
+    {
+        let proper = string(email, proper)?;
+        if !proper.contains('<') {
+            return Err(format!("identity for '{}' must look like 'Name <email>'", email));
+        }
+        lines.push_str(&format!("{} <{}>\n", proper, email));
+    }
+    Ok(Mailmap::parse(&lines))
+}
+
+/// `"Proper Name <proper@email>" = ["commit@email", "Commit Name <commit@email>"]`,
+/// where naming the commit name only maps commits by both, as a mailmap
+fn aliases (table: &Table) -> Result<Mailmap, String>
+{
+    let mut lines = String::new();
+    for (proper, identities) in table
+    {
+        if !proper.contains('<') {
+            return Err(format!("alias '{}' must look like 'Name <email>'", proper));
+        }
+        for identity in strings(proper, identities)?
+        {
+            let identity = identity.trim();
+            if identity.contains('<') {
+                lines.push_str(&format!("{} {}\n", proper, identity));
+            } else {
+                lines.push_str(&format!("{} <{}>\n", proper, identity));
+            }
+        }
+    }
+    Ok(Mailmap::parse(&lines))
+}
+
+/// The options of a profile as command line arguments, one list per
+/// option: `true` is a flag, other values are its value and arrays repeat
+/// the option; one letter names are short options
+pub fn profile_args (profile: &str, options: &Table) -> Result<Vec<Vec<String>>, String>
+{
+    let mut args = Vec::new();
+    for (name, value) in options
+    {
+        let option = if name.chars().count() == 1 { format!("-{}", name) } else { format!("--{}", name) };
+        let scalar = |value: &Value| match value {
+            Value::String(s) => Ok(s.clone()),
+            Value::Integer(n) => Ok(n.to_string()),
+            Value::Float(n) => Ok(n.to_string()),
+            _ => Err(format!("profile '{}': '{}' must be a boolean, string, number or array",
+                             profile, name)),
+        };
+        match value {
+            Value::Boolean(true) => args.push(vec![option]),
+            Value::Boolean(false) => {}
+            Value::Array(items) => {
+                for item in items
+                {
+                    args.push(vec![option.clone(), scalar(item)?]);
+                }
+            }
+            value => args.push(vec![option, scalar(value)?]),
+        }
+    }
+    Ok(args)
+}
+
+fn parse_rule (pattern: &str, table: &Table) -> Result<Rule, String>
+{
+    let mut rule = Rule {
+        pattern: Pattern::new(pattern),
+        exclude: Vec::new(),
+        thresholds: Thresholds::default(),
+    };
+    for (key, value) in table
+    {
+        match key.as_str() {
+            "exclude" => rule.exclude = strings(key, value)?.iter().map(|p| Pattern::new(p)).collect(),
+            "thresholds" => rule.thresholds = thresholds(table_of(key, value)?)?,
+            _ => return Err(format!("unknown key 'rules.\"{}\".{}'", pattern, key)),
+        }
+    }
+    Ok(rule)
+}
+
+fn forge (name: &str, table: &Table) -> Result<Forge, String>
+{
+    let mut forge = Forge::default();
+    for (key, value) in table
+    {
+        match key.as_str() {
+            "url" => forge.url = Some(string(key, value)?.trim_end_matches('/').to_string()),
+            "users" => {
+                for (email, user) in table_of(key, value)?
+                {
+                    forge.users.insert(email.clone(), string(email, user)?.to_string());
+                }
+            }
+            _ => return Err(format!("unknown key '{}.{}'", name, key)),
+        }
+    }
+    Ok(forge)
+}
+
+fn email (table: &Table) -> Result<Email, String>
+{
+    let mut email = Email::default();
+    for (key, value) in table
+    {
+        match key.as_str() {
+            "smtp" => email.smtp = Some(string(key, value)?.to_string()),
+            "user" => email.user = Some(string(key, value)?.to_string()),
+            "from" => email.from = Some(string(key, value)?.to_string()),
+            "to" => email.to = strings(key, value)?,
+            _ => return Err(format!("unknown key 'email.{}'", key)),
+        }
+    }
+    Ok(email)
+}
+
+fn thresholds (table: &Table) -> Result<Thresholds, String>
+{
+    let mut thresholds = Thresholds::default();
+    for (key, value) in table
+    {
+        match key.as_str() {
+            "min_bus_factor" => thresholds.min_bus_factor = Some(count(key, value)?),
+            "max_single_owner" => thresholds.max_single_owner = Some(positive(key, value)?),
+            "warn_min_bus_factor" => thresholds.warn_min_bus_factor = Some(count(key, value)?),
+            "warn_max_single_owner" => thresholds.warn_max_single_owner = Some(positive(key, value)?),
+            "require_owner" => thresholds.require_owner = Some(positive(key, value)?),
+            _ => return Err(format!("unknown key 'thresholds.{}'", key)),
+        }
+    }
+    Ok(thresholds)
+}
+
+/// Levels by label, each the least share of a file it takes
+fn expertise (table: &Table) -> Result<Expertise, String>
+{
+    let mut levels = Vec::new();
+    for (label, value) in table
+    {
+        levels.push((label.clone(), not_negative(label, value)?));
+    }
+    levels.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(Expertise { levels })
+}
+
+fn roles (table: &Table) -> Result<Roles, String>
+{
+    let mut roles = Roles::default();
+    for (name, value) in table
+    {
+        let rule = match name.as_str() {
+            "maintainer" => &mut roles.maintainer,
+            "regular" => &mut roles.regular,
+            _ => return Err(format!("unknown key 'roles.{}'", name)),
+        };
+        for (key, value) in table_of(name, value)?
+        {
+            match key.as_str() {
+                "min_commits" => rule.min_commits = match value.as_integer() {
+                    Some(n) if n >= 0 => Some(n as usize),
+                    _ => return Err(format!("'{}' must be an integer of at least 0", key)),
+                },
+                "min_share" => rule.min_share = Some(not_negative(key, value)?),
+                "min_span_days" => rule.min_span_days = Some(not_negative(key, value)?),
+                _ => return Err(format!("unknown key 'roles.{}.{}'", name, key)),
+            }
+        }
+    }
+    Ok(roles)
+}
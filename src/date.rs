@@ -0,0 +1,259 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the epoch right now
+pub fn now () -> i64
+{
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// (year, month, day) of a day counted from 1970-01-01
+fn civil_from_days (days: i64) -> (i64, u32, u32)
+{
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Days from 1970-01-01 to a (year, month, day)
+fn days_from_civil (year: i64, month: u32, day: u32) -> i64
+{
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// A point in time as given on the command line: `YYYY-MM-DD` or
+/// `YYYY-MM-DD HH:MM:SS` in UTC, or `<n> <unit> ago` with a unit of days,
+/// weeks, months or years, or a plain number of seconds since the epoch
+pub fn parse (s: &str) -> Result<i64, String>
+{
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<i64>() {
+        return Ok(secs);
+    }
+    if let Some(relative) = s.strip_suffix("ago") {
+        return parse_relative(relative.trim()).map(|secs| now() - secs)
+            .ok_or_else(|| format!("invalid relative date '{}'", s));
+    }
+    parse_datetime(s).ok_or_else(|| format!("invalid date '{}', expected YYYY-MM-DD", s))
+}
+
+/// A length of time as given on the command line: `<n><unit>` with a unit
+/// of `d`, `w`, `m` for months or `y`, or `<n> <unit>` spelled out as for
+/// `parse`
+pub fn parse_duration (s: &str) -> Result<i64, String>
+{
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let unit = match s[digits..].trim() {
+        "d" => "day",
+        "w" => "week",
+        "m" => "month",
+        "y" => "year",
+        unit => unit,
+    };
+    parse_relative(&format!("{} {}", &s[..digits], unit))
+        .filter(|&secs| secs > 0)
+        .ok_or_else(|| format!("invalid duration '{}', expected e.g. 180d", s))
+}
+
+/// Seconds in `<n> <unit>`, months and years of average length
+fn parse_relative (s: &str) -> Option<i64>
+{
+    let mut parts = s.split_whitespace();
+    let n: i64 = parts.next()?.parse().ok()?;
+    let unit = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let secs = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 3600,
+        "day" => 86_400,
+        "week" => 7 * 86_400,
+        "month" => 2_629_746,
+        "year" => 31_556_952,
+        _ => return None,
+    };
+    Some(n * secs)
+}
+
+fn parse_datetime (s: &str) -> Option<i64>
+{
+    let (date, time) = match s.find([' ', 'T']) {
+        Some(i) => (&s[..i], Some(s[i + 1..].trim_end_matches('Z'))),
+        None => (s, None),
+    };
+    let mut date = date.split('-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    if date.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut secs = days_from_civil(year, month as u32, day as u32) * 86_400;
+    if let Some(time) = time {
+        let mut time = time.split(':').map(|p| p.parse::<i64>().ok());
+        let hours = time.next()??;
+        let minutes = time.next().unwrap_or(Some(0))?;
+        let seconds = time.next().unwrap_or(Some(0))?;
+        secs += hours * 3600 + minutes * 60 + seconds;
+    }
+    Some(secs)
+}
+
+/// `YYYY-MM-DD` of a timestamp, in UTC
+pub fn format_date (secs: i64) -> String
+{
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` of a timestamp
+pub fn format_datetime (secs: i64) -> String
+{
+    let time = secs.rem_euclid(86_400);
+    format!("{} {:02}:{:02}:{:02} UTC",
+            format_date(secs), time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// The abbreviated English name of the month of a timestamp, in UTC
+pub fn format_month (secs: i64) -> &'static str
+{
+    let (_, month, _) = civil_from_days(secs.div_euclid(86_400));
+    MONTHS[month as usize - 1]
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` of a timestamp
+pub fn format_rfc3339 (secs: i64) -> String
+{
+    let time = secs.rem_euclid(86_400);
+    format!("{}T{:02}:{:02}:{:02}Z", format_date(secs), time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// A timestamp as the `Date` header of an email has it, in UTC
+pub fn format_rfc2822 (secs: i64) -> String
+{
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    let days = secs.div_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let time = secs.rem_euclid(86_400);
+    format!("{}, {} {} {:04} {:02}:{:02}:{:02} +0000",
+            DAYS[days.rem_euclid(7) as usize], day, MONTHS[month as usize - 1], year,
+            time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// The quarter of the year a timestamp falls in, numbered `year * 4 +
+/// quarter - 1` so that consecutive quarters are consecutive numbers
+pub fn quarter (secs: i64) -> i64
+{
+    let (year, month, _) = civil_from_days(secs.div_euclid(86_400));
+    year * 4 + (month as i64 - 1) / 3
+}
+
+/// `YYYYQn` of a quarter numbered as by `quarter`
+pub fn format_quarter (quarter: i64) -> String
+{
+    format!("{:04}Q{}", quarter.div_euclid(4), quarter.rem_euclid(4) + 1)
+}
+
+/// A duration in seconds the way people say how old something is: days
+/// for under two months, months for under two years, then years
+pub fn format_age (secs: i64) -> String
+{
+    let secs = secs.max(0);
+    if secs < 2 * 2_629_746 {
+        let days = secs / 86_400;
+        format!("{} day{}", days, if days == 1 { "" } else { "s" })
+    } else if secs < 2 * 31_556_952 {
+        format!("{} months", secs / 2_629_746)
+    } else {
+        format!("{:.1} years", secs as f64 / 31_556_952.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates ()
+    {
+        assert_eq!(parse("1970-01-01"), Ok(0));
+        assert_eq!(parse("2024-02-29"), Ok(1_709_164_800));
+        assert_eq!(parse("2024-02-29 12:30:15"), Ok(1_709_209_815));
+        assert_eq!(parse("2024-02-29T12:30:15Z"), Ok(1_709_209_815));
+        assert_eq!(parse(" 1700000000 "), Ok(1_700_000_000));
+        assert!(parse("2024-13-01").is_err());
+        assert!(parse("2024-02").is_err());
+        assert!(parse("yesterday").is_err());
+    }
+
+    #[test]
+    fn relative_dates ()
+    {
+        let ago = parse("2 weeks ago").unwrap();
+        assert!((now() - ago - 14 * 86_400).abs() <= 1);
+        assert!(parse("1 day ago").is_ok());
+        assert!(parse("3 fortnights ago").is_err());
+    }
+
+    #[test]
+    fn durations ()
+    {
+        assert_eq!(parse_duration("180d"), Ok(180 * 86_400));
+        assert_eq!(parse_duration("2w"), Ok(14 * 86_400));
+        assert_eq!(parse_duration("1y"), Ok(31_556_952));
+        assert_eq!(parse_duration("6 months"), Ok(6 * 2_629_746));
+        assert!(parse_duration("0d").is_err());
+        assert!(parse_duration("d").is_err());
+    }
+
+    #[test]
+    fn formats_round_trip ()
+    {
+        for &secs in &[0, 951_782_400, 1_709_209_815, -86_400]
+        {
+            assert_eq!(parse(&format_date(secs)), Ok(secs - secs.rem_euclid(86_400)));
+            assert_eq!(parse(&format_rfc3339(secs)), Ok(secs));
+        }
+        assert_eq!(format_datetime(1_709_209_815), "2024-02-29 12:30:15 UTC");
+        assert_eq!(format_rfc2822(0), "Thu, 1 Jan 1970 00:00:00 +0000");
+        assert_eq!(format_month(1_709_209_815), "Feb");
+    }
+
+    #[test]
+    fn quarters ()
+    {
+        let q = quarter(parse("2024-02-29").unwrap());
+        assert_eq!(format_quarter(q), "2024Q1");
+        assert_eq!(quarter(parse("2024-04-01").unwrap()), q + 1);
+        assert_eq!(format_quarter(quarter(parse("2023-12-31").unwrap())), "2023Q4");
+    }
+
+    #[test]
+    fn ages ()
+    {
+        assert_eq!(format_age(-5), "0 days");
+        assert_eq!(format_age(86_400), "1 day");
+        assert_eq!(format_age(90 * 86_400), "2 months");
+        assert_eq!(format_age(3 * 31_556_952), "3.0 years");
+    }
+}
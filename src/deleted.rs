@@ -0,0 +1,110 @@
+//! Knowledge of code that is gone
+//!
+//! Blame only sees the lines that survive. Someone who wrote a subsystem
+//! and later tore it out owns none of it, yet knows its pitfalls better
+//! than anyone. Every line an author added to a file over its history, as
+//! `git log --follow` tells, that is not among the lines they own now has
+//! since been deleted or replaced.
+//!
+//! The same numbers the other way round are how much of what each author
+//! wrote survives: a proxy for whose code stands the test of time and whose
+//! gets rewritten.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use git2::Repository;
+
+use crate::error::Error;
+use crate::git::{self, output};
+use crate::ownership::{normalize_email, owner_key};
+use crate::FileReport;
+
+/// What one author wrote of a file that is gone
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    /// Lines added over the history of the file
+    pub added: usize,
+    /// Of those, the lines gone since
+    pub deleted: usize,
+    /// Lines they own now, which may be more than they added to the file
+    /// when lines were copied or moved in from elsewhere
+    pub surviving: usize,
+    /// Author time of their latest commit to the file, seconds since the
+    /// epoch
+    pub last: i64,
+}
+
+/// Everyone who wrote lines of `file` up to `rev` that are not among the
+/// lines they own in `file`, most lines gone first; `mailmap` has git map
+/// their identities as the analysis did
+pub fn deleted (repo: &Repository, file: &FileReport, rev: &str, mailmap: bool) -> Result<Vec<Author>, Error>
+{
+    let mut authors: Vec<Author> = written(repo, file, rev, mailmap)?.into_iter()
+        .filter(|author| author.deleted > 0)
+        .collect();
+    authors.sort_by(|a, b| b.deleted.cmp(&a.deleted).then_with(|| a.email.cmp(&b.email)));
+    Ok(authors)
+}
+
+/// Everyone who added lines to `file` up to `rev`, with how many of them
+/// survive, most lines added first
+pub fn survival (repo: &Repository, file: &FileReport, rev: &str, mailmap: bool) -> Result<Vec<Author>, Error>
+{
+    let mut authors: Vec<Author> = written(repo, file, rev, mailmap)?.into_iter()
+        .filter(|author| author.added > 0)
+        .collect();
+    authors.sort_by(|a, b| b.added.cmp(&a.added).then_with(|| a.email.cmp(&b.email)));
+    Ok(authors)
+}
+
+/// The `git log` that `deleted` and `survival` run for `path`
+pub fn command (repo: &Repository, path: &Path, rev: &str, mailmap: bool) -> Result<Command, Error>
+{
+    let format = if mailmap { "--format=%x00%aN%x1f%aE%x1f%at" } else { "--format=%x00%an%x1f%ae%x1f%at" };
+    git::command(repo, &["log".as_ref(), "--no-color".as_ref(), "--follow".as_ref(), "--numstat".as_ref(),
+                         format.as_ref(), rev.as_ref(), "--".as_ref(), path.as_os_str()])
+}
+
+/// Everyone who committed to `file` up to `rev`, with the lines they added
+/// and own
+fn written (repo: &Repository, file: &FileReport, rev: &str, mailmap: bool) -> Result<Vec<Author>, Error>
+{
+    let output = output(command(repo, Path::new(&file.path), rev, mailmap)?)?;
+
+    let mut authors: HashMap<String, Author> = HashMap::new();
+    for commit in output.split('\0').skip(1)
+    {
+        let (header, stats) = commit.split_once('\n').unwrap_or((commit, ""));
+        let fields: Vec<&str> = header.split('\x1f').collect();
+        let (name, email, time) = match fields.as_slice() {
+            [name, email, time] => (name.trim(), normalize_email(email).into_owned(), time.parse::<i64>().unwrap_or(0)),
+            _ => continue,
+        };
+        let author = authors.entry(owner_key(name, &email).into_owned()).or_insert_with(|| Author {
+            name: name.to_string(),
+            email,
+            added: 0,
+            deleted: 0,
+            surviving: 0,
+            last: time,
+        });
+        author.last = author.last.max(time);
+        // <added> <removed> <path>, with - for both for a binary file
+        for line in stats.lines()
+        {
+            if let Some(Ok(added)) = line.split('\t').next().map(str::parse::<usize>) {
+                author.added += added;
+            }
+        }
+    }
+
+    for (key, author) in &mut authors
+    {
+        author.surviving = file.owners.get(key).map_or(0, |owner| owner.lines());
+        author.deleted = author.added.saturating_sub(author.surviving);
+    }
+    Ok(authors.into_values().collect())
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{bus_factor, FileReport};
+
+/// Ownership of every tracked line below a directory
+pub struct Directory {
+    pub path: String,
+    /// Lines per owner email
+    pub owners: HashMap<String, usize>,
+}
+
+impl Directory {
+    pub fn lines (&self) -> usize
+    {
+        self.owners.values().sum::<usize>()
+    }
+
+    pub fn bus_factor (&self) -> usize
+    {
+        bus_factor(self.owners.values().cloned())
+    }
+
+    /// Lines owned by the largest owner
+    pub fn top_owner_lines (&self) -> usize
+    {
+        self.owners.values().cloned().max().unwrap_or(0)
+    }
+}
+
+/// Roll file ownership up into every directory containing the files, the
+/// work tree root being `.`; directories are ordered by path
+pub fn directories (files: &[FileReport]) -> Vec<Directory>
+{
+    let mut dirs: BTreeMap<String, Directory> = BTreeMap::new();
+    for file in files
+    {
+        for dir in Path::new(&file.path).ancestors().skip(1)
+        {
+            let path = match dir.to_str() {
+                Some("") => ".".to_string(),
+                _ => dir.display().to_string(),
+            };
+            let entry = dirs.entry(path.clone()).or_insert_with(|| Directory {
+                path,
+                owners: HashMap::new(),
+            });
+            for (key, owner) in &file.owners
+            {
+                *entry.owners.entry(key.clone()).or_insert(0) += owner.lines();
+            }
+        }
+    }
+    dirs.into_values().collect()
+}
@@ -0,0 +1,361 @@
+//! Checking the environment before a run does
+//!
+//! Setup problems otherwise surface halfway through a run, as a blame that
+//! fails or a request that is turned down. `doctor` goes through what runs
+//! depend on, from git to the tokens of the forges, and says for everything
+//! that is off what to do about it. Errors are what would make runs fail
+//! and have `doctor` exit with 1; warnings are what makes results less
+//! accurate or features unavailable.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use git2::Repository;
+
+use whoknows::blame::{self, Backend};
+use whoknows::cache::Cache;
+use whoknows::config::{self, Config};
+use whoknows::mailmap::Mailmap;
+use whoknows::platform;
+use whoknows::roster::Roster;
+#[cfg(feature = "forges")]
+use whoknows::Error;
+
+#[cfg(feature = "forges")]
+use crate::{gerrit, github, gitlab};
+
+/// The user credentials sign in as, unless there are none to try
+#[cfg(feature = "forges")]
+type Checked = Option<Result<String, Error>>;
+
+/// The oldest git that everything works with
+const GIT: (u32, u32) = (2, 23);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Level {
+    Ok,
+    Note,
+    Warning,
+    Error,
+}
+
+struct Finding {
+    level: Level,
+    what: String,
+    /// What to do about it
+    fix: Option<String>,
+}
+
+/// What the checks found, in the order they ran
+#[derive(Default)]
+pub struct Doctor {
+    findings: Vec<Finding>,
+}
+
+impl Doctor {
+    pub fn new () -> Doctor
+    {
+        Doctor::default()
+    }
+
+    fn add (&mut self, level: Level, what: String, fix: Option<&str>)
+    {
+        self.findings.push(Finding { level, what, fix: fix.map(str::to_string) });
+    }
+
+    fn ok (&mut self, what: String)
+    {
+        self.add(Level::Ok, what, None);
+    }
+
+    fn note (&mut self, what: String, fix: &str)
+    {
+        self.add(Level::Note, what, Some(fix));
+    }
+
+    fn warning (&mut self, what: String, fix: &str)
+    {
+        self.add(Level::Warning, what, Some(fix));
+    }
+
+    fn error (&mut self, what: String, fix: &str)
+    {
+        self.add(Level::Error, what, Some(fix));
+    }
+
+    /// Whether anything would make runs fail
+    pub fn failed (&self) -> bool
+    {
+        self.findings.iter().any(|finding| finding.level == Level::Error)
+    }
+
+    /// Every finding, with the fix below those that need one
+    pub fn report (&self) -> String
+    {
+        let mut text = String::new();
+        for finding in &self.findings
+        {
+            let label = match finding.level {
+                Level::Ok => "ok",
+                Level::Note => "note",
+                Level::Warning => "warning",
+                Level::Error => "error",
+            };
+            text.push_str(&format!("{:<8} {}\n", label, finding.what));
+            if let Some(fix) = &finding.fix {
+                text.push_str(&format!("{:<8} fix: {}\n", "", fix));
+            }
+        }
+        let count = |level| self.findings.iter().filter(|finding| finding.level == level).count();
+        text.push_str(&format!("\nErrors: {}, warnings: {}\n", count(Level::Error), count(Level::Warning)));
+        text
+    }
+
+    /// git and its version; without it the git backend, and the commands
+    /// that run git log, cannot work at all
+    pub fn git (&mut self, backend: Backend)
+    {
+        let git = platform::git().to_string_lossy().into_owned();
+        match blame::git_version() {
+            None if backend == Backend::Git => self.error(
+                format!("git not found as {}", git),
+                "install git, put it on the PATH or name it in WHOKNOWS_GIT; --backend git needs it"),
+            None => self.warning(
+                format!("git not found as {}", git),
+                "install git, put it on the PATH or name it in WHOKNOWS_GIT; history, grep, renames and \
+                 the like run it"),
+            Some((major, minor, patch)) if (major, minor) < GIT => self.warning(
+                format!("git {}.{}.{} at {}, older than {}.{}", major, minor, patch, git, GIT.0, GIT.1),
+                "upgrade git; --ignore-revs-file and --detect-noise need 2.23 or later"),
+            Some((major, minor, patch)) => self.ok(format!("git {}.{}.{} at {}", major, minor, patch, git)),
+        }
+    }
+
+    /// curl, which every request to a forge, webhook or mail server goes
+    /// through
+    pub fn curl (&mut self)
+    {
+        let found = Command::new("curl").arg("--version")
+            .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if found {
+            self.ok("curl found".to_string());
+        } else {
+            self.warning("curl not found".to_string(),
+                         "install curl; reviewers on forges, --notify and report --email need it");
+        }
+    }
+
+    /// The repository at or above `start`, and what kind it is
+    pub fn repository (&mut self, start: &Path) -> Option<Repository>
+    {
+        let repo = match whoknows::discover(start) {
+            Ok(repo) => repo,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                self.error(format!("no git repository at or above {}", start.display()),
+                           "run doctor in a work tree, or give the path of one");
+                return None;
+            }
+            Err(e) => {
+                self.error(format!("the repository at {} cannot be opened: {}", start.display(), e.message()),
+                           "check that git can read it, with git status");
+                return None;
+            }
+        };
+        match repo.workdir() {
+            Some(workdir) => self.ok(format!("repository at {}", workdir.display())),
+            None => self.error(format!("bare repository at {}", repo.path().display()),
+                               "run in a clone with a work tree, or give a URL to analyze a clone of it"),
+        }
+        if repo.is_worktree() {
+            self.note("a linked worktree, with a blame cache of its own".to_string(),
+                      "give --cache-dir, or cache_dir in the configuration, to share results between worktrees");
+        }
+        if repo.is_shallow() {
+            self.warning("a shallow clone: lines older than its history are all blamed on its oldest commit"
+                             .to_string(),
+                         "git fetch --unshallow");
+        }
+        if repo.head().is_err() {
+            self.warning("HEAD has no commits yet".to_string(), "commit something to analyze");
+        }
+        Some(repo)
+    }
+
+    /// Every configuration source by itself, so that a broken one is named,
+    /// then all of them together
+    pub fn configuration (&mut self, repo: Option<&Repository>) -> Option<Config>
+    {
+        let fix = "fix or remove the setting; git whoknows config show lists every setting and where it comes from";
+        let mut broken = false;
+        let mut files = Vec::new();
+        files.extend(config::user_file());
+        if let Some(workdir) = repo.and_then(Repository::workdir) {
+            files.push(workdir.join(config::REPO_FILE));
+        }
+        for path in files
+        {
+            match Config::from_file(&path) {
+                Ok(_) if path.exists() => self.ok(format!("configuration in {}", path.display())),
+                Ok(_) => {}
+                Err(e) => {
+                    self.error(e.to_string(), fix);
+                    broken = true;
+                }
+            }
+        }
+        if let Some(repo) = repo {
+            for local in &[false, true]
+            {
+                if let Err(e) = Config::from_git(repo, *local) {
+                    self.error(e.to_string(), fix);
+                    broken = true;
+                }
+            }
+        }
+        if let Err(e) = Config::from_env() {
+            self.error(e.to_string(), "fix or unset the variable");
+            broken = true;
+        }
+        match repo {
+            Some(repo) if !broken => match Config::load(repo) {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    self.error(e.to_string(), fix);
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// `.mailmap`, the identities of the configuration and the teams
+    pub fn identities (&mut self, repo: &Repository, config: &Config)
+    {
+        if let Some(path) = repo.workdir().map(|workdir| workdir.join(".mailmap")).filter(|path| path.exists()) {
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    let unparsed = Mailmap::unparsed(&text);
+                    if unparsed.is_empty() {
+                        self.ok(format!("{} with {} entries", path.display(), Mailmap::parse(&text).len()));
+                    } else {
+                        let lines: Vec<String> = unparsed.iter().map(usize::to_string).collect();
+                        self.warning(format!("{}: ignoring what is in no form of gitmailmap(5), on line {}",
+                                             path.display(), lines.join(", ")),
+                                     "write them as Proper Name <proper@email> <commit@email> or one of \
+                                      the shorter forms");
+                    }
+                }
+                Err(e) => self.error(format!("{}: {}", path.display(), e), "make it readable"),
+            }
+        }
+        if !config.identities.is_empty() {
+            self.ok(format!("{} identities and aliases in the configuration", config.identities.len()));
+        }
+        for (team, members) in &config.teams
+        {
+            if members.is_empty() {
+                self.warning(format!("team '{}' has no members", team), "list their emails, or remove the team");
+            }
+            for member in members.iter().filter(|member| !member.contains('@'))
+            {
+                self.warning(format!("'{}' of team '{}' is not an email", member, team),
+                             "teams list members by the email of their commits");
+            }
+        }
+        let mut seen: Vec<(String, &str)> = Vec::new();
+        for (team, members) in &config.teams
+        {
+            for member in members
+            {
+                let member = member.to_lowercase();
+                match seen.iter().find(|(email, _)| *email == member) {
+                    Some((_, first)) => self.note(
+                        format!("{} is in teams '{}' and '{}', and counts for the first", member, first, team),
+                        "keep everyone in one team"),
+                    None => seen.push((member, team)),
+                }
+            }
+        }
+        if !config.teams.is_empty() {
+            self.ok(format!("{} teams in the configuration", config.teams.len()));
+        }
+    }
+
+    /// The roster of `--roster`
+    pub fn roster (&mut self, path: &Path)
+    {
+        match Roster::from_file(path) {
+            Ok(_) => self.ok(format!("roster in {}", path.display())),
+            Err(e) => self.error(e.to_string(),
+                                 "give --roster a file listing the people still around as active = [\"email\", ...]"),
+        }
+    }
+
+    /// That the cache can be read and written, and what is left in it
+    pub fn cache (&mut self, repo: &Repository, shared: Option<&Path>)
+    {
+        let cache = Cache::open(repo, shared);
+        for dir in &[cache.dir(), cache.entries_dir()]
+        {
+            if let Err(e) = writable(dir) {
+                self.error(format!("cannot write to the cache in {}: {}", dir.display(), e),
+                           "fix its permissions, or run with --no-cache");
+            }
+            if !cache.is_shared() {
+                break;
+            }
+        }
+        match cache.entries() {
+            Ok(entries) => self.ok(format!("cache in {} with {} entries", cache.entries_dir().display(), entries.len())),
+            Err(e) => self.error(format!("cannot read the cache in {}: {}", cache.entries_dir().display(), e),
+                                 "fix its permissions, or git whoknows cache clear"),
+        }
+        if let Ok(outdated) = cache.outdated() {
+            if outdated > 0 {
+                self.note(format!("{} cache entries of an older version, which are never read", outdated),
+                          "git whoknows cache clear");
+            }
+        }
+        if cache.dir().join("checkpoint").exists() {
+            self.note("an interrupted run left a checkpoint".to_string(),
+                      "repeat the run with --resume to skip the files it finished");
+        }
+    }
+
+    /// The tokens of the forges, tried where they are set
+    #[cfg(feature = "forges")]
+    pub fn forges (&mut self, config: &Config)
+    {
+        let checks: [(&str, &str, Checked); 3] = [
+            ("GitHub", "GITHUB_TOKEN", github::token_user(&config.github)),
+            ("GitLab", "GITLAB_TOKEN", gitlab::token_user(&config.gitlab)),
+            ("Gerrit", "GERRIT_USER and GERRIT_PASSWORD",
+             config.gerrit.url.as_deref().and_then(gerrit::credentials_user)),
+        ];
+        if config.gerrit.url.is_none() && std::env::var_os("GERRIT_USER").is_some() {
+            self.note("GERRIT_USER is set, but not the server to try it on".to_string(),
+                      "give [gerrit] url in the configuration");
+        }
+        for (forge, variables, user) in checks
+        {
+            match user {
+                Some(Ok(user)) => self.ok(format!("{} accepts {} as {}", forge, variables, user)),
+                Some(Err(e)) => self.error(format!("{} could not be signed in to with {}: {}", forge, variables, e),
+                                           "check that the credentials are current and that the server is reachable"),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Fail unless a file can be created in `dir`, which is created if need be
+fn writable (dir: &Path) -> std::io::Result<()>
+{
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!("doctor.{}.tmp", std::process::id()));
+    fs::write(&probe, "")?;
+    fs::remove_file(&probe)
+}
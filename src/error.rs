@@ -0,0 +1,118 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    #[cfg(feature = "git")]
+    Git(git2::Error),
+    Io(io::Error),
+    /// Running or reading `git blame` failed
+    Blame(String),
+    /// A path given on the command line cannot be analyzed
+    Path(String),
+    /// A report could not be written to the requested destination
+    Export(String),
+    /// The requested options do not make sense together
+    Usage(String),
+    /// A configuration file cannot be read
+    Config(String),
+    /// A request to a code hosting service failed
+    Remote(String),
+    /// The path to start from is not inside a git repository
+    NotRepository(String),
+    /// A file exists but git does not track it, so there is nothing to blame
+    Untracked(String),
+    /// Analyzing the file at the path failed
+    File(String, Box<Error>),
+}
+
+impl Error {
+    /// What the command line tool exits with for this error: 2 for
+    /// usage and configuration errors, 3 outside of a repository, 4 for a
+    /// path that is not tracked or cannot be analyzed otherwise, 5 when git or blame fails and 6 for
+    /// anything else; 1 is left to `check` finding files over a threshold
+    pub fn exit_code (&self) -> i32
+    {
+        match self {
+            Error::Usage(_) | Error::Config(_) => 2,
+            Error::NotRepository(_) => 3,
+            Error::Path(_) | Error::Untracked(_) => 4,
+            #[cfg(feature = "git")]
+            Error::Git(_) => 5,
+            Error::Blame(_) => 5,
+            Error::Io(_) | Error::Export(_) | Error::Remote(_) => 6,
+            Error::File(_, e) => e.exit_code(),
+        }
+    }
+
+    /// A name for the kind of error that programs can match on
+    pub fn kind (&self) -> &'static str
+    {
+        match self {
+            #[cfg(feature = "git")]
+            Error::Git(_) => "git",
+            Error::Io(_) => "io",
+            Error::Blame(_) => "blame",
+            Error::Path(_) => "path",
+            Error::Export(_) => "export",
+            Error::Usage(_) => "usage",
+            Error::Config(_) => "config",
+            Error::Remote(_) => "remote",
+            Error::NotRepository(_) => "not_repository",
+            Error::Untracked(_) => "untracked",
+            Error::File(_, e) => e.kind(),
+        }
+    }
+
+    /// The file the error is about, when it is about one
+    pub fn path (&self) -> Option<&str>
+    {
+        match self {
+            Error::Untracked(path) | Error::File(path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// What went wrong, without the path of `Error::File`
+    pub fn message (&self) -> String
+    {
+        match self {
+            Error::File(_, e) => e.message(),
+            e => e.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "git")]
+            Error::Git(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Blame(msg) => write!(f, "blame failed: {}", msg),
+            Error::Path(msg) => write!(f, "{}", msg),
+            Error::Export(msg) => write!(f, "export failed: {}", msg),
+            Error::Usage(msg) => write!(f, "{}", msg),
+            Error::Config(msg) => write!(f, "invalid configuration: {}", msg),
+            Error::Remote(msg) => write!(f, "request failed: {}", msg),
+            Error::NotRepository(msg) => write!(f, "{}", msg),
+            Error::Untracked(path) => write!(f, "{} is not tracked; nothing to blame", path),
+            Error::File(path, e) => write!(f, "{}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "git")]
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Error {
+        Error::Git(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
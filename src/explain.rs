@@ -0,0 +1,123 @@
+//! How the numbers of every owner of a file came about
+//!
+//! The score of an owner is the sum over the commits their lines survive
+//! from of the lines, times what a line of the commit is worth with
+//! `--complexity`, times how much is left of it after decay. Who the owner
+//! is comes from the authors of those commits, merged through `.mailmap`,
+//! the identities of the configuration, or only by normalizing case and
+//! Unicode; the authors as recorded in the commits tell which.
+
+use std::collections::HashMap;
+
+use git2::{Oid, Repository};
+
+use crate::analyze::Options;
+use crate::complexity::Weights;
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::{FileReport, Owner};
+
+/// Seconds in a day
+const DAY: f64 = 86_400.0;
+
+/// What the lines of one commit add to the score of their owner
+pub struct Commit {
+    pub commit: Oid,
+    /// Author time, seconds since the epoch
+    pub time: i64,
+    pub lines: usize,
+    /// What a line is worth, 1 without `--complexity`
+    pub weight: f64,
+    /// What is left of a line after decay, 1 without it
+    pub decay: f64,
+}
+
+impl Commit {
+    pub fn score (&self) -> f64
+    {
+        self.lines as f64 * self.weight * self.decay
+    }
+}
+
+/// An author of commits merged into an owner under another identity
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+    /// `.mailmap`, `identities` or `normalization`
+    pub rule: &'static str,
+}
+
+pub struct Explanation<'a> {
+    pub owner: &'a Owner,
+    /// Largest score first
+    pub commits: Vec<Commit>,
+    /// Most lines first
+    pub identities: Vec<Identity>,
+}
+
+/// How the score and identity of every owner of `file`, as analyzed with
+/// `opts`, came about; in the order of `FileReport::ranked_owners`
+pub fn explain<'a> (
+    repo: &Repository,
+    file: &'a FileReport,
+    opts: &Options,
+    weights: Option<&Weights>,
+    now: i64
+) -> Result<Vec<Explanation<'a>>, Error>
+{
+    let mailmap = match repo.workdir() {
+        Some(workdir) if opts.mailmap => Mailmap::from_file(&workdir.join(".mailmap"))?,
+        _ => Mailmap::default(),
+    };
+    let mut explanations = Vec::new();
+    for owner in file.ranked_owners()
+    {
+        let mut commits: Vec<Commit> = owner.commits.iter()
+            .map(|(oid, c)| Commit {
+                commit: *oid,
+                time: c.time,
+                lines: c.lines,
+                weight: weights.map_or(1.0, |w| w.of_commit(*oid)),
+                decay: opts.decay.map_or(1.0, |half_life| {
+                    let age = (now - c.time).max(0) as f64 / DAY;
+                    0.5f64.powf(age / half_life)
+                }),
+            })
+            .collect();
+        commits.sort_by(|a, b| b.score().partial_cmp(&a.score())
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| b.time.cmp(&a.time)));
+
+        let mut identities: HashMap<(String, String), Identity> = HashMap::new();
+        for commit in &commits
+        {
+            // Uncommitted lines have no author to merge
+            let found = match repo.find_commit(commit.commit) {
+                Ok(found) => found,
+                Err(_) => continue,
+            };
+            let author = found.author();
+            let name = String::from_utf8_lossy(author.name_bytes()).into_owned();
+            let email = String::from_utf8_lossy(author.email_bytes()).into_owned();
+            if name == owner.name && email == owner.email {
+                continue;
+            }
+            let rule = if opts.identities.resolve(&name, &email) != (name.as_str(), email.as_str()) {
+                "identities"
+            } else if mailmap.resolve(&name, &email) != (name.as_str(), email.as_str()) {
+                ".mailmap"
+            } else {
+                "normalization"
+            };
+            identities.entry((name.clone(), email.clone()))
+                .or_insert(Identity { name, email, lines: 0, rule })
+                .lines += commit.lines;
+        }
+        let mut identities: Vec<Identity> = identities.into_values().collect();
+        identities.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+
+        explanations.push(Explanation { owner, commits, identities });
+    }
+    Ok(explanations)
+}
@@ -0,0 +1,205 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use git2::Repository;
+
+use crate::RepoReport;
+use crate::blame::Backend;
+use crate::commits::Commits;
+use crate::date;
+use crate::error::Error;
+use crate::ownership::owner_key;
+
+/// A persistent destination for analysis results, given as `<kind>:<path>`
+pub enum Export {
+    Sqlite(PathBuf),
+}
+
+impl FromStr for Export {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Export, String> {
+        let mut parts = s.splitn(2, ':');
+        let kind = parts.next().unwrap_or("");
+        let path = match parts.next() {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => return Err(format!("expected <kind>:<path>, got '{}'", s)),
+        };
+        match kind {
+            "sqlite" => Ok(Export::Sqlite(path)),
+            _ => Err(format!("unknown export kind '{}'", kind)),
+        }
+    }
+}
+
+impl Export {
+    /// Store `report`, looking up commits the way `backend` blames
+    pub fn write (&self, repo: &Repository, report: &RepoReport, backend: Backend) -> Result<(), Error>
+    {
+        match self {
+            Export::Sqlite(path) => {
+                let mut script = migrations(path)?;
+                script.push_str(&sqlite_script(report, &mut Commits::new(repo, backend)?)?);
+                run_sqlite(path, &script)
+            }
+        }
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    revision TEXT
+);
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    path TEXT NOT NULL,
+    lines INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS authors (
+    id INTEGER PRIMARY KEY,
+    key TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS commits (
+    oid TEXT PRIMARY KEY,
+    author_id INTEGER NOT NULL REFERENCES authors(id),
+    time INTEGER NOT NULL,
+    summary TEXT
+);
+CREATE TABLE IF NOT EXISTS ownership (
+    file_id INTEGER NOT NULL REFERENCES files(id),
+    author_id INTEGER NOT NULL REFERENCES authors(id),
+    commit_oid TEXT NOT NULL REFERENCES commits(oid),
+    lines INTEGER NOT NULL
+);
+";
+
+/// Databases of older versions keyed authors by email alone, which made
+/// one author of everyone without an email; their `authors` table is
+/// rebuilt keyed by `owner_key`, keeping the ids the other tables refer to
+const AUTHORS_BY_KEY: &str = "
+BEGIN;
+CREATE TABLE authors_by_key (
+    id INTEGER PRIMARY KEY,
+    key TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL
+);
+INSERT INTO authors_by_key (id, key, name, email)
+    SELECT id, CASE WHEN email = '' THEN lower(trim(name)) ELSE email END, name, email FROM authors;
+DROP TABLE authors;
+ALTER TABLE authors_by_key RENAME TO authors;
+COMMIT;
+";
+
+/// What brings the database at `path` up to the current schema
+fn migrations (path: &Path) -> Result<String, Error>
+{
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    let output = Command::new("sqlite3")
+        .arg("-readonly")
+        .arg(path)
+        .arg("SELECT name FROM pragma_table_info('authors');")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::Export(format!("could not run sqlite3: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::Export(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let columns: Vec<&str> = stdout.lines().collect();
+    let outdated = !columns.is_empty() && !columns.contains(&"key");
+    Ok(if outdated { AUTHORS_BY_KEY.to_string() } else { String::new() })
+}
+
+/// Build a script that records one run; every run gets its own rows in
+/// `runs`, `files` and `ownership` so that older reports stay queryable
+fn sqlite_script (report: &RepoReport, commits: &mut Commits) -> Result<String, Error>
+{
+    let now = date::now();
+    let revision = match report.revision {
+        Some(oid) => quote(&oid.to_string()),
+        None => "NULL".to_string(),
+    };
+
+    let mut sql = String::from(SCHEMA);
+    sql.push_str("BEGIN;\n");
+    sql.push_str(&format!(
+        "INSERT INTO runs (created_at, revision) VALUES ({}, {});\n", now, revision));
+    sql.push_str("CREATE TEMP TABLE current_run AS SELECT last_insert_rowid() AS id;\n");
+    sql.push_str("CREATE TEMP TABLE current_file (id INTEGER);\n");
+
+    for file in &report.files
+    {
+        sql.push_str(&format!(
+            "INSERT INTO files (run_id, path, lines) SELECT id, {}, {} FROM current_run;\n",
+            quote(&file.path), file.lines()));
+        sql.push_str("DELETE FROM current_file;\n");
+        sql.push_str("INSERT INTO current_file VALUES (last_insert_rowid());\n");
+
+        for owner in file.ranked_owners()
+        {
+            let key = quote(&owner_key(&owner.name, &owner.email));
+            sql.push_str(&format!(
+                "INSERT INTO authors (key, name, email) VALUES ({}, {}, {}) \
+                 ON CONFLICT(key) DO UPDATE SET name = excluded.name, email = excluded.email;\n",
+                key, quote(&owner.name), quote(&owner.email)));
+
+            for (oid, contribution) in &owner.commits
+            {
+                // Uncommitted lines have no commit to refer to
+                if oid.is_zero() {
+                    continue;
+                }
+                let lines = contribution.lines;
+                let commit = commits.get(*oid)?;
+                sql.push_str(&format!(
+                    "INSERT OR IGNORE INTO commits (oid, author_id, time, summary) \
+                     SELECT {}, id, {}, {} FROM authors WHERE key = {};\n",
+                    quote(&oid.to_string()), commit.time, quote(commit.summary()), key));
+                sql.push_str(&format!(
+                    "INSERT INTO ownership (file_id, author_id, commit_oid, lines) \
+                     SELECT current_file.id, authors.id, {}, {} \
+                     FROM current_file, authors WHERE authors.key = {};\n",
+                    quote(&oid.to_string()), lines, key));
+            }
+        }
+    }
+    sql.push_str("COMMIT;\n");
+    Ok(sql)
+}
+
+/// Quote a value as an SQL string literal
+fn quote (value: &str) -> String
+{
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Feed the script to the `sqlite3` shell, which creates the database on
+/// first use
+fn run_sqlite (path: &Path, script: &str) -> Result<(), Error>
+{
+    let mut child = Command::new("sqlite3")
+        .arg("-bail")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Export(format!("could not run sqlite3: {}", e)))?;
+
+    child.stdin.take().unwrap().write_all(script.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::Export(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(())
+}
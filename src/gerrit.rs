@@ -0,0 +1,128 @@
+//! Changes on Gerrit
+//!
+//! With `GERRIT_USER` and `GERRIT_PASSWORD`, the HTTP password of the
+//! account, requests go to the authenticated `/a/` endpoints; anonymous
+//! reads are enough for public changes.
+
+use whoknows::config::Forge;
+use whoknows::json::{self, Value};
+use whoknows::reviewers::Reviewer;
+use whoknows::Error;
+
+use crate::http::{self, Request};
+
+/// What Gerrit puts in front of every JSON response against XSSI
+const XSSI_PREFIX: &str = ")]}'";
+
+pub struct Gerrit<'c> {
+    url: String,
+    change: String,
+    credentials: Option<(String, String)>,
+    config: &'c Forge,
+}
+
+/// What a change modifies and who uploaded it
+pub struct Change {
+    /// Paths before the change of the files it modifies or deletes
+    pub files: Vec<String>,
+    pub owner_email: Option<String>,
+    pub owner_username: Option<String>,
+}
+
+impl<'c> Gerrit<'c> {
+    /// The change `change` on the server at `url`, given by number, by
+    /// Change-Id or as `project~number`
+    pub fn new (url: &str, change: &str, config: &'c Forge) -> Gerrit<'c>
+    {
+        let user = std::env::var("GERRIT_USER").ok();
+        let password = std::env::var("GERRIT_PASSWORD").ok();
+        Gerrit {
+            url: url.trim_end_matches('/').to_string(),
+            change: http::encode(change),
+            credentials: user.zip(password),
+            config,
+        }
+    }
+
+    /// `path` below the change, such as `/revisions/current/files`
+    fn url (&self, path: &str) -> String
+    {
+        let authenticated = if self.credentials.is_some() { "/a" } else { "" };
+        format!("{}{}/changes/{}{}", self.url, authenticated, self.change, path)
+    }
+
+    fn authorized (&self, request: Request) -> Request
+    {
+        match &self.credentials {
+            Some((user, password)) => request.user(user, password),
+            None => request,
+        }
+    }
+
+    fn json (request: Request) -> Result<Value, Error>
+    {
+        let body = request.send()?;
+        let body = body.strip_prefix(XSSI_PREFIX).unwrap_or(&body);
+        json::parse(body).map_err(|e| Error::Remote(format!("unexpected response from Gerrit: {}", e)))
+    }
+
+    pub fn change (&self) -> Result<Change, Error>
+    {
+        let files = Gerrit::json(self.authorized(Request::get(&self.url("/revisions/current/files"))))?;
+        let files = match files {
+            Value::Object(files) => files.into_iter()
+                // Magic files such as /COMMIT_MSG are not in the tree
+                .filter(|(path, _)| !path.starts_with('/'))
+                .filter_map(|(path, info)| match info.get("status").and_then(Value::as_str) {
+                    Some("A") => None,
+                    Some("R") | Some("C") => info.get("old_path").and_then(Value::as_str).map(str::to_string),
+                    _ => Some(path),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let detail = Gerrit::json(self.authorized(Request::get(&self.url("?o=DETAILED_ACCOUNTS"))))?;
+        let owner = |key| detail.get("owner").and_then(|o| o.get(key)).and_then(Value::as_str).map(str::to_string);
+        Ok(Change { files, owner_email: owner("email"), owner_username: owner("username") })
+    }
+
+    /// How `reviewer` is named to Gerrit: by the account configured in
+    /// `[gerrit.users]`, or else by email, which Gerrit resolves itself
+    pub fn account (&self, reviewer: &Reviewer) -> String
+    {
+        self.config.user(&reviewer.email).unwrap_or(&reviewer.email).to_string()
+    }
+
+    /// Post `review`, a ReviewInput such as that of `review_input`
+    pub fn add_reviewers (&self, review: &Value) -> Result<(), Error>
+    {
+        if self.credentials.is_none() {
+            return Err(Error::Usage("adding reviewers on Gerrit needs GERRIT_USER and \
+                                     GERRIT_PASSWORD".to_string()));
+        }
+        self.authorized(Request::post(&self.url("/revisions/current/review"), review)).send()?;
+        Ok(())
+    }
+}
+
+/// The username `GERRIT_USER` and `GERRIT_PASSWORD` sign in as on the
+/// server at `url`, `None` without them
+pub fn credentials_user (url: &str) -> Option<Result<String, Error>>
+{
+    let user = std::env::var("GERRIT_USER").ok()?;
+    let password = std::env::var("GERRIT_PASSWORD").ok()?;
+    let request = Request::get(&format!("{}/a/accounts/self", url.trim_end_matches('/'))).user(&user, &password);
+    Some(Gerrit::json(request).and_then(|account| account.get("username").and_then(Value::as_str).map(str::to_string)
+        .ok_or_else(|| Error::Remote("no username in the response of Gerrit".to_string()))))
+}
+
+/// The body for `POST /changes/{id}/revisions/current/review` that adds
+/// `accounts` as reviewers
+pub fn review_input (accounts: &[String]) -> Value
+{
+    let reviewers = accounts.iter()
+        .map(|account| Value::object(vec![("reviewer", account.as_str().into())]))
+        .collect();
+    Value::object(vec![("reviewers", Value::Array(reviewers))])
+}
@@ -0,0 +1,45 @@
+//! Running git itself, for what libgit2 cannot do
+//!
+//! Every command runs in the work tree of a repository with its git
+//! directory and work tree named explicitly, as git would otherwise go by
+//! `GIT_DIR` and `GIT_WORK_TREE`, and fails with what git said on stderr.
+
+use std::ffi::OsStr;
+use std::process::{Command, Stdio};
+
+use git2::Repository;
+
+use crate::error::Error;
+use crate::platform;
+
+/// `git <args>` in the work tree of `repo`
+pub fn command (repo: &Repository, args: &[&OsStr]) -> Result<Command, Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+    let mut cmd = Command::new(platform::git());
+    cmd.current_dir(workdir)
+        .arg("--git-dir").arg(repo.path())
+        .arg("--work-tree").arg(workdir)
+        .args(args);
+    Ok(cmd)
+}
+
+/// The output of `git <args>` in the work tree of `repo`
+pub(crate) fn run (repo: &Repository, args: &[&OsStr]) -> Result<String, Error>
+{
+    output(command(repo, args)?)
+}
+
+/// The output of `cmd`, one of `command`
+pub(crate) fn output (mut cmd: Command) -> Result<String, Error>
+{
+    let output = cmd.stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::Blame(format!("could not run git: {}", e)))?;
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Blame(errors.trim().trim_start_matches("fatal: ").to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,209 @@
+//! Pull requests and teams on GitHub
+//!
+//! Needs a token in `GITHUB_TOKEN` (or `GH_TOKEN`) for private repositories,
+//! for requesting reviews and for the members of teams. `[github] url` points at the API of a GitHub
+//! Enterprise server instead of api.github.com.
+
+use std::collections::BTreeMap;
+
+use whoknows::config::Forge;
+use whoknows::json::Value;
+use whoknows::reviewers::Reviewer;
+use whoknows::Error;
+
+use crate::http::{self, Request};
+
+const API: &str = "https://api.github.com";
+
+/// Most items the API returns in one page
+const PAGE: usize = 100;
+
+pub struct GitHub<'c> {
+    api: String,
+    repo: String,
+    token: Option<String>,
+    config: &'c Forge,
+}
+
+impl<'c> GitHub<'c> {
+    /// The repository `repo`, given as `owner/name`
+    pub fn new (repo: &str, config: &'c Forge) -> Result<GitHub<'c>, Error>
+    {
+        match repo.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {}
+            _ => return Err(Error::Usage(format!("--github takes owner/repo, not '{}'", repo))),
+        }
+        Ok(GitHub {
+            api: config.url.clone().unwrap_or_else(|| API.to_string()),
+            repo: repo.to_string(),
+            token: token(),
+            config,
+        })
+    }
+
+    fn url (&self, path: &str) -> String
+    {
+        format!("{}/repos/{}/{}", self.api, self.repo, path)
+    }
+
+    fn authorized (&self, request: Request) -> Request
+    {
+        authorized(&self.token, request)
+    }
+
+    /// The changes of pull request `pr` as a unified diff
+    pub fn diff (&self, pr: u64) -> Result<String, Error>
+    {
+        let request = Request::get(&self.url(&format!("pulls/{}", pr)))
+            .header("Accept: application/vnd.github.diff");
+        self.authorized(request).send()
+    }
+
+    /// The login of whoever opened pull request `pr`
+    pub fn author (&self, pr: u64) -> Result<String, Error>
+    {
+        let request = Request::get(&self.url(&format!("pulls/{}", pr)))
+            .header("Accept: application/vnd.github+json");
+        let pull = self.authorized(request).json()?;
+        pull.get("user").and_then(|user| user.get("login")).and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| Error::Remote(format!("pull request {} has no author", pr)))
+    }
+
+    /// The login of `reviewer`: configured in `[github.users]`, part of a
+    /// noreply address, or that of the author of one of their commits
+    pub fn login (&self, reviewer: &Reviewer) -> Option<String>
+    {
+        if let Some(login) = known_login(self.config, &reviewer.email) {
+            return Some(login);
+        }
+        // The commit may never have been pushed, then there is no telling
+        let request = Request::get(&self.url(&format!("commits/{}", reviewer.commit)))
+            .header("Accept: application/vnd.github+json");
+        let commit = self.authorized(request).json().ok()?;
+        commit.get("author")?.get("login")?.as_str().map(str::to_string)
+    }
+
+    /// Ask `logins` to review pull request `pr`
+    pub fn request_reviews (&self, pr: u64, logins: &[String]) -> Result<(), Error>
+    {
+        let body = Value::object(vec![
+            ("reviewers", Value::Array(logins.iter().map(|l| l.as_str().into()).collect())),
+        ]);
+        let request = Request::post(&self.url(&format!("pulls/{}/requested_reviewers", pr)), &body)
+            .header("Accept: application/vnd.github+json");
+        self.authorized(request).send()?;
+        Ok(())
+    }
+}
+
+/// The teams of an organization
+pub struct Organization<'c> {
+    api: String,
+    org: String,
+    token: Option<String>,
+    config: &'c Forge,
+}
+
+impl<'c> Organization<'c> {
+    pub fn new (org: &str, config: &'c Forge) -> Organization<'c>
+    {
+        Organization {
+            api: config.url.clone().unwrap_or_else(|| API.to_string()),
+            org: org.to_string(),
+            token: token(),
+            config,
+        }
+    }
+
+    /// Every page of the array at `path` of the API
+    fn pages (&self, path: &str) -> Result<Vec<Value>, Error>
+    {
+        let mut items = Vec::new();
+        for page in 1..
+        {
+            let url = format!("{}/{}?per_page={}&page={}", self.api, path, PAGE, page);
+            let request = Request::get(&url).header("Accept: application/vnd.github+json");
+            let json = authorized(&self.token, request).json()?;
+            let array = json.as_array().ok_or_else(|| Error::Remote(format!("{}: not an array", url)))?;
+            items.extend(array.iter().cloned());
+            if array.len() < PAGE {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// The logins of the members of every team of the organization, by the
+    /// slug of the team
+    pub fn teams (&self) -> Result<BTreeMap<String, Vec<String>>, Error>
+    {
+        let org = http::encode(&self.org);
+        let mut teams = BTreeMap::new();
+        for team in self.pages(&format!("orgs/{}/teams", org))?
+        {
+            let slug = match team.get("slug").and_then(Value::as_str) {
+                Some(slug) => slug.to_string(),
+                None => continue,
+            };
+            let members = self.pages(&format!("orgs/{}/teams/{}/members", org, http::encode(&slug)))?;
+            let logins = members.iter()
+                .filter_map(|member| member.get("login").and_then(Value::as_str).map(str::to_string))
+                .collect();
+            teams.insert(slug, logins);
+        }
+        Ok(teams)
+    }
+
+    /// The login of whoever commits as `email`: configured in
+    /// `[github.users]`, part of a noreply address, or that of the author
+    /// of a commit with that email in a repository of the organization
+    pub fn login (&self, email: &str) -> Option<String>
+    {
+        if let Some(login) = known_login(self.config, email) {
+            return Some(login);
+        }
+        let query = http::encode(&format!("author-email:{} org:{}", email, self.org));
+        let url = format!("{}/search/commits?q={}&per_page=1", self.api, query);
+        let request = Request::get(&url).header("Accept: application/vnd.github+json");
+        let found = authorized(&self.token, request).json().ok()?;
+        let commit = found.get("items")?.as_array()?.first()?;
+        commit.get("author")?.get("login")?.as_str().map(str::to_string)
+    }
+}
+
+/// The login `GITHUB_TOKEN` belongs to, `None` without a token
+pub fn token_user (config: &Forge) -> Option<Result<String, Error>>
+{
+    let token = token()?;
+    let api = config.url.as_deref().unwrap_or(API);
+    let user = authorized(&Some(token), Request::get(&format!("{}/user", api))).json();
+    Some(user.and_then(|user| user.get("login").and_then(Value::as_str).map(str::to_string)
+        .ok_or_else(|| Error::Remote("no login in the response of GitHub".to_string()))))
+}
+
+/// The login of `email` without asking: configured in `[github.users]` or
+/// part of a noreply address
+fn known_login (config: &Forge, email: &str) -> Option<String>
+{
+    if let Some(login) = config.user(email) {
+        return Some(login.to_string());
+    }
+    // Either login@ or id+login@
+    let user = email.strip_suffix("@users.noreply.github.com")?;
+    Some(user.rsplit('+').next().unwrap_or(user).to_string())
+}
+
+fn token () -> Option<String>
+{
+    std::env::var("GITHUB_TOKEN").or_else(|_| std::env::var("GH_TOKEN")).ok()
+}
+
+fn authorized (token: &Option<String>, request: Request) -> Request
+{
+    let request = request.header("X-GitHub-Api-Version: 2022-11-28");
+    match token {
+        Some(token) => request.header(&format!("Authorization: Bearer {}", token)),
+        None => request,
+    }
+}
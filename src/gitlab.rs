@@ -0,0 +1,127 @@
+//! Merge requests on GitLab
+//!
+//! The instance is gitlab.com unless `[gitlab] url` names another, and the
+//! token in `GITLAB_TOKEN` is sent with every request; notes and reviewers
+//! need one with the `api` scope.
+
+use whoknows::config::Forge;
+use whoknows::json::Value;
+use whoknows::reviewers::Reviewer;
+use whoknows::Error;
+
+use crate::http::{self, Request};
+
+const URL: &str = "https://gitlab.com";
+
+pub struct GitLab<'c> {
+    api: String,
+    project: String,
+    token: Option<String>,
+    config: &'c Forge,
+}
+
+/// What a merge request changes and who opened it
+pub struct MergeRequest {
+    /// Paths before the change of the files it modifies or deletes
+    pub files: Vec<String>,
+    pub author: String,
+}
+
+impl<'c> GitLab<'c> {
+    /// The project `project`, given by its path such as `group/name` or by
+    /// its id
+    pub fn new (project: &str, config: &'c Forge) -> Result<GitLab<'c>, Error>
+    {
+        if project.is_empty() || project.starts_with('/') || project.ends_with('/') {
+            return Err(Error::Usage(format!("--gitlab takes group/project, not '{}'", project)));
+        }
+        let url = config.url.as_deref().unwrap_or(URL);
+        Ok(GitLab {
+            api: format!("{}/api/v4", url),
+            project: http::encode(project),
+            token: std::env::var("GITLAB_TOKEN").ok(),
+            config,
+        })
+    }
+
+    fn url (&self, path: &str) -> String
+    {
+        format!("{}/projects/{}/{}", self.api, self.project, path)
+    }
+
+    fn authorized (&self, request: Request) -> Request
+    {
+        match &self.token {
+            Some(token) => request.header(&format!("PRIVATE-TOKEN: {}", token)),
+            None => request,
+        }
+    }
+
+    pub fn merge_request (&self, mr: u64) -> Result<MergeRequest, Error>
+    {
+        let request = Request::get(&self.url(&format!("merge_requests/{}/changes", mr)));
+        let mr = self.authorized(request).json()?;
+        let files = mr.get("changes").and_then(Value::as_array).unwrap_or(&[]).iter()
+            .filter(|change| !matches!(change.get("new_file"), Some(Value::Bool(true))))
+            .filter_map(|change| change.get("old_path").and_then(Value::as_str))
+            .map(str::to_string)
+            .collect();
+        let author = mr.get("author").and_then(|a| a.get("username")).and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string();
+        Ok(MergeRequest { files, author })
+    }
+
+    /// The username of `reviewer`: configured in `[gitlab.users]` or that
+    /// of the account with their email, which the token must be allowed to
+    /// see
+    pub fn username (&self, reviewer: &Reviewer) -> Option<String>
+    {
+        if let Some(username) = self.config.user(&reviewer.email) {
+            return Some(username.to_string());
+        }
+        let request = Request::get(&format!("{}/users?search={}", self.api, http::encode(&reviewer.email)));
+        let users = self.authorized(request).json().ok()?;
+        users.as_array()?.first()?.get("username")?.as_str().map(str::to_string)
+    }
+
+    fn user_id (&self, username: &str) -> Result<f64, Error>
+    {
+        let request = Request::get(&format!("{}/users?username={}", self.api, http::encode(username)));
+        let users = self.authorized(request).json()?;
+        users.as_array().and_then(|users| users.first())
+            .and_then(|user| user.get("id")).and_then(Value::as_f64)
+            .ok_or_else(|| Error::Remote(format!("no GitLab user '{}'", username)))
+    }
+
+    /// Make `usernames` the reviewers of merge request `mr`
+    pub fn set_reviewers (&self, mr: u64, usernames: &[String]) -> Result<(), Error>
+    {
+        let ids = usernames.iter()
+            .map(|username| self.user_id(username).map(Value::Number))
+            .collect::<Result<_, _>>()?;
+        let body = Value::object(vec![("reviewer_ids", Value::Array(ids))]);
+        let request = Request::put(&self.url(&format!("merge_requests/{}", mr)), &body);
+        self.authorized(request).send()?;
+        Ok(())
+    }
+
+    /// Comment on merge request `mr` with `text`, in Markdown
+    pub fn note (&self, mr: u64, text: &str) -> Result<(), Error>
+    {
+        let body = Value::object(vec![("body", text.into())]);
+        let request = Request::post(&self.url(&format!("merge_requests/{}/notes", mr)), &body);
+        self.authorized(request).send()?;
+        Ok(())
+    }
+}
+
+/// The username `GITLAB_TOKEN` belongs to, `None` without a token
+pub fn token_user (config: &Forge) -> Option<Result<String, Error>>
+{
+    let token = std::env::var("GITLAB_TOKEN").ok()?;
+    let url = config.url.as_deref().unwrap_or(URL);
+    let request = Request::get(&format!("{}/api/v4/user", url)).header(&format!("PRIVATE-TOKEN: {}", token));
+    Some(request.json().and_then(|user| user.get("username").and_then(Value::as_str).map(str::to_string)
+        .ok_or_else(|| Error::Remote("no username in the response of GitLab".to_string()))))
+}
@@ -0,0 +1,206 @@
+//! Path patterns in the style of `.gitignore`
+//!
+//! `*` and `?` stay within one path component, `**` spans any number of
+//! them. A pattern without a slash matches a file or directory name at any
+//! depth, one with a slash is anchored at the root of the repository, and
+//! a matching directory takes everything below it along.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pattern: String,
+    /// Match from the root rather than at any depth
+    anchored: bool,
+}
+
+impl Pattern {
+    pub fn new (pattern: &str) -> Pattern
+    {
+        let pattern = pattern.trim().trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        // Directories take their contents along anyway
+        let pattern = pattern.strip_suffix("/**").unwrap_or(pattern);
+        Pattern {
+            anchored,
+            pattern: pattern.trim_start_matches('/').to_string(),
+        }
+    }
+
+    pub fn as_str (&self) -> &str
+    {
+        &self.pattern
+    }
+
+    /// Whether `path`, relative to the repository root with `/` separators,
+    /// matches
+    pub fn matches (&self, path: &str) -> bool
+    {
+        let path = path.as_bytes();
+        let boundaries = |i: &usize| *i == 0 || path[*i - 1] == b'/';
+        let ends = |i: &usize| *i == path.len() || path[*i] == b'/';
+
+        let starts: Vec<usize> = if self.anchored {
+            vec![0]
+        } else {
+            (0..path.len()).filter(boundaries).collect()
+        };
+        starts.into_iter().any(|start| {
+            (start + 1..=path.len())
+                .filter(ends)
+                .any(|end| matches(self.pattern.as_bytes(), &path[start..end]))
+        })
+    }
+}
+
+/// A list of patterns as in a `.gitignore` file
+///
+/// One pattern per line, `#` starting a comment line and `!` negating a
+/// pattern, so that a file an earlier pattern ignores is included again.
+/// The last pattern that matches decides.
+#[derive(Clone, Default)]
+pub struct Ignore {
+    /// Patterns with whether they are negated
+    patterns: Vec<(Pattern, bool)>,
+}
+
+impl Ignore {
+    /// Read `path`; a missing file ignores nothing
+    pub fn from_file (path: &Path) -> io::Result<Ignore>
+    {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Ignore::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Ignore::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse (text: &str) -> Ignore
+    {
+        let mut patterns = Vec::new();
+        for line in text.lines()
+        {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // `\!` and `\#` start patterns with those characters
+            let (line, negated) = match line.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (line.strip_prefix('\\').unwrap_or(line), false),
+            };
+            patterns.push((Pattern::new(line), negated));
+        }
+        Ignore { patterns }
+    }
+
+    /// Add the patterns of `other` after, and so with priority over, ours
+    pub fn extend (&mut self, other: Ignore)
+    {
+        self.patterns.extend(other.patterns);
+    }
+
+    pub fn is_ignored (&self, path: &str) -> bool
+    {
+        self.patterns.iter().rev()
+            .find(|(pattern, _)| pattern.matches(path))
+            .is_some_and(|(_, negated)| !negated)
+    }
+}
+
+fn matches (pattern: &[u8], path: &[u8]) -> bool
+{
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(b'*') if pattern.starts_with(b"**") => {
+            let rest = &pattern[2..];
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            // Zero or more whole components, or anything when last
+            if rest.is_empty() {
+                return true;
+            }
+            (0..=path.len()).any(|i| (i == 0 || path[i - 1] == b'/') && matches(rest, &path[i..]))
+        }
+        Some(b'*') => {
+            (0..=path.len())
+                .take_while(|&i| i == 0 || path[i - 1] != b'/')
+                .any(|i| matches(&pattern[1..], &path[i..]))
+        }
+        Some(b'?') => match path.first() {
+            Some(&c) if c != b'/' => matches(&pattern[1..], &path[1..]),
+            _ => false,
+        },
+        Some(&c) => path.first() == Some(&c) && matches(&pattern[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_at_any_depth ()
+    {
+        let pattern = Pattern::new("*.min.js");
+        assert!(pattern.matches("app.min.js"));
+        assert!(pattern.matches("web/static/app.min.js"));
+        assert!(!pattern.matches("app.js"));
+        // A matching directory takes its contents along
+        assert!(Pattern::new("vendor").matches("third/vendor/lib/a.c"));
+        assert!(!Pattern::new("vendor").matches("vendored/a.c"));
+    }
+
+    #[test]
+    fn anchored ()
+    {
+        let pattern = Pattern::new("/docs/*.md");
+        assert!(pattern.matches("docs/index.md"));
+        assert!(!pattern.matches("src/docs/index.md"));
+        // `*` stays within one component
+        assert!(!pattern.matches("docs/api/index.md"));
+        assert!(Pattern::new("src/gen/").matches("src/gen/parser.rs"));
+    }
+
+    #[test]
+    fn wildcards ()
+    {
+        assert!(Pattern::new("docs/**/*.md").matches("docs/index.md"));
+        assert!(Pattern::new("docs/**/*.md").matches("docs/a/b/index.md"));
+        assert!(Pattern::new("docs/**").matches("docs/a/b/index.md"));
+        assert!(Pattern::new("**/fixtures").matches("a/b/fixtures/x.json"));
+        assert!(Pattern::new("file?.txt").matches("file1.txt"));
+        assert!(!Pattern::new("file?.txt").matches("file10.txt"));
+        assert!(!Pattern::new("a?b").matches("a/b"));
+    }
+
+    #[test]
+    fn ignore_file ()
+    {
+        let ignore = Ignore::parse("# generated\n\
+                                    *.lock\n\
+                                    vendor/\n\
+                                    !vendor/ours/\n\
+                                    \\#notes.txt\n\
+                                    \n");
+        assert!(ignore.is_ignored("Cargo.lock"));
+        assert!(ignore.is_ignored("vendor/theirs/lib.c"));
+        assert!(!ignore.is_ignored("vendor/ours/lib.c"));
+        assert!(ignore.is_ignored("#notes.txt"));
+        assert!(!ignore.is_ignored("src/main.rs"));
+        assert!(!ignore.is_ignored("# generated"));
+    }
+
+    #[test]
+    fn later_patterns_win ()
+    {
+        let mut ignore = Ignore::parse("!keep.lock");
+        ignore.extend(Ignore::parse("*.lock"));
+        assert!(ignore.is_ignored("keep.lock"));
+        let mut ignore = Ignore::parse("*.lock");
+        ignore.extend(Ignore::parse("!keep.lock"));
+        assert!(!ignore.is_ignored("keep.lock"));
+        assert!(!Ignore::default().is_ignored("anything"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Who removed code and who knew it
+//!
+//! `git blame --reverse <start>..<end>` tells, for every line of a file as
+//! of `<start>`, the last commit up to `<end>` that still had it. Lines it
+//! does not follow all the way to `<end>` were removed by the next commit
+//! to change the file after that one. Along with whoever wrote those lines,
+//! found by blaming the file as of `<start>`, that answers who deleted the
+//! retry logic and who understood it when hunting down a regression.
+
+use std::path::Path;
+
+use git2::{Oid, Repository};
+
+use crate::blame;
+use crate::error::Error;
+use crate::git;
+use crate::ownership::normalize_email;
+
+/// Lines of the file as of the start of the range that are gone by its end
+pub struct Gone {
+    /// First line, counting from 1, as of the start
+    pub line: usize,
+    pub lines: usize,
+    /// The last commit of the range that still had them
+    pub last_seen: Oid,
+}
+
+/// The commit that removed lines
+pub struct Removal {
+    pub commit: Oid,
+    pub name: String,
+    pub email: String,
+    /// Author time, seconds since the epoch
+    pub time: i64,
+    pub summary: String,
+}
+
+/// Every run of lines of `path` as of `start` that is no longer there as
+/// of `end`, top to bottom
+pub fn gone (repo: &Repository, path: &Path, start: Oid, end: Oid) -> Result<Vec<Gone>, Error>
+{
+    let range = format!("{}..{}", start, end);
+    let output = git::run(repo, &["blame".as_ref(), "--reverse".as_ref(), "--porcelain".as_ref(),
+                                  range.as_ref(), "--".as_ref(), path.as_os_str()])?;
+    let mut gone: Vec<Gone> = Vec::new();
+    let mut line = 1;
+    // Porcelain output is in the order of the lines
+    for hunk in blame::parse(&output)?
+    {
+        if hunk.commit != end {
+            match gone.last_mut() {
+                Some(last) if last.last_seen == hunk.commit && last.line + last.lines == line => {
+                    last.lines += hunk.lines;
+                }
+                _ => gone.push(Gone { line, lines: hunk.lines, last_seen: hunk.commit }),
+            }
+        }
+        line += hunk.lines;
+    }
+    Ok(gone)
+}
+
+/// The first commit after `last_seen` on the way to `end` to change
+/// `path`, which is the one that removed what `last_seen` was the last to
+/// have; `mailmap` has git map its author
+pub fn removal (repo: &Repository, path: &Path, last_seen: Oid, end: Oid, mailmap: bool) -> Result<Option<Removal>, Error>
+{
+    let format = if mailmap { "--format=%H%x1f%aN%x1f%aE%x1f%at%x1f%s" } else { "--format=%H%x1f%an%x1f%ae%x1f%at%x1f%s" };
+    let range = format!("{}..{}", last_seen, end);
+    let output = git::run(repo, &["log".as_ref(), "--no-color".as_ref(), "--ancestry-path".as_ref(),
+                                  "--reverse".as_ref(), format.as_ref(), range.as_ref(), "--".as_ref(), path.as_os_str()])?;
+    let first = match output.lines().next() {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let fields: Vec<&str> = first.splitn(5, '\x1f').collect();
+    match fields.as_slice() {
+        [hash, name, email, time, summary] => Ok(Some(Removal {
+            commit: Oid::from_str(hash)?,
+            name: name.trim().to_string(),
+            email: normalize_email(email).into_owned(),
+            time: time.parse().unwrap_or(0),
+            summary: summary.to_string(),
+        })),
+        _ => Ok(None),
+    }
+}
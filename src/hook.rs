@@ -0,0 +1,73 @@
+//! Git hooks that run the analysis on their own
+//!
+//! The pre-push hook lists the owners of what is about to be pushed, so
+//! that suggested reviewers show up without anyone asking for them. It
+//! never stops a push.
+
+use std::fs;
+use std::path::PathBuf;
+
+use git2::Repository;
+
+use whoknows::Error;
+
+/// Line that marks a hook as ours, so that it may be replaced
+const MARKER: &str = "# Installed by git whoknows install-hook";
+
+const PRE_PUSH: &str = r#"#!/bin/sh
+# Installed by git whoknows install-hook
+# Lists the owners of the code changed by the commits being pushed.
+
+remote="$1"
+zero=$(git hash-object --stdin </dev/null | tr '0-9a-f' '0')
+
+while read -r local_ref local_sha remote_ref remote_sha
+do
+    # Deleting a branch changes nothing worth reviewing
+    [ "$local_sha" = "$zero" ] && continue
+    if [ "$remote_sha" = "$zero" ]; then
+        # A new branch: everything the remote does not have yet
+        first=$(git rev-list --reverse "$local_sha" --not --remotes="$remote" | head -n 1)
+        [ -n "$first" ] || continue
+        base=$(git rev-parse -q --verify "$first^") || continue
+    else
+        base="$remote_sha"
+    fi
+    echo "Suggested reviewers for ${remote_ref#refs/heads/}:"
+    git diff "$base" "$local_sha" | git whoknows -q --rev "$base" reviewers
+done
+
+exit 0
+"#;
+
+/// Write the hook `name` into the hooks directory of `repo`, replacing an
+/// existing one only if it is ours or `force` is set
+pub fn install (repo: &Repository, name: &str, force: bool) -> Result<PathBuf, Error>
+{
+    let script = match name {
+        "pre-push" => PRE_PUSH,
+        _ => return Err(Error::Usage(format!("unknown hook '{}', expected pre-push", name))),
+    };
+    let dir = match repo.config()?.get_path("core.hooksPath") {
+        Ok(dir) if dir.is_absolute() => dir,
+        // Relative to the root of the work tree, like git itself has it
+        Ok(dir) => repo.workdir().unwrap_or_else(|| repo.path()).join(dir),
+        Err(_) => repo.path().join("hooks"),
+    };
+    let path = dir.join(name);
+
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !force && !existing.contains(MARKER) {
+            return Err(Error::Usage(format!("{} already exists, use --force to replace it",
+                                            path.display())));
+        }
+    }
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
@@ -0,0 +1,141 @@
+//! HTTP requests through the `curl` command
+//!
+//! Everything about a request goes to curl as a config file on stdin, so
+//! that tokens do not show up in the process list.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use whoknows::json::{self, Value};
+use whoknows::Error;
+
+pub struct Request {
+    method: &'static str,
+    url: String,
+    headers: Vec<String>,
+    /// `user:password` for basic authentication
+    user: Option<String>,
+    body: Option<String>,
+}
+
+impl Request {
+    pub fn get (url: &str) -> Request
+    {
+        Request { method: "GET", url: url.to_string(), headers: Vec::new(), user: None, body: None }
+    }
+
+    pub fn post (url: &str, body: &Value) -> Request
+    {
+        Request::post_json(url, body.to_string())
+    }
+
+    /// POST `body`, which is JSON already
+    pub fn post_json (url: &str, body: String) -> Request
+    {
+        Request { method: "POST", ..Request::get(url) }
+            .header("Content-Type: application/json")
+            .body(body)
+    }
+
+    pub fn put (url: &str, body: &Value) -> Request
+    {
+        Request { method: "PUT", ..Request::post(url, body) }
+    }
+
+    pub fn header (mut self, header: &str) -> Request
+    {
+        self.headers.push(header.to_string());
+        self
+    }
+
+    pub fn user (mut self, user: &str, password: &str) -> Request
+    {
+        self.user = Some(format!("{}:{}", user, password));
+        self
+    }
+
+    pub fn body (mut self, body: String) -> Request
+    {
+        self.body = Some(body);
+        self
+    }
+
+    /// The body of a successful response
+    pub fn send (&self) -> Result<String, Error>
+    {
+        let mut config = format!("url = {}\nrequest = {}\nsilent\nshow-error\nlocation\n\
+                                  write-out = \"\\n%{{http_code}}\"\n",
+                                 quote(&self.url), self.method);
+        for header in &self.headers
+        {
+            config.push_str(&format!("header = {}\n", quote(header)));
+        }
+        if let Some(user) = &self.user {
+            config.push_str(&format!("user = {}\n", quote(user)));
+        }
+        if let Some(body) = &self.body {
+            config.push_str(&format!("data-binary = {}\n", quote(body)));
+        }
+
+        let mut child = Command::new("curl")
+            .args(["--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Remote(format!("could not run curl: {}", e)))?;
+        child.stdin.take().unwrap().write_all(config.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::Remote(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+        if !status.starts_with('2') {
+            return Err(Error::Remote(format!("{} {}: HTTP {}: {}", self.method, self.url, status,
+                                             body.trim())));
+        }
+        Ok(body.to_string())
+    }
+
+    /// The body of a successful response as JSON
+    pub fn json (&self) -> Result<Value, Error>
+    {
+        json::parse(&self.send()?).map_err(|e| Error::Remote(format!("{}: {}", self.url, e)))
+    }
+}
+
+/// `s` as a quoted string of a curl config file
+pub fn quote (s: &str) -> String
+{
+    let mut quoted = String::from("\"");
+    for c in s.chars()
+    {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `s` with everything but unreserved characters percent-encoded, for a
+/// path segment or query value
+pub fn encode (s: &str) -> String
+{
+    let mut encoded = String::new();
+    for b in s.bytes()
+    {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
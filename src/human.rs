@@ -0,0 +1,82 @@
+//! Numbers and dates as people read them
+//!
+//! With `--human`, tables show `12.3k` rather than `12345` lines and `2
+//! years ago` rather than a date, which makes big reports easier to scan.
+//! Only what is printed for people goes through here; JSON, porcelain and
+//! the other formats for programs always carry the raw values.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use whoknows::date;
+
+static HUMAN: AtomicBool = AtomicBool::new(false);
+
+/// Format numbers and dates for people from now on
+pub fn enable ()
+{
+    HUMAN.store(true, Ordering::Relaxed);
+}
+
+fn enabled () -> bool
+{
+    HUMAN.load(Ordering::Relaxed)
+}
+
+/// `n` as is, or with `--human` with thousands separators up to 9,999 and
+/// in thousands or millions with one decimal from there
+pub fn count (n: usize) -> String
+{
+    if !enabled() {
+        return n.to_string();
+    }
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=9_999 => format!("{},{:03}", n / 1000, n % 1000),
+        10_000..=999_999 => format!("{:.1}k", n as f64 / 1e3),
+        _ => format!("{:.1}M", n as f64 / 1e6),
+    }
+}
+
+/// `n` as `count` shows it, followed by `noun` or its plural in `s`
+pub fn counted (n: usize, noun: &str) -> String
+{
+    format!("{} {}{}", count(n), noun, if n == 1 { "" } else { "s" })
+}
+
+/// `secs` as `date::format_date` does, or with `--human` as how long ago
+/// that was
+pub fn date (secs: i64) -> String
+{
+    if enabled() { ago(secs) } else { date::format_date(secs) }
+}
+
+/// `secs` as `date::format_datetime` does, or with `--human` as how long
+/// ago that was
+pub fn datetime (secs: i64) -> String
+{
+    if enabled() { ago(secs) } else { date::format_datetime(secs) }
+}
+
+/// `3 days ago`, `1 year ago` and the like, in the largest unit that fits
+fn ago (secs: i64) -> String
+{
+    const UNITS: [(&str, i64); 6] = [
+        ("year", 365 * 86400),
+        ("month", 30 * 86400),
+        ("week", 7 * 86400),
+        ("day", 86400),
+        ("hour", 3600),
+        ("minute", 60),
+    ];
+    let elapsed = date::now() - secs;
+    if elapsed < 0 {
+        return date::format_datetime(secs);
+    }
+    match UNITS.iter().find(|(_, unit)| elapsed >= *unit) {
+        Some((name, unit)) => {
+            let n = elapsed / unit;
+            format!("{} {}{} ago", n, name, if n == 1 { "" } else { "s" })
+        }
+        None => "just now".to_string(),
+    }
+}
@@ -0,0 +1,247 @@
+//! People who committed under more than one identity
+//!
+//! Someone who committed as `Alice Smith <alice@corp.com>` and as `alice
+//! <alice@gmail.com>` is two owners to every report, each with half the
+//! knowledge, until `.mailmap` merges them. Identities are likely one
+//! person when their names are the same but for case, accents and
+//! punctuation, when the user part of an email spells the other's name or
+//! is the same at another domain, or when the names are a typo apart. The
+//! suggestions are for a person to review, not to apply blindly.
+
+use std::collections::HashMap;
+
+use git2::Repository;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::ownership::{normalize_email, owner_key};
+
+/// User parts of emails that say nothing about who is behind them
+const GENERIC: [&str; 9] = ["admin", "dev", "git", "info", "mail", "noreply", "root", "ubuntu", "user"];
+
+/// One identity as reports tell owners apart, after `.mailmap`
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+    /// Commits authored under it
+    pub commits: usize,
+    /// The identities of those commits as they were recorded, with the
+    /// commits of each, most first
+    pub spellings: Vec<(String, String, usize)>,
+}
+
+/// Identities that are likely one person
+pub struct Cluster {
+    /// The identity with most commits, which the others map to
+    pub canonical: Identity,
+    pub others: Vec<Identity>,
+    /// Why the identities were put together, each reason once
+    pub reasons: Vec<&'static str>,
+}
+
+impl Cluster {
+    /// `.mailmap` lines mapping every recorded spelling of the other
+    /// identities to the canonical one
+    pub fn mailmap_lines (&self) -> Vec<String>
+    {
+        let canonical = format!("{} <{}>", self.canonical.name, self.canonical.email);
+        self.others.iter()
+            .flat_map(|other| &other.spellings)
+            .map(|(name, email, _)| format!("{} {} <{}>", canonical, name, email))
+            .collect()
+    }
+}
+
+/// The identities of the authors of every commit up to `rev`, merged
+/// through `mailmap` and grouped where they are likely one person, most
+/// commits first
+pub fn suggest (repo: &Repository, rev: &str, mailmap: &Mailmap) -> Result<Vec<Cluster>, Error>
+{
+    let mut walk = repo.revwalk()?;
+    walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?;
+    let mut recorded: HashMap<(String, String), usize> = HashMap::new();
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        let author = commit.author();
+        let name = String::from_utf8_lossy(author.name_bytes()).trim().to_string();
+        let email = String::from_utf8_lossy(author.email_bytes()).trim().to_string();
+        *recorded.entry((name, email)).or_insert(0) += 1;
+    }
+
+    let mut identities: Vec<Identity> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for ((name, email), commits) in recorded
+    {
+        let (resolved_name, resolved_email) = mailmap.resolve(&name, &email);
+        let key = owner_key(resolved_name, resolved_email).into_owned();
+        let i = *index.entry(key).or_insert_with(|| {
+            identities.push(Identity {
+                name: resolved_name.to_string(),
+                email: normalize_email(resolved_email).into_owned(),
+                commits: 0,
+                spellings: Vec::new(),
+            });
+            identities.len() - 1
+        });
+        identities[i].commits += commits;
+        identities[i].spellings.push((name, email, commits));
+    }
+    for identity in &mut identities
+    {
+        identity.spellings.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+    }
+    // A noreply address is a poor one to map the others to
+    let noreply = |identity: &Identity| identity.email.contains("noreply");
+    identities.sort_by(|a, b| b.commits.cmp(&a.commits)
+        .then_with(|| noreply(a).cmp(&noreply(b)))
+        .then_with(|| a.email.cmp(&b.email)));
+
+    // Union-find over the identities, remembering why each pair was joined
+    let mut parent: Vec<usize> = (0..identities.len()).collect();
+    let mut reasons: Vec<(usize, &'static str)> = Vec::new();
+    for a in 0..identities.len()
+    {
+        for b in a + 1..identities.len()
+        {
+            if let Some(reason) = likely_same(&identities[a], &identities[b]) {
+                let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                // The identity with more commits, sorted first, stays the root
+                parent[ra.max(rb)] = ra.min(rb);
+                reasons.push((a, reason));
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..identities.len()
+    {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    let mut reasons_of: HashMap<usize, Vec<&'static str>> = HashMap::new();
+    for (a, reason) in reasons
+    {
+        let found = reasons_of.entry(root(&mut parent, a)).or_default();
+        if !found.contains(&reason) {
+            found.push(reason);
+        }
+    }
+
+    let mut slots: Vec<Option<Identity>> = identities.into_iter().map(Some).collect();
+    let mut roots: Vec<usize> = groups.keys().copied().filter(|r| groups[r].len() > 1).collect();
+    roots.sort_unstable();
+    let clusters = roots.into_iter()
+        .map(|r| {
+            let mut members = groups[&r].iter().map(|&i| slots[i].take().unwrap());
+            Cluster {
+                canonical: members.next().unwrap(),
+                others: members.collect(),
+                reasons: reasons_of.remove(&r).unwrap_or_default(),
+            }
+        })
+        .collect();
+    Ok(clusters)
+}
+
+/// The set `i` is in, as the index of its first identity
+fn root (parent: &mut [usize], mut i: usize) -> usize
+{
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Why `a` and `b` are likely one person, if they are
+fn likely_same (a: &Identity, b: &Identity) -> Option<&'static str>
+{
+    let (name_a, name_b) = (words(&a.name), words(&b.name));
+    let (user_a, user_b) = (user(&a.email), user(&b.email));
+    // A single word such as "alex" is too common to go by
+    if name_a.len() > 1 && sorted(&name_a) == sorted(&name_b) {
+        return Some("same name");
+    }
+    if spells(&user_a, &name_b) || spells(&user_b, &name_a) {
+        return Some("email spells name");
+    }
+    if user_a.len() >= 4 && user_a == user_b && !GENERIC.contains(&user_a.as_str()) {
+        return Some("same email user");
+    }
+    let (joined_a, joined_b) = (name_a.concat(), name_b.concat());
+    let allowed = match joined_a.chars().count().min(joined_b.chars().count()) {
+        n if n >= 12 => 2,
+        n if n >= 6 => 1,
+        _ => 0,
+    };
+    if name_a.len() > 1 && name_b.len() > 1 && allowed > 0 && distance(&joined_a, &joined_b) <= allowed {
+        return Some("similar name");
+    }
+    None
+}
+
+/// The words of `name` in lower case without accents or punctuation
+fn words (name: &str) -> Vec<String>
+{
+    let plain: String = name.nfd().filter(|c| !is_combining_mark(*c)).collect::<String>().to_lowercase();
+    plain.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn sorted (words: &[String]) -> Vec<&str>
+{
+    let mut sorted: Vec<&str> = words.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+    sorted
+}
+
+/// The user part of `email` without a `+tag`, the numeric id of a GitHub
+/// noreply address or punctuation
+fn user (email: &str) -> String
+{
+    let local = email.split('@').next().unwrap_or("");
+    let local = if email.ends_with("@users.noreply.github.com") {
+        local.split_once('+').map_or(local, |(_, login)| login)
+    } else {
+        local.split('+').next().unwrap_or(local)
+    };
+    words(local).concat()
+}
+
+/// Whether the user part of an email spells `name`: all of its words,
+/// either way round, or the initial of the first and the last
+fn spells (user: &str, name: &[String]) -> bool
+{
+    if name.len() < 2 || user.len() < 4 {
+        return false;
+    }
+    let (first, last) = (&name[0], &name[name.len() - 1]);
+    let initial = first.chars().next().map(String::from).unwrap_or_default();
+    user == name.concat()
+        || user == format!("{}{}", last, first)
+        || user == format!("{}{}", initial, last)
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn distance (a: &str, b: &str) -> usize
+{
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate()
+    {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len()
+        {
+            let next = (diagonal + (ca != b[j]) as usize).min(row[j] + 1).min(row[j + 1] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
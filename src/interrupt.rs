@@ -0,0 +1,39 @@
+//! Ctrl-C handling for long analyses
+//!
+//! The first interrupt only raises a flag, which the blame workers check
+//! before starting on another file, so the run can still report what it
+//! has. The handler then steps aside and a second interrupt kills the
+//! process as usual.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt (_signal: libc::c_int)
+{
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+/// Catch the next SIGINT instead of dying from it
+#[cfg(unix)]
+pub fn install ()
+{
+    let handler = on_interrupt as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install ()
+{
+}
+
+pub fn interrupted () -> bool
+{
+    INTERRUPTED.load(Ordering::SeqCst)
+}
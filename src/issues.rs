@@ -0,0 +1,96 @@
+//! Tickets that shaped a file, found in the summaries of its commits
+//!
+//! Keys like `JIRA-123` and references like `#456` in the summary of a
+//! commit credit the lines it still owns to that ticket, which tells which
+//! pieces of work the code came out of and who did them.
+
+use std::collections::HashMap;
+
+use crate::commits::Commits;
+use crate::error::Error;
+use crate::FileReport;
+
+/// A ticket and the lines of one file that survive from its commits
+pub struct Issue {
+    pub key: String,
+    pub lines: usize,
+    /// Names of the authors who worked it and their lines, most first
+    pub authors: Vec<(String, usize)>,
+}
+
+/// The issue keys in `summary`, in order and without repetitions: project
+/// keys such as `ABC-12` and numbers such as `#34`
+pub fn keys (summary: &str) -> Vec<String>
+{
+    let chars: Vec<char> = summary.chars().collect();
+    let word = |i: usize| chars.get(i).is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    let digits = |from: usize| (from..chars.len()).take_while(|&i| chars[i].is_ascii_digit()).count();
+
+    let mut keys: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let starts_word = i == 0 || !word(i - 1);
+        let key_len = if chars[i] == '#' && starts_word {
+            match digits(i + 1) {
+                0 => None,
+                n => Some(n + 1),
+            }
+        } else if starts_word && chars[i].is_ascii_uppercase() {
+            let project = (i..chars.len())
+                .take_while(|&j| chars[j].is_ascii_uppercase() || chars[j].is_ascii_digit() || chars[j] == '_')
+                .count();
+            match chars.get(i + project) {
+                Some('-') if project >= 2 => match digits(i + project + 1) {
+                    0 => None,
+                    n => Some(project + 1 + n),
+                },
+                _ => None,
+            }
+        } else {
+            None
+        };
+        match key_len {
+            Some(len) if !word(i + len) => {
+                let key: String = chars[i..i + len].iter().collect();
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+                i += len;
+            }
+            _ => i += 1,
+        }
+    }
+    keys
+}
+
+/// The issues of the commits `file` owns lines through, most lines first
+pub fn issues (file: &FileReport, commits: &mut Commits) -> Result<Vec<Issue>, Error>
+{
+    let mut found: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for owner in file.owners.values()
+    {
+        for (oid, contribution) in &owner.commits
+        {
+            // Uncommitted lines have no message
+            if oid.is_zero() {
+                continue;
+            }
+            for key in keys(commits.get(*oid)?.summary())
+            {
+                *found.entry(key).or_default().entry(owner.name.as_str()).or_insert(0) += contribution.lines;
+            }
+        }
+    }
+
+    let mut issues: Vec<Issue> = found.into_iter()
+        .map(|(key, authors)| {
+            let mut authors: Vec<(String, usize)> = authors.into_iter()
+                .map(|(name, lines)| (name.to_string(), lines))
+                .collect();
+            authors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            Issue { key, lines: authors.iter().map(|(_, lines)| lines).sum(), authors }
+        })
+        .collect();
+    issues.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.key.cmp(&b.key)));
+    Ok(issues)
+}
@@ -0,0 +1,112 @@
+//! Jujutsu workspaces
+//!
+//! jj keeps its commits in a git repository: `.git` next to `.jj` when the
+//! two are colocated, otherwise one inside `.jj/repo/store`. Either can be
+//! blamed like any other, but jj's view of it differs from git's in two
+//! ways. What is in the work tree is a commit of its own, `@`, snapshotted
+//! by the next jj command, while git's HEAD is its parent; and revisions
+//! are named by change ids and revsets git does not know. So revisions are
+//! resolved by `jj` itself, which snapshots the working copy first, and
+//! analyzed as of the git commit they stand for, files included. Without
+//! `jj` on the `PATH`, revisions are left to git.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+
+use crate::error::Error;
+use crate::platform;
+
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    /// The jj workspace `start` is in, found by its `.jj` directory
+    pub fn discover (start: &Path) -> Option<Workspace>
+    {
+        let start = platform::canonicalize(start).ok()?;
+        let root = start.ancestors().find(|dir| dir.join(".jj").is_dir())?;
+        Some(Workspace { root: root.to_path_buf() })
+    }
+
+    pub fn root (&self) -> &Path
+    {
+        &self.root
+    }
+
+    /// The git repository the commits are kept in, with the workspace as its
+    /// work tree
+    pub fn open_git (&self) -> Result<Repository, Error>
+    {
+        // A workspace added by `jj workspace add` points to the repo of the
+        // first one
+        let mut repo = self.root.join(".jj").join("repo");
+        if repo.is_file() {
+            let target = fs::read_to_string(&repo)?;
+            repo = self.root.join(".jj").join(target.trim());
+        }
+        let store = repo.join("store");
+        let target = fs::read_to_string(store.join("git_target"))
+            .map_err(|_| Error::NotRepository(format!("{} is a jj workspace without a git store", self.root.display())))?;
+        let git = Repository::open(store.join(target.trim()))?;
+        git.set_workdir(&self.root, false)?;
+        Ok(git)
+    }
+
+    /// The git commit of `rev`, a change id, commit id or any other single
+    /// revision of a revset as jj takes them; None if `jj` cannot be run
+    pub fn resolve (&self, rev: &str) -> Result<Option<Oid>, Error>
+    {
+        let output = match Command::new("jj")
+            .current_dir(&self.root)
+            .args(["log", "--no-graph", "--color", "never", "-r", rev, "-T", "commit_id ++ \"\\n\""])
+            .output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if !output.status.success() {
+            return Err(Error::Usage(format!("jj: {}", String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let ids: Vec<&str> = stdout.lines().filter(|line| !line.is_empty()).collect();
+        match ids[..] {
+            [id] => Ok(Some(Oid::from_str(id)?)),
+            [] => Err(Error::Usage(format!("jj: {} names no revision", rev))),
+            _ => Err(Error::Usage(format!("jj: {} stands for {} revisions, not one", rev, ids.len()))),
+        }
+    }
+}
+
+/// The files of `commit` below any of `paths`, relative to the work tree,
+/// in the order of the tree; a path naming no file of the commit is an
+/// [`Error::Untracked`]
+pub fn tracked_files (repo: &Repository, commit: Oid, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error>
+{
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut all = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            all.push(PathBuf::from(format!("{}{}", dir, String::from_utf8_lossy(entry.name_bytes()))));
+        }
+        TreeWalkResult::Ok
+    })?;
+    let mut files = Vec::new();
+    for path in paths
+    {
+        let relative = crate::repo_relative(repo, path)?;
+        if !path.is_dir() && !all.contains(&relative) {
+            return Err(Error::Untracked(relative.display().to_string()));
+        }
+        for file in all.iter().filter(|file| file.starts_with(&relative))
+        {
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,438 @@
+//! Just enough JSON to answer API requests and read API responses
+
+use std::fmt;
+
+use crate::blame::{BlameHunk, Hunk};
+use crate::oid::Oid;
+use crate::{percent, FileReport, Owner};
+
+#[derive(Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order they are written
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// An object from `(key, value)` pairs
+    pub fn object<K: Into<String>> (members: Vec<(K, Value)>) -> Value
+    {
+        Value::Object(members.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    /// The member `key` of an object
+    pub fn get (&self, key: &str) -> Option<&Value>
+    {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str (&self) -> Option<&str>
+    {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64 (&self) -> Option<f64>
+    {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array (&self) -> Option<&[Value]>
+    {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a JSON document
+pub fn parse (text: &str) -> Result<Value, String>
+{
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error (&self, msg: &str) -> String
+    {
+        format!("invalid JSON at character {}: {}", self.pos, msg)
+    }
+
+    fn skip_whitespace (&mut self)
+    {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn next (&mut self) -> Result<char, String>
+    {
+        let c = *self.chars.get(self.pos).ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    /// Take `c` if it is next
+    fn eat (&mut self, c: char) -> bool
+    {
+        let next = self.chars.get(self.pos) == Some(&c);
+        if next {
+            self.pos += 1;
+        }
+        next
+    }
+
+    fn expect (&mut self, word: &str) -> Result<(), String>
+    {
+        for expected in word.chars()
+        {
+            if !self.eat(expected) {
+                return Err(self.error(&format!("expected '{}'", word)));
+            }
+        }
+        Ok(())
+    }
+
+    fn value (&mut self) -> Result<Value, String>
+    {
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.expect("true").map(|_| Value::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some('n') => self.expect("null").map(|_| Value::Null),
+            Some(_) => self.number(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn object (&mut self) -> Result<Value, String>
+    {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.chars.get(self.pos) != Some(&'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return Err(self.error("expected ':'"));
+            }
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Value::Object(members));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array (&mut self) -> Result<Value, String>
+    {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn string (&mut self) -> Result<String, String>
+    {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(s),
+                '\\' => match self.next()? {
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    'r' => s.push('\r'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'u' => {
+                        let mut code = self.hex()?;
+                        // Characters beyond the BMP come as surrogate pairs
+                        if (0xd800..0xdc00).contains(&code) && self.chars[self.pos..].starts_with(&['\\', 'u']) {
+                            self.pos += 2;
+                            let low = self.hex()?;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                        }
+                        s.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn hex (&mut self) -> Result<u32, String>
+    {
+        let start = self.pos;
+        let digits: String = (0..4).map(|_| self.next()).collect::<Result<_, _>>()?;
+        u32::from_str_radix(&digits, 16).map_err(|_| {
+            self.pos = start;
+            self.error("invalid \\u escape")
+        })
+    }
+
+    fn number (&mut self) -> Result<Value, String>
+    {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(Value::Number).map_err(|_| self.error(&format!("invalid value '{}'", text)))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            // JSON has no NaN or infinities
+            Value::Number(n) if !n.is_finite() => write!(f, "null"),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write_string(f, s),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate()
+                {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate()
+                {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string (f: &mut fmt::Formatter, s: &str) -> fmt::Result
+{
+    write!(f, "\"")?;
+    for c in s.chars()
+    {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// A file with its owners, largest first
+pub fn file_report (file: &FileReport) -> Value
+{
+    labeled_report(file, |_| None)
+}
+
+/// A file with its owners as `file_report`, each with the `expertise`
+/// that `label` gives them, if any
+pub fn labeled_report<F> (file: &FileReport, label: F) -> Value
+    where F: Fn(&Owner) -> Option<String>
+{
+    let total = file.lines();
+    let owners = file.ranked_owners().into_iter()
+        .map(|owner| {
+            let mut fields = vec![
+                ("name", owner.name.as_str().into()),
+                ("email", owner.email.as_str().into()),
+                ("lines", owner.lines().into()),
+                ("share", percent(owner.lines(), total).into()),
+                ("commits", owner.commits.len().into()),
+                ("lines_per_commit", owner.lines_per_commit().into()),
+                ("score", owner.score.into()),
+            ];
+            if let Some(label) = label(owner) {
+                fields.push(("expertise", label.into()));
+            }
+            Value::object(fields)
+        })
+        .collect();
+    Value::object(vec![
+        ("path", file.path.as_str().into()),
+        ("lines", total.into()),
+        ("bus_factor", file.bus_factor().into()),
+        ("owners", Value::Array(owners)),
+    ])
+}
+
+/// A hunk of blame, its commit as a hex string
+pub fn hunk (hunk: &Hunk) -> Value
+{
+    Value::object(vec![
+        ("commit", hunk.commit.to_string().into()),
+        ("name", hunk.name.into()),
+        ("email", hunk.email.into()),
+        ("time", (hunk.time as f64).into()),
+        ("lines", hunk.lines.into()),
+        ("origin", hunk.origin.map_or(Value::Null, Value::from)),
+    ])
+}
+
+/// A hunk of blame as written by `hunk`, `None` if it is not one
+pub fn blame_hunk (value: &Value) -> Option<BlameHunk>
+{
+    Some(BlameHunk {
+        commit: Oid::from_str(value.get("commit")?.as_str()?).ok()?,
+        name: value.get("name")?.as_str()?.to_string(),
+        email: value.get("email")?.as_str()?.to_string(),
+        time: value.get("time")?.as_f64()? as i64,
+        lines: value.get("lines")?.as_f64()? as usize,
+        origin: value.get("origin").and_then(Value::as_str).map(String::from),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values ()
+    {
+        let value = parse(r#" {"a": [1, -2.5e1, true, false, null], "b": {}, "c": [], "d": "x"} "#).unwrap();
+        assert_eq!(value.to_string(), r#"{"a":[1,-25,true,false,null],"b":{},"c":[],"d":"x"}"#);
+        assert_eq!(value.get("d").and_then(Value::as_str), Some("x"));
+        assert_eq!(value.get("a").and_then(Value::as_array).map(<[Value]>::len), Some(5));
+        assert!(value.get("missing").is_none());
+    }
+
+    #[test]
+    fn members_keep_their_order ()
+    {
+        let value = parse(r#"{"z": 1, "a": 2, "m": 3}"#).unwrap();
+        assert_eq!(value.to_string(), r#"{"z":1,"a":2,"m":3}"#);
+    }
+
+    #[test]
+    fn escapes ()
+    {
+        let value = parse(r#""q\" b\\ s\/ \n\t\r\b\f é 😀""#).unwrap();
+        assert_eq!(value.as_str(), Some("q\" b\\ s/ \n\t\r\u{8}\u{c} é 😀"));
+        // Lone surrogates cannot be characters
+        assert_eq!(parse(r#""\ud800""#).unwrap().as_str(), Some("\u{fffd}"));
+    }
+
+    #[test]
+    fn written_back_escaped ()
+    {
+        let value = Value::from("quote \" back \\ line \n bell \u{7} é");
+        assert_eq!(value.to_string(), r#""quote \" back \\ line \n bell \u0007 é""#);
+        assert_eq!(parse(&value.to_string()).unwrap().as_str(), value.as_str());
+        assert_eq!(Value::Number(f64::NAN).to_string(), "null");
+        assert_eq!(Value::from(3usize).to_string(), "3");
+    }
+
+    #[test]
+    fn errors_name_the_character ()
+    {
+        assert_eq!(parse("[1,]").err().unwrap(), "invalid JSON at character 3: invalid value ''");
+        assert_eq!(parse("[1 2]").err().unwrap(), "invalid JSON at character 3: expected ',' or ']'");
+        assert_eq!(parse(r#"{"a" 1}"#).err().unwrap(), "invalid JSON at character 5: expected ':'");
+        assert_eq!(parse("{1: 2}").err().unwrap(), "invalid JSON at character 1: expected a key");
+        assert_eq!(parse(r#"{"a": 1"#).err().unwrap(), "invalid JSON at character 7: expected ',' or '}'");
+        assert_eq!(parse(r#""open"#).err().unwrap(), "invalid JSON at character 5: unexpected end");
+        assert_eq!(parse("tru").err().unwrap(), "invalid JSON at character 3: expected 'true'");
+        assert_eq!(parse("nul!").err().unwrap(), "invalid JSON at character 3: expected 'null'");
+        assert_eq!(parse(r#""\uzzzz""#).err().unwrap(), "invalid JSON at character 3: invalid \\u escape");
+        assert_eq!(parse("1 2").err().unwrap(), "invalid JSON at character 2: trailing characters");
+        assert_eq!(parse("  ").err().unwrap(), "invalid JSON at character 2: expected a value");
+    }
+}
@@ -0,0 +1,53 @@
+//! JUnit XML for the results of `check`
+//!
+//! Every file is a test case that fails when it crosses a threshold, which
+//! is what Jenkins, GitLab and most other CI systems know how to show.
+//! Warnings fail nothing and go to the output of the test case instead.
+
+use std::fmt::Write;
+
+/// A test suite with one test case per `(path, violations, warnings)`
+pub fn report (results: &[(&str, Vec<String>, Vec<String>)]) -> String
+{
+    let failures = results.iter().filter(|(_, violations, _)| !violations.is_empty()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(xml, "<testsuite name=\"git-whoknows\" tests=\"{}\" failures=\"{}\" errors=\"0\">",
+                     results.len(), failures);
+    for (path, violations, warnings) in results
+    {
+        let _ = write!(xml, "  <testcase classname=\"ownership\" name=\"{}\"", escape(path));
+        if violations.is_empty() && warnings.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        if !violations.is_empty() {
+            let _ = writeln!(xml, "    <failure message=\"{}\">{}</failure>",
+                             escape(&violations[0]), escape(&violations.join("\n")));
+        }
+        if !warnings.is_empty() {
+            let warnings: Vec<String> = warnings.iter().map(|warning| format!("warning: {}", warning)).collect();
+            let _ = writeln!(xml, "    <system-out>{}</system-out>", escape(&warnings.join("\n")));
+        }
+        xml.push_str("  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape (s: &str) -> String
+{
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars()
+    {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,174 @@
+//! Telling what language a file is written in
+//!
+//! By file name, then by extension, then by the interpreter named on a `#!`
+//! line, which is enough for the files of most repositories. Files that no
+//! one writes by hand, such as images, archives and lock files, are assets.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::FileReport;
+
+/// Languages by the file names and extensions that give them away
+const LANGUAGES: &[(&str, &[&str], &[&str])] = &[
+    // (name, file names, extensions)
+    ("c", &[], &["c", "h"]),
+    ("cmake", &["CMakeLists.txt"], &["cmake"]),
+    ("cpp", &[], &["cc", "cpp", "cxx", "c++", "hh", "hpp", "hxx"]),
+    ("csharp", &[], &["cs"]),
+    ("css", &[], &["css", "scss", "sass", "less"]),
+    ("dart", &[], &["dart"]),
+    ("dockerfile", &["Dockerfile", "Containerfile"], &["dockerfile"]),
+    ("elixir", &[], &["ex", "exs"]),
+    ("erlang", &[], &["erl", "hrl"]),
+    ("go", &[], &["go"]),
+    ("haskell", &[], &["hs", "lhs"]),
+    ("html", &[], &["html", "htm"]),
+    ("java", &[], &["java"]),
+    ("javascript", &[], &["js", "mjs", "cjs", "jsx"]),
+    ("json", &[], &["json"]),
+    ("kotlin", &[], &["kt", "kts"]),
+    ("lua", &[], &["lua"]),
+    ("make", &["Makefile", "GNUmakefile", "makefile"], &["mk"]),
+    ("markdown", &[], &["md", "markdown"]),
+    ("nix", &[], &["nix"]),
+    ("objective-c", &[], &["m", "mm"]),
+    ("ocaml", &[], &["ml", "mli"]),
+    ("perl", &[], &["pl", "pm"]),
+    ("php", &[], &["php"]),
+    ("protobuf", &[], &["proto"]),
+    ("python", &["SConstruct", "SConscript"], &["py", "pyi", "pyw"]),
+    ("r", &[], &["r"]),
+    ("ruby", &["Gemfile", "Rakefile"], &["rb", "rake", "gemspec"]),
+    ("rust", &[], &["rs"]),
+    ("scala", &[], &["scala", "sc"]),
+    ("shell", &[], &["sh", "bash", "zsh", "fish"]),
+    ("sql", &[], &["sql"]),
+    ("swift", &[], &["swift"]),
+    ("tex", &[], &["tex", "sty", "cls"]),
+    ("toml", &[], &["toml"]),
+    ("typescript", &[], &["ts", "tsx", "mts", "cts"]),
+    ("xml", &[], &["xml", "xsd", "xsl"]),
+    ("yaml", &[], &["yaml", "yml"]),
+    ("zig", &[], &["zig"]),
+];
+
+/// Languages by the interpreter of a `#!` line
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("bash", "shell"),
+    ("sh", "shell"),
+    ("zsh", "shell"),
+    ("node", "javascript"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("python", "python"),
+    ("python2", "python"),
+    ("python3", "python"),
+    ("ruby", "ruby"),
+    ("lua", "lua"),
+];
+
+/// Extensions of files that are not written by hand
+const ASSETS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "tiff", "psd",
+    "pdf", "woff", "woff2", "ttf", "otf", "eot",
+    "zip", "gz", "tgz", "bz2", "xz", "7z", "tar", "jar", "war",
+    "so", "dylib", "dll", "exe", "a", "o", "class", "pyc", "wasm",
+    "mp3", "mp4", "wav", "ogg", "mov", "avi", "webm",
+    "lock",
+];
+
+/// Lock files whose names do not end in `.lock`
+const LOCK_FILES: &[&str] = &["package-lock.json", "npm-shrinkwrap.json", "go.sum"];
+
+/// Every language that can be detected, by name
+pub fn names () -> impl Iterator<Item = &'static str>
+{
+    LANGUAGES.iter().map(|(name, _, _)| *name)
+}
+
+/// The language of `path`, looking at its first line in `workdir` if the
+/// name does not tell
+pub fn detect (workdir: &Path, path: &Path) -> Option<&'static str>
+{
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    for (language, names, extensions) in LANGUAGES
+    {
+        if names.contains(&name) || extension.as_deref().is_some_and(|e| extensions.contains(&e)) {
+            return Some(language);
+        }
+    }
+    if extension.is_some() {
+        return None;
+    }
+    let interpreter = shebang(&workdir.join(path))?;
+    INTERPRETERS.iter().find(|(i, _)| *i == interpreter).map(|(_, language)| *language)
+}
+
+/// Whether `path` is an image, archive, lock file or the like
+pub fn is_asset (path: &Path) -> bool
+{
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    LOCK_FILES.contains(&name) || extension.is_some_and(|e| ASSETS.contains(&e.as_str()))
+}
+
+/// The lines someone owns across files, by language
+pub struct Breakdown {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+    /// Most lines first; files of no known language count as `other`
+    pub languages: Vec<(&'static str, usize)>,
+}
+
+/// The lines of every owner of `files` by language, telling languages by
+/// the files in `workdir`, most lines first
+pub fn breakdown (workdir: &Path, files: &[FileReport]) -> Vec<Breakdown>
+{
+    let mut owners: HashMap<&str, (&str, &str, HashMap<&'static str, usize>)> = HashMap::new();
+    for file in files
+    {
+        let language = detect(workdir, Path::new(&file.path)).unwrap_or("other");
+        for (key, owner) in &file.owners
+        {
+            let (_, _, languages) = owners.entry(key)
+                .or_insert_with(|| (&owner.name, &owner.email, HashMap::new()));
+            *languages.entry(language).or_insert(0) += owner.lines();
+        }
+    }
+
+    let mut breakdowns: Vec<Breakdown> = owners.into_values()
+        .map(|(name, email, languages)| {
+            let mut languages: Vec<(&'static str, usize)> = languages.into_iter().collect();
+            languages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            Breakdown {
+                name: name.to_string(),
+                email: email.to_string(),
+                lines: languages.iter().map(|(_, lines)| lines).sum(),
+                languages,
+            }
+        })
+        .collect();
+    breakdowns.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+    breakdowns
+}
+
+/// The interpreter named by the `#!` line of `file`, without a path and
+/// looking through `env`
+fn shebang (file: &Path) -> Option<String>
+{
+    let mut start = [0; 128];
+    let len = File::open(file).ok()?.read(&mut start).ok()?;
+    let start = String::from_utf8_lossy(&start[..len]);
+    let line = start.lines().next()?;
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+    Some(program.to_string())
+}
@@ -0,0 +1,227 @@
+//! Find out who knows about a file
+//!
+//! Blames files and aggregates the surviving lines by author. The
+//! `git-whoknows` command is a thin front end over this crate. Without the
+//! `git` feature only the aggregation of blame output produced elsewhere
+//! is left, see `blame::report`.
+
+#[cfg(feature = "git")]
+pub mod anonymize;
+pub mod blame;
+#[cfg(feature = "git")]
+pub mod cache;
+#[cfg(feature = "git")]
+pub mod calendar;
+#[cfg(feature = "git")]
+pub mod commits;
+#[cfg(feature = "git")]
+pub mod complexity;
+#[cfg(feature = "git")]
+pub mod config;
+pub mod date;
+#[cfg(feature = "git")]
+pub mod deleted;
+pub mod directory;
+pub mod error;
+#[cfg(feature = "git")]
+pub mod explain;
+#[cfg(feature = "git")]
+pub mod export;
+#[cfg(feature = "git")]
+pub mod git;
+pub mod glob;
+#[cfg(feature = "git")]
+pub mod gone;
+#[cfg(feature = "git")]
+pub mod identities;
+#[cfg(feature = "git")]
+pub mod issues;
+#[cfg(feature = "git")]
+pub mod jj;
+pub mod json;
+pub mod language;
+pub mod mailmap;
+pub mod metric;
+#[cfg(feature = "git")]
+pub mod noise;
+pub mod oid;
+#[cfg(feature = "git")]
+pub mod package;
+#[cfg(feature = "git")]
+pub mod pairing;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "git")]
+pub mod pickaxe;
+pub mod platform;
+pub mod porcelain;
+#[cfg(feature = "git")]
+pub mod prometheus;
+pub mod regex;
+#[cfg(feature = "git")]
+pub mod renames;
+#[cfg(feature = "git")]
+pub mod reportdiff;
+#[cfg(feature = "git")]
+pub mod reviewed;
+#[cfg(feature = "git")]
+pub mod reviewers;
+#[cfg(feature = "git")]
+pub mod risk;
+#[cfg(feature = "git")]
+pub mod roster;
+pub mod schema;
+#[cfg(feature = "git")]
+pub mod shards;
+#[cfg(feature = "git")]
+pub mod signatures;
+#[cfg(feature = "git")]
+pub mod snapshot;
+#[cfg(feature = "git")]
+pub mod symbol;
+pub mod teams;
+#[cfg(feature = "git")]
+pub mod testcode;
+#[cfg(feature = "git")]
+pub mod timeseries;
+#[cfg(feature = "git")]
+pub mod timezone;
+#[cfg(feature = "git")]
+pub mod topics;
+pub mod trace;
+#[cfg(feature = "git")]
+pub mod vcs;
+#[cfg(feature = "git")]
+pub mod workspace;
+
+#[cfg(feature = "git")]
+mod analyze;
+mod ownership;
+#[cfg(feature = "git")]
+mod toml;
+
+#[cfg(feature = "git")]
+pub use analyze::{
+    analyze_file,
+    analyze_files,
+    analyze_files_iter,
+    analyze_repo,
+    analyze_repo_iter,
+    blame_regions,
+    commands,
+    discover,
+    ensure_tracked,
+    load_mailmap,
+    path_at,
+    reopen,
+    repo_relative,
+    tracked_files,
+    Analyzer,
+    FileReports,
+    Observer,
+    Options,
+    Region,
+    Silent,
+};
+pub use error::Error;
+pub use ownership::{bus_factor, owner_key, percent, Contribution, Difference, FileReport, Owner, RepoReport, Span};
+
+#[cfg(all(test, feature = "git"))]
+mod tests {
+    use std::path::Path;
+
+    use git2::{Repository, Signature, Time};
+
+    use super::*;
+
+    /// A new repository named after `name` with a commit for every one of
+    /// `commits`, by an author and email writing a file with contents, a
+    /// day apart
+    fn repository (name: &str, commits: &[(&str, &str, &str, &str)]) -> Repository
+    {
+        let dir = std::env::temp_dir().join(format!("git-whoknows-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        for (i, &(author, email, path, contents)) in commits.iter().enumerate()
+        {
+            std::fs::write(dir.join(path), contents).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(path)).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = Signature::new(author, email, &Time::new(1_700_000_000 + i as i64 * 86_400, 0)).unwrap();
+            let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, path, &tree, &parents).unwrap();
+        }
+        repo
+    }
+
+    fn remove (repo: Repository)
+    {
+        std::fs::remove_dir_all(repo.workdir().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn file ()
+    {
+        let repo = repository("file", &[
+            ("Alice", "alice@example.com", "a.txt", "one\ntwo\nthree\n"),
+            ("Bob", "bob@example.com", "a.txt", "one\ntwo\nthree\nfour\n"),
+        ]);
+        for &backend in &[blame::Backend::Libgit2, blame::Backend::Git]
+        {
+            let report = Analyzer::new(&repo).backend(backend).cache(false).file(Path::new("a.txt")).unwrap();
+            assert_eq!(report.lines(), 4);
+            assert_eq!(report.owners["alice@example.com"].lines(), 3);
+            assert_eq!(report.owners["bob@example.com"].lines(), 1);
+            assert_eq!(report.ranked_owners()[0].name, "Alice");
+            assert_eq!(report.bus_factor(), 1);
+        }
+        remove(repo);
+    }
+
+    #[test]
+    fn cached_file ()
+    {
+        let repo = repository("cached", &[("Alice", "alice@example.com", "a.txt", "one\ntwo\n")]);
+        let analyzer = Analyzer::new(&repo);
+        let blamed = analyzer.file(Path::new("a.txt")).unwrap();
+        let cached = analyzer.file(Path::new("a.txt")).unwrap();
+        assert_eq!(cached.lines(), blamed.lines());
+        assert_eq!(cached.owners["alice@example.com"].lines(), 2);
+        remove(repo);
+    }
+
+    #[test]
+    fn mailmap ()
+    {
+        let repo = repository("mailmap", &[
+            ("Alice", "alice@example.com", "a.txt", "one\n"),
+            ("alice", "alice@old.example.com", "a.txt", "one\ntwo\n"),
+            ("Bob", "bob@example.com", ".mailmap", "Alice <alice@example.com> <alice@old.example.com>\n"),
+        ]);
+        let report = Analyzer::new(&repo).cache(false).file(Path::new("a.txt")).unwrap();
+        assert_eq!(report.owners.len(), 1);
+        assert_eq!(report.owners["alice@example.com"].lines(), 2);
+        let report = Analyzer::new(&repo).cache(false).mailmap(false).file(Path::new("a.txt")).unwrap();
+        assert_eq!(report.owners.len(), 2);
+        remove(repo);
+    }
+
+    #[test]
+    fn repo ()
+    {
+        let repo = repository("repo", &[
+            ("Alice", "alice@example.com", "a.txt", "one\ntwo\n"),
+            ("Bob", "bob@example.com", "b.txt", "one\n"),
+        ]);
+        let report = Analyzer::new(&repo).cache(false).repo().unwrap();
+        let mut files: Vec<(&str, usize)> = report.files.iter().map(|file| (file.path.as_str(), file.lines())).collect();
+        files.sort();
+        assert_eq!(files, [("a.txt", 2), ("b.txt", 1)]);
+        assert!(!report.partial);
+        assert_eq!(report.revision, Some(repo.head().unwrap().peel_to_commit().unwrap().id()));
+        remove(repo);
+    }
+}
@@ -0,0 +1,104 @@
+//! Mailing reports through the `curl` command
+//!
+//! The server, the sender and the recipients come from the `[email]` table
+//! of the configuration, the password from `WHOKNOWS_SMTP_PASSWORD`. As
+//! with HTTP requests, curl gets everything but the message as a config
+//! file on stdin, so that the password does not show up in the process
+//! list; the message itself goes through a file in a directory of its
+//! own, removed after sending.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use whoknows::config::Email;
+use whoknows::{date, platform, Error};
+
+use crate::http::quote;
+
+/// Mail `body` as plain text under `subject` to the recipients of `email`
+pub fn send (email: &Email, subject: &str, body: &str) -> Result<(), Error>
+{
+    let missing = |key: &str| Error::Config(format!("email.{} is not set, which report --email needs", key));
+    let smtp = email.smtp.as_deref().ok_or_else(|| missing("smtp"))?;
+    let from = email.from.as_deref().ok_or_else(|| missing("from"))?;
+    if email.to.is_empty() {
+        return Err(missing("to"));
+    }
+
+    let mut config = format!("url = {}\nmail-from = {}\nsilent\nshow-error\n", quote(smtp), quote(&address(from)));
+    for to in &email.to
+    {
+        config.push_str(&format!("mail-rcpt = {}\n", quote(&address(to))));
+    }
+    if let Some(user) = &email.user {
+        let password = std::env::var("WHOKNOWS_SMTP_PASSWORD").unwrap_or_default();
+        config.push_str(&format!("user = {}\nssl-reqd\n", quote(&format!("{}:{}", user, password))));
+    }
+    let dir = platform::temp_dir("git-whoknows-mail")?;
+    let message = dir.join("report.eml");
+    let written = OpenOptions::new().write(true).create_new(true).open(&message)
+        .and_then(|mut file| file.write_all(self::message(from, &email.to, subject, body).as_bytes()));
+    let sent = written.map_err(Error::from).and_then(|()| {
+        config.push_str(&format!("upload-file = {}\n", quote(&message.to_string_lossy())));
+        curl(&config)
+    });
+    let _ = fs::remove_dir_all(&dir);
+    sent
+}
+
+fn curl (config: &str) -> Result<(), Error>
+{
+    let mut child = Command::new("curl")
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Remote(format!("could not run curl: {}", e)))?;
+    child.stdin.take().unwrap().write_all(config.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::Remote(format!("could not mail the report: {}",
+                                         String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(())
+}
+
+/// The message with its headers, lines ending in CRLF as SMTP has them
+fn message (from: &str, to: &[String], subject: &str, body: &str) -> String
+{
+    let mut message = format!("From: {}\nTo: {}\nSubject: {}\nDate: {}\nMIME-Version: 1.0\n\
+                               Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n",
+                              from, to.join(", "), header(subject), date::format_rfc2822(date::now()));
+    message.push_str(body);
+    message.lines().map(|line| format!("{}\r\n", line)).collect()
+}
+
+/// `text` for a header, as an encoded word if it is not all ASCII
+fn header (text: &str) -> String
+{
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    let mut encoded = String::from("=?utf-8?Q?");
+    for b in text.bytes()
+    {
+        match b {
+            b' ' => encoded.push('_'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b',' => encoded.push(b as char),
+            b => encoded.push_str(&format!("={:02X}", b)),
+        }
+    }
+    encoded.push_str("?=");
+    encoded
+}
+
+/// The bare address of `mailbox`, which may be given as `Name <address>`
+fn address (mailbox: &str) -> String
+{
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => mailbox[start + 1..end].trim().to_string(),
+        _ => mailbox.trim().to_string(),
+    }
+}
@@ -0,0 +1,218 @@
+//! Identity mapping from `.mailmap` files
+//!
+//! Understands the four forms described in gitmailmap(5):
+//!
+//! ```text
+//! Proper Name <commit@email>
+//! <proper@email> <commit@email>
+//! Proper Name <proper@email> <commit@email>
+//! Proper Name <proper@email> Commit Name <commit@email>
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone)]
+struct Entry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    /// Only map commits by this name, if given
+    commit_name: Option<String>,
+    /// Lower cased for matching, as emails are matched case-insensitively
+    commit_email: String,
+}
+
+#[derive(Clone, Default)]
+pub struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+impl Mailmap {
+    /// Read `path`; a missing file is an empty mailmap
+    pub fn from_file (path: &Path) -> io::Result<Mailmap>
+    {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Mailmap::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Mailmap::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse (text: &str) -> Mailmap
+    {
+        let mut entries = Vec::new();
+        for line in text.lines()
+        {
+            let line = match line.find('#') {
+                Some(i) => &line[..i],
+                None => line,
+            };
+            if let Some(entry) = parse_line(line) {
+                entries.push(entry);
+            }
+        }
+        Mailmap { entries }
+    }
+
+    /// Add the entries of `other` after, and so with priority over, ours
+    pub fn extend (&mut self, other: &Mailmap)
+    {
+        self.entries.extend(other.entries.iter().cloned());
+    }
+
+    pub fn is_empty (&self) -> bool
+    {
+        self.entries.is_empty()
+    }
+
+    pub fn len (&self) -> usize
+    {
+        self.entries.len()
+    }
+
+    /// Numbers of the lines of `text`, counting from 1, that are neither
+    /// blank nor comments nor in any of the four forms, and so ignored
+    pub fn unparsed (text: &str) -> Vec<usize>
+    {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.split('#').next().unwrap_or("");
+                !line.trim().is_empty() && parse_line(line).is_none()
+            })
+            .map(|(n, _)| n + 1)
+            .collect()
+    }
+
+    /// The canonical (name, email) for a commit identity; later entries
+    /// win, and an entry naming the commit name beats one that does not
+    pub fn resolve<'a> (&'a self, name: &'a str, email: &'a str) -> (&'a str, &'a str)
+    {
+        match self.entry(name, email) {
+            Some(entry) => (
+                entry.proper_name.as_deref().unwrap_or(name),
+                entry.proper_email.as_deref().unwrap_or(email),
+            ),
+            None => (name, email),
+        }
+    }
+
+    /// The name an entry gives a commit identity, if one does
+    pub fn proper_name (&self, name: &str, email: &str) -> Option<&str>
+    {
+        self.entry(name, email)?.proper_name.as_deref()
+    }
+
+    /// The entry that maps a commit identity, see `resolve`
+    fn entry (&self, name: &str, email: &str) -> Option<&Entry>
+    {
+        let lower = email.to_lowercase();
+        let mut best: Option<&Entry> = None;
+        for entry in &self.entries
+        {
+            if entry.commit_email != lower {
+                continue;
+            }
+            match &entry.commit_name {
+                Some(commit_name) if commit_name != name => continue,
+                Some(_) => best = Some(entry),
+                None => {
+                    if best.is_none_or(|b| b.commit_name.is_none()) {
+                        best = Some(entry);
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Split a line into its `(name, email)` parts, names may be empty
+fn parse_line (line: &str) -> Option<Entry>
+{
+    let mut parts = Vec::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('<') {
+        let close = open + rest[open..].find('>')?;
+        let name = rest[..open].trim();
+        let email = rest[open + 1..close].trim();
+        parts.push((name, email));
+        rest = &rest[close + 1..];
+    }
+
+    let non_empty = |s: &str| if s.is_empty() { None } else { Some(s.to_string()) };
+    match parts.as_slice() {
+        [(name, email)] => Some(Entry {
+            proper_name: non_empty(name),
+            proper_email: None,
+            commit_name: None,
+            commit_email: email.to_lowercase(),
+        }),
+        [(name, proper_email), (commit_name, commit_email)] => Some(Entry {
+            proper_name: non_empty(name),
+            proper_email: non_empty(proper_email),
+            commit_name: non_empty(commit_name),
+            commit_email: commit_email.to_lowercase(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_forms ()
+    {
+        let mailmap = Mailmap::parse("Alice <alice@example.com>\n\
+                                      <bob@example.com> <bob@old.example.com>\n\
+                                      Carol <carol@example.com> <c@example.com>\n\
+                                      Dave <dave@example.com> Davey <d@example.com>\n");
+        assert_eq!(mailmap.len(), 4);
+        assert_eq!(mailmap.resolve("alice", "alice@example.com"), ("Alice", "alice@example.com"));
+        assert_eq!(mailmap.resolve("Bob", "bob@old.example.com"), ("Bob", "bob@example.com"));
+        assert_eq!(mailmap.resolve("C", "c@example.com"), ("Carol", "carol@example.com"));
+        assert_eq!(mailmap.resolve("Davey", "d@example.com"), ("Dave", "dave@example.com"));
+        // Only commits by the name given are mapped by the fourth form
+        assert_eq!(mailmap.resolve("Someone", "d@example.com"), ("Someone", "d@example.com"));
+    }
+
+    #[test]
+    fn emails_match_in_any_case ()
+    {
+        let mailmap = Mailmap::parse("Alice <alice@example.com> <Alice@Example.COM>");
+        assert_eq!(mailmap.resolve("a", "ALICE@example.com"), ("Alice", "alice@example.com"));
+    }
+
+    #[test]
+    fn later_and_named_entries_win ()
+    {
+        let mailmap = Mailmap::parse("First <a@example.com>\n\
+                                      Named <n@example.com> Alias <a@example.com>\n\
+                                      Second <a@example.com>\n");
+        assert_eq!(mailmap.resolve("Other", "a@example.com"), ("Second", "a@example.com"));
+        assert_eq!(mailmap.resolve("Alias", "a@example.com"), ("Named", "n@example.com"));
+        assert_eq!(mailmap.proper_name("Other", "a@example.com"), Some("Second"));
+        assert_eq!(mailmap.proper_name("Other", "b@example.com"), None);
+    }
+
+    #[test]
+    fn extended_entries_win ()
+    {
+        let mut mailmap = Mailmap::parse("Repository <a@example.com>");
+        mailmap.extend(&Mailmap::parse("Configured <a@example.com>"));
+        assert_eq!(mailmap.resolve("a", "a@example.com").0, "Configured");
+    }
+
+    #[test]
+    fn comments_and_unparsed_lines ()
+    {
+        let text = "# people\nAlice <a@example.com> # the one\n\nnot an entry\n<unclosed\n";
+        let mailmap = Mailmap::parse(text);
+        assert_eq!(mailmap.len(), 1);
+        assert_eq!(mailmap.resolve("a", "a@example.com").0, "Alice");
+        assert_eq!(Mailmap::unparsed(text), [4, 5]);
+    }
+}
@@ -1,137 +1,4452 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
-use std::fmt;
-use git2::{BlameOptions, Repository, Signature, BlameHunk, Oid};
-use std::path::{PathBuf};
+use git2::{Oid, Repository};
 use structopt::StructOpt;
-use std::collections::HashMap;
 
+use whoknows::anonymize;
+use whoknows::blame::{self, Backend};
+use whoknows::cache::Cache;
+use whoknows::calendar::{self, Calendar};
+use whoknows::config::{self, Config, Thresholds};
+use whoknows::commits::Commits;
+use whoknows::complexity::Weights;
+use whoknows::explain;
+use whoknows::export::Export;
+use whoknows::issues::{self, Issue};
+use whoknows::jj::Workspace;
+use whoknows::json::{self, Value};
+use whoknows::language;
+use whoknows::metric::{Context, Metrics, Subject};
+use whoknows::noise;
+use whoknows::platform;
+use whoknows::regex::Regex;
+use whoknows::reportdiff;
+use whoknows::reviewers::{self, Reviewer};
+use whoknows::roster::{self, Roster};
+use whoknows::signatures;
+use whoknows::snapshot::{self, Snapshot};
+use whoknows::timeseries;
+use whoknows::timezone::{self, FileZones, OwnerZone};
+use whoknows::topics::{self, Topics};
+use whoknows::trace::{self, Level, LogFormat};
+use whoknows::vcs::{self, Mercurial, Vcs};
+use whoknows::{date, percent, prometheus, Analyzer, Error, FileReport, RepoReport};
+
+mod checkpoint;
+mod clipboard;
+mod doctor;
+#[cfg(feature = "forges")]
+mod gerrit;
+#[cfg(feature = "forges")]
+mod github;
+#[cfg(feature = "forges")]
+mod gitlab;
+mod hook;
+mod human;
+// Webhooks only post
+#[cfg_attr(not(feature = "forges"), allow(dead_code))]
+mod http;
+mod interrupt;
+mod junit;
+mod mail;
+mod man;
+mod notify;
+mod output;
+mod pick;
+mod progress;
+mod query;
+mod remote;
+#[cfg(feature = "serve")]
+mod serve;
+mod table;
+mod watch;
+
+use checkpoint::{Checkpoint, Checkpointing};
+use clipboard::CopyFormat;
+use output::Output;
+use doctor::Doctor;
+#[cfg(feature = "forges")]
+use gerrit::Gerrit;
+#[cfg(feature = "forges")]
+use github::{GitHub, Organization};
+#[cfg(feature = "forges")]
+use gitlab::GitLab;
+use progress::{Progress, ProgressFormat};
+use remote::Clone;
+use table::{Align, Table, Truncate};
+
+/// Owners of its file shown with a line
+const LINE_OWNERS: usize = 3;
+
+/// Consecutive lines by one author that make a quickfix entry
+const QUICKFIX_MIN_LINES: usize = 10;
+
+/// Hex digits of the hashes in tables unless --abbrev says otherwise
+const SHORT_HASH: usize = 12;
+
+/// Topics shown for each owner
+const TOPICS: usize = 3;
+
+/// Columns of the tables in a mailed report
+const MAIL_WIDTH: usize = 100;
+
+/// Files and owners in a report
+const REPORT_TOP: usize = 10;
+
+/// Find out who knows the code of a repository, from who wrote its lines
 #[derive(StructOpt)]
+#[structopt(setting = structopt::clap::AppSettings::SubcommandsNegateReqs)]
 #[allow(non_snake_case)]
 struct Args {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+    #[structopt(global = true, long = "format", env = "WHOKNOWS_FORMAT",
+                possible_values = FORMATS)]
+    /// output format, table unless configured otherwise
+    format: Option<Format>,
+    #[structopt(global = true, long = "porcelain", value_name = "version", min_values = 0, require_equals = true,
+                possible_values = whoknows::porcelain::VERSIONS)]
+    /// write every file as lines for scripts, in a format that only
+    /// changes with the version asked for, v1 by default
+    porcelain: Option<Option<String>>,
     #[structopt(name = "path", parse(from_os_str))]
-    arg_path: PathBuf,
-    #[structopt(short = "M")]
+    /// files to analyze, directories stand for all tracked files below them;
+    /// picked on the terminal when none are given there
+    arg_paths: Vec<PathBuf>,
+    #[structopt(long = "pick", conflicts_with = "query-server")]
+    /// choose the files to analyze from the tracked files, or those below
+    /// the given paths, by typing parts of their path
+    pick: bool,
+    #[structopt(global = true, long = "lang", env = "WHOKNOWS_LANG", value_name = "languages", number_of_values = 1, use_delimiter = true,
+                parse(try_from_str = parse_language))]
+    /// only analyze files in these languages, e.g. rust,python
+    languages: Vec<String>,
+    #[structopt(global = true, long = "metric", env = "WHOKNOWS_METRIC", value_name = "name", number_of_values = 1)]
+    /// with --format metrics, only compute these metrics
+    metrics: Vec<String>,
+    #[structopt(global = true, short = "M")]
     /// find line moves within and across files
     flag_M: bool,
-    #[structopt(short = "C")]
-    /// find line copies within and across files
-    flag_C: bool,
-    #[structopt(short = "F")]
+    #[structopt(global = true, short = "C", parse(from_occurrences))]
+    /// find lines copied from files changed in the same commit; -CC also
+    /// from the files of the commit creating the file, -CCC from any file
+    flag_C: u8,
+    #[structopt(global = true, short = "F")]
     /// follow only the first parent commits
     flag_F: bool,
+    #[structopt(global = true, long = "no-follow")]
+    /// do not follow files across renames, their lines from before are
+    /// blamed on the commit that gave them their path
+    no_follow: bool,
+    #[structopt(global = true, long = "rev", env = "WHOKNOWS_REV", value_name = "rev")]
+    /// blame as of this revision instead of the work tree
+    rev: Option<String>,
+    #[structopt(global = true, long = "branch", env = "WHOKNOWS_BRANCH", value_name = "name", conflicts_with = "rev")]
+    /// blame the files as they are on this local or remote branch, without
+    /// checking it out
+    branch: Option<String>,
+    #[structopt(global = true, long = "vs", env = "WHOKNOWS_VS", value_name = "rev")]
+    /// also show how the ownership of every file differs from its
+    /// ownership at <rev>, e.g. origin/main
+    vs: Option<String>,
+    #[structopt(global = true, long = "ignore-revs-file", env = "WHOKNOWS_IGNORE_REVS_FILE", value_name = "file", parse(from_os_str))]
+    /// ignore the commits listed in the file, as git blame does; needs
+    /// --backend git
+    ignore_revs_file: Option<PathBuf>,
+    #[structopt(global = true, long = "detect-noise")]
+    /// ignore formatting and codemod commits, found by their message or by
+    /// how many lines they rewrote; needs --backend git
+    detect_noise: bool,
+    #[structopt(global = true, long = "decay", env = "WHOKNOWS_DECAY", value_name = "days")]
+    /// rank owners by lines weighted by age, halving every <days>
+    decay: Option<f64>,
+    #[structopt(global = true, long = "since", env = "WHOKNOWS_SINCE", value_name = "date", parse(try_from_str = date::parse))]
+    /// only count lines of commits authored since <date>, e.g. 2020-01-31
+    /// or "12 months ago"
+    since: Option<i64>,
+    #[structopt(global = true, long = "until", env = "WHOKNOWS_UNTIL", value_name = "date", parse(try_from_str = date::parse))]
+    /// only count lines of commits authored until <date>
+    until: Option<i64>,
+    #[structopt(global = true, long = "no-merges")]
+    /// leave out lines attributed to merge commits
+    no_merges: bool,
+    #[structopt(global = true, long = "exclude-author", env = "WHOKNOWS_EXCLUDE_AUTHOR", value_name = "regex", number_of_values = 1)]
+    /// leave out authors whose name or email matches, e.g. '\[bot\]$'
+    exclude_authors: Vec<Regex>,
+    #[structopt(global = true, long = "only-domain", env = "WHOKNOWS_ONLY_DOMAIN", value_name = "domain", number_of_values = 1)]
+    /// keep only authors with an email at <domain> or below it
+    only_domains: Vec<String>,
+    #[structopt(global = true, long = "min-commits", env = "WHOKNOWS_MIN_COMMITS", value_name = "n")]
+    /// leave out authors with fewer than <n> commits in the history
+    min_commits: Option<usize>,
+    #[structopt(global = true, long = "no-mailmap")]
+    /// do not merge identities through .mailmap
+    no_mailmap: bool,
+    #[structopt(global = true, long = "backend", env = "WHOKNOWS_BACKEND", possible_values = &["libgit2", "git"])]
+    /// compute blame in process (libgit2, the default) or by running git
+    /// blame
+    backend: Option<Backend>,
+    #[structopt(global = true, short = "j", long = "jobs", env = "WHOKNOWS_JOBS", value_name = "n")]
+    /// analyze at most <n> files at a time, defaults to the number of cores
+    jobs: Option<usize>,
+    #[structopt(global = true, long = "no-cache")]
+    /// always blame, neither reading nor updating .git/whoknows-cache
+    no_cache: bool,
+    #[structopt(global = true, long = "cache-dir", env = "WHOKNOWS_CACHE_DIR", value_name = "dir", parse(from_os_str))]
+    /// keep blame results in <dir>, shared by every clone and worktree of
+    /// the project, rather than in .git/whoknows-cache
+    cache_dir: Option<PathBuf>,
+    #[structopt(global = true, long = "incremental", conflicts_with = "no-cache")]
+    /// report which files changed since the previous incremental run; only
+    /// those are blamed again, everything else comes from the cache
+    incremental: bool,
+    #[structopt(global = true, long = "watch")]
+    /// keep running and print the report again for files as they change
+    /// in the work tree or when HEAD moves
+    watch: bool,
+    #[structopt(global = true, long = "resume")]
+    /// skip the files an interrupted or crashed run with the same options
+    /// already finished
+    resume: bool,
+    #[structopt(long = "query-server")]
+    /// answer newline delimited JSON requests such as {"file": "src/main.rs",
+    /// "line": 120} on stdin, one line of JSON each on stdout
+    query_server: bool,
+    #[structopt(global = true, long = "roster", env = "WHOKNOWS_ROSTER", value_name = "file", parse(from_os_str))]
+    /// mark the authors missing from the active list of this TOML file
+    /// as departed and count the lines nobody left can answer for
+    roster_file: Option<PathBuf>,
+    #[structopt(global = true, long = "active-window", env = "WHOKNOWS_ACTIVE_WINDOW", value_name = "duration",
+                parse(try_from_str = date::parse_duration))]
+    /// only show authors with a commit anywhere in the repository within
+    /// this long, e.g. 180d, folding everyone else into "inactive owners"
+    active_window: Option<i64>,
+    #[structopt(global = true, long = "anonymize")]
+    /// replace every author by a pseudonym hashed from their identity and
+    /// the salt, the same in every run with the same salt
+    anonymize: bool,
+    #[structopt(global = true, long = "salt", env = "WHOKNOWS_SALT", value_name = "salt", hide_env_values = true)]
+    /// the secret that --anonymize hashes identities with
+    salt: Option<String>,
+    #[structopt(global = true, long = "abbrev", env = "WHOKNOWS_ABBREV", value_name = "n",
+                parse(try_from_str = parse_abbrev))]
+    /// show commit hashes in tables and text output cut to <n> hex digits;
+    /// JSON and exports always carry full hashes
+    abbrev: Option<usize>,
+    #[structopt(global = true, long = "blame-chunk", env = "WHOKNOWS_BLAME_CHUNK", value_name = "lines")]
+    /// with the git backend, blame files longer than this many lines that
+    /// many at a time, 50000 by default, 0 for whole files
+    blame_chunk: Option<usize>,
+    #[structopt(global = true, long = "contents", value_name = "file", parse(from_os_str), conflicts_with = "watch")]
+    /// blame the contents of <file>, or of stdin for -, as those of the one
+    /// file analyzed, such as an unsaved editor buffer; lines that are not
+    /// in its last commit count as not committed yet. Uses the git backend
+    contents_file: Option<PathBuf>,
+    #[structopt(global = true, long = "copy", conflicts_with_all = &["watch", "query-server"])]
+    /// also put the report on the clipboard
+    copy: bool,
+    #[structopt(global = true, long = "copy-format", env = "WHOKNOWS_COPY_FORMAT", possible_values = &["markdown", "text"])]
+    /// copy the report with its tables in Markdown, the default, or as
+    /// printed
+    copy_format: Option<CopyFormat>,
+    #[structopt(global = true, long = "output", short = "o", env = "WHOKNOWS_OUTPUT", value_name = "file",
+                parse(from_os_str), conflicts_with = "watch")]
+    /// write the report to <file> instead of stdout, compressed with gzip
+    /// or zstd when it ends in .gz or .zst
+    output: Option<PathBuf>,
+    #[structopt(global = true, long = "dry-run")]
+    /// print every git command that analyzing the files would run, from
+    /// blame to the git log of --deleted, without running anything
+    dry_run: bool,
+    #[structopt(global = true, long = "no-truncate")]
+    /// never shorten names, emails or paths to fit the terminal
+    no_truncate: bool,
+    #[structopt(global = true, long = "human")]
+    /// show numbers in tables as 3,200 or 12.3k and dates as how long ago
+    /// they were; other formats keep raw values
+    human: bool,
+    #[structopt(global = true, long = "width", env = "WHOKNOWS_WIDTH", value_name = "columns", conflicts_with = "no-truncate")]
+    /// fit tables into this many columns instead of the width of the
+    /// terminal, also when the output is not a terminal
+    width: Option<usize>,
+    #[structopt(global = true, long = "redact-emails")]
+    /// mask the part of every email before the domain
+    redact_emails: bool,
+    #[structopt(global = true, long = "issues")]
+    /// also list the tickets, such as JIRA-123 or #456, named in the
+    /// summaries of the commits each file owes its lines to
+    issues: bool,
+    #[structopt(global = true, long = "topics")]
+    /// also list the words most frequent in the summaries of each owner's
+    /// commits, as their topics
+    topics: bool,
+    #[structopt(global = true, long = "include-reviewers")]
+    /// also list who reviewed the commits each file owes its lines to, by
+    /// their Reviewed-by and Acked-by trailers
+    include_reviewers: bool,
+    #[structopt(global = true, long = "include-signoffs")]
+    /// also list who shepherded the commits each file owes its lines to,
+    /// by their Signed-off-by trailers other than their authors'
+    include_signoffs: bool,
+    #[structopt(global = true, long = "signatures")]
+    /// after the files, list how many of each author's lines come from
+    /// commits with a good signature, as git log checks them
+    signatures: bool,
+    #[structopt(global = true, long = "split-tests")]
+    /// show the owners of production code and of tests apart, tests being
+    /// found by convention, by the tests setting and as #[cfg(test)]
+    /// modules
+    split_tests: bool,
+    #[structopt(global = true, long = "complexity")]
+    /// rank owners by a score in which a line counts for more the deeper
+    /// it is nested and the more branches it takes, shown as an extra column
+    complexity: bool,
+    #[structopt(global = true, long = "explain")]
+    /// after the owners of a file, show how the lines and score of each add
+    /// up from commits, weights and decay, and which identities were merged
+    /// into them
+    explain: bool,
+    #[structopt(global = true, long = "deleted")]
+    /// after the owners of a file, list who wrote lines of it that have
+    /// since been deleted or replaced, from git log --follow
+    deleted: bool,
+    #[structopt(global = true, long = "survival")]
+    /// after the owners of a file, list how many of the lines every author
+    /// added to it over its history survive, from git log --follow
+    survival: bool,
+    #[structopt(global = true, long = "roles")]
+    /// label every owner as maintainer, regular or visitor of the file by
+    /// their commits, share and how long they have worked on it
+    roles: bool,
+    #[structopt(global = true, long = "expertise")]
+    /// label every owner as expert, contributor or visitor of the file by
+    /// their share of it, or by the levels of the [expertise] configuration;
+    /// also in --format json and parquet
+    expertise: bool,
+    #[structopt(global = true, short = "v", long = "verbose", parse(from_occurrences))]
+    /// also show for every owner of a file in which quarters the commits
+    /// their lines survive from were made, how old those lines are and the
+    /// commit of the oldest; -vv also logs how long finding the repository
+    /// and blaming and aggregating every file take on stderr, -vvv also
+    /// parsing and cache lookups
+    verbose: u8,
+    #[structopt(global = true, long = "log-format", env = "WHOKNOWS_LOG_FORMAT", possible_values = &["text", "json"])]
+    /// write the log of -vv as text, the default, or as one JSON object
+    /// per line
+    log_format: Option<LogFormat>,
+    #[structopt(global = true, long = "errors", env = "WHOKNOWS_ERRORS", possible_values = &["text", "json"])]
+    /// report a failure as a line of text, the default, or as a JSON object
+    /// with its exit code, kind, path and message on stderr
+    errors: Option<ErrorFormat>,
+    #[structopt(global = true, long = "relative-to", env = "WHOKNOWS_RELATIVE_TO",
+                possible_values = &["repo", "cwd", "none"])]
+    /// show the paths of files relative to the root of the repository, the
+    /// default, to the current directory, or as absolute paths
+    relative_to: Option<RelativeTo>,
+    #[structopt(global = true, short = "q", long = "quiet")]
+    /// do not show progress or other chatter on stderr
+    quiet: bool,
+    #[structopt(global = true, long = "progress", env = "WHOKNOWS_PROGRESS", possible_values = &["bar", "json"])]
+    /// show progress as a bar on a terminal, the default, or write every
+    /// step of the analysis as a JSON object per line on stderr, even with
+    /// --quiet
+    progress: Option<ProgressFormat>,
+    #[structopt(global = true, long = "export", env = "WHOKNOWS_EXPORT", value_name = "kind:path")]
+    /// also store the results, e.g. sqlite:whoknows.db
+    export: Option<Export>,
+    #[structopt(global = true, long = "notify", env = "WHOKNOWS_NOTIFY", value_name = "webhook-url")]
+    /// post the files that dropped to a low bus factor since the previous
+    /// notification to a Slack compatible webhook
+    notify: Option<String>,
+    #[structopt(global = true, long = "notify-template", env = "WHOKNOWS_NOTIFY_TEMPLATE", value_name = "file",
+                parse(from_os_str), requires = "notify")]
+    /// the JSON to post instead of a Slack message, see the README for
+    /// its placeholders
+    notify_template: Option<PathBuf>,
+    #[structopt(global = true, long = "profile", env = "WHOKNOWS_PROFILE", value_name = "name")]
+    /// add the options of a [profile.<name>] of the configuration
+    profile: Option<String>,
+    #[structopt(skip)]
+    config: Config,
+    #[structopt(skip)]
+    roster: Option<Roster>,
+    /// What repository relative paths are shown below, set by
+    /// --relative-to once the repository is found
+    #[structopt(skip)]
+    path_base: Option<PathBuf>,
+    /// What --contents names, read once
+    #[structopt(skip)]
+    contents: Option<Arc<[u8]>>,
+    /// The commit of --rev, or of the working copy without it, in a jj
+    /// workspace
+    #[structopt(skip)]
+    jj_commit: Option<Oid>,
+    /// The options of `SCOPES` given on the command line, rather than by a
+    /// variable, the configuration or a profile
+    #[structopt(skip)]
+    given: Vec<&'static str>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Show who owns the lines of each file, also what a path given
+    /// without any command does
+    File {
+        #[structopt(name = "path", parse(from_os_str), required = true)]
+        /// files to analyze, directories stand for all tracked files below
+        /// them
+        paths: Vec<PathBuf>,
+    },
+    /// Show who owns the lines of each directory as a whole
+    Dir {
+        #[structopt(name = "path", parse(from_os_str), required = true)]
+        /// directories to sum up, each with all tracked files below it
+        paths: Vec<PathBuf>,
+    },
+    /// Show who owns the lines of the whole repository
+    Repo,
+    /// Show who owns the code that a diff changes: uncommitted changes,
+    /// those since <rev> or those of <rev>..<rev>
+    Diff {
+        #[structopt(name = "range")]
+        /// what to diff, as for git diff
+        range: Option<String>,
+        #[structopt(skip)]
+        paths: Vec<PathBuf>,
+    },
+    /// List files by bus factor, lowest first
+    BusFactor {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to list, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Rank files by how much it would hurt to lose their expert: recent
+    /// changes times the share of the top owner, more for inactive owners
+    Risk {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to rank, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Suggest for every file with a bus factor of 1 a second owner to
+    /// pair with its expert: whoever active owns most code close to it
+    Pairs {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to consider, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Show the timezones the owners of each file work in and flag files
+    /// whose owners all share one
+    Timezones {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to list, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Show how many lines each team owns, the teams of [teams] or those of
+    /// a GitHub organization
+    Teams {
+        #[structopt(long = "github-org", env = "WHOKNOWS_GITHUB_ORG", value_name = "org")]
+        /// the teams of this GitHub organization, their members matched to
+        /// authors by login
+        github_org: Option<String>,
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to consider, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Show when each author committed to the paths, day by day over the
+    /// last year, as a calendar like GitHub's
+    Heatmap {
+        #[structopt(long = "weeks", value_name = "n", default_value = "52")]
+        /// show the last <n> weeks
+        weeks: usize,
+        #[structopt(long = "html")]
+        /// write a page of HTML instead of drawing on the terminal
+        html: bool,
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files and directories to count commits to, the whole repository
+        /// without any
+        paths: Vec<PathBuf>,
+    },
+    /// Show ownership and bus factor across several repositories, each
+    /// person counted once in all of them
+    Workspace {
+        #[structopt(long = "manifest", value_name = "file", parse(from_os_str))]
+        /// analyze the repositories of the [workspace] table of <file>,
+        /// with its identities
+        manifest: Option<PathBuf>,
+        #[structopt(name = "repository", parse(from_os_str))]
+        /// repositories to analyze besides those of the manifest
+        repos: Vec<PathBuf>,
+    },
+    /// Sum up the knowledge health of the repository: bus factor, riskiest
+    /// files, directories and top owners
+    Report {
+        #[structopt(long = "email")]
+        /// mail the report to the recipients of [email] in the
+        /// configuration instead of printing it
+        email: bool,
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to sum up, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Show who last changed one line, given as <path>:<line>, and who owns
+    /// most of its file
+    Line {
+        #[structopt(name = "path:line")]
+        /// the line to look up, e.g. src/main.rs:120
+        location: String,
+    },
+    /// Show who owns a function now and who changed it over its history,
+    /// found as by git log -L :<name>:<path>
+    Symbol {
+        /// the function, or a regular expression matching its first line
+        name: String,
+        #[structopt(parse(from_os_str))]
+        /// the file it is in
+        path: PathBuf,
+    },
+    /// Show who owns a range of lines now and everyone who changed those
+    /// lines over their history, followed as by git log -L <range>:<path>
+    History {
+        #[structopt(short = "L", value_name = "range")]
+        /// the lines, as git log -L takes them: e.g. 10,40, 10,+30 or
+        /// /^fn main/,/^}/
+        range: String,
+        #[structopt(parse(from_os_str))]
+        /// the file they are in
+        path: PathBuf,
+    },
+    /// Show who wrote the lines of a file that a range of commits removed
+    /// and who removed them, found as by git blame --reverse
+    Gone {
+        /// the commits, as <rev>..<rev>; lines of the file at the first that
+        /// the second no longer has, HEAD if left out
+        range: String,
+        #[structopt(parse(from_os_str))]
+        /// the file, as of the first commit
+        path: PathBuf,
+    },
+    /// Rank who added or removed code matching a string anywhere in the
+    /// repository, found as by git log -S
+    Grep {
+        /// the string to look for, or with --regex a regular expression
+        pattern: String,
+        #[structopt(long = "regex")]
+        /// find commits that changed lines matching <pattern> as a regular
+        /// expression, as git log -G
+        regex: bool,
+        #[structopt(long = "max", env = "WHOKNOWS_MAX", value_name = "n", default_value = "10")]
+        /// list at most <n> authors
+        max: usize,
+    },
+    /// Suggest .mailmap lines for authors who committed under several
+    /// identities, found by their names and emails
+    Identities,
+    /// Show whose code a commit changed, blamed as of its parent
+    Commit {
+        #[structopt(name = "commit")]
+        /// the commit to look at
+        rev: String,
+    },
+    /// Print a CODEOWNERS file naming the owners of most of each file
+    Codeowners {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to list, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Answer ownership questions over HTTP: GET /ownership?path=<file>
+    /// and POST /reviewers with a unified diff as the body
+    Serve {
+        #[structopt(long = "listen", env = "WHOKNOWS_LISTEN", default_value = "127.0.0.1:7878")]
+        /// address to listen on
+        listen: String,
+    },
+    /// Suggest reviewers for the unified diff on stdin or for a pull or
+    /// merge request
+    Reviewers {
+        #[structopt(long = "github", env = "WHOKNOWS_GITHUB", value_name = "owner/repo", requires = "pr",
+                    conflicts_with = "gitlab")]
+        /// fetch the diff of a pull request of this GitHub repository
+        github: Option<String>,
+        #[structopt(long = "pr", value_name = "number", requires = "github")]
+        /// the pull request to suggest reviewers for
+        pr: Option<u64>,
+        #[structopt(long = "gitlab", env = "WHOKNOWS_GITLAB", value_name = "group/project", requires = "mr")]
+        /// fetch the changes of a merge request of this GitLab project
+        gitlab: Option<String>,
+        #[structopt(long = "mr", value_name = "iid", requires = "gitlab")]
+        /// the merge request to suggest reviewers for
+        mr: Option<u64>,
+        #[structopt(long = "gerrit", env = "WHOKNOWS_GERRIT", value_name = "url", requires = "change")]
+        /// the Gerrit server of --change, unless configured
+        gerrit: Option<String>,
+        #[structopt(long = "change", value_name = "id", conflicts_with_all = &["github", "gitlab"])]
+        /// print the reviewers of this Gerrit change as input for its
+        /// review endpoint
+        change: Option<String>,
+        #[structopt(long = "request")]
+        /// ask the suggested reviewers for a review of the pull request or
+        /// change, or make them the reviewers of the merge request
+        request: bool,
+        #[structopt(long = "note", requires = "gitlab")]
+        /// comment on the merge request with the suggested reviewers
+        note: bool,
+        #[structopt(long = "max", env = "WHOKNOWS_MAX", value_name = "n", default_value = "3")]
+        /// suggest at most <n> reviewers
+        max: usize,
+    },
+    /// Exit with 1 if any file crosses the thresholds, after listing them
+    Check {
+        #[structopt(long = "min-bus-factor", env = "WHOKNOWS_MIN_BUS_FACTOR", value_name = "n")]
+        /// fail files with a lower bus factor
+        min_bus_factor: Option<usize>,
+        #[structopt(long = "max-single-owner", env = "WHOKNOWS_MAX_SINGLE_OWNER", value_name = "percent")]
+        /// fail files of which one author owns more
+        max_single_owner: Option<f64>,
+        #[structopt(long = "warn-min-bus-factor", env = "WHOKNOWS_WARN_MIN_BUS_FACTOR", value_name = "n")]
+        /// warn about files with a lower bus factor, without failing them
+        warn_min_bus_factor: Option<usize>,
+        #[structopt(long = "warn-max-single-owner", env = "WHOKNOWS_WARN_MAX_SINGLE_OWNER", value_name = "percent")]
+        /// warn about files of which one author owns more, without failing
+        /// them
+        warn_max_single_owner: Option<f64>,
+        #[structopt(long = "require-owner", env = "WHOKNOWS_REQUIRE_OWNER", value_name = "percent")]
+        /// fail files of which nobody on the --roster owns at least this
+        /// much
+        require_owner: Option<f64>,
+        #[structopt(long = "junit", env = "WHOKNOWS_JUNIT", value_name = "file", parse(from_os_str))]
+        /// also write the results as JUnit XML, one test case per file
+        junit: Option<PathBuf>,
+        #[structopt(name = "path", parse(from_os_str), required = true)]
+        /// files to check, directories stand for all tracked files below
+        /// them
+        paths: Vec<PathBuf>,
+    },
+    /// Install a git hook that lists owners of the outgoing changes
+    InstallHook {
+        #[structopt(possible_values = &["pre-push"])]
+        /// the hook to install
+        hook: String,
+        #[structopt(long = "force")]
+        /// replace a hook that was not installed by git whoknows
+        force: bool,
+    },
+    /// Print the completion script for a shell
+    Completions {
+        #[structopt(possible_values = &structopt::clap::Shell::variants(), case_insensitive = true)]
+        /// the shell to complete in
+        shell: structopt::clap::Shell,
+    },
+    /// Print the manual page in roff
+    Man,
+    /// Check git, the repository, the configuration, identities, the cache
+    /// and the tokens of forges, and tell how to fix what is off
+    Doctor {
+        #[structopt(parse(from_os_str))]
+        /// the repository to check, that of the current directory by default
+        path: Option<PathBuf>,
+    },
+    /// Print the JSON Schema of the JSON that --query-server, serve and
+    /// --errors json write
+    Schema,
+    /// Inspect or clean up .git/whoknows-cache
+    Cache {
+        #[structopt(subcommand)]
+        action: CacheAction,
+    },
+    /// Blame every tracked file into .git/whoknows-cache ahead of time, so
+    /// that later runs with the same options are instant
+    Warm {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files and directories to blame, the whole repository without any
+        paths: Vec<PathBuf>,
+    },
+    /// Show or change the configuration
+    Config {
+        #[structopt(subcommand)]
+        action: ConfigAction,
+    },
+    /// Keep ownership as a git note on a commit, or compare two of them
+    Snapshot {
+        #[structopt(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Show what changed between two reports saved with --format json, or
+    /// snapshots: new and departed owners, bus factor regressions and
+    /// files that crossed thresholds
+    ReportDiff {
+        #[structopt(name = "old", parse(from_os_str))]
+        /// the earlier report
+        old: PathBuf,
+        #[structopt(name = "new", parse(from_os_str))]
+        /// the later report
+        new: PathBuf,
+    },
+    /// Combine the reports of runs over parts of the repository, saved with
+    /// --format json or a file per line, into one with identities merged
+    /// and totals over all of them
+    Merge {
+        #[structopt(name = "shard", required = true, parse(from_os_str))]
+        /// the reports, files of later ones winning over the same files of
+        /// earlier ones
+        shards: Vec<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+enum ConfigAction {
+    /// Show every setting in effect and where it comes from
+    Show,
+    /// Set a key, dotted as in thresholds.min_bus_factor, in .git-whoknows.toml
+    Set {
+        #[structopt(long = "user")]
+        /// write to the configuration of the user instead of the repository
+        user: bool,
+        /// the key to set
+        key: String,
+        /// the value, a string unless it reads as a number, boolean or array
+        value: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum SnapshotAction {
+    /// Save who owns the lines of HEAD, or of --rev, as a note on it in
+    /// refs/notes/whoknows
+    Save {
+        #[structopt(name = "path", parse(from_os_str))]
+        /// files to cover, the whole repository unless given
+        paths: Vec<PathBuf>,
+    },
+    /// Show how ownership moved between the snapshots of two commits
+    Diff {
+        /// the commit of the earlier snapshot
+        from: String,
+        /// the commit of the later snapshot, HEAD unless given
+        to: Option<String>,
+    },
+    /// Print every snapshot as a time series of lines, shares and bus
+    /// factor per author, for Grafana and other dashboards
+    Series {
+        #[structopt(long = "csv")]
+        /// write CSV instead of JSON
+        csv: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum CacheAction {
+    /// Show the size of the cache and how often it was hit
+    Status,
+    /// Remove the whole cache
+    Clear,
+    /// Remove entries for blobs not in HEAD or the tip of a branch or tag
+    Prune,
+}
+
+/// The commands an option goes with, for those that not all commands
+/// honor
+#[derive(Clone, Copy)]
+enum Scope {
+    /// Those printing the owners of every file: file and diff
+    Files,
+    /// Those printing owner tables of files or directories
+    Tables,
+    /// Those analyzing files
+    Analysis,
+    /// Those showing owners
+    Owners,
+    /// Those blaming
+    Blame,
+    /// Those reading the history
+    History,
+    /// Those merging identities
+    Mailmap,
+    /// Those telling who left
+    Roster,
+    /// Those blaming, and doctor
+    Backend,
+    /// Those blaming, doctor and cache
+    Cache,
+}
+
+/// The options only some commands honor: their argument, how they are
+/// given and what they go with
+const SCOPES: &[(&str, &str, Scope)] = &[
+    ("lang", "--lang", Scope::Analysis),
+    ("metric", "--metric", Scope::Analysis),
+    ("flag-m", "-M", Scope::Blame),
+    ("flag-c", "-C", Scope::Blame),
+    ("flag-f", "-F", Scope::Blame),
+    ("no-follow", "--no-follow", Scope::Blame),
+    ("rev", "--rev", Scope::History),
+    ("branch", "--branch", Scope::History),
+    ("vs", "--vs", Scope::Files),
+    ("ignore-revs-file", "--ignore-revs-file", Scope::Blame),
+    ("detect-noise", "--detect-noise", Scope::Blame),
+    ("decay", "--decay", Scope::Blame),
+    ("since", "--since", Scope::Blame),
+    ("until", "--until", Scope::Blame),
+    ("no-merges", "--no-merges", Scope::Blame),
+    ("exclude-author", "--exclude-author", Scope::Blame),
+    ("only-domain", "--only-domain", Scope::Blame),
+    ("min-commits", "--min-commits", Scope::Blame),
+    ("no-mailmap", "--no-mailmap", Scope::Mailmap),
+    ("backend", "--backend", Scope::Backend),
+    ("jobs", "--jobs", Scope::Blame),
+    ("no-cache", "--no-cache", Scope::Backend),
+    ("cache-dir", "--cache-dir", Scope::Cache),
+    ("incremental", "--incremental", Scope::Analysis),
+    ("watch", "--watch", Scope::Analysis),
+    ("resume", "--resume", Scope::Analysis),
+    ("roster", "--roster", Scope::Roster),
+    ("active-window", "--active-window", Scope::Analysis),
+    ("blame-chunk", "--blame-chunk", Scope::Blame),
+    ("issues", "--issues", Scope::Files),
+    ("topics", "--topics", Scope::Files),
+    ("include-reviewers", "--include-reviewers", Scope::Files),
+    ("include-signoffs", "--include-signoffs", Scope::Files),
+    ("signatures", "--signatures", Scope::Files),
+    ("split-tests", "--split-tests", Scope::Tables),
+    ("complexity", "--complexity", Scope::Tables),
+    ("explain", "--explain", Scope::Files),
+    ("deleted", "--deleted", Scope::Files),
+    ("survival", "--survival", Scope::Files),
+    ("roles", "--roles", Scope::Owners),
+    ("expertise", "--expertise", Scope::Owners),
+    ("progress", "--progress", Scope::Analysis),
+    ("export", "--export", Scope::Analysis),
+    ("notify", "--notify", Scope::Analysis),
+    ("notify-template", "--notify-template", Scope::Analysis),
+];
+
+/// Whether the option `name` was given on the command line, to the
+/// subcommand of `matches` or below it
+fn given (matches: &structopt::clap::ArgMatches, name: &str) -> bool
+{
+    matches.occurrences_of(name) > 0 || matches.subcommand().1.is_some_and(|sub| given(sub, name))
+}
+
+/// What --format accepts, parquet only in builds that can write it
+#[cfg(feature = "parquet")]
+const FORMATS: &[&str] = &["table", "json", "parquet", "prometheus", "metrics", "quickfix"];
+#[cfg(not(feature = "parquet"))]
+const FORMATS: &[&str] = &["table", "json", "prometheus", "metrics", "quickfix"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Table,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    Prometheus,
+    Metrics,
+    Quickfix,
+    Porcelain,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "table" => Ok(Format::Table),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(Format::Parquet),
+            #[cfg(not(feature = "parquet"))]
+            "parquet" => Err("this build does not include parquet support, rebuild with --features parquet".to_string()),
+            "prometheus" => Ok(Format::Prometheus),
+            "metrics" => Ok(Format::Metrics),
+            "quickfix" => Ok(Format::Quickfix),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
 }
 
-struct TrackedFile {
-    #[allow(dead_code)]
-    path: String,
-    owners: HashMap<String, Owner>
+/// For every owner of `file`, a bar per quarter from the first to the last
+/// any of them has lines from, as high as the commits made in it, so that
+/// a steady maintainer looks different from someone busy years ago
+fn quarter_table (file: &FileReport) -> Table
+{
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let owners = file.ranked_owners();
+    let quarters: Vec<_> = owners.iter().map(|owner| owner.commits_by_quarter()).collect();
+    let first = quarters.iter().filter_map(|q| q.keys().next()).min().copied().unwrap_or(0);
+    let last = quarters.iter().filter_map(|q| q.keys().next_back()).max().copied().unwrap_or(0);
+    let most = quarters.iter().flat_map(|q| q.values()).max().copied().unwrap_or(1);
+
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("First", Align::Left, Truncate::Never)
+        .column("Last", Align::Left, Truncate::Never)
+        .column(&format!("{} to {}", date::format_quarter(first), date::format_quarter(last)),
+                Align::Left, Truncate::Never);
+    for (owner, quarters) in owners.iter().zip(&quarters)
+    {
+        let bars: String = (first..=last)
+            .map(|quarter| match quarters.get(&quarter) {
+                Some(&commits) => BARS[(commits * BARS.len()).div_ceil(most).clamp(1, BARS.len()) - 1],
+                None => ' ',
+            })
+            .collect();
+        let span = |quarter: Option<&i64>| quarter.map(|&q| date::format_quarter(q)).unwrap_or_default();
+        table.row(vec![
+            owner.name.clone(),
+            owner.email.clone(),
+            span(quarters.keys().next()),
+            span(quarters.keys().next_back()),
+            bars,
+        ]);
+    }
+    table
 }
 
-impl TrackedFile {
-    fn new (path: &String) -> TrackedFile
+/// For every owner of `file`, how old their newest, median and oldest
+/// lines are, which tells knowledge from last month from the same number
+/// of lines written years ago
+fn age_table (file: &FileReport, now: i64) -> Table
+{
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Newest", Align::Right, Truncate::Never)
+        .column("Median", Align::Right, Truncate::Never)
+        .column("Oldest", Align::Right, Truncate::Never);
+    for owner in file.ranked_owners()
     {
-        TrackedFile {
-            path: path.clone(),
-            owners: HashMap::new(),
+        if let Some((newest, median, oldest)) = owner.line_ages(now) {
+            table.row(vec![
+                owner.name.clone(),
+                owner.email.clone(),
+                date::format_age(newest),
+                date::format_age(median),
+                date::format_age(oldest),
+            ]);
         }
     }
+    table
+}
 
-    fn add_hunk (
-        &mut self,
-        commit: &BlameHunk
-    )
+/// For every owner of `file` the commit their oldest surviving lines come
+/// from, the change their ownership started with
+fn oldest_table (file: &FileReport, commits: &mut Commits, args: &Args) -> Result<Table, Error>
+{
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Commit", Align::Left, Truncate::Never)
+        .column("Date", Align::Left, Truncate::Never)
+        .column("Summary", Align::Left, Truncate::End);
+    for owner in file.ranked_owners()
+    {
+        if let Some((oid, time)) = owner.oldest_commit() {
+            let (hash, summary) = if oid.is_zero() {
+                (String::new(), "Not committed yet".to_string())
+            } else {
+                (args.hash(oid, SHORT_HASH), commits.get(oid)?.summary().to_string())
+            };
+            table.row(vec![owner.name.clone(), owner.email.clone(), hash, human::date(time), summary]);
+        }
+    }
+    Ok(table)
+}
+
+/// How the lines and score of every owner of `file` add up, and who they
+/// were merged from
+fn print_explanation (out: &mut dyn Write, repo: &Repository, file: &FileReport, args: &Args) -> Result<(), Error>
+{
+    let opts = args.options();
+    let weights = match args.complexity {
+        true => Some(Weights::of(repo, Path::new(&file.path), &opts)?),
+        false => None,
+    };
+    writeln!(out, "Explanation:")?;
+    for explanation in explain::explain(repo, file, &opts, weights.as_ref(), date::now())?
+    {
+        let owner = explanation.owner;
+        let score: f64 = explanation.commits.iter().map(explain::Commit::score).sum();
+        writeln!(out, "{} <{}>: {} lines from {} commits, score {:.1}",
+                 owner.name, args.shown_email(&owner.email), owner.lines(), explanation.commits.len(), score)?;
+        for identity in &explanation.identities
+        {
+            writeln!(out, "  merged from {} <{}> by {}, {} lines",
+                     identity.name, args.shown_email(&identity.email), identity.rule, identity.lines)?;
+        }
+        let mut table = Table::new();
+        table.column("Commit", Align::Left, Truncate::Never)
+            .column("Date", Align::Left, Truncate::Never)
+            .column("Lines", Align::Right, Truncate::Never);
+        if weights.is_some() {
+            table.column("Weight", Align::Right, Truncate::Never);
+        }
+        if args.decay.is_some() {
+            table.column("Decay", Align::Right, Truncate::Never);
+        }
+        table.column("Score", Align::Right, Truncate::Never);
+        for commit in &explanation.commits
+        {
+            let hash = match commit.commit.is_zero() {
+                true => "uncommitted".to_string(),
+                false => args.hash(commit.commit, SHORT_HASH),
+            };
+            let mut row = vec![hash, human::date(commit.time), human::count(commit.lines)];
+            if weights.is_some() {
+                row.push(format!("x{:.2}", commit.weight));
+            }
+            if args.decay.is_some() {
+                row.push(format!("x{:.2}", commit.decay));
+            }
+            row.push(format!("{:.1}", commit.score()));
+            table.row(row);
+        }
+        write!(out, "{}", table.render(table::terminal_width()))?;
+    }
+    Ok(())
+}
+
+/// Print who wrote lines of `file` that are gone, unless nobody did
+fn print_deleted (out: &mut dyn Write, repo: &Repository, file: &FileReport, args: &Args) -> Result<(), Error>
+{
+    let rev = args.rev.as_deref().unwrap_or("HEAD");
+    let authors = whoknows::deleted::deleted(repo, file, rev, !args.no_mailmap)?;
+    if authors.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "Wrote code since deleted:")?;
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Added", Align::Right, Truncate::Never)
+        .column("Deleted", Align::Right, Truncate::Never)
+        .column("Last change", Align::Left, Truncate::Never);
+    for author in authors
+    {
+        table.row(vec![
+            author.name,
+            args.shown_email(&author.email),
+            human::count(author.added),
+            human::count(author.deleted),
+            human::date(author.last),
+        ]);
+    }
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+/// The renames of `file`, oldest first, in the order a reader follows the
+/// name to the one it has now
+fn rename_table (renames: &[whoknows::renames::Rename], args: &Args) -> Table
+{
+    let mut table = Table::new();
+    table.column("Date", Align::Left, Truncate::Never)
+        .column("Commit", Align::Left, Truncate::Never)
+        .column("From", Align::Left, Truncate::End)
+        .column("To", Align::Left, Truncate::End);
+    for rename in renames
     {
-        let owner = Owner::new(&commit.final_signature());
-        self.owners.entry(owner.email.clone()).or_insert(owner).add_hunk(commit);
+        table.row(vec![
+            human::date(rename.time),
+            args.hash(rename.commit, SHORT_HASH),
+            args.shown_path(&rename.from),
+            args.shown_path(&rename.to),
+        ]);
     }
+    table
 }
 
-struct Owner {
-    #[allow(dead_code)]
-    name: String,
-    email: String,
-    commits: HashMap<Oid,usize>,
+/// The renames of `file` as JSON objects, oldest first
+fn renames_json (repo: &Repository, file: &FileReport, args: &Args) -> Result<Value, Error>
+{
+    let renames = whoknows::renames::chain(repo, Path::new(&file.path), args.rev.as_deref().unwrap_or("HEAD"))?;
+    Ok(Value::Array(renames.iter()
+        .map(|rename| Value::object(vec![
+            ("commit", rename.commit.to_string().into()),
+            ("time", (rename.time as f64).into()),
+            ("from", args.shown_path(&rename.from).into()),
+            ("to", args.shown_path(&rename.to).into()),
+        ]))
+        .collect()))
 }
 
-impl Owner {
-    fn new (sig: &Signature) -> Owner
+/// Print how much of what everyone added to `file` survives, unless
+/// nobody added anything
+fn print_survival (out: &mut dyn Write, repo: &Repository, file: &FileReport, args: &Args) -> Result<(), Error>
+{
+    let rev = args.rev.as_deref().unwrap_or("HEAD");
+    let authors = whoknows::deleted::survival(repo, file, rev, !args.no_mailmap)?;
+    if authors.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "Survival of added lines:")?;
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Added", Align::Right, Truncate::Never)
+        .column("Surviving", Align::Right, Truncate::Never)
+        .column("Survival", Align::Right, Truncate::Never);
+    for author in authors
     {
-        let email = String::from_utf8_lossy(sig.email_bytes()).to_string();
-        let name = String::from_utf8_lossy(sig.name_bytes()).to_string();
-        Owner {
-            name,
-            email,
-            commits: HashMap::new(),
+        // Lines moved in from elsewhere are not more than all surviving
+        let surviving = author.surviving.min(author.added);
+        table.row(vec![
+            author.name,
+            args.shown_email(&author.email),
+            human::count(author.added),
+            human::count(surviving),
+            format!("{:.1}%", percent(surviving, author.added)),
+        ]);
+    }
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+/// What the paths of files in reports are relative to
+#[derive(Clone, Copy, PartialEq)]
+enum RelativeTo {
+    Repo,
+    Cwd,
+    /// Absolute paths
+    None,
+}
+
+impl std::str::FromStr for RelativeTo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RelativeTo, String> {
+        match s {
+            "repo" => Ok(RelativeTo::Repo),
+            "cwd" => Ok(RelativeTo::Cwd),
+            "none" => Ok(RelativeTo::None),
+            _ => Err(format!("unknown base of paths '{}'", s)),
         }
     }
+}
 
-    fn add_hunk (
-        &mut self,
-        commit: &BlameHunk
-    )
+/// `to` relative to the directory `from`, both absolute, going up with
+/// `..` where they part
+fn relative_path (from: &Path, to: &Path) -> PathBuf
+{
+    let common = from.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+    let mut relative: PathBuf = from.components().skip(common).map(|_| Component::ParentDir).collect();
+    relative.extend(to.components().skip(common));
+    relative
+}
+
+/// How a failure is reported
+#[derive(Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ErrorFormat, String> {
+        match s {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("unknown error format '{}'", s)),
+        }
+    }
+}
+
+/// The owners of `file`, only the first `top` of them if given
+fn owner_table (
+    file: &FileReport,
+    score: bool,
+    config: &Config,
+    roster: Option<&Roster>,
+    roles: bool,
+    expertise: bool,
+    top: Option<usize>
+) -> Table
+{
+    let total = file.lines();
+    let teams = !config.teams.is_empty();
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email);
+    if teams {
+        table.column("Team", Align::Left, Truncate::End);
+    }
+    if roster.is_some() {
+        table.column("Status", Align::Left, Truncate::Never);
+    }
+    if roles {
+        table.column("Role", Align::Left, Truncate::Never);
+    }
+    if expertise {
+        table.column("Expertise", Align::Left, Truncate::Never);
+    }
+    table.column("Lines", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Commits", Align::Right, Truncate::Never)
+        .column("Per commit", Align::Right, Truncate::Never);
+    if score {
+        table.column("Score", Align::Right, Truncate::Never);
+    }
+
+    for owner in file.ranked_owners().into_iter().take(top.unwrap_or(usize::MAX))
+    {
+        let mut row = vec![owner.name.clone(), owner.email.clone()];
+        if teams {
+            row.push(config.team(&owner.email).unwrap_or("").to_string());
+        }
+        if let Some(roster) = roster {
+            row.push(if roster.is_active(owner) { "active" } else { "departed" }.to_string());
+        }
+        if roles {
+            row.push(config.roles.role(owner, file).to_string());
+        }
+        if expertise {
+            row.push(config.expertise.of(owner, file).to_string());
+        }
+        row.extend(vec![
+            human::count(owner.lines()),
+            format!("{:.1}%", percent(owner.lines(), total)),
+            human::count(owner.commits.len()),
+            format!("{:.1}", owner.lines_per_commit()),
+        ]);
+        if score {
+            row.push(format!("{:.1}", owner.score));
+        }
+        table.row(row);
+    }
+    table
+}
+
+impl Args {
+    /// Set the flags whose `WHOKNOWS_*` variable is true; clap only reads
+    /// the variables of options that take a value
+    fn apply_env (&mut self) -> Result<(), Error>
+    {
+        let flags = [
+            ("WHOKNOWS_MOVES", &mut self.flag_M),
+            ("WHOKNOWS_FIRST_PARENT", &mut self.flag_F),
+            ("WHOKNOWS_NO_FOLLOW", &mut self.no_follow),
+            ("WHOKNOWS_DETECT_NOISE", &mut self.detect_noise),
+            ("WHOKNOWS_NO_MERGES", &mut self.no_merges),
+            ("WHOKNOWS_NO_MAILMAP", &mut self.no_mailmap),
+            ("WHOKNOWS_NO_CACHE", &mut self.no_cache),
+            ("WHOKNOWS_INCREMENTAL", &mut self.incremental),
+            ("WHOKNOWS_WATCH", &mut self.watch),
+            ("WHOKNOWS_RESUME", &mut self.resume),
+            ("WHOKNOWS_ISSUES", &mut self.issues),
+            ("WHOKNOWS_TOPICS", &mut self.topics),
+            ("WHOKNOWS_INCLUDE_REVIEWERS", &mut self.include_reviewers),
+            ("WHOKNOWS_INCLUDE_SIGNOFFS", &mut self.include_signoffs),
+            ("WHOKNOWS_SIGNATURES", &mut self.signatures),
+            ("WHOKNOWS_SPLIT_TESTS", &mut self.split_tests),
+            ("WHOKNOWS_COMPLEXITY", &mut self.complexity),
+            ("WHOKNOWS_ROLES", &mut self.roles),
+            ("WHOKNOWS_EXPERTISE", &mut self.expertise),
+            ("WHOKNOWS_ANONYMIZE", &mut self.anonymize),
+            ("WHOKNOWS_REDACT_EMAILS", &mut self.redact_emails),
+            ("WHOKNOWS_NO_TRUNCATE", &mut self.no_truncate),
+            ("WHOKNOWS_HUMAN", &mut self.human),
+            ("WHOKNOWS_COPY", &mut self.copy),
+            ("WHOKNOWS_DRY_RUN", &mut self.dry_run),
+            ("WHOKNOWS_PICK", &mut self.pick),
+            ("WHOKNOWS_QUIET", &mut self.quiet),
+        ];
+        for (name, flag) in flags
+        {
+            *flag |= env_flag(name)?;
+        }
+        // A level, or true for one -C
+        if let Ok(value) = std::env::var("WHOKNOWS_COPIES") {
+            let level = match value.trim().parse::<u8>() {
+                Ok(level) if level <= 3 => level,
+                _ => env_flag("WHOKNOWS_COPIES")? as u8,
+            };
+            self.flag_C = self.flag_C.max(level);
+        }
+        if self.incremental && self.no_cache {
+            return Err(Error::Usage("--incremental cannot be used with --no-cache".to_string()));
+        }
+        if self.anonymize {
+            self.check_anonymize()?;
+        }
+        Ok(())
+    }
+
+    /// That --anonymize has a salt to hash with, without which pseudonyms
+    /// of known emails are easily guessed, and nothing to show that it
+    /// leaves as it is
+    fn check_anonymize (&self) -> Result<(), Error>
+    {
+        if self.salt.as_deref().is_none_or(str::is_empty) {
+            return Err(Error::Usage("--anonymize needs a secret salt, given with --salt or WHOKNOWS_SALT".to_string()));
+        }
+        if self.explain {
+            return Err(Error::Usage("--explain names the identities that --anonymize hides".to_string()));
+        }
+        if self.deleted || self.survival {
+            return Err(Error::Usage("--deleted and --survival name the authors that --anonymize hides".to_string()));
+        }
+        let command = match &self.command {
+            Some(Command::Serve { .. }) => "serve",
+            Some(Command::Line { .. }) => "line",
+            Some(Command::Symbol { .. }) => "symbol",
+            Some(Command::History { .. }) => "history",
+            Some(Command::Gone { .. }) => "gone",
+            Some(Command::Grep { .. }) => "grep",
+            Some(Command::Identities) => "identities",
+            Some(Command::Commit { .. }) => "commit",
+            Some(Command::Heatmap { .. }) => "heatmap",
+            Some(Command::Reviewers { .. }) => "reviewers",
+            Some(Command::Check { .. }) => "check",
+            Some(Command::Snapshot { action: SnapshotAction::Diff { .. } })
+            | Some(Command::Snapshot { action: SnapshotAction::Series { .. } }) => "snapshot",
+            Some(Command::ReportDiff { .. }) => "report-diff",
+            Some(Command::Merge { .. }) => "merge",
+            Some(Command::Workspace { .. }) => "workspace",
+            None if self.query_server => "--query-server",
+            _ => return Ok(()),
+        };
+        Err(Error::Usage(format!("--anonymize does not go with {}, which would name authors", command)))
+    }
+
+    /// The salt of --anonymize, which `check_anonymize` makes sure there is
+    fn salt (&self) -> &str
+    {
+        self.salt.as_deref().unwrap_or_default()
+    }
+
+    /// That every option given goes with the command
+    fn check_scope (&self) -> Result<(), Error>
+    {
+        for &(name, option, scope) in SCOPES
+        {
+            if self.given.contains(&name) && !self.honors(scope) {
+                return Err(Error::Usage(format!("{} does not go with {}", option, self.command_name())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the command is one of those of `scope`
+    fn honors (&self, scope: Scope) -> bool
+    {
+        let command = self.command.as_ref();
+        match scope {
+            Scope::Files => !self.query_server && matches!(
+                command,
+                None | Some(Command::File { .. }) | Some(Command::Diff { .. })
+            ),
+            Scope::Tables => self.honors(Scope::Files) || matches!(
+                command,
+                Some(Command::Dir { .. }) | Some(Command::Repo)
+            ),
+            Scope::Analysis => self.analyzes(),
+            Scope::Owners => self.analyzes() || matches!(
+                command,
+                Some(Command::Line { .. })
+                | Some(Command::Commit { .. })
+                | Some(Command::Symbol { .. })
+                | Some(Command::History { .. })
+                | Some(Command::Gone { .. })
+                | Some(Command::Merge { .. })
+            ),
+            Scope::Blame => self.analyzes() || self.query_server || matches!(
+                command,
+                Some(Command::Serve { .. })
+                | Some(Command::Reviewers { .. })
+                | Some(Command::Line { .. })
+                | Some(Command::Commit { .. })
+                | Some(Command::Symbol { .. })
+                | Some(Command::History { .. })
+                | Some(Command::Gone { .. })
+                | Some(Command::Workspace { .. })
+            ),
+            Scope::History => self.honors(Scope::Blame) || matches!(
+                command,
+                Some(Command::Heatmap { .. }) | Some(Command::Grep { .. }) | Some(Command::Identities)
+            ),
+            Scope::Mailmap => self.honors(Scope::History) || matches!(command, Some(Command::Merge { .. })),
+            Scope::Roster => self.honors(Scope::Owners) || matches!(command, Some(Command::Doctor { .. })),
+            Scope::Backend => self.honors(Scope::Blame) || matches!(command, Some(Command::Doctor { .. })),
+            Scope::Cache => self.honors(Scope::Backend) || matches!(command, Some(Command::Cache { .. })),
+        }
+    }
+
+    /// Whether the command analyzes files, all of them as the file command
+    /// does before it shows them its own way
+    fn analyzes (&self) -> bool
+    {
+        match &self.command {
+            None => !self.query_server,
+            Some(Command::File { .. })
+            | Some(Command::Dir { .. })
+            | Some(Command::Repo)
+            | Some(Command::Diff { .. })
+            | Some(Command::BusFactor { .. })
+            | Some(Command::Risk { .. })
+            | Some(Command::Pairs { .. })
+            | Some(Command::Timezones { .. })
+            | Some(Command::Teams { .. })
+            | Some(Command::Warm { .. })
+            | Some(Command::Report { .. })
+            | Some(Command::Codeowners { .. })
+            | Some(Command::Check { .. })
+            | Some(Command::Snapshot { action: SnapshotAction::Save { .. } }) => true,
+            Some(_) => false,
+        }
+    }
+
+    /// The command, as it is given
+    fn command_name (&self) -> &'static str
+    {
+        match &self.command {
+            None if self.query_server => "--query-server",
+            None | Some(Command::File { .. }) => "file",
+            Some(Command::Dir { .. }) => "dir",
+            Some(Command::Repo) => "repo",
+            Some(Command::Diff { .. }) => "diff",
+            Some(Command::BusFactor { .. }) => "bus-factor",
+            Some(Command::Risk { .. }) => "risk",
+            Some(Command::Pairs { .. }) => "pairs",
+            Some(Command::Timezones { .. }) => "timezones",
+            Some(Command::Teams { .. }) => "teams",
+            Some(Command::Heatmap { .. }) => "heatmap",
+            Some(Command::Workspace { .. }) => "workspace",
+            Some(Command::Report { .. }) => "report",
+            Some(Command::Line { .. }) => "line",
+            Some(Command::Symbol { .. }) => "symbol",
+            Some(Command::History { .. }) => "history",
+            Some(Command::Gone { .. }) => "gone",
+            Some(Command::Grep { .. }) => "grep",
+            Some(Command::Identities) => "identities",
+            Some(Command::Commit { .. }) => "commit",
+            Some(Command::Codeowners { .. }) => "codeowners",
+            Some(Command::Serve { .. }) => "serve",
+            Some(Command::Reviewers { .. }) => "reviewers",
+            Some(Command::Check { .. }) => "check",
+            Some(Command::InstallHook { .. }) => "install-hook",
+            Some(Command::Completions { .. }) => "completions",
+            Some(Command::Man) => "man",
+            Some(Command::Doctor { .. }) => "doctor",
+            Some(Command::Schema) => "schema",
+            Some(Command::Cache { .. }) => "cache",
+            Some(Command::Warm { .. }) => "warm",
+            Some(Command::Config { .. }) => "config",
+            Some(Command::Snapshot { action: SnapshotAction::Save { .. } }) => "snapshot save",
+            Some(Command::Snapshot { action: SnapshotAction::Diff { .. } }) => "snapshot diff",
+            Some(Command::Snapshot { action: SnapshotAction::Series { .. } }) => "snapshot series",
+            Some(Command::ReportDiff { .. }) => "report-diff",
+            Some(Command::Merge { .. }) => "merge",
+        }
+    }
+
+    /// Fill in what the command line left open from the configuration
+    fn apply_config (&mut self, config: Config) -> Result<(), Error>
     {
-        *self.commits.entry(commit.final_commit_id()).or_insert(0) += commit.lines_in_hunk();
+        if self.format.is_none() {
+            if let Some(format) = &config.format {
+                self.format = Some(format.parse().map_err(Error::Config)?);
+            }
+        }
+        self.backend = self.backend.or(config.backend);
+        self.decay = self.decay.or(config.decay);
+        self.jobs = self.jobs.or(config.jobs);
+        self.cache_dir = self.cache_dir.take().or_else(|| config.cache_dir.clone());
+        self.no_mailmap |= config.mailmap == Some(false);
+        self.exclude_authors.extend(config.exclude_authors.iter().cloned());
+        self.config = config;
+        Ok(())
+    }
+
+    /// The paths to analyze, given without any command or to the command
+    fn paths (&self) -> &[PathBuf]
+    {
+        match &self.command {
+            Some(Command::File { paths })
+            | Some(Command::Dir { paths })
+            | Some(Command::Diff { paths, .. })
+            | Some(Command::BusFactor { paths })
+            | Some(Command::Risk { paths })
+            | Some(Command::Pairs { paths })
+            | Some(Command::Timezones { paths })
+            | Some(Command::Teams { paths, .. })
+            | Some(Command::Warm { paths })
+            | Some(Command::Report { paths, .. })
+            | Some(Command::Codeowners { paths })
+            | Some(Command::Snapshot { action: SnapshotAction::Save { paths } })
+            | Some(Command::Check { paths, .. }) => paths,
+            _ => &self.arg_paths,
+        }
+    }
+
+    fn paths_mut (&mut self) -> &mut Vec<PathBuf>
+    {
+        match &mut self.command {
+            Some(Command::File { paths })
+            | Some(Command::Dir { paths })
+            | Some(Command::Diff { paths, .. })
+            | Some(Command::BusFactor { paths })
+            | Some(Command::Risk { paths })
+            | Some(Command::Pairs { paths })
+            | Some(Command::Timezones { paths })
+            | Some(Command::Teams { paths, .. })
+            | Some(Command::Warm { paths })
+            | Some(Command::Report { paths, .. })
+            | Some(Command::Codeowners { paths })
+            | Some(Command::Snapshot { action: SnapshotAction::Save { paths } })
+            | Some(Command::Check { paths, .. }) => paths,
+            _ => &mut self.arg_paths,
+        }
+    }
+
+    /// Stand in the root of the work tree for the paths of commands that
+    /// cover the whole repository without any
+    fn whole_repository (&mut self, repo: &Repository)
+    {
+        let whole = matches!(self.command,
+                             Some(Command::Repo) | Some(Command::BusFactor { .. }) | Some(Command::Risk { .. })
+                             | Some(Command::Pairs { .. }) | Some(Command::Timezones { .. })
+                             | Some(Command::Teams { .. }) | Some(Command::Warm { .. })
+                             | Some(Command::Report { .. })
+                             | Some(Command::Codeowners { .. })
+                             | Some(Command::Snapshot { action: SnapshotAction::Save { .. } }));
+        if let (true, Some(workdir)) = (whole && self.paths().is_empty(), repo.workdir()) {
+            *self.paths_mut() = vec![workdir.to_path_buf()];
+        }
+    }
+
+    /// The URL given as the first path, if it is one
+    fn url (&self) -> Option<String>
+    {
+        self.paths().first()
+            .map(|p| p.to_string_lossy().into_owned())
+            .filter(|p| remote::is_url(p))
+    }
+
+    /// Run git for blame, as only it can fetch what a clone left out
+    fn remote_backend (&mut self) -> Result<(), Error>
+    {
+        if self.backend == Some(Backend::Libgit2) {
+            return Err(Error::Usage("a repository given by URL needs --backend git".to_string()));
+        }
+        self.backend = Some(Backend::Git);
+        Ok(())
+    }
+
+    /// Analyze `clone` instead of the URL given as the first path
+    fn use_clone (&mut self, clone: &Clone) -> Result<(), Error>
+    {
+        if self.url().is_none() {
+            return Ok(());
+        }
+        self.remote_backend()?;
+        let paths = clone.paths(&self.paths()[1..]);
+        *self.paths_mut() = paths;
+        Ok(())
+    }
+
+    fn format (&self) -> Format
+    {
+        if self.porcelain.is_some() {
+            return Format::Porcelain;
+        }
+        self.format.unwrap_or(Format::Table)
+    }
+
+    fn backend (&self) -> Backend
+    {
+        match self.contents_file {
+            // Only git blame takes other contents
+            Some(_) => self.backend.unwrap_or(Backend::Git),
+            None => self.backend.unwrap_or(Backend::Libgit2),
+        }
     }
 
-    fn lines (&self) -> usize
+    /// `oid` cut to the length given with --abbrev, otherwise to `default`
+    /// hex digits
+    fn hash (&self, oid: Oid, default: usize) -> String
     {
-       self.commits.values().sum::<usize>()
+        let hex = oid.to_string();
+        let len = self.abbrev.unwrap_or(default).min(hex.len());
+        hex[..len].to_string()
     }
 
+    /// `email` as reports may show it: left out if the configuration hides
+    /// emails, masked with --redact-emails
+    fn shown_email (&self, email: &str) -> String
+    {
+        (self.email_shown())(email)
+    }
+
+    /// What `shown_email` makes of an email, for what outlives `self`
+    fn email_shown (&self) -> fn(&str) -> String
+    {
+        if self.config.hide_emails.unwrap_or(false) {
+            |_| String::new()
+        } else if self.redact_emails {
+            anonymize::redact_email
+        } else {
+            str::to_string
+        }
+    }
+
+    /// `reviewer` with their email as reports may show it
+    fn shown_reviewer (&self, reviewer: Reviewer) -> Reviewer
+    {
+        Reviewer { email: self.shown_email(&reviewer.email), ..reviewer }
+    }
+
+    /// Whether emails are shown other than as they are
+    fn hides_emails (&self) -> bool
+    {
+        self.config.hide_emails.unwrap_or(false) || self.redact_emails
+    }
+
+    /// `file` with the emails of its owners as reports may show them, see
+    /// `shown_email`
+    fn show_emails (&self, file: &mut FileReport)
+    {
+        if self.hides_emails() {
+            for owner in file.owners.values_mut()
+            {
+                owner.email = self.shown_email(&owner.email);
+            }
+        }
+    }
+
+    /// `path`, relative to the root of the repository, as reports show it
+    fn shown_path (&self, path: &str) -> String
+    {
+        match &self.path_base {
+            Some(base) => base.join(path).display().to_string(),
+            None => path.to_string(),
+        }
+    }
+
+    /// `files` with their paths as reports show them, for writers that
+    /// take the reports themselves
+    fn shown_files<'a> (&self, files: &'a [FileReport]) -> Cow<'a, [FileReport]>
+    {
+        if self.path_base.is_none() {
+            return Cow::Borrowed(files);
+        }
+        Cow::Owned(files.iter()
+            .map(|file| FileReport { path: self.shown_path(&file.path), ..file.clone() })
+            .collect())
+    }
+
+    /// Where --relative-to has paths shown from in the work tree `workdir`
+    fn set_path_base (&mut self, workdir: Option<&Path>) -> Result<(), Error>
+    {
+        let workdir = match workdir {
+            Some(workdir) => platform::canonicalize(workdir)?,
+            None => return Ok(()),
+        };
+        self.path_base = match self.relative_to.unwrap_or(RelativeTo::Repo) {
+            RelativeTo::Repo => None,
+            RelativeTo::Cwd => Some(relative_path(&platform::canonicalize(&std::env::current_dir()?)?, &workdir)),
+            RelativeTo::None => Some(workdir),
+        };
+        Ok(())
+    }
+
+    /// Whether owners are ranked by something other than their lines
+    fn scored (&self) -> bool
+    {
+        self.decay.is_some() || self.complexity
+    }
+
+    fn options (&self) -> whoknows::Options
+    {
+        whoknows::Options {
+            blame: blame::Options {
+                moves: self.flag_M,
+                copies: self.flag_C.min(3),
+                first_parent: self.flag_F,
+                rev: self.rev.clone(),
+                ignore_revs: self.ignore_revs_file.clone(),
+                no_follow: self.no_follow,
+                chunk: self.blame_chunk,
+                contents: self.contents.clone(),
+            },
+            backend: self.backend(),
+            cache: !self.no_cache,
+            cache_dir: self.cache_dir.clone(),
+            mailmap: !self.no_mailmap,
+            identities: self.config.identities.clone(),
+            no_merges: self.no_merges,
+            exclude_authors: self.exclude_authors.clone(),
+            only_domains: self.only_domains.clone(),
+            min_commits: self.min_commits,
+            decay: self.decay,
+            since: self.since,
+            until: self.until,
+            jobs: self.jobs.map(|jobs| jobs.max(1)),
+        }
+    }
 }
 
-impl fmt::Display for Owner {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} <{}>: Lines: {} Count: {}",
-               self.name,
-               self.email,
-               self.lines(),
-               self.commits.len())
+/// What tells runs apart for resuming: everything that changes the result
+fn run_id (repo: &Repository, args: &Args, paths: &[PathBuf]) -> String
+{
+    let head = repo.head().ok().and_then(|h| h.target()).map(|o| o.to_string()).unwrap_or_default();
+    let ignore_revs = args.ignore_revs_file.as_ref()
+        .and_then(|f| std::fs::read(f).ok())
+        .unwrap_or_default();
+    // Relative dates move on with every second, so compare windows by day
+    let day = |time: Option<i64>| time.map(|t| t.div_euclid(86_400));
+    let mut text = format!("{} {:?} {:?} {:?} {:?} {} {} {} {} {} {:?} {} {:?} {:?} {:?}\n",
+                           head, args.rev, args.decay, day(args.since), day(args.until),
+                           args.flag_M, args.flag_C, args.flag_F,
+                           args.backend() == Backend::Git, args.no_mailmap, args.exclude_authors,
+                           args.no_merges, args.only_domains, args.min_commits,
+                           String::from_utf8_lossy(&ignore_revs));
+    for path in paths
+    {
+        text.push_str(&format!("{}\n", path.display()));
     }
+    git2::Oid::hash_object(git2::ObjectType::Blob, text.as_bytes())
+        .map(|oid| oid.to_string())
+        .unwrap_or(text)
+}
 
+/// The full reference of the local branch `name`, or else of the remote
+/// tracking branch, so that a tag of the same name cannot be picked instead
+fn branch_rev (repo: &Repository, name: &str) -> Result<String, Error>
+{
+    let branch = repo.find_branch(name, git2::BranchType::Local)
+        .or_else(|_| repo.find_branch(name, git2::BranchType::Remote))
+        .map_err(|_| Error::Usage(format!("no branch '{}'", name)))?;
+    let reference = branch.get().name()
+        .ok_or_else(|| Error::Usage(format!("branch '{}' is not named in UTF-8", name)))?;
+    Ok(reference.to_string())
+}
+
+/// Find the noise commits and have blame ignore them along with those of
+/// `--ignore-revs-file`; with --dry-run, only where they would be listed
+fn ignore_noise (repo: &Repository, args: &mut Args) -> Result<(), Error>
+{
+    if args.backend() != Backend::Git {
+        return Err(Error::Usage("--detect-noise needs --backend git".to_string()));
+    }
+    let path = Cache::new(repo).dir().join("noise-revs");
+    if args.dry_run {
+        args.ignore_revs_file = Some(path);
+        return Ok(());
+    }
+    let found = noise::detect(repo, args.rev.as_deref())?;
+    noise::write_ignore_revs(&path, &found, args.ignore_revs_file.as_ref())?;
+    if !args.quiet {
+        eprintln!("noise: ignoring {} commits, listed in {}", found.len(), path.display());
+    }
+    args.ignore_revs_file = Some(path);
+    Ok(())
 }
 
-fn run(args: &Args) -> Result<(), git2::Error> {
-    //let path = Path::new(&args.arg_path[..]);
-    let repo = Repository::discover(&args.arg_path)?;
+/// The command line again with the options of `profile` in front, except
+/// for those it already gives
+fn with_profile (config: &Config, profile: &str) -> Result<Args, Error>
+{
+    let options = config.profiles.get(profile)
+        .ok_or_else(|| Error::Usage(format!("no profile '{}' in the configuration", profile)))?;
+    let profile_args = whoknows::config::profile_args(profile, options).map_err(Error::Config)?;
 
-    let path = args.arg_path.strip_prefix(
-        repo.path()
-            .parent()
-            .unwrap()
-        ).unwrap();
+    let mut given = std::env::args_os();
+    let mut args = vec![given.next().unwrap_or_default()];
+    let given: Vec<_> = given.collect();
+    for option in profile_args
+    {
+        let overridden = given.iter().any(|arg| {
+            let arg = arg.to_string_lossy();
+            arg == option[0] || arg.starts_with(&format!("{}=", option[0]))
+        });
+        if !overridden {
+            args.extend(option.into_iter().map(Into::into));
+        }
+    }
+    args.extend(given);
+    Args::from_iter_safe(args)
+        .map_err(|e| {
+            // Without the usage that follows the first line
+            let message = e.message.lines().next().unwrap_or("").trim_start_matches("error: ");
+            Error::Config(format!("profile '{}': {}", profile, message))
+        })
+}
 
-    // Prepare our blame options
-    let mut opts = BlameOptions::new();
-    opts.track_copies_same_commit_moves(args.flag_M)
-        .track_copies_same_commit_copies(args.flag_C)
-        .first_parent(args.flag_F);
+/// Whether the variable `name` is set to a true value such as `1`
+fn env_flag (name: &str) -> Result<bool, Error>
+{
+    match std::env::var(name) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "" | "0" | "false" | "no" | "off" => Ok(false),
+            _ => Err(Error::Usage(format!("{} must be true or false, not '{}'", name, value))),
+        },
+        Err(_) => Ok(false),
+    }
+}
 
-    let mut tracker = TrackedFile::new(&path.display().to_string());
+fn parse_language (name: &str) -> Result<String, String>
+{
+    let name = name.trim().to_ascii_lowercase();
+    if language::names().any(|known| known == name) {
+        Ok(name)
+    } else {
+        Err(format!("unknown language '{}', expected one of {}",
+                    name, language::names().collect::<Vec<_>>().join(", ")))
+    }
+}
 
-    let blame = repo.blame_file(path, Some(&mut opts))?;
+/// At least 4 hex digits, as for `git --abbrev`
+fn parse_abbrev (n: &str) -> Result<usize, String>
+{
+    match n.trim().parse::<usize>() {
+        Ok(n) if n >= 4 => Ok(n),
+        _ => Err(format!("invalid length '{}', expected a number of at least 4", n)),
+    }
+}
 
-    for hunk in blame.iter()
+/// Every git command that analyzing `paths` would run as a command line,
+/// after a comment on how many files there are and how they would be
+/// blamed, and on what else is done in process
+fn print_plan (out: &mut dyn Write, repo: &Repository, paths: &[PathBuf], args: &Args) -> Result<(), Error>
+{
+    let backend = match args.backend() {
+        Backend::Git => "by running these commands",
+        Backend::Libgit2 => "by running these commands, git blame in process by libgit2",
+    };
+    writeln!(out, "# {} files, analyzed {}", paths.len(), backend)?;
+    let opts = args.options();
+    if let (true, Some(file)) = (args.detect_noise, &opts.blame.ignore_revs) {
+        writeln!(out, "# noise commits found in process in the history of {}, listed in {}",
+                 args.rev.as_deref().unwrap_or("HEAD"), file.display())?;
+    }
+    let mut commands = Vec::new();
+    let rev = args.rev.as_deref().unwrap_or("HEAD");
+    for path in paths
+    {
+        commands.extend(whoknows::commands(repo, path, &opts)?);
+        if let Some(vs) = &args.vs {
+            let mut base = opts.clone();
+            base.blame.rev = Some(vs.clone());
+            commands.extend(whoknows::commands(repo, path, &base)?);
+        }
+        for _ in [args.deleted, args.survival].iter().filter(|&&run| run)
+        {
+            commands.push(whoknows::deleted::command(repo, path, rev, !args.no_mailmap)?);
+        }
+        if args.verbose > 0 {
+            commands.push(whoknows::renames::command(repo, path, rev)?);
+        }
+    }
+    let looks_up_commits = args.issues || args.topics || args.include_reviewers || args.include_signoffs
+        || args.verbose > 0 || args.export.is_some() || matches!(args.command, Some(Command::Timezones { .. }));
+    if args.backend() == Backend::Git && looks_up_commits {
+        commands.push(whoknows::commits::command(repo));
+    }
+    if args.signatures {
+        commands.push(whoknows::signatures::command(repo));
+    }
+    for command in &commands
     {
-        tracker.add_hunk(&hunk);
+        writeln!(out, "{}", blame::command_line(command))?;
     }
+    Ok(())
+}
 
-    println!("File: {}", tracker.path);
-    let mut owners: Vec<&Owner> = tracker.owners.values().collect();
-    owners.sort_by(|a, b| b.lines().cmp(&a.lines()));
+/// Whether the command analyzes files, and so has a plan for --dry-run
+/// to print, or runs nothing anyway
+fn plans (args: &Args) -> bool
+{
+    args.analyzes() || matches!(
+        args.command,
+        Some(Command::Completions { .. }) | Some(Command::Man) | Some(Command::Schema)
+    )
+}
 
-    for owner in owners
+/// The tracked files named by the arguments, less assets, files in other
+/// languages than those asked for and those the configuration excludes,
+/// unless they were named explicitly
+fn files_to_analyze (repo: &Repository, args: &Args) -> Result<Vec<PathBuf>, Error>
+{
+    // With --rev, files that are gone from the work tree are looked up in
+    // the tree of the revision instead
+    let (present, gone): (Vec<PathBuf>, Vec<PathBuf>) = match (&args.rev, args.jj_commit) {
+        (Some(_), None) => args.paths().iter().cloned().partition(|p| p.exists()),
+        _ => (args.paths().to_vec(), Vec::new()),
+    };
+    let mut historical = Vec::new();
+    if let Some(rev) = &args.rev {
+        for path in &gone
+        {
+            match whoknows::path_at(repo, path, rev)? {
+                Some(found) => historical.push(found),
+                None => return Err(Error::Path(format!("{} does not exist, nor at {}", path.display(), rev))),
+            }
+        }
+    }
+    let mut paths = match args.jj_commit {
+        // Files jj snapshotted are in no index
+        Some(commit) => whoknows::jj::tracked_files(repo, commit, &present)?,
+        None if present.is_empty() && !historical.is_empty() => Vec::new(),
+        None => whoknows::tracked_files(repo, &present)?,
+    };
+    let explicit: Vec<PathBuf> = present.iter()
+        .filter(|p| !p.is_dir())
+        .filter_map(|p| whoknows::repo_relative(repo, p).ok())
+        .chain(historical.iter().cloned())
+        .collect();
+    for path in historical
     {
-        println!("  {}", owner);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
     }
+    let workdir = repo.workdir().unwrap_or_else(|| Path::new("."));
+    let wanted = |path: &Path| {
+        if args.languages.is_empty() {
+            return !language::is_asset(path);
+        }
+        language::detect(workdir, path).is_some_and(|l| args.languages.iter().any(|w| w == l))
+    };
+    paths.retain(|p| explicit.contains(p) || (!args.config.excluded(p) && wanted(p)));
+    if args.contents.is_some() && paths.len() != 1 {
+        return Err(Error::Usage(format!("--contents needs exactly one file, not {}", paths.len())));
+    }
+    Ok(paths)
+}
+
+/// The contents of `file`, or of stdin for `-`, as --contents gives them
+fn read_contents (file: &Path) -> Result<Arc<[u8]>, Error>
+{
+    let mut contents = Vec::new();
+    let read = match file == Path::new("-") {
+        true => std::io::stdin().read_to_end(&mut contents).map(drop),
+        false => std::fs::read(file).map(|read| contents = read),
+    };
+    read.map_err(|e| Error::Path(format!("cannot read {}: {}", file.display(), e)))?;
+    Ok(contents.into())
+}
 
+/// Replace the paths given, or the whole work tree without any, by files
+/// among those they name picked on the terminal
+fn pick_paths (repo: &Repository, args: &mut Args) -> Result<(), Error>
+{
+    if !args.pick && !pick::interactive() {
+        return Err(Error::Usage("no path given; name the files to analyze or pick them with --pick".to_string()));
+    }
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?
+        .to_path_buf();
+    if args.arg_paths.is_empty() {
+        args.arg_paths = vec![workdir.clone()];
+    }
+    let files: Vec<String> = files_to_analyze(repo, args)?.iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    args.arg_paths = pick::pick(&files)?.into_iter().map(|path| workdir.join(path)).collect();
     Ok(())
 }
 
-fn main() {
-    let args = Args::from_args();
-    match run(&args) {
-        Ok(()) => {}
-        Err(e) => println!("error: {}", e)
+/// How many of `paths` differ between the tree of the last incremental run
+/// and `tree`; all of them when there was no such run
+fn changed_since_last_run (repo: &Repository, tree: &git2::Tree, paths: &[PathBuf]) -> usize
+{
+    let last = Cache::new(repo).last_run().and_then(|oid| repo.find_tree(oid).ok());
+    let last = match last {
+        Some(last) => last,
+        None => return paths.len(),
+    };
+
+    let mut changed = HashSet::new();
+    if let Ok(diff) = repo.diff_tree_to_tree(Some(&last), Some(tree), None) {
+        for delta in diff.deltas()
+        {
+            changed.extend(delta.new_file().path().map(Path::to_path_buf));
+        }
+    }
+    paths.iter().filter(|path| changed.contains(*path)).count()
+}
+
+/// Whether the run passed: false when check or doctor found what should
+/// fail it
+fn run(args: &mut Args, out: &mut Output) -> Result<bool, Error> {
+    if args.human || env_flag("WHOKNOWS_HUMAN")? {
+        human::enable();
+    }
+    args.check_scope()?;
+    if args.anonymize {
+        args.check_anonymize()?;
+    }
+    if args.dry_run && !plans(args) {
+        return Err(Error::Usage("--dry-run only plans the analysis of files, which this command does not do".to_string()));
+    }
+    if args.dry_run && args.url().is_some() {
+        return Err(Error::Usage("--dry-run cannot plan the analysis of a repository before it is cloned".to_string()));
+    }
+    // Nothing to do with any repository
+    match &args.command {
+        Some(Command::Completions { shell }) => {
+            Args::clap().gen_completions_to("git-whoknows", *shell, out);
+            return Ok(true);
+        }
+        Some(Command::Man) => {
+            write!(out, "{}", man::page(Args::clap()))?;
+            return Ok(true);
+        }
+        Some(Command::Schema) => {
+            writeln!(out, "{}", whoknows::schema::schema())?;
+            return Ok(true);
+        }
+        Some(Command::Workspace { manifest, repos }) => {
+            let (manifest, repos) = (manifest.clone(), repos.clone());
+            return workspace(out, args, manifest.as_deref(), &repos).map(|()| true);
+        }
+        // Also where there is no repository, to say so
+        Some(Command::Doctor { path }) => {
+            let path = path.clone().unwrap_or_else(|| PathBuf::from("."));
+            return doctor(out, args, &path);
+        }
+        _ => {}
+    }
+    let clone = match args.url() {
+        Some(url) => {
+            args.remote_backend()?;
+            let clone = Clone::new(&url, &args.paths()[1..], args.quiet)?;
+            args.use_clone(&clone)?;
+            Some(clone)
+        }
+        None => None,
+    };
+    let level = match args.verbose {
+        0 | 1 => Level::Off,
+        2 => Level::Debug,
+        _ => Level::Trace,
+    };
+    trace::init(level, args.log_format.unwrap_or(LogFormat::Text));
+    let start = args.paths().first().map(PathBuf::as_path).unwrap_or_else(|| Path::new("."));
+    // A path gone from the work tree may still be in the tree of --rev,
+    // and its repository is that of the closest directory left
+    let start = match args.rev {
+        Some(_) if !start.exists() => start.ancestors().skip(1)
+            .find(|dir| dir.as_os_str().is_empty() || dir.exists())
+            .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+            .unwrap_or(start),
+        _ => start,
+    };
+    let mut span = trace::span(Level::Debug, "discover");
+    span.record("start", start.display());
+    let jj = Workspace::discover(start);
+    let repo = match (whoknows::discover(start), &jj) {
+        // Not colocated, so only jj knows where its git store is
+        (Err(e), Some(jj)) if e.code() == git2::ErrorCode::NotFound => Ok(jj.open_git()?),
+        (Err(e), None) if e.code() == git2::ErrorCode::NotFound => match Mercurial::discover(start) {
+            Some(hg) => {
+                drop(span);
+                args.apply_env()?;
+                return mercurial(out, &hg, args).map(|()| true);
+            }
+            None => Err(e),
+        },
+        (found, _) => found,
+    };
+    let repo = repo.map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound if !start.exists() => Error::Path(format!("{} does not exist", start.display())),
+        git2::ErrorCode::NotFound => not_a_repository(start),
+        _ => e.into(),
+    })?;
+    span.record("git_dir", repo.path().display());
+    drop(span);
+    args.set_path_base(repo.workdir())?;
+    // Before loading the configuration, so that a broken one can be fixed
+    if let Some(Command::Config { action }) = &args.command {
+        return config_command(out, &repo, action, args.quiet).map(|()| true);
+    }
+    args.apply_env()?;
+    let config = Config::load(&repo)?;
+    if let Some(profile) = &args.profile {
+        *args = with_profile(&config, profile)?;
+        args.apply_env()?;
+        if let Some(clone) = &clone {
+            args.use_clone(clone)?;
+        }
+    }
+    args.apply_config(config)?;
+    // A jj workspace whose git repository is some other one's is not ours
+    let workdir = repo.workdir().and_then(|dir| platform::canonicalize(dir).ok());
+    if let Some(jj) = jj.filter(|jj| workdir.as_deref() == Some(jj.root())) {
+        let commit = match jj.resolve(args.rev.as_deref().unwrap_or("@"))? {
+            Some(commit) => commit,
+            // Without jj, what git has of it has to do
+            None => repo.revparse_single(args.rev.as_deref().unwrap_or("HEAD"))?.peel_to_commit()?.id(),
+        };
+        args.rev = Some(commit.to_string());
+        args.jj_commit = Some(commit);
+    }
+    // A profile may have asked for it
+    if args.human {
+        human::enable();
+    }
+    if args.no_truncate {
+        table::set_width(None);
+    } else if let Some(width) = args.width {
+        table::set_width(Some(width));
+    }
+    if let Some(path) = &args.output {
+        // Tables are not cut to the terminal in a file
+        table::set_width(None);
+        out.redirect(path)?;
+    }
+    if args.copy {
+        if args.watch {
+            return Err(Error::Usage("--copy cannot be used with --watch".to_string()));
+        }
+        clipboard::start(out);
+    }
+    if let Some(path) = &args.roster_file {
+        args.roster = Some(Roster::from_file(path)?);
+    }
+    if let Some(file) = &args.contents_file {
+        if !matches!(args.command, None | Some(Command::File { .. }) | Some(Command::Line { .. })) {
+            return Err(Error::Usage("--contents only goes with file and line".to_string()));
+        }
+        args.contents = Some(read_contents(file)?);
+    }
+    if let Some(branch) = &args.branch {
+        args.rev = Some(branch_rev(&repo, branch)?);
+    }
+    if args.backend() == Backend::Git {
+        blame::check_git(&args.options().blame)?;
+        if args.detect_noise {
+            blame::require_git("--detect-noise", 2, 23)?;
+        }
+    }
+    if args.detect_noise {
+        ignore_noise(&repo, args)?;
+    }
+    args.whole_repository(&repo);
+    // A snapshot is of the commit it is saved on, whatever the work tree has
+    if let (Some(Command::Snapshot { .. }), None) = (&args.command, &args.rev) {
+        args.rev = Some("HEAD".to_string());
+    }
+    if args.command.is_none() && !args.query_server && (args.pick || args.arg_paths.is_empty()) {
+        pick_paths(&repo, args)?;
+    }
+    if let Some(Command::Diff { range, .. }) = &args.command {
+        if args.rev.is_some() {
+            return Err(Error::Usage("diff takes the revisions to compare instead of --rev".to_string()));
+        }
+        let (base, files) = diff_files(&repo, range.as_deref())?;
+        args.rev = Some(base);
+        *args.paths_mut() = files.into_iter().filter(|path| !args.config.excluded(path)).collect();
+    }
+    let args = &*args;
+    match &args.command {
+        Some(Command::Serve { listen }) => return serve(&repo, listen, args).map(|()| true),
+        Some(Command::Line { location }) => return line(out, &repo, args, location).map(|()| true),
+        Some(Command::Symbol { name, path }) => return symbol(out, &repo, args, name, path).map(|()| true),
+        Some(Command::History { range, path }) => return history(out, &repo, args, range, path).map(|()| true),
+        Some(Command::Gone { range, path }) => return gone(out, &repo, args, range, path).map(|()| true),
+        Some(Command::Grep { pattern, regex, max }) => return grep(out, &repo, args, pattern, *regex, *max).map(|()| true),
+        Some(Command::Identities) => return identities(out, &repo, args).map(|()| true),
+        Some(Command::Commit { rev }) => return commit(out, &repo, args, rev).map(|()| true),
+        Some(Command::Heatmap { weeks, html, paths }) => return heatmap(out, &repo, args, paths, *weeks, *html).map(|()| true),
+        Some(Command::Reviewers { github, pr, gitlab, mr, gerrit, change, request, note, max }) => {
+            return match (github, pr, gitlab, mr, change) {
+                (Some(github), Some(pr), _, _, _) => github_reviewers(out, &repo, args, github, *pr, *request, *max),
+                (_, _, Some(gitlab), Some(mr), _) => {
+                    gitlab_reviewers(out, &repo, args, gitlab, *mr, *request, *note, *max)
+                }
+                (_, _, _, _, Some(change)) => {
+                    gerrit_reviewers(out, &repo, args, gerrit.as_deref(), change, *request, *max)
+                }
+                _ if *request => {
+                    Err(Error::Usage("--request needs --github, --gitlab or --change".to_string()))
+                }
+                _ => {
+                    let mut diff = String::new();
+                    std::io::stdin().read_to_string(&mut diff)?;
+                    let reviewers = suggest_reviewers(&repo, args, reviewers::touched_files(&diff))?;
+                    let reviewers: Vec<_> = reviewers.into_iter().take(*max).map(|r| (args.shown_reviewer(r), None)).collect();
+                    write!(out, "{}", reviewer_table(&reviewers, None).render(table::terminal_width()))?;
+                    Ok(())
+                }
+            }.map(|()| true);
+        }
+        Some(Command::Check {
+            min_bus_factor, max_single_owner, warn_min_bus_factor, warn_max_single_owner, require_owner, junit, ..
+        }) => {
+            let limits = Thresholds {
+                min_bus_factor: *min_bus_factor,
+                max_single_owner: *max_single_owner,
+                warn_min_bus_factor: *warn_min_bus_factor,
+                warn_max_single_owner: *warn_max_single_owner,
+                require_owner: *require_owner,
+            };
+            return check(out, &repo, args, &limits, junit.as_deref());
+        }
+        Some(Command::InstallHook { hook, force }) => {
+            let path = hook::install(&repo, hook, *force)?;
+            if !args.quiet {
+                eprintln!("Installed {}", path.display());
+            }
+            return Ok(true);
+        }
+        Some(Command::Cache { action }) => return cache_command(out, &repo, action, args).map(|()| true),
+        Some(Command::Snapshot { action: SnapshotAction::Diff { from, to } }) => {
+            return snapshot_diff(out, &repo, args, from, to.as_deref().unwrap_or("HEAD")).map(|()| true);
+        }
+        Some(Command::Snapshot { action: SnapshotAction::Series { csv } }) => return snapshot_series(out, &repo, *csv).map(|()| true),
+        Some(Command::ReportDiff { old, new }) => return report_diff(out, args, old, new).map(|()| true),
+        Some(Command::Merge { shards }) => return merge_shards(out, &repo, args, shards).map(|()| true),
+        Some(Command::Completions { .. }) | Some(Command::Man) | Some(Command::Schema)
+        | Some(Command::Workspace { .. }) | Some(Command::Doctor { .. }) => {
+            unreachable!("handled before opening the repository")
+        }
+        Some(Command::Config { .. }) => unreachable!("handled before loading the configuration"),
+        None if args.query_server => return query::serve(&repo, &args.options()).map(|()| true),
+        Some(Command::Warm { .. }) if args.no_cache => {
+            return Err(Error::Usage("warm fills the cache, which --no-cache turns off".to_string()));
+        }
+        Some(Command::File { .. })
+        | Some(Command::Dir { .. })
+        | Some(Command::Repo)
+        | Some(Command::Diff { .. })
+        | Some(Command::BusFactor { .. })
+        | Some(Command::Risk { .. })
+        | Some(Command::Pairs { .. })
+        | Some(Command::Timezones { .. })
+        | Some(Command::Teams { .. })
+        | Some(Command::Warm { .. })
+        | Some(Command::Report { .. })
+        | Some(Command::Codeowners { .. })
+        | Some(Command::Snapshot { action: SnapshotAction::Save { .. } })
+        | None => {}
+    }
+
+    if args.watch && args.format() != Format::Table {
+        return Err(Error::Usage("--watch only works with the table format".to_string()));
+    }
+
+    let paths = match &args.command {
+        // Deleted files are no longer in the work tree, only at the base
+        Some(Command::Diff { paths, .. }) => paths.clone(),
+        _ => files_to_analyze(&repo, args)?,
+    };
+    if args.dry_run {
+        print_plan(out, &repo, &paths, args)?;
+        return Ok(true);
+    }
+    let head_tree = if args.incremental {
+        let tree = repo.head()?.peel_to_tree()?;
+        let changed = changed_since_last_run(&repo, &tree, &paths);
+        if !args.quiet {
+            eprintln!("incremental: {} of {} files changed since the last run",
+                      changed, paths.len());
+        }
+        Some(tree.id())
+    } else {
+        None
+    };
+
+    let checkpoint = Checkpoint::new(&Cache::new(&repo), &run_id(&repo, args, &paths));
+    let mut done = if args.resume { checkpoint.load() } else { HashMap::new() };
+    if let Some(half_life) = args.decay {
+        for report in done.values_mut()
+        {
+            report.decay(half_life, date::now());
+        }
+    }
+    if args.resume && !args.quiet {
+        eprintln!("resume: {} of {} files were already done", done.len(), paths.len());
+    }
+    let todo: Vec<PathBuf> = paths.iter().filter(|p| !done.contains_key(*p)).cloned().collect();
+    let _ = checkpoint.start(&done.values().collect::<Vec<_>>());
+
+    interrupt::install();
+    let progress = Progress::new(todo.len(), !args.quiet && todo.len() > 1, args.progress.unwrap_or(ProgressFormat::Bar));
+    let observer = Checkpointing { inner: &progress, checkpoint: &checkpoint };
+    let mut span = trace::span(Level::Debug, "analyze");
+    span.record("files", todo.len());
+    let before = Cache::new(&repo).stats();
+    let report = Analyzer::new(&repo).options(args.options()).files(&todo, &observer);
+    drop(span);
+    progress.clear();
+    let mut report = report?;
+
+    // Put the files of the earlier attempt back in their place
+    if !done.is_empty() {
+        let mut fresh: HashMap<PathBuf, FileReport> = report.files.drain(..)
+            .map(|f| (PathBuf::from(&f.path), f))
+            .collect();
+        report.files = paths.iter()
+            .filter_map(|p| done.remove(p).or_else(|| fresh.remove(p)))
+            .collect();
+        report.partial = report.files.len() < paths.len();
+    }
+    if !report.partial {
+        checkpoint.remove();
+    }
+    if report.partial {
+        eprintln!("interrupted: results cover {} of {} files", report.files.len(), paths.len());
+    }
+
+    if let (Some(tree), false) = (head_tree, report.partial) {
+        Cache::new(&repo).set_last_run(tree)?;
+    }
+    if let Some(window) = args.active_window {
+        let mailmap = whoknows::load_mailmap(&repo, &args.options())?;
+        let active = roster::active_authors(&repo, args.rev.as_deref(), &mailmap, date::now() - window)?;
+        for file in &mut report.files
+        {
+            roster::fold_inactive(file, &active);
+        }
+    }
+    if args.anonymize {
+        // Blamed again, line by line
+        if matches!(args.format(), Format::Quickfix) {
+            return Err(Error::Usage("--format quickfix names the authors that --anonymize hides".to_string()));
+        }
+        for file in &mut report.files
+        {
+            *file = anonymize::anonymize(file, args.salt());
+        }
+    }
+    for file in &mut report.files
+    {
+        args.show_emails(file);
+    }
+
+    match (&args.command, args.format()) {
+        (Some(Command::Warm { .. }), _) => {
+            let after = Cache::new(&repo).stats();
+            if !args.quiet {
+                eprintln!("Cached {} files: {} blamed, {} already cached",
+                         report.files.len(), after.misses.saturating_sub(before.misses),
+                         after.hits.saturating_sub(before.hits));
+            }
+        }
+        (Some(Command::Codeowners { .. }), _) => write!(out, "{}", codeowners(&report, &args.config))?,
+        (Some(Command::Snapshot { .. }), _) => {
+            if report.partial {
+                return Err(Error::Usage("interrupted: not saving a partial snapshot".to_string()));
+            }
+            let commit = repo.revparse_single(args.rev.as_deref().unwrap_or("HEAD"))?.peel_to_commit()?.id();
+            let snapshot = Snapshot::new(commit, &report.files);
+            snapshot::save(&repo, &snapshot)?;
+            if !args.quiet {
+                eprintln!("Saved the ownership of {} files on {} in {}",
+                          snapshot.files.len(), args.hash(commit, SHORT_HASH), snapshot::NOTES_REF);
+            }
+        }
+        (Some(Command::Report { email: true, .. }), _) => {
+            if report.partial {
+                return Err(Error::Usage("interrupted: not mailing a partial report".to_string()));
+            }
+            let subject = format!("Knowledge report for {} on {}", repo_name(&repo), date::format_date(date::now()));
+            mail::send(&args.config.email, &subject, &summary(&repo, &report, args, Some(MAIL_WIDTH))?)?;
+            if !args.quiet {
+                eprintln!("Mailed the report to {}", args.config.email.to.join(", "));
+            }
+        }
+        (Some(Command::Report { .. }), _) => write!(out, "{}", summary(&repo, &report, args, table::terminal_width())?)?,
+        (Some(Command::Dir { paths: dirs }), Format::Table) => {
+            print_groups(out, &repo, &report, args, "Directory", dirs)?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+        }
+        (Some(Command::Repo), Format::Table) => {
+            print_groups(out, &repo, &report, args, "Repository", args.paths())?;
+            writeln!(out, "Directories:")?;
+            write!(out, "{}", directory_table(&report).render(table::terminal_width()))?;
+            let packages = whoknows::package::packages(&repo, args.rev.as_deref(), &report.files)?;
+            if !packages.is_empty() {
+                writeln!(out, "Packages:")?;
+                write!(out, "{}", package_table(&packages).render(table::terminal_width()))?;
+            }
+            writeln!(out, "Languages:")?;
+            write!(out, "{}", language_table(&repo, &report).render(table::terminal_width()))?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+        }
+        (Some(Command::BusFactor { .. }), Format::Table) => {
+            write!(out, "{}", bus_factor_table(&report, args).render(table::terminal_width()))?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+        }
+        (Some(Command::Risk { .. }), Format::Table) => {
+            let mailmap = whoknows::load_mailmap(&repo, &args.options())?;
+            let risks = whoknows::risk::assess(&repo, &report.files, args.rev.as_deref(), &mailmap, date::now())?;
+            write!(out, "{}", risk_table(&risks, args).render(table::terminal_width()))?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+        }
+        (Some(Command::Pairs { .. }), Format::Table) => {
+            let mailmap = whoknows::load_mailmap(&repo, &args.options())?;
+            let now = date::now();
+            let risks = whoknows::risk::assess(&repo, &report.files, args.rev.as_deref(), &mailmap, now)?;
+            let pairings = whoknows::pairing::suggest(&report.files, &risks, now);
+            write!(out, "{}", pairing_table(&pairings, args).render(table::terminal_width()))?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+        }
+        (Some(Command::Timezones { .. }), Format::Table) => {
+            let owners = timezone::owner_zones(&report.files, &mut Commits::new(&repo, args.backend())?)?;
+            let files: Vec<FileZones> = report.files.iter().map(|file| FileZones::new(file, &owners)).collect();
+            write!(out, "{}", timezone_table(&files, args).render(table::terminal_width()))?;
+            writeln!(out, "Owners:")?;
+            write!(out, "{}", owner_zone_table(&owners).render(table::terminal_width()))?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+            let owned = files.iter().filter(|file| file.lines() > 0);
+            let single = owned.clone().filter(|file| file.single_zone()).count();
+            writeln!(out, "Single timezone: {} of {} files", single, owned.count())?;
+        }
+        (Some(Command::Teams { github_org, .. }), Format::Table) => {
+            let teams = match github_org {
+                Some(org) => github_teams(org, &report, args)?,
+                None => args.config.teams.clone(),
+            };
+            if teams.is_empty() {
+                return Err(Error::Usage("no teams: configure [teams] or give --github-org".to_string()));
+            }
+            let owned = whoknows::teams::ownership(&report.files, &teams);
+            write!(out, "{}", team_table(&owned, report.lines()).render(table::terminal_width()))?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+            writeln!(out, "Unassigned: {} lines of authors in no team",
+                     whoknows::teams::unassigned(&report.files, &teams))?;
+        }
+        (_, Format::Table) => {
+            print_tables(out, &repo, &report, args)?;
+            write!(out, "{}", footer(&repo, &report, paths.len(), args))?;
+        }
+        #[cfg(feature = "parquet")]
+        (_, Format::Parquet) => write_parquet(out, &args.shown_files(&report.files), args)?,
+        (_, Format::Prometheus) => {
+            let mailmap = whoknows::load_mailmap(&repo, &args.options())?;
+            prometheus::write(&mut *out, &repo, args.rev.as_deref(), &mailmap, &args.shown_files(&report.files))?;
+        }
+        (_, Format::Metrics) => write!(out, "{}", measurements(&args.metrics, &report, args)?)?,
+        (_, Format::Quickfix) => write!(out, "{}", quickfix(&repo, &report, args)?)?,
+        // Only v1 so far
+        (_, Format::Porcelain) => write!(out, "{}", whoknows::porcelain::v1(&args.shown_files(&report.files)))?,
+        (_, Format::Json) => {
+            let expertise = &args.config.expertise;
+            let mut files = Vec::new();
+            for (file, shown) in report.files.iter().zip(args.shown_files(&report.files).iter())
+            {
+                let mut value = json::labeled_report(shown, |owner| args.expertise.then(|| expertise.of(owner, shown).to_string()));
+                if let (Value::Object(members), true) = (&mut value, args.verbose > 0) {
+                    members.push(("renames".to_string(), renames_json(&repo, file, args)?));
+                }
+                files.push(value);
+            }
+            writeln!(out, "{}", Value::Array(files))?;
+        }
+    }
+
+    if let Some(export) = &args.export {
+        // A history with half a run in it would read like lost ownership
+        if report.partial {
+            eprintln!("interrupted: not exporting partial results");
+        } else {
+            export.write(&repo, &report, args.backend())?;
+        }
+    }
+
+    if let Some(url) = &args.notify {
+        if report.partial {
+            eprintln!("interrupted: not notifying about partial results");
+        } else {
+            notify::notify(&repo, url, args.notify_template.as_deref(), &report, &args.config, args.quiet)?;
+        }
+    }
+
+    if args.watch {
+        let analyzer = Analyzer::new(&repo).options(args.options());
+        watch::watch(&repo, || files_to_analyze(&repo, args), |changed| {
+            let report = analyzer.files(changed, &whoknows::Silent)?;
+            writeln!(out, "\nChanged at {}", date::format_datetime(date::now()))?;
+            print_tables(out, &repo, &report, args)
+        })?;
+    }
+
+    Ok(true)
+}
+
+/// The owners of `files`, most lines first
+fn suggest_reviewers (repo: &Repository, args: &Args, files: Vec<String>) -> Result<Vec<Reviewer>, Error>
+{
+    let tree = repo.revparse_single(args.rev.as_deref().unwrap_or("HEAD"))?.peel_to_tree()?;
+    // New files have nobody to ask yet
+    let paths: Vec<PathBuf> = files.into_iter()
+        .map(PathBuf::from)
+        .filter(|path| tree.get_path(path).is_ok() && !args.config.excluded(path))
+        .collect();
+    let report = Analyzer::new(repo).options(args.options()).files(&paths, &whoknows::Silent)?;
+    Ok(reviewers::rank(&report.files))
+}
+
+/// Suggest reviewers for pull request `pr` of `github`, other than its
+/// author, and ask them for a review if `request` is set
+#[cfg(feature = "forges")]
+fn github_reviewers (
+    out: &mut dyn Write,
+    repo: &Repository,
+    args: &Args,
+    github: &str,
+    pr: u64,
+    request: bool,
+    max: usize
+) -> Result<(), Error>
+{
+    let github = GitHub::new(github, &args.config.github)?;
+    let author = github.author(pr)?;
+    let mut suggested = Vec::new();
+    let files = reviewers::touched_files(&github.diff(pr)?);
+    for reviewer in suggest_reviewers(repo, args, files)?
+    {
+        if suggested.len() == max {
+            break;
+        }
+        let login = github.login(&reviewer);
+        if login.as_deref() != Some(author.as_str()) {
+            suggested.push((args.shown_reviewer(reviewer), login));
+        }
+    }
+    write!(out, "{}", reviewer_table(&suggested, Some("Login")).render(table::terminal_width()))?;
+
+    if request {
+        let logins: Vec<String> = suggested.into_iter().filter_map(|(_, login)| login).collect();
+        if logins.is_empty() {
+            return Err(Error::Remote(format!("no GitHub login for any reviewer of pull request {}", pr)));
+        }
+        github.request_reviews(pr, &logins)?;
+        if !args.quiet {
+            eprintln!("requested reviews from {}", logins.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// The teams of GitHub organization `org` with the emails of the owners in
+/// `report` who are their members
+#[cfg(feature = "forges")]
+fn github_teams (org: &str, report: &RepoReport, args: &Args) -> Result<BTreeMap<String, Vec<String>>, Error>
+{
+    let org = Organization::new(org, &args.config.github);
+    let teams = org.teams()?;
+    let mut logins: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut emails: Vec<&str> = report.files.iter()
+        .flat_map(|file| file.owners.values())
+        .map(|owner| owner.email.as_str())
+        .collect();
+    emails.sort_unstable();
+    emails.dedup();
+    for email in emails
+    {
+        match org.login(email) {
+            Some(login) => logins.entry(login.to_lowercase()).or_default().push(email.to_string()),
+            None if args.verbose > 0 => eprintln!("no GitHub login for {}", email),
+            None => {}
+        }
+    }
+    Ok(teams.into_iter()
+        .map(|(team, members)| {
+            let emails = members.iter()
+                .flat_map(|login| logins.get(&login.to_lowercase()).into_iter().flatten().cloned())
+                .collect();
+            (team, emails)
+        })
+        .collect())
+}
+
+/// Suggest reviewers for merge request `mr` of `gitlab`, other than its
+/// author, make them its reviewers if `request` is set and mention them in
+/// a note if `note` is
+#[cfg(feature = "forges")]
+#[allow(clippy::too_many_arguments)]
+fn gitlab_reviewers (
+    out: &mut dyn Write,
+    repo: &Repository,
+    args: &Args,
+    gitlab: &str,
+    mr: u64,
+    request: bool,
+    note: bool,
+    max: usize
+) -> Result<(), Error>
+{
+    let gitlab = GitLab::new(gitlab, &args.config.gitlab)?;
+    let merge_request = gitlab.merge_request(mr)?;
+    let mut suggested = Vec::new();
+    for reviewer in suggest_reviewers(repo, args, merge_request.files)?
+    {
+        if suggested.len() == max {
+            break;
+        }
+        let username = gitlab.username(&reviewer);
+        if username.as_deref() != Some(merge_request.author.as_str()) {
+            suggested.push((args.shown_reviewer(reviewer), username));
+        }
+    }
+    write!(out, "{}", reviewer_table(&suggested, Some("Username")).render(table::terminal_width()))?;
+
+    let usernames: Vec<String> = suggested.iter().filter_map(|(_, username)| username.clone()).collect();
+    if (request || note) && usernames.is_empty() {
+        return Err(Error::Remote(format!("no GitLab username for any reviewer of merge request {}", mr)));
+    }
+    if request {
+        gitlab.set_reviewers(mr, &usernames)?;
+        if !args.quiet {
+            eprintln!("set reviewers to {}", usernames.join(", "));
+        }
+    }
+    if note {
+        let mut text = "Suggested reviewers, by the lines they own of the changed files:\n\n".to_string();
+        for (reviewer, username) in suggested.iter().filter_map(|(r, u)| u.as_ref().map(|u| (r, u)))
+        {
+            text.push_str(&format!("- @{} ({} lines)\n", username, reviewer.lines));
+        }
+        gitlab.note(mr, &text)?;
+        if !args.quiet {
+            eprintln!("posted a note on merge request {}", mr);
+        }
+    }
+    Ok(())
+}
+
+/// Print the reviewers for `change` on `gerrit`, other than its owner, as
+/// a ReviewInput, and post it if `request` is set
+#[cfg(feature = "forges")]
+fn gerrit_reviewers (
+    out: &mut dyn Write,
+    repo: &Repository,
+    args: &Args,
+    gerrit: Option<&str>,
+    change: &str,
+    request: bool,
+    max: usize
+) -> Result<(), Error>
+{
+    let url = gerrit.or(args.config.gerrit.url.as_deref())
+        .ok_or_else(|| Error::Usage("--change needs --gerrit or a [gerrit] url".to_string()))?;
+    let gerrit = Gerrit::new(url, change, &args.config.gerrit);
+    let change = gerrit.change()?;
+    let owner = [change.owner_email.as_deref(), change.owner_username.as_deref()];
+    let accounts: Vec<String> = suggest_reviewers(repo, args, change.files)?.iter()
+        .filter(|reviewer| !owner.iter().flatten().any(|o| o.eq_ignore_ascii_case(&reviewer.email)))
+        .map(|reviewer| gerrit.account(reviewer))
+        .filter(|account| !owner.contains(&Some(account.as_str())))
+        .take(max)
+        .collect();
+
+    // Accounts are emails unless the server has usernames for them
+    let shown: Vec<String> = accounts.iter()
+        .map(|account| if account.contains('@') { args.shown_email(account) } else { account.clone() })
+        .collect();
+    writeln!(out, "{}", gerrit::review_input(&shown))?;
+    if request {
+        gerrit.add_reviewers(&gerrit::review_input(&accounts))?;
+        if !args.quiet {
+            eprintln!("added reviewers {}", shown.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Reviewers with their user name on a code hosting service, in a column
+/// named `users` if given
+fn reviewer_table (reviewers: &[(Reviewer, Option<String>)], users: Option<&str>) -> Table
+{
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email);
+    if let Some(users) = users {
+        table.column(users, Align::Left, Truncate::End);
+    }
+    table.column("Lines", Align::Right, Truncate::Never);
+    for (reviewer, user) in reviewers
+    {
+        let mut row = vec![reviewer.name.clone(), reviewer.email.clone()];
+        if users.is_some() {
+            row.push(user.clone().unwrap_or_else(|| "?".to_string()));
+        }
+        row.push(human::count(reviewer.lines));
+        table.row(row);
+    }
+    table
+}
+
+fn cache_command (out: &mut dyn Write, repo: &Repository, action: &CacheAction, args: &Args) -> Result<(), Error>
+{
+    let quiet = args.quiet;
+    let cache = Cache::open(repo, args.cache_dir.as_deref());
+    match action {
+        CacheAction::Status => {
+            let entries = cache.entries()?;
+            let stats = cache.stats();
+            let lookups = stats.hits + stats.misses;
+            writeln!(out, "Cache: {}", cache.dir().display())?;
+            if cache.is_shared() {
+                writeln!(out, "Shared: {}", cache.entries_dir().display())?;
+            }
+            writeln!(out, "Entries: {} ({})", entries.len(),
+                     format_size(entries.iter().map(|e| e.size).sum()))?;
+            writeln!(out, "Lookups: {} hits, {} misses ({:.1}% hit rate)",
+                     stats.hits, stats.misses, percent(stats.hits, lookups))?;
+        }
+        CacheAction::Clear => {
+            cache.clear()?;
+            if !quiet {
+                eprintln!("Removed {}", cache.dir().display());
+                if cache.is_shared() {
+                    eprintln!("Removed {}", cache.entries_dir().display());
+                }
+            }
+        }
+        CacheAction::Prune => {
+            let (removed, bytes) = cache.prune(repo)?;
+            if !quiet {
+                eprintln!("Removed {} entries ({})", removed, format_size(bytes));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print how ownership moved from the snapshot saved on `from` to that on
+/// `to`
+fn snapshot_diff (out: &mut dyn Write, repo: &Repository, args: &Args, from: &str, to: &str) -> Result<(), Error>
+{
+    let before = snapshot::load(repo, repo.revparse_single(from)?.peel_to_commit()?.id())?;
+    let after = snapshot::load(repo, repo.revparse_single(to)?.peel_to_commit()?.id())?;
+    let drifts = snapshot::diff(&before, &after);
+    if drifts.is_empty() {
+        writeln!(out, "Same ownership in {} and {}", from, to)?;
+    }
+    let bus_factor = |factor: Option<usize>| factor.map_or("-".to_string(), |f| f.to_string());
+    for drift in drifts
+    {
+        writeln!(out, "File: {}", drift.path)?;
+        match drift.bus_factor {
+            (None, _) => writeln!(out, "Not in the snapshot of {}", from)?,
+            (_, None) => writeln!(out, "Not in the snapshot of {}", to)?,
+            (old, new) => writeln!(out, "Bus factor {} -> {}", bus_factor(old), bus_factor(new))?,
+        }
+        if drift.differences.is_empty() {
+            continue;
+        }
+        let mut table = Table::new();
+        table.column("Name", Align::Left, Truncate::End)
+            .column("Email", Align::Left, Truncate::Email)
+            .column("Before", Align::Right, Truncate::Never)
+            .column("After", Align::Right, Truncate::Never)
+            .column("Change", Align::Right, Truncate::Never);
+        for difference in &drift.differences
+        {
+            table.row(vec![
+                difference.name.clone(),
+                args.shown_email(&difference.email),
+                difference.before.to_string(),
+                difference.after.to_string(),
+                format!("{:+}", difference.change()),
+            ]);
+        }
+        write!(out, "{}", table.render(table::terminal_width()))?;
+    }
+    Ok(())
+}
+
+/// Print the calendars of the authors of `paths` over the last `weeks`
+/// weeks, as HTML if `html` is set
+fn heatmap (out: &mut dyn Write, repo: &Repository, args: &Args, paths: &[PathBuf], weeks: usize, html: bool) -> Result<(), Error>
+{
+    let paths = paths.iter()
+        .map(|path| whoknows::repo_relative(repo, path))
+        .collect::<Result<Vec<PathBuf>, Error>>()?;
+    let mailmap = whoknows::load_mailmap(repo, &args.options())?;
+    let calendar = Calendar::new(date::now(), weeks);
+    let mut authors = calendar::activity(repo, args.rev.as_deref(), &paths, &mailmap, &calendar)?;
+    for author in &mut authors
+    {
+        author.email = args.shown_email(&author.email);
+    }
+    if html {
+        let shown: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+        let title = match shown.is_empty() {
+            true => format!("Commits to {}", repo_name(repo)),
+            false => format!("Commits to {} in {}", shown.join(", "), repo_name(repo)),
+        };
+        write!(out, "{}", calendar::html(&authors, &calendar, &title))?;
+    } else if authors.is_empty() {
+        writeln!(out, "No commits in the last {} weeks", calendar.weeks)?;
+    } else {
+        write!(out, "{}", calendar::text(&authors, &calendar))?;
+    }
+    Ok(())
+}
+
+/// Check what runs depend on for the repository at or above `start`;
+/// whether nothing would make them fail
+fn doctor (out: &mut dyn Write, args: &mut Args, start: &Path) -> Result<bool, Error>
+{
+    args.apply_env()?;
+    let mut doctor = Doctor::new();
+    let repo = doctor.repository(start);
+    let config = doctor.configuration(repo.as_ref());
+    if let Some(config) = &config {
+        args.apply_config(config.clone())?;
+    }
+    doctor.git(args.backend());
+    doctor.curl();
+    if let Some(repo) = &repo {
+        doctor.identities(repo, config.as_ref().unwrap_or(&args.config));
+    }
+    if let Some(path) = &args.roster_file {
+        doctor.roster(path);
+    }
+    if let (Some(repo), false) = (&repo, args.no_cache) {
+        doctor.cache(repo, args.cache_dir.as_deref());
+    }
+    #[cfg(feature = "forges")]
+    doctor.forges(&args.config);
+    write!(out, "{}", doctor.report())?;
+    Ok(!doctor.failed())
+}
+
+/// The files of the report saved in `path`
+fn read_report (path: &Path) -> Result<Vec<FileReport>, Error>
+{
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::Path(format!("{}: {}", path.display(), e)))?;
+    reportdiff::decode(&text).map_err(|e| Error::File(path.display().to_string(), Box::new(e)))
+}
+
+/// Print what changed from the report saved in `old` to that in `new`
+fn report_diff (out: &mut dyn Write, args: &Args, old: &Path, new: &Path) -> Result<(), Error>
+{
+    let (before, after) = (read_report(old)?, read_report(new)?);
+    let diff = reportdiff::compare(&before, &after);
+
+    let person_table = |people: &[reportdiff::Person]| {
+        let mut table = Table::new();
+        table.column("Name", Align::Left, Truncate::End)
+            .column("Email", Align::Left, Truncate::Email)
+            .column("Lines", Align::Right, Truncate::Never);
+        for person in people
+        {
+            table.row(vec![person.name.clone(), args.shown_email(&person.email), human::count(person.lines)]);
+        }
+        table
+    };
+    if !diff.joined.is_empty() {
+        writeln!(out, "New owners:")?;
+        write!(out, "{}", person_table(&diff.joined).render(table::terminal_width()))?;
+    }
+    if !diff.departed.is_empty() {
+        writeln!(out, "Departed owners:")?;
+        write!(out, "{}", person_table(&diff.departed).render(table::terminal_width()))?;
+    }
+    if !diff.regressions.is_empty() {
+        writeln!(out, "Bus factor regressions:")?;
+        let mut table = Table::new();
+        table.column("Path", Align::Left, Truncate::End)
+            .column("Before", Align::Right, Truncate::Never)
+            .column("After", Align::Right, Truncate::Never);
+        for regression in &diff.regressions
+        {
+            table.row(vec![regression.path.clone(), regression.before.to_string(), regression.after.to_string()]);
+        }
+        write!(out, "{}", table.render(table::terminal_width()))?;
+    }
+
+    // Files beyond their thresholds now that were within them before
+    let beyond: HashSet<&str> = before.iter()
+        .filter(|file| !violations(file, args, &Thresholds::default(), None).is_empty())
+        .map(|file| file.path.as_str())
+        .collect();
+    let mut crossed = 0;
+    for file in &after
+    {
+        let violations = violations(file, args, &Thresholds::default(), None);
+        if violations.is_empty() || beyond.contains(file.path.as_str()) {
+            continue;
+        }
+        if crossed == 0 {
+            writeln!(out, "Crossed thresholds:")?;
+        }
+        crossed += 1;
+        writeln!(out, "{}: {}", file.path, violations.join(", "))?;
+    }
+
+    let whole = |files: &[FileReport]| {
+        let mut whole = FileReport::new(".");
+        for file in files
+        {
+            whole.merge(file);
+        }
+        whole
+    };
+    let (old_whole, new_whole) = (whole(&before), whole(&after));
+    writeln!(out, "Files: {} -> {}, lines: {} -> {}, bus factor: {} -> {}",
+             before.len(), after.len(), old_whole.lines(), new_whole.lines(),
+             old_whole.bus_factor(), new_whole.bus_factor())?;
+    Ok(())
+}
+
+/// Print the reports saved in `shards` as one, with the identities of the
+/// repository merged over all of them
+fn merge_shards (out: &mut dyn Write, repo: &Repository, args: &Args, shards: &[PathBuf]) -> Result<(), Error>
+{
+    let parts = shards.iter().map(|path| read_report(path)).collect::<Result<Vec<_>, _>>()?;
+    let mailmap = whoknows::load_mailmap(repo, &args.options())?;
+    let merged = whoknows::shards::merge(parts, &mailmap);
+    if !args.quiet {
+        for path in &merged.overlaps
+        {
+            eprintln!("warning: {} is in several shards, taking it from the last", path);
+        }
+    }
+    let mut report = RepoReport { revision: None, files: merged.files, partial: false };
+    for file in &mut report.files
+    {
+        args.show_emails(file);
+    }
+
+    match args.format() {
+        Format::Table => {
+            for file in &report.files
+            {
+                writeln!(out, "File: {}", args.shown_path(&file.path))?;
+                let table = owner_table(file, false, &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+                write!(out, "{}", table.render(table::terminal_width()))?;
+            }
+            let mut whole = FileReport::new(".");
+            for file in &report.files
+            {
+                whole.merge(file);
+            }
+            writeln!(out, "All shards:")?;
+            let table = owner_table(&whole, false, &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+            write!(out, "{}", table.render(table::terminal_width()))?;
+            // Not the commits, which reports do not name, so that those
+            // touching files of several shards would be counted again
+            writeln!(out, "\nTotal: {}, {}, bus factor {}", human::counted(report.lines(), "line"),
+                     human::counted(report.owners().len(), "author"), report.bus_factor())?;
+            writeln!(out, "Merged {} from {}", human::counted(report.files.len(), "file"),
+                     human::counted(shards.len(), "shard"))?;
+            if let Some(risks) = risks(&report, &args.config) {
+                writeln!(out, "Risks: {}", risks)?;
+            }
+        }
+        Format::Porcelain => write!(out, "{}", whoknows::porcelain::v1(&args.shown_files(&report.files)))?,
+        Format::Json => {
+            let expertise = &args.config.expertise;
+            let files: Vec<Value> = args.shown_files(&report.files).iter()
+                .map(|file| json::labeled_report(file, |owner| args.expertise.then(|| expertise.of(owner, file).to_string())))
+                .collect();
+            writeln!(out, "{}", Value::Array(files))?;
+        }
+        _ => return Err(Error::Usage("merge writes tables, JSON or porcelain".to_string())),
+    }
+    Ok(())
+}
+
+/// Print the points of every snapshot, as CSV if `csv` is set and JSON
+/// otherwise
+fn snapshot_series (out: &mut dyn Write, repo: &Repository, csv: bool) -> Result<(), Error>
+{
+    let mut points = Vec::new();
+    for (time, snapshot) in timeseries::snapshots(repo)?
+    {
+        points.extend(timeseries::points(time, &snapshot));
+    }
+    if csv {
+        write!(out, "{}", timeseries::csv(&points))?;
+    } else {
+        writeln!(out, "{}", timeseries::json(&points))?;
+    }
+    Ok(())
+}
+
+/// Ownership across the repositories of `manifest` and `repos`
+fn workspace (out: &mut dyn Write, args: &mut Args, manifest: Option<&Path>, repos: &[PathBuf]) -> Result<(), Error>
+{
+    let mut members = Vec::new();
+    if let Some(path) = manifest {
+        let config = Config::from_file(path)?;
+        if config.workspace.is_empty() {
+            return Err(Error::Config(format!("{}: no repositories in [workspace]", path.display())));
+        }
+        members = whoknows::workspace::members(path, &config);
+        args.apply_config(config)?;
+    }
+    members.extend(whoknows::workspace::members_of(repos));
+    if members.is_empty() {
+        return Err(Error::Usage("workspace needs a --manifest or repositories to analyze".to_string()));
+    }
+    let parts = whoknows::workspace::analyze(&members, &args.options())?;
+    match args.format() {
+        Format::Table => {
+            write!(out, "{}", member_table(&parts, args).render(table::terminal_width()))?;
+            writeln!(out, "Owners:")?;
+            let owners = whoknows::workspace::owners(&parts);
+            write!(out, "{}", workspace_owner_table(&owners, args).render(table::terminal_width()))?;
+            let combined = whoknows::workspace::combined(&parts);
+            let files: usize = parts.iter().map(|part| part.report.files.len()).sum();
+            writeln!(out, "Total: {} lines in {} files of {} repositories, bus factor {}",
+                     combined.lines(), files, parts.len(), combined.bus_factor())?;
+        }
+        Format::Porcelain => {
+            let files: Vec<FileReport> = parts.into_iter().flat_map(|part| part.report.files).collect();
+            write!(out, "{}", whoknows::porcelain::v1(&files))?;
+        }
+        _ => return Err(Error::Usage("a workspace only has the table and porcelain formats".to_string())),
+    }
+    Ok(())
+}
+
+/// Each repository of a workspace with its bus factor and top owner
+fn member_table (parts: &[whoknows::workspace::Part], args: &Args) -> Table
+{
+    let mut table = Table::new();
+    table.column("Repository", Align::Left, Truncate::End)
+        .column("Files", Align::Right, Truncate::Never)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Bus factor", Align::Right, Truncate::Never)
+        .column("Top owner", Align::Left, Truncate::End)
+        .column("Share", Align::Right, Truncate::Never);
+    for part in parts
+    {
+        let combined = whoknows::workspace::combined(std::slice::from_ref(part));
+        let (owner, share) = match combined.ranked_owners().first() {
+            Some(owner) if owner.lines() > 0 => (
+                format!("{} <{}>", owner.name, args.shown_email(&owner.email)),
+                format!("{:.1}%", percent(owner.lines(), combined.lines())),
+            ),
+            _ => (String::new(), String::new()),
+        };
+        table.row(vec![
+            part.name.clone(),
+            part.report.files.len().to_string(),
+            human::count(combined.lines()),
+            combined.bus_factor().to_string(),
+            owner,
+            share,
+        ]);
+    }
+    table
+}
+
+/// Everyone owning lines of a workspace and the repositories they know
+fn workspace_owner_table (owners: &[whoknows::workspace::Owner], args: &Args) -> Table
+{
+    let total: usize = owners.iter().map(|owner| owner.lines).sum();
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Repositories", Align::Left, Truncate::End);
+    for owner in owners
+    {
+        table.row(vec![
+            owner.name.clone(),
+            args.shown_email(&owner.email),
+            human::count(owner.lines),
+            format!("{:.1}%", percent(owner.lines, total)),
+            owner.repositories.iter().cloned().collect::<Vec<String>>().join(", "),
+        ]);
+    }
+    table
+}
+
+/// Ownership in a Mercurial repository: the owners of files only, as the
+/// history, cache and configuration of the rest need git
+fn mercurial (out: &mut dyn Write, hg: &Mercurial, args: &Args) -> Result<(), Error>
+{
+    let paths = match &args.command {
+        None | Some(Command::File { .. }) => args.paths().to_vec(),
+        Some(Command::Repo) => vec![hg.root().to_path_buf()],
+        Some(_) => return Err(Error::Usage(format!("{} is in a {} repository, where only ownership of files \
+                                                    can be shown", hg.root().display(), hg.name()))),
+    };
+    let mut files = vcs::analyze(hg, &paths, &args.options().blame)?;
+    if args.anonymize {
+        files = files.iter().map(|file| anonymize::anonymize(file, args.salt())).collect();
+    }
+    match args.format() {
+        Format::Table => {
+            for file in &files
+            {
+                writeln!(out, "File: {}", file.path)?;
+                let table = owner_table(file, false, &args.config, None, false, false, None);
+                write!(out, "{}", table.render(table::terminal_width()))?;
+            }
+        }
+        Format::Porcelain => write!(out, "{}", whoknows::porcelain::v1(&files))?,
+        _ => return Err(Error::Usage(format!("in a {} repository only the table and porcelain formats work",
+                                             hg.name()))),
+    }
+    Ok(())
+}
+
+/// Why `path` has no repository to be analyzed in, mentioning the one
+/// the current directory is in if it does not contain `path`
+fn not_a_repository (path: &Path) -> Error
+{
+    let message = format!("{} is not in a git work tree", path.display());
+    let workdir = whoknows::discover(Path::new(".")).ok()
+        .and_then(|repo| repo.workdir().and_then(|dir| platform::canonicalize(dir).ok()));
+    match workdir {
+        Some(workdir) => Error::NotRepository(format!(
+            "{}; the current directory is in the repository at {}, which {} is outside of",
+            message, workdir.display(), path.display())),
+        None => Error::NotRepository(message),
+    }
+}
+
+fn config_command (out: &mut dyn Write, repo: &Repository, action: &ConfigAction, quiet: bool) -> Result<(), Error>
+{
+    match action {
+        ConfigAction::Show => {
+            let mut table = Table::new();
+            table.column("Key", Align::Left, Truncate::Never)
+                .column("Value", Align::Left, Truncate::End)
+                .column("Source", Align::Left, Truncate::End);
+            for setting in config::settings(repo)?
+            {
+                table.row(vec![setting.key, setting.value, setting.source]);
+            }
+            write!(out, "{}", table.render(table::terminal_width()))?;
+        }
+        ConfigAction::Set { user, key, value } => {
+            let path = if *user {
+                config::user_file().ok_or_else(|| Error::Config("no home directory for the user configuration".to_string()))?
+            } else {
+                repo.workdir()
+                    .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?
+                    .join(config::REPO_FILE)
+            };
+            config::set(&path, key, value)?;
+            if !quiet {
+                eprintln!("Set {} in {}", key, path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn format_size (bytes: u64) -> String
+{
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Who `whoknows::reviewed` credits with the lines of a file besides
+/// their authors
+type Credited = fn (&FileReport, &mut Commits, &whoknows::mailmap::Mailmap) -> Result<Vec<whoknows::reviewed::Reviewer>, Error>;
+
+fn print_tables (out: &mut dyn Write, repo: &Repository, report: &RepoReport, args: &Args) -> Result<(), Error>
+{
+    let credits = args.include_reviewers || args.include_signoffs;
+    let mut commits = if args.issues || args.topics || credits || args.verbose > 0 {
+        Some(Commits::new(repo, args.backend())?)
+    } else {
+        None
+    };
+    let mailmap = if credits { Some(whoknows::load_mailmap(repo, &args.options())?) } else { None };
+    let bases = match &args.vs {
+        Some(rev) => Some(bases(repo, report, args, rev)?),
+        None => None,
+    };
+    for file in &report.files
+    {
+        writeln!(out, "File: {}", args.shown_path(&file.path))?;
+        if !file.renamed_from.is_empty() {
+            writeln!(out, "Renamed from: {}", file.renamed_from.join(", "))?;
+        }
+        if file.lines() == 0 {
+            // A table of nobody says less than this
+            writeln!(out, "Empty file")?;
+        } else if args.split_tests {
+            let (source, tests) = split_tests(repo, file, args)?;
+            print_split(out, &source, &tests, args)?;
+        } else if args.complexity {
+            let mut weighed = FileReport::new(&file.path);
+            weighed.merge(file);
+            weigh(repo, file, args, &mut [&mut weighed])?;
+            let table = owner_table(&weighed, args.scored(), &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+            write!(out, "{}", table.render(table::terminal_width()))?;
+        } else {
+            let table = owner_table(file, args.decay.is_some(), &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+            write!(out, "{}", table.render(table::terminal_width()))?;
+        }
+        if args.explain && file.lines() > 0 && !args.split_tests {
+            print_explanation(out, repo, file, args)?;
+        }
+        if args.deleted {
+            print_deleted(out, repo, file, args)?;
+        }
+        if args.survival {
+            print_survival(out, repo, file, args)?;
+        }
+        if args.verbose > 0 && file.lines() > 0 {
+            writeln!(out, "Commits by quarter:")?;
+            write!(out, "{}", quarter_table(file).render(table::terminal_width()))?;
+            writeln!(out, "Line ages:")?;
+            write!(out, "{}", age_table(file, date::now()).render(table::terminal_width()))?;
+            if let Some(commits) = &mut commits {
+                writeln!(out, "Oldest lines:")?;
+                write!(out, "{}", oldest_table(file, commits, args)?.render(table::terminal_width()))?;
+            }
+            let renames = whoknows::renames::chain(repo, Path::new(&file.path), args.rev.as_deref().unwrap_or("HEAD"))?;
+            if !renames.is_empty() {
+                writeln!(out, "Renames:")?;
+                write!(out, "{}", rename_table(&renames, args).render(table::terminal_width()))?;
+            }
+        }
+        if let Some(roster) = &args.roster {
+            let departed = roster.departed_lines(file);
+            writeln!(out, "Departed: {} lines ({:.1}%)", departed, percent(departed, file.lines()))?;
+        }
+        if let (Some(bases), Some(rev)) = (&bases, &args.vs) {
+            print_differences(out, file, bases.get(&file.path), rev)?;
+        }
+        if let (Some(commits), Some(mailmap)) = (&mut commits, &mailmap) {
+            let lists = [
+                (args.include_reviewers, "Reviewed by:", whoknows::reviewed::reviewers as Credited),
+                (args.include_signoffs, "Shepherded by:", whoknows::reviewed::shepherds),
+            ];
+            for &(_, heading, credited) in lists.iter().filter(|(wanted, _, _)| *wanted)
+            {
+                let mut reviewers = credited(file, commits, mailmap)?;
+                if args.anonymize {
+                    for reviewer in &mut reviewers
+                    {
+                        let key = whoknows::owner_key(&reviewer.name, &reviewer.email).into_owned();
+                        (reviewer.name, reviewer.email) = anonymize::pseudonym(&key, args.salt());
+                    }
+                }
+                for reviewer in &mut reviewers
+                {
+                    reviewer.email = args.shown_email(&reviewer.email);
+                }
+                if !reviewers.is_empty() {
+                    writeln!(out, "{}", heading)?;
+                    write!(out, "{}", reviewed_table(&reviewers, file.lines()).render(table::terminal_width()))?;
+                }
+            }
+        }
+        if let (Some(commits), true) = (&mut commits, args.issues) {
+            let issues = issues::issues(file, commits)?;
+            if !issues.is_empty() {
+                writeln!(out, "Issues:")?;
+                write!(out, "{}", issue_table(&issues, file.lines()).render(table::terminal_width()))?;
+            }
+        }
+        if let (Some(commits), true) = (&mut commits, args.topics) {
+            let topics = topics::topics(file, commits, TOPICS)?;
+            if !topics.is_empty() {
+                writeln!(out, "Topics:")?;
+                write!(out, "{}", topic_table(&topics).render(table::terminal_width()))?;
+            }
+        }
+    }
+    if args.signatures {
+        let statuses = signatures::check(repo, &report.files)?;
+        let mut signers = signatures::signers(&report.files, &statuses);
+        for signer in &mut signers
+        {
+            if args.anonymize {
+                let key = whoknows::owner_key(&signer.name, &signer.email).into_owned();
+                (signer.name, signer.email) = anonymize::pseudonym(&key, args.salt());
+            }
+            signer.email = args.shown_email(&signer.email);
+        }
+        if !signers.is_empty() {
+            writeln!(out, "Signed commits:")?;
+            write!(out, "{}", signed_table(&signers).render(table::terminal_width()))?;
+        }
+    }
+    Ok(())
+}
+
+/// One table per path of `groups`, headed by `label`, for the files of
+/// `report` below it as if they were one file
+fn print_groups (
+    out: &mut dyn Write,
+    repo: &Repository,
+    report: &RepoReport,
+    args: &Args,
+    label: &str,
+    groups: &[PathBuf]
+) -> Result<(), Error>
+{
+    for group in groups
+    {
+        let relative = whoknows::repo_relative(repo, group)?;
+        let name = match relative.to_string_lossy() {
+            name if name.is_empty() => ".".to_string(),
+            name => name.into_owned(),
+        };
+        let mut merged = FileReport::new(&name);
+        let (mut source, mut tests) = (FileReport::new(&name), FileReport::new(&name));
+        for file in report.files.iter().filter(|file| Path::new(&file.path).starts_with(&relative))
+        {
+            if args.complexity && !args.split_tests {
+                let mut weighed = FileReport::new(&file.path);
+                weighed.merge(file);
+                weigh(repo, file, args, &mut [&mut weighed])?;
+                merged.merge(&weighed);
+            } else {
+                merged.merge(file);
+            }
+            if args.split_tests {
+                let (file_source, file_tests) = split_tests(repo, file, args)?;
+                source.merge(&file_source);
+                tests.merge(&file_tests);
+            }
+        }
+        writeln!(out, "{}: {}", label, name)?;
+        if args.split_tests {
+            print_split(out, &source, &tests, args)?;
+        } else {
+            let table = owner_table(&merged, args.scored(), &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+            write!(out, "{}", table.render(table::terminal_width()))?;
+        }
+        if let Some(roster) = &args.roster {
+            let departed = roster.departed_lines(&merged);
+            writeln!(out, "Departed: {} lines ({:.1}%)", departed, percent(departed, merged.lines()))?;
+        }
+    }
+    Ok(())
+}
+
+/// `file` as production code and as tests, see `testcode::split`
+fn split_tests (repo: &Repository, file: &FileReport, args: &Args) -> Result<(FileReport, FileReport), Error>
+{
+    let is_test = args.config.is_test(Path::new(&file.path));
+    let (mut source, mut tests) = whoknows::testcode::split(repo, file, is_test, &args.options())?;
+    if args.complexity {
+        weigh(repo, file, args, &mut [&mut source, &mut tests])?;
+    }
+    Ok((source, tests))
+}
+
+/// Score the owners of `parts` of `file` by the complexity of their lines
+fn weigh (repo: &Repository, file: &FileReport, args: &Args, parts: &mut [&mut FileReport]) -> Result<(), Error>
+{
+    let weights = Weights::of(repo, Path::new(&file.path), &args.options())?;
+    let now = date::now();
+    for part in parts
+    {
+        weights.apply(part, args.decay, now);
+    }
+    Ok(())
+}
+
+/// The owners of the production code and of the tests, each under its
+/// heading unless there is none of it
+fn print_split (out: &mut dyn Write, source: &FileReport, tests: &FileReport, args: &Args) -> Result<(), Error>
+{
+    for (heading, part) in [("Production code:", source), ("Tests:", tests)]
+    {
+        if part.lines() > 0 {
+            writeln!(out, "{}", heading)?;
+            let table = owner_table(part, args.scored(), &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+            write!(out, "{}", table.render(table::terminal_width()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Every file with its bus factor and the owner it depends on most,
+/// lowest bus factor, then most lines and then by path
+fn bus_factor_table (report: &RepoReport, args: &Args) -> Table
+{
+    let mut files: Vec<&FileReport> = report.files.iter().collect();
+    files.sort_by(|a, b| a.bus_factor().cmp(&b.bus_factor())
+                  .then_with(|| b.lines().cmp(&a.lines()))
+                  .then_with(|| a.path.cmp(&b.path)));
+    let mut table = Table::new();
+    table.column("Path", Align::Left, Truncate::End)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Bus factor", Align::Right, Truncate::Never)
+        .column("Top owner", Align::Left, Truncate::End)
+        .column("Share", Align::Right, Truncate::Never);
+    for file in files
+    {
+        let (owner, share) = match file.ranked_owners().first() {
+            Some(owner) => (owner.name.clone(), format!("{:.1}%", percent(owner.lines(), file.lines()))),
+            None => (String::new(), String::new()),
+        };
+        table.row(vec![args.shown_path(&file.path), human::count(file.lines()), file.bus_factor().to_string(), owner, share]);
+    }
+    table
+}
+
+/// Files by risk, highest first, with what the score is made of
+fn risk_table (risks: &[whoknows::risk::Risk], args: &Args) -> Table
+{
+    let mut table = Table::new();
+    table.column("Path", Align::Left, Truncate::End)
+        .column("Risk", Align::Right, Truncate::Never)
+        .column("Changes", Align::Right, Truncate::Never)
+        .column("Top owner", Align::Right, Truncate::Never)
+        .column("Inactive", Align::Right, Truncate::Never);
+    for risk in risks
+    {
+        table.row(vec![
+            args.shown_path(&risk.path),
+            format!("{:.1}", risk.score),
+            risk.churn.to_string(),
+            format!("{:.1}%", risk.concentration * 100.0),
+            format!("{:.1}%", risk.inactivity * 100.0),
+        ]);
+    }
+    table
+}
+
+/// The timezones of the owners of every file, those owned from a single
+/// timezone first
+fn timezone_table (files: &[FileZones], args: &Args) -> Table
+{
+    let mut files: Vec<&FileZones> = files.iter().filter(|file| file.lines() > 0).collect();
+    files.sort_by_key(|file| !file.single_zone());
+    let mut table = Table::new();
+    table.column("Path", Align::Left, Truncate::End)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Timezones", Align::Right, Truncate::Never)
+        .column("Largest", Align::Left, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Single", Align::Left, Truncate::Never);
+    for file in files
+    {
+        let (offset, lines) = file.zones[0];
+        table.row(vec![
+            args.shown_path(&file.path),
+            human::count(file.lines()),
+            file.zones.len().to_string(),
+            timezone::format_offset(offset),
+            format!("{:.1}%", percent(lines, file.lines())),
+            if file.single_zone() { "yes" } else { "" }.to_string(),
+        ]);
+    }
+    table
+}
+
+/// Every owner with their timezone and local working hours, east first
+fn owner_zone_table (owners: &HashMap<String, OwnerZone>) -> Table
+{
+    let mut owners: Vec<&OwnerZone> = owners.values().collect();
+    owners.sort_by(|a, b| b.offset.cmp(&a.offset).then_with(|| a.email.cmp(&b.email)));
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Timezone", Align::Left, Truncate::Never)
+        .column("Hours", Align::Left, Truncate::Never);
+    for owner in owners
+    {
+        table.row(vec![
+            owner.name.clone(),
+            owner.email.clone(),
+            timezone::format_offset(owner.offset),
+            format!("{:02}-{:02}", owner.hours.0, owner.hours.1 + 1),
+        ]);
+    }
+    table
+}
+
+/// Files depending on one owner, riskiest first, with whom to pair them
+/// with and the closest file that person owns lines of
+fn pairing_table (pairings: &[whoknows::pairing::Pairing], args: &Args) -> Table
+{
+    let mut table = Table::new();
+    table.column("Path", Align::Left, Truncate::End)
+        .column("Owner", Align::Left, Truncate::End)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Pair with", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Knows", Align::Left, Truncate::End);
+    for pairing in pairings
+    {
+        let (name, email, closest) = match &pairing.candidate {
+            Some(candidate) => (candidate.name.clone(), candidate.email.clone(), candidate.closest.clone()),
+            None => ("nobody".to_string(), String::new(), String::new()),
+        };
+        table.row(vec![
+            args.shown_path(&pairing.path),
+            pairing.owner.clone(),
+            format!("{:.1}%", pairing.share * 100.0),
+            name,
+            email,
+            if closest.is_empty() { closest } else { args.shown_path(&closest) },
+        ]);
+    }
+    table
+}
+
+/// Every directory with files in `report`, in the order of their paths,
+/// with the bus factor and top owner of all lines below it
+fn directory_table (report: &RepoReport) -> Table
+{
+    let mut table = Table::new();
+    table.column("Directory", Align::Left, Truncate::End)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Bus factor", Align::Right, Truncate::Never)
+        .column("Top owner", Align::Left, Truncate::Email)
+        .column("Share", Align::Right, Truncate::Never);
+    for dir in whoknows::directory::directories(&report.files)
+    {
+        let (owner, share) = match dir.owners.iter().max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0))) {
+            Some((owner, &lines)) => (owner.clone(), format!("{:.1}%", percent(lines, dir.lines()))),
+            None => (String::new(), String::new()),
+        };
+        table.row(vec![dir.path.clone(), human::count(dir.lines()), dir.bus_factor().to_string(), owner, share]);
+    }
+    table
+}
+
+/// Each team with the lines of `total` its members own
+fn team_table (teams: &[whoknows::teams::Team], total: usize) -> Table
+{
+    let mut table = Table::new();
+    table.column("Team", Align::Left, Truncate::End)
+        .column("Owners", Align::Right, Truncate::Never)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Files", Align::Right, Truncate::Never)
+        .column("Leads", Align::Right, Truncate::Never);
+    for team in teams
+    {
+        table.row(vec![
+            team.name.clone(),
+            team.owners.to_string(),
+            human::count(team.lines),
+            format!("{:.1}%", percent(team.lines, total)),
+            team.files.to_string(),
+            team.led.to_string(),
+        ]);
+    }
+    table
+}
+
+/// Each package with its bus factor and the email of its top owner
+fn package_table (packages: &[whoknows::package::Package]) -> Table
+{
+    let mut table = Table::new();
+    table.column("Package", Align::Left, Truncate::End)
+        .column("Kind", Align::Left, Truncate::Never)
+        .column("Path", Align::Left, Truncate::End)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Bus factor", Align::Right, Truncate::Never)
+        .column("Top owner", Align::Left, Truncate::Email)
+        .column("Share", Align::Right, Truncate::Never);
+    for package in packages
+    {
+        let (owner, share) = match package.owners.iter().max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0))) {
+            Some((owner, &lines)) => (owner.clone(), format!("{:.1}%", percent(lines, package.lines()))),
+            None => (String::new(), String::new()),
+        };
+        table.row(vec![
+            package.name.clone(),
+            package.kind.to_string(),
+            package.path.clone(),
+            human::count(package.lines()),
+            package.bus_factor().to_string(),
+            owner,
+            share,
+        ]);
+    }
+    table
+}
+
+/// Every owner in `report`, most lines first, with the share of their
+/// lines in each language
+fn language_table (repo: &Repository, report: &RepoReport) -> Table
+{
+    let workdir = repo.workdir().unwrap_or_else(|| Path::new("."));
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Languages", Align::Left, Truncate::End);
+    for owner in language::breakdown(workdir, &report.files)
+    {
+        let languages: Vec<String> = owner.languages.iter()
+            .map(|(language, lines)| format!("{} {:.1}%", language, percent(*lines, owner.lines)))
+            .collect();
+        table.row(vec![owner.name, owner.email, human::count(owner.lines), languages.join(", ")]);
+    }
+    table
+}
+
+/// A CODEOWNERS line for every file, naming the fewest owners who own
+/// more than half of it by their GitHub login if configured, otherwise by
+/// email
+fn codeowners (report: &RepoReport, config: &Config) -> String
+{
+    let mut text = String::from("# Generated by git whoknows from who owns most of the lines\n");
+    for file in &report.files
+    {
+        let owners: Vec<String> = file.ranked_owners().iter()
+            .take(file.bus_factor())
+            .map(|owner| match config.github.user(&owner.email) {
+                Some(login) => format!("@{}", login),
+                None => owner.email.clone(),
+            })
+            .collect();
+        if !owners.is_empty() {
+            text.push_str(&format!("/{} {}\n", file.path.replace(' ', "\\ "), owners.join(" ")));
+        }
+    }
+    text
+}
+
+/// Print the author and commit of the line at `location`, `path:line`,
+/// and the top owners of its file
+fn line (out: &mut dyn Write, repo: &Repository, args: &Args, location: &str) -> Result<(), Error>
+{
+    let (path, number) = location.rsplit_once(':')
+        .and_then(|(path, number)| Some((path, number.parse::<usize>().ok().filter(|n| *n > 0)?)))
+        .ok_or_else(|| Error::Usage(format!("'{}' is not <path>:<line>", location)))?;
+    let path = whoknows::repo_relative(repo, Path::new(path))?;
+    whoknows::ensure_tracked(repo, &path)?;
+    let options = args.options();
+    let region = whoknows::blame_regions(repo, &path, &options)?.into_iter()
+        .find(|r| (r.line..r.line + r.lines).contains(&number))
+        .ok_or_else(|| Error::Path(format!("{} has no line {}", path.display(), number)))?;
+
+    writeln!(out, "{}:{}", args.shown_path(&path.display().to_string()), number)?;
+    writeln!(out, "Author:  {} <{}>", region.name, args.shown_email(&region.email))?;
+    if region.commit.is_zero() {
+        writeln!(out, "Commit:  not committed yet")?;
+    } else {
+        let commit = repo.find_commit(region.commit)?;
+        writeln!(out, "Commit:  {}", args.hash(region.commit, usize::MAX))?;
+        writeln!(out, "Date:    {}", human::datetime(region.time))?;
+        writeln!(out, "Summary: {}", commit.summary().unwrap_or(""))?;
+    }
+
+    let mut file = whoknows::analyze_file(repo, &path, &options)?;
+    args.show_emails(&mut file);
+    writeln!(out, "\nTop owners of {}:", args.shown_path(&file.path))?;
+    let table = owner_table(&file, args.decay.is_some(), &args.config, args.roster.as_ref(), args.roles, args.expertise, Some(LINE_OWNERS));
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+/// Print the owners of the lines `rev` changed, by file and in total
+fn commit (out: &mut dyn Write, repo: &Repository, args: &Args, rev: &str) -> Result<(), Error>
+{
+    let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+    writeln!(out, "Commit {}: {}", args.hash(commit.id(), usize::MAX), commit.summary().unwrap_or(""))?;
+    let mut files = reviewers::changed_by(repo, commit.id(), &args.options())?;
+    files.retain(|file| !args.config.excluded(Path::new(&file.path)));
+    let changed: Vec<_> = reviewers::rank(&files).into_iter().map(|r| (args.shown_reviewer(r), None)).collect();
+    for file in &mut files
+    {
+        args.show_emails(file);
+        writeln!(out, "File: {}", args.shown_path(&file.path))?;
+        let table = owner_table(file, false, &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+        write!(out, "{}", table.render(table::terminal_width()))?;
+    }
+    if changed.is_empty() {
+        writeln!(out, "Changed nobody's code")?;
+    } else {
+        writeln!(out, "\nChanged the code of:")?;
+        write!(out, "{}", reviewer_table(&changed, None).render(table::terminal_width()))?;
+    }
+    Ok(())
+}
+
+/// Print the owners of the lines of the function `name` in `path` and
+/// everyone who changed it before
+fn symbol (out: &mut dyn Write, repo: &Repository, args: &Args, name: &str, path: &Path) -> Result<(), Error>
+{
+    let path = whoknows::repo_relative(repo, path)?;
+    whoknows::ensure_tracked(repo, &path)?;
+    let rev = args.rev.clone().unwrap_or_else(|| "HEAD".to_string());
+    let (first, count) = whoknows::symbol::lines(repo, name, &path, &rev)?;
+    writeln!(out, "Function {} in {}, lines {}-{}", name, path.display(), first, first + count - 1)?;
+    let history = whoknows::symbol::history(repo, name, &path, &rev, !args.no_mailmap)?;
+    print_region(out, repo, args, &path, &rev, (first, count), history)
+}
+
+/// Print the owners of the lines `range` of `path` and everyone who
+/// changed them before
+fn history (out: &mut dyn Write, repo: &Repository, args: &Args, range: &str, path: &Path) -> Result<(), Error>
+{
+    let path = whoknows::repo_relative(repo, path)?;
+    whoknows::ensure_tracked(repo, &path)?;
+    let rev = args.rev.clone().unwrap_or_else(|| "HEAD".to_string());
+    let (first, count) = whoknows::symbol::range_lines(repo, range, &path, &rev)?;
+    writeln!(out, "Lines {}-{} of {}", first, first + count - 1, path.display())?;
+    let history = whoknows::symbol::range_history(repo, range, &path, &rev, !args.no_mailmap)?;
+    print_region(out, repo, args, &path, &rev, (first, count), history)
+}
+
+/// Print the owners of `count` lines of `path` from `first` on as of
+/// `rev`, and `history`
+fn print_region (
+    out: &mut dyn Write,
+    repo: &Repository,
+    args: &Args,
+    path: &Path,
+    rev: &str,
+    (first, count): (usize, usize),
+    history: Vec<whoknows::symbol::Author>
+) -> Result<(), Error>
+{
+    let mut options = args.options();
+    options.blame.rev = Some(rev.to_string());
+
+    let mut owners = FileReport::new(&path.to_string_lossy());
+    for region in whoknows::blame_regions(repo, path, &options)?
+    {
+        let start = region.line.max(first);
+        let end = (region.line + region.lines).min(first + count);
+        if start < end {
+            owners.add_hunk(&blame::Hunk {
+                commit: region.commit,
+                name: &region.name,
+                email: &region.email,
+                time: region.time,
+                lines: end - start,
+                origin: None,
+            });
+        }
+    }
+    args.show_emails(&mut owners);
+    writeln!(out, "Owners now:")?;
+    let table = owner_table(&owners, false, &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+    write!(out, "{}", table.render(table::terminal_width()))?;
+
+    writeln!(out, "Changed by:")?;
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Commits", Align::Right, Truncate::Never)
+        .column("Added", Align::Right, Truncate::Never)
+        .column("Removed", Align::Right, Truncate::Never)
+        .column("Last change", Align::Left, Truncate::Never);
+    for author in history
+    {
+        table.row(vec![
+            author.name,
+            args.shown_email(&author.email),
+            author.commits.to_string(),
+            human::count(author.added),
+            author.removed.to_string(),
+            human::date(author.last),
+        ]);
+    }
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+/// Print who wrote the lines of `path` that the commits of `range` removed,
+/// and who removed them
+fn gone (out: &mut dyn Write, repo: &Repository, args: &Args, range: &str, path: &Path) -> Result<(), Error>
+{
+    let (from, to) = match range.split_once("..") {
+        Some((from, to)) => (from, if to.is_empty() { "HEAD" } else { to }),
+        None => return Err(Error::Usage(format!("'{}' is not a range of commits as <rev>..<rev>", range))),
+    };
+    let start = repo.revparse_single(from)?.peel_to_commit()?;
+    let end = repo.revparse_single(to)?.peel_to_commit()?.id();
+    // The file may be long gone from the work tree
+    let path = whoknows::path_at(repo, path, from)?
+        .ok_or_else(|| Error::Path(format!("{} is not in {}", path.display(), from)))?;
+    let gone = whoknows::gone::gone(repo, &path, start.id(), end)?;
+    if gone.is_empty() {
+        writeln!(out, "All of {} as of {} is still in {}", path.display(), from, to)?;
+        return Ok(());
+    }
+
+    let mut removals = HashMap::new();
+    for run in &gone
+    {
+        if let Entry::Vacant(entry) = removals.entry(run.last_seen) {
+            entry.insert(whoknows::gone::removal(repo, &path, run.last_seen, end, !args.no_mailmap)?);
+        }
+    }
+
+    // Whoever wrote the lines, blamed as of the start of the range
+    let mut options = args.options();
+    options.blame.rev = Some(start.id().to_string());
+    let regions = whoknows::blame_regions(repo, &path, &options)?;
+
+    let mut written = FileReport::new(&path.to_string_lossy());
+    let mut removed = FileReport::new(&path.to_string_lossy());
+    // Lines from one commit removed by one commit, first and past the last
+    let mut runs: Vec<(usize, usize, &whoknows::Region, Option<&whoknows::gone::Removal>)> = Vec::new();
+    for run in &gone
+    {
+        let removal = removals.get(&run.last_seen).and_then(Option::as_ref);
+        for region in &regions
+        {
+            let first = region.line.max(run.line);
+            let end = (region.line + region.lines).min(run.line + run.lines);
+            if first >= end {
+                continue;
+            }
+            written.add_hunk(&blame::Hunk {
+                commit: region.commit,
+                name: &region.name,
+                email: &region.email,
+                time: region.time,
+                lines: end - first,
+                origin: None,
+            });
+            if let Some(removal) = removal {
+                removed.add_hunk(&blame::Hunk {
+                    commit: removal.commit,
+                    name: &removal.name,
+                    email: &removal.email,
+                    time: removal.time,
+                    lines: end - first,
+                    origin: None,
+                });
+            }
+            match runs.last_mut() {
+                Some((_, last, by, removed_by)) if *last == first && by.commit == region.commit
+                    && removed_by.map(|r| r.commit) == removal.map(|r| r.commit) => *last = end,
+                _ => runs.push((first, end, region, removal)),
+            }
+        }
+    }
+
+    let mut table = Table::new();
+    table.column("Lines", Align::Left, Truncate::Never)
+        .column("Written by", Align::Left, Truncate::End)
+        .column("Written", Align::Left, Truncate::Never)
+        .column("Removed by", Align::Left, Truncate::End)
+        .column("Removed", Align::Left, Truncate::Never)
+        .column("Commit", Align::Left, Truncate::Never)
+        .column("Summary", Align::Left, Truncate::End);
+    for (first, end, region, removal) in runs
+    {
+        table.row(vec![
+            if end - first == 1 { first.to_string() } else { format!("{}-{}", first, end - 1) },
+            region.name.clone(),
+            human::date(region.time),
+            removal.map_or("?".to_string(), |r| r.name.clone()),
+            removal.map_or(String::new(), |r| human::date(r.time)),
+            removal.map_or(String::new(), |r| args.hash(r.commit, SHORT_HASH)),
+            removal.map_or(String::new(), |r| r.summary.clone()),
+        ]);
+    }
+
+    args.show_emails(&mut written);
+    args.show_emails(&mut removed);
+    writeln!(out, "Lines of {} as of {} gone by {}:", path.display(), from, to)?;
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    writeln!(out, "Written by:")?;
+    let table = owner_table(&written, false, &args.config, args.roster.as_ref(), args.roles, args.expertise, None);
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    writeln!(out, "Removed by:")?;
+    let table = owner_table(&removed, false, &args.config, args.roster.as_ref(), false, false, None);
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+/// Print who added or removed code matching `pattern`, most commits first
+fn grep (out: &mut dyn Write, repo: &Repository, args: &Args, pattern: &str, regex: bool, max: usize) -> Result<(), Error>
+{
+    let rev = args.rev.as_deref().unwrap_or("HEAD");
+    let authors = whoknows::pickaxe::search(repo, pattern, regex, rev, !args.no_mailmap)?;
+    if authors.is_empty() {
+        writeln!(out, "No commit changed code matching '{}'", pattern)?;
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Commits", Align::Right, Truncate::Never)
+        .column("Files", Align::Right, Truncate::Never)
+        .column("Last change", Align::Left, Truncate::Never);
+    for author in authors.into_iter().take(max)
+    {
+        table.row(vec![
+            author.name,
+            args.shown_email(&author.email),
+            author.commits.to_string(),
+            author.files.to_string(),
+            human::date(author.last),
+        ]);
+    }
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+/// Print the identities that are likely one person as .mailmap lines to
+/// review, each group after a comment telling why and with how many
+/// commits
+fn identities (out: &mut dyn Write, repo: &Repository, args: &Args) -> Result<(), Error>
+{
+    // Mailmap lines are nothing without the emails they map
+    if args.hides_emails() {
+        return Err(Error::Usage("identities suggests mailmap lines, which need the emails that \
+                                 --redact-emails and hide_emails leave out".to_string()));
+    }
+    let rev = args.rev.as_deref().unwrap_or("HEAD");
+    let mailmap = whoknows::load_mailmap(repo, &args.options())?;
+    let clusters = whoknows::identities::suggest(repo, rev, &mailmap)?;
+    if clusters.is_empty() {
+        writeln!(out, "# No authors seem to have committed under several identities")?;
+        return Ok(());
+    }
+    for (i, cluster) in clusters.iter().enumerate()
+    {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        let commits: Vec<String> = std::iter::once(&cluster.canonical).chain(&cluster.others)
+            .map(|identity| format!("{} <{}> {}", identity.name, identity.email, identity.commits))
+            .collect();
+        writeln!(out, "# {}: {}", cluster.reasons.join(", "), commits.join(", "))?;
+        for line in cluster.mailmap_lines()
+        {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// The revision a diff of `range` starts from and the paths on that side
+/// of it, which leaves out added files
+fn diff_files (repo: &Repository, range: Option<&str>) -> Result<(String, Vec<PathBuf>), Error>
+{
+    let tree = |rev: &str| -> Result<git2::Tree, Error> {
+        Ok(repo.revparse_single(if rev.is_empty() { "HEAD" } else { rev })?.peel_to_tree()?)
+    };
+    let range = range.unwrap_or("HEAD");
+    let (base, diff) = if let Some((from, to)) = range.split_once("...") {
+        let from = repo.revparse_single(if from.is_empty() { "HEAD" } else { from })?.id();
+        let to_id = repo.revparse_single(if to.is_empty() { "HEAD" } else { to })?.id();
+        let base = repo.merge_base(from, to_id)?.to_string();
+        let diff = repo.diff_tree_to_tree(Some(&tree(&base)?), Some(&tree(to)?), None)?;
+        (base, diff)
+    } else if let Some((from, to)) = range.split_once("..") {
+        let from = if from.is_empty() { "HEAD" } else { from };
+        (from.to_string(), repo.diff_tree_to_tree(Some(&tree(from)?), Some(&tree(to)?), None)?)
+    } else {
+        (range.to_string(), repo.diff_tree_to_workdir_with_index(Some(&tree(range)?), None)?)
+    };
+
+    let mut paths = Vec::new();
+    for delta in diff.deltas()
+    {
+        if delta.status() != git2::Delta::Added && delta.status() != git2::Delta::Untracked {
+            paths.extend(delta.old_file().path().map(Path::to_path_buf));
+        }
+    }
+    Ok((base, paths))
+}
+
+/// The files of `report` as of `rev`, by path, leaving out those it does
+/// not have
+fn bases (repo: &Repository, report: &RepoReport, args: &Args, rev: &str) -> Result<HashMap<String, FileReport>, Error>
+{
+    let tree = repo.revparse_single(rev)?.peel_to_tree()?;
+    let paths: Vec<PathBuf> = report.files.iter()
+        .map(|file| PathBuf::from(&file.path))
+        .filter(|path| tree.get_path(path).is_ok())
+        .collect();
+    let mut options = args.options();
+    options.blame.rev = Some(rev.to_string());
+    let mut bases = Analyzer::new(repo).options(options).files(&paths, &whoknows::Silent)?;
+    for file in &mut bases.files
+    {
+        args.show_emails(file);
+    }
+    Ok(bases.files.into_iter().map(|file| (file.path.clone(), file)).collect())
+}
+
+/// How the owners of `file` differ from those of `base`, the same file at
+/// `rev`
+fn print_differences (out: &mut dyn Write, file: &FileReport, base: Option<&FileReport>, rev: &str) -> Result<(), Error>
+{
+    let base = match base {
+        Some(base) => base,
+        None => return Ok(writeln!(out, "Versus {}: new file", rev)?),
+    };
+    let differences = file.differences(base);
+    if differences.is_empty() {
+        return Ok(writeln!(out, "Versus {}: same ownership", rev)?);
+    }
+    writeln!(out, "Versus {}: bus factor {} -> {}", rev, base.bus_factor(), file.bus_factor())?;
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Before", Align::Right, Truncate::Never)
+        .column("After", Align::Right, Truncate::Never)
+        .column("Change", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never);
+    for difference in differences
+    {
+        let share = percent(difference.after, file.lines()) - percent(difference.before, base.lines());
+        table.row(vec![
+            difference.name.clone(),
+            difference.email.clone(),
+            difference.before.to_string(),
+            difference.after.to_string(),
+            format!("{:+}", difference.change()),
+            format!("{:+.1}%", share),
+        ]);
+    }
+    write!(out, "{}", table.render(table::terminal_width()))?;
+    Ok(())
+}
+
+fn issue_table (issues: &[Issue], total: usize) -> Table
+{
+    let mut table = Table::new();
+    table.column("Issue", Align::Left, Truncate::Never)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Authors", Align::Left, Truncate::End);
+    for issue in issues
+    {
+        let authors: Vec<String> = issue.authors.iter()
+            .map(|(name, lines)| format!("{} ({})", name, lines))
+            .collect();
+        table.row(vec![
+            issue.key.clone(),
+            human::count(issue.lines),
+            format!("{:.1}%", percent(issue.lines, total)),
+            authors.join(", "),
+        ]);
+    }
+    table
+}
+
+/// Every owner with their topics
+fn topic_table (topics: &[Topics]) -> Table
+{
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Topics", Align::Left, Truncate::End);
+    for owner in topics
+    {
+        table.row(vec![owner.name.clone(), owner.email.clone(), owner.words.join(", ")]);
+    }
+    table
+}
+
+/// Authors with the share of their lines from signed commits
+fn signed_table (signers: &[signatures::Signer]) -> Table
+{
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Signed", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Unchecked", Align::Right, Truncate::Never);
+    for signer in signers
+    {
+        table.row(vec![
+            signer.name.clone(),
+            signer.email.clone(),
+            human::count(signer.lines),
+            human::count(signer.signed),
+            format!("{:.1}%", percent(signer.signed, signer.lines)),
+            human::count(signer.unchecked),
+        ]);
+    }
+    table
+}
+
+/// Reviewers with the lines of the commits they reviewed out of `total`
+fn reviewed_table (reviewers: &[whoknows::reviewed::Reviewer], total: usize) -> Table
+{
+    let mut table = Table::new();
+    table.column("Name", Align::Left, Truncate::End)
+        .column("Email", Align::Left, Truncate::Email)
+        .column("Lines", Align::Right, Truncate::Never)
+        .column("Share", Align::Right, Truncate::Never)
+        .column("Commits", Align::Right, Truncate::Never);
+    for reviewer in reviewers
+    {
+        table.row(vec![
+            reviewer.name.clone(),
+            reviewer.email.clone(),
+            human::count(reviewer.lines),
+            format!("{:.1}%", percent(reviewer.lines, total)),
+            reviewer.commits.to_string(),
+        ]);
+    }
+    table
+}
+
+/// The name of the directory of `repo`
+fn repo_name (repo: &Repository) -> String
+{
+    repo.workdir().unwrap_or_else(|| repo.path())
+        .file_name().map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The totals of `report` followed by its riskiest files, its directories
+/// and its top owners, the tables `width` columns wide
+fn summary (repo: &Repository, report: &RepoReport, args: &Args, width: Option<usize>) -> Result<String, Error>
+{
+    let mailmap = whoknows::load_mailmap(repo, &args.options())?;
+    let mut risks = whoknows::risk::assess(repo, &report.files, args.rev.as_deref(), &mailmap, date::now())?;
+    risks.truncate(REPORT_TOP);
+    let mut merged = FileReport::new(".");
+    for file in &report.files
+    {
+        merged.merge(file);
+    }
+
+    let mut summary = format!("Knowledge report for {}\n\n", repo_name(repo));
+    summary.push_str(footer(repo, report, report.files.len(), args).trim_start());
+    summary.push_str("\nRiskiest files:\n");
+    summary.push_str(&risk_table(&risks, args).render(width));
+    summary.push_str("\nDirectories:\n");
+    summary.push_str(&directory_table(report).render(width));
+    summary.push_str("\nTop owners:\n");
+    let owners = owner_table(&merged, args.scored(), &args.config, args.roster.as_ref(), args.roles, args.expertise, Some(REPORT_TOP));
+    summary.push_str(&owners.render(width));
+    Ok(summary)
+}
+
+/// Totals over all analyzed files plus when and at which revision they
+/// were analyzed, out of `requested` files
+fn footer (repo: &Repository, report: &RepoReport, requested: usize, args: &Args) -> String
+{
+    let revision = match (report.revision, repo.head()) {
+        (Some(oid), Ok(head)) => {
+            let short = args.hash(oid, SHORT_HASH);
+            match head.shorthand() {
+                Some(name) if head.is_branch() && head.target() == Some(oid) => format!("{} ({})", short, name),
+                _ => short,
+            }
+        }
+        (Some(oid), Err(_)) => args.hash(oid, SHORT_HASH),
+        (None, _) => "no commits".to_string(),
+    };
+
+    let mut footer = format!("\nTotal: {}, {}, {}, bus factor {}\n\
+                              Analyzed {} at {}\n",
+                             human::counted(report.lines(), "line"),
+                             human::counted(report.owners().len(), "author"),
+                             human::counted(report.commits(), "commit"),
+                             report.bus_factor(),
+                             date::format_datetime(date::now()),
+                             revision);
+    if let Some(risks) = risks(report, &args.config) {
+        footer.push_str(&format!("Risks: {}\n", risks));
+    }
+    if let Some(roster) = &args.roster {
+        let departed = report.files.iter().map(|f| roster.departed_lines(f)).sum::<usize>();
+        footer.push_str(&format!("Departed: {} lines ({:.1}%) owned by people no longer around\n",
+                                 human::count(departed), percent(departed, report.lines())));
+    }
+    if report.partial {
+        footer.push_str(&format!("Partial: interrupted after {} of {} files\n",
+                                 report.files.len(), requested));
+    }
+    footer
+}
+
+/// The selected metrics as tab separated `metric path author value` lines,
+/// with `-` as the author of values about the whole file
+fn measurements (names: &[String], report: &RepoReport, args: &Args) -> Result<String, Error>
+{
+    let mut metrics = Metrics::builtin();
+    if !names.is_empty() {
+        let known = metrics.names();
+        if let Some(unknown) = names.iter().find(|n| !known.contains(&n.as_str())) {
+            return Err(Error::Usage(format!("unknown metric '{}', expected one of {}",
+                                            unknown, known.join(", "))));
+        }
+        metrics.retain(names);
+    }
+
+    let mut out = String::new();
+    for m in metrics.run(report, &Context { now: date::now() })
+    {
+        let author = match &m.subject {
+            Subject::File => "-",
+            Subject::Author(email) => email,
+        };
+        out.push_str(&format!("{}\t{}\t{}\t{}\n", m.metric, args.shown_path(&m.path), author, m.value));
+    }
+    Ok(out)
+}
+
+/// List the files beyond `limits` or, where they do not say, the
+/// thresholds of the configuration, and write them to `junit` as JUnit XML
+/// if given; whether there were none beyond the thresholds that fail
+fn check (out: &mut dyn Write, repo: &Repository, args: &Args, limits: &Thresholds, junit: Option<&Path>) -> Result<bool, Error>
+{
+    if limits.is_empty() && !args.config.has_thresholds() {
+        return Err(Error::Usage("check needs --min-bus-factor, --max-single-owner, their --warn-* \
+                                 counterparts, --require-owner or thresholds in the configuration".to_string()));
+    }
+    let paths = files_to_analyze(repo, args)?;
+    let required = |path: &PathBuf| {
+        let mut thresholds = args.config.thresholds_for(path);
+        thresholds.override_with(limits);
+        thresholds.require_owner.is_some()
+    };
+    if args.roster.is_none() && paths.iter().any(required) {
+        return Err(Error::Usage("requiring an owner needs the --roster of who is still around".to_string()));
+    }
+    if args.dry_run {
+        print_plan(out, repo, &paths, args)?;
+        return Ok(true);
+    }
+    let progress = Progress::new(paths.len(), !args.quiet && paths.len() > 1, args.progress.unwrap_or(ProgressFormat::Bar));
+    let report = Analyzer::new(repo).options(args.options()).files(&paths, &progress);
+    progress.clear();
+    let report = report?;
+
+    let results: Vec<(&str, Vec<String>, Vec<String>)> = report.files.iter()
+        .map(|file| {
+            let roster = args.roster.as_ref();
+            (file.path.as_str(), violations(file, args, limits, roster), warnings(file, args, limits))
+        })
+        .collect();
+    for (path, violations, warnings) in &results
+    {
+        for violation in violations
+        {
+            writeln!(out, "{}: error: {}", args.shown_path(path), violation)?;
+        }
+        for warning in warnings
+        {
+            writeln!(out, "{}: warning: {}", args.shown_path(path), warning)?;
+        }
+    }
+    let failed = results.iter().filter(|(_, violations, _)| !violations.is_empty()).count();
+    let warned = results.iter().filter(|(_, violations, warnings)| violations.is_empty() && !warnings.is_empty()).count();
+    writeln!(out, "Checked {}: {} beyond the thresholds, {} beyond the warning thresholds",
+             human::counted(results.len(), "file"), failed, warned)?;
+    if let Some(junit) = junit {
+        std::fs::write(junit, junit::report(&results))?;
+    }
+    Ok(failed == 0)
+}
+
+/// How `file` crosses its thresholds, the configured ones overridden by
+/// `limits`; an owner is only required with a `roster` to find them on
+fn violations (file: &FileReport, args: &Args, limits: &Thresholds, roster: Option<&Roster>) -> Vec<String>
+{
+    let mut thresholds = args.config.thresholds_for(Path::new(&file.path));
+    thresholds.override_with(limits);
+    let mut violations = breaches(file, &thresholds, args);
+    if let (Some(min), Some(roster)) = (thresholds.require_owner, roster) {
+        let known = file.owners.values().any(|owner| roster.is_active(owner) && percent(owner.lines(), file.lines()) >= min);
+        if !known && file.lines() > 0 {
+            violations.push(format!("nobody on the roster owns {}% or more", min));
+        }
+    }
+    violations
+}
+
+/// How `file` crosses the thresholds that only warn, where it does not
+/// already cross the same thresholds that fail
+fn warnings (file: &FileReport, args: &Args, limits: &Thresholds) -> Vec<String>
+{
+    let mut thresholds = args.config.thresholds_for(Path::new(&file.path));
+    thresholds.override_with(limits);
+    let mut warnings = thresholds.warnings();
+    if thresholds.bus_factor_too_low(file) {
+        warnings.min_bus_factor = None;
+    }
+    if thresholds.single_owner_too_high(file) {
+        warnings.max_single_owner = None;
+    }
+    breaches(file, &warnings, args)
+}
+
+/// How `file` crosses the limits of `thresholds`
+fn breaches (file: &FileReport, thresholds: &Thresholds, args: &Args) -> Vec<String>
+{
+    let mut breaches = Vec::new();
+    if thresholds.bus_factor_too_low(file) {
+        breaches.push(format!("bus factor {} is below {}",
+                              file.bus_factor(), thresholds.min_bus_factor.unwrap_or(0)));
+    }
+    if thresholds.single_owner_too_high(file) {
+        if let Some(owner) = file.ranked_owners().first() {
+            breaches.push(format!("{} <{}> owns {:.1}%, more than {}%",
+                                  owner.name, args.shown_email(&owner.email), percent(owner.lines(), file.lines()),
+                                  thresholds.max_single_owner.unwrap_or(0.0)));
+        }
+    }
+    breaches
+}
+
+/// `path:line: owner (share)` for every run of at least
+/// `QUICKFIX_MIN_LINES` lines written by one author, for the quickfix list
+/// of an editor
+fn quickfix (repo: &Repository, report: &RepoReport, args: &Args) -> Result<String, Error>
+{
+    let opts = args.options();
+    let mut out = String::new();
+    for file in &report.files
+    {
+        let mut runs: Vec<(usize, usize, String)> = Vec::new();
+        for region in whoknows::blame_regions(repo, Path::new(&file.path), &opts)?
+        {
+            let key = whoknows::owner_key(&region.name, &region.email).into_owned();
+            match runs.last_mut() {
+                Some((_, lines, owner)) if *owner == key => *lines += region.lines,
+                _ => runs.push((region.line, region.lines, key)),
+            }
+        }
+        for (line, lines, key) in runs.into_iter().filter(|(_, lines, _)| *lines >= QUICKFIX_MIN_LINES)
+        {
+            // Owners the options leave out are nobody's risk
+            if let Some(owner) = file.owners.get(&key) {
+                out.push_str(&format!("{}:{}: {} <{}> ({:.1}%): {} lines by one author\n",
+                                      args.shown_path(&file.path), line, owner.name, owner.email,
+                                      percent(owner.lines(), file.lines()), lines));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// How many files cross the thresholds for their path, if any are
+/// configured
+fn risks (report: &RepoReport, config: &Config) -> Option<String>
+{
+    if !config.has_thresholds() {
+        return None;
+    }
+    let (mut low, mut high) = (0, 0);
+    for file in &report.files
+    {
+        let thresholds = config.thresholds_for(Path::new(&file.path));
+        low += thresholds.bus_factor_too_low(file) as usize;
+        high += thresholds.single_owner_too_high(file) as usize;
+    }
+    Some(format!("{} files with too low a bus factor, {} files with too large a single owner",
+                 low, high))
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet (out: &mut dyn Write, files: &[FileReport], args: &Args) -> Result<(), Error>
+{
+    let label = |file: &FileReport, owner: &whoknows::Owner| args.config.expertise.of(owner, file).to_string();
+    let label: Option<whoknows::parquet::Label> = if args.expertise { Some(&label) } else { None };
+    whoknows::parquet::write_files(out, files, label)?;
+    Ok(())
+}
+
+#[cfg(feature = "serve")]
+fn serve (repo: &Repository, listen: &str, args: &Args) -> Result<(), Error>
+{
+    serve::serve(repo, listen, args.options(), args.email_shown(), args.quiet)
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve (_repo: &Repository, _listen: &str, _args: &Args) -> Result<(), Error>
+{
+    Err(Error::Usage("this build does not include the HTTP server, \
+                      rebuild with --features serve".to_string()))
+}
+
+#[cfg(not(feature = "forges"))]
+fn without_forges () -> Result<(), Error>
+{
+    Err(Error::Usage("this build does not include GitHub, GitLab and Gerrit support, \
+                      rebuild with --features forges".to_string()))
+}
+
+#[cfg(not(feature = "forges"))]
+fn github_reviewers (_: &mut dyn Write, _: &Repository, _: &Args, _: &str, _: u64, _: bool, _: usize) -> Result<(), Error>
+{
+    without_forges()
+}
+
+#[cfg(not(feature = "forges"))]
+fn github_teams (_: &str, _: &RepoReport, _: &Args) -> Result<BTreeMap<String, Vec<String>>, Error>
+{
+    without_forges().map(|()| BTreeMap::new())
+}
+
+#[cfg(not(feature = "forges"))]
+#[allow(clippy::too_many_arguments)]
+fn gitlab_reviewers (_: &mut dyn Write, _: &Repository, _: &Args, _: &str, _: u64, _: bool, _: bool, _: usize) -> Result<(), Error>
+{
+    without_forges()
+}
+
+#[cfg(not(feature = "forges"))]
+fn gerrit_reviewers (_: &mut dyn Write, _: &Repository, _: &Args, _: Option<&str>, _: &str, _: bool, _: usize) -> Result<(), Error>
+{
+    without_forges()
+}
+
+/// `e` as `{"error": {"code": .., "kind": .., "path": .., "message": ..}}`,
+/// the path being null unless the error is about one file
+fn error_json (e: &Error) -> Value
+{
+    Value::object(vec![("error", Value::object(vec![
+        ("code", Value::Number(e.exit_code() as f64)),
+        ("kind", e.kind().into()),
+        ("path", e.path().map_or(Value::Null, Into::into)),
+        ("message", e.message().into()),
+    ]))])
+}
+
+fn main() {
+    platform::enable_ansi();
+    let mut args = match Args::clap().get_matches_safe() {
+        Ok(matches) => {
+            let mut args = Args::from_clap(&matches);
+            args.given = SCOPES.iter().map(|&(name, ..)| name).filter(|name| given(&matches, name)).collect();
+            args
+        }
+        // --help and --version go to stdout and succeed
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            eprintln!("{}", e.message);
+            std::process::exit(2);
+        }
+    };
+    let mut out = Output::stdout();
+    let result = run(&mut args, &mut out);
+    let result = match (result, out.finish()) {
+        // Only what a run that went through wrote is copied, failed checks
+        // included
+        (Ok(passed), Ok(Some(kept))) => {
+            let format = args.copy_format.unwrap_or(CopyFormat::Markdown);
+            clipboard::copy(&kept, format, args.quiet).map(|()| passed)
+        }
+        (Ok(passed), written) => written.map(|_| passed),
+        (Err(e), _) => Err(e),
+    };
+    if interrupt::interrupted() {
+        std::process::exit(130);
+    }
+    if let Err(e) = result {
+        // Whatever reads the report stopping early, as head does, is not
+        // worth an error
+        if matches!(&e, Error::Io(e) if e.kind() == std::io::ErrorKind::BrokenPipe) {
+            std::process::exit(0);
+        }
+        match args.errors.unwrap_or(ErrorFormat::Text) {
+            ErrorFormat::Text => eprintln!("error: {}", e),
+            ErrorFormat::Json => eprintln!("{}", error_json(&e)),
+        }
+        std::process::exit(e.exit_code());
+    }
+    if result.is_ok_and(|passed| !passed) {
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,138 @@
+//! A manual page in roff, made from the help of the command line
+//!
+//! clap 2 has no generator for manual pages, so the page is put together
+//! from the help text of the command and of every subcommand, which stays
+//! the single description of the options.
+
+use structopt::clap::{App, ErrorKind};
+
+const NAME: &str = "git-whoknows";
+
+/// The whole manual page of `app`, in section 1
+pub fn page (app: App) -> String
+{
+    let mut roff = format!(".TH {} 1 \"\" \"{} {}\" \"Git Manual\"\n",
+                           NAME.to_uppercase(), NAME, env!("CARGO_PKG_VERSION"));
+    let top = Help::parse(&help(&app, &[]));
+    roff.push_str(&format!(".SH NAME\n{} \\- {}\n", escape(NAME), escape(&top.about.join(" "))));
+    roff.push_str(&format!(".SH SYNOPSIS\n.nf\n{}\n.fi\n", escape(&top.usage.join("\n"))));
+    top.write_sections(&mut roff, ".SH");
+
+    let subcommands: Vec<String> = top.entries("SUBCOMMANDS").map(|(name, _)| name.to_string()).collect();
+    if !subcommands.is_empty() {
+        roff.push_str(".SH COMMANDS\n");
+    }
+    for name in subcommands.iter().filter(|name| *name != "help")
+    {
+        subcommand(&app, &[name], &mut roff);
+    }
+    roff
+}
+
+/// The part of the page about the subcommand at `path`, and those below it
+fn subcommand (app: &App, path: &[&str], roff: &mut String)
+{
+    let sub = Help::parse(&help(app, path));
+    roff.push_str(&format!(".SS {} {}\n{}\n", escape(NAME), escape(&path.join(" ")), escape(&sub.about.join(" "))));
+    roff.push_str(&format!(".PP\n.nf\n{}\n.fi\n", escape(&sub.usage.join("\n"))));
+    sub.write_sections(roff, ".PP\n.B");
+    for (name, _) in sub.entries("SUBCOMMANDS").filter(|(name, _)| *name != "help")
+    {
+        let mut path = path.to_vec();
+        path.push(name);
+        subcommand(app, &path, roff);
+    }
+}
+
+/// What `--help` prints for the subcommand at `path`
+fn help (app: &App, path: &[&str]) -> String
+{
+    let mut argv = vec![NAME];
+    argv.extend(path);
+    argv.push("--help");
+    match app.clone().set_term_width(0).get_matches_from_safe(argv) {
+        Err(e) if e.kind == ErrorKind::HelpDisplayed => e.message,
+        _ => String::new(),
+    }
+}
+
+/// The help of one command, in the sections clap prints
+struct Help {
+    about: Vec<String>,
+    usage: Vec<String>,
+    /// Section name, then its lines without indentation
+    sections: Vec<(String, Vec<String>)>,
+}
+
+impl Help {
+    fn parse (text: &str) -> Help
+    {
+        let mut help = Help { about: Vec::new(), usage: Vec::new(), sections: Vec::new() };
+        // The first line is the name and version
+        for line in text.lines().skip(1)
+        {
+            if !line.starts_with(' ') && line.ends_with(':') {
+                help.sections.push((line.trim_end_matches(':').to_string(), Vec::new()));
+            } else if line.trim().is_empty() {
+                continue;
+            } else {
+                match help.sections.last_mut() {
+                    Some((_, lines)) => lines.push(line.trim().to_string()),
+                    None => help.about.push(line.trim().to_string()),
+                }
+            }
+        }
+        if let Some(i) = help.sections.iter().position(|(name, _)| name == "USAGE") {
+            help.usage = help.sections.remove(i).1;
+        }
+        help
+    }
+
+    /// Name and description of every entry of `section`
+    fn entries<'h> (&'h self, section: &'h str) -> impl Iterator<Item = (&'h str, &'h str)>
+    {
+        self.sections.iter()
+            .filter(move |(name, _)| name == section)
+            .flat_map(|(_, lines)| lines.iter())
+            .map(|line| match line.find("  ") {
+                Some(i) => (&line[..i], line[i..].trim()),
+                None => (line.as_str(), ""),
+            })
+    }
+
+    /// Every section but the subcommands, each entry a tagged paragraph
+    fn write_sections (&self, roff: &mut String, heading: &str)
+    {
+        for (name, _) in self.sections.iter().filter(|(name, _)| name != "SUBCOMMANDS")
+        {
+            roff.push_str(&format!("{} {}\n", heading, name));
+            for (spec, description) in self.entries(name)
+            {
+                roff.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", escape(spec), escape(&hide_env_value(description))));
+            }
+        }
+    }
+}
+
+/// `[env: NAME=value]` without the value, which is whatever happened to be
+/// set when the page was made
+fn hide_env_value (description: &str) -> String
+{
+    match (description.find("[env: "), description.rfind(']')) {
+        (Some(start), Some(end)) if start < end => {
+            let name = description[start + 6..end].split('=').next().unwrap_or("");
+            format!("{}[env: {}]{}", &description[..start], name, &description[end + 1..])
+        }
+        _ => description.to_string(),
+    }
+}
+
+/// `text` safe to put in a roff line of text
+fn escape (text: &str) -> String
+{
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    escaped.lines()
+        .map(|line| if line.starts_with('.') || line.starts_with('\'') { format!("\\&{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,204 @@
+//! Pluggable analyses over blamed files
+//!
+//! A `Metric` looks at one file at a time, with its owners and the commits
+//! their lines survive from, and emits named values for the file or for
+//! its authors. `Metrics` runs every registered metric over each file in a
+//! single pass, so new analyses do not need their own walk over the data.
+
+use crate::{FileReport, RepoReport};
+
+/// Seconds in a day
+const DAY: f64 = 86_400.0;
+
+/// What a value is about
+#[derive(Clone, Debug, PartialEq)]
+pub enum Subject {
+    File,
+    /// An author of the file, by email
+    Author(String),
+}
+
+/// One value produced by a metric
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    pub metric: String,
+    pub path: String,
+    pub subject: Subject,
+    pub value: f64,
+}
+
+/// Shared facts about the run handed to every metric
+pub struct Context {
+    /// Seconds since the epoch the analysis is done as of
+    pub now: i64,
+}
+
+/// Collects the values of one metric for one file
+pub struct Sink<'a> {
+    metric: &'a str,
+    path: &'a str,
+    out: &'a mut Vec<Measurement>,
+}
+
+impl<'a> Sink<'a> {
+    pub fn file (&mut self, value: f64)
+    {
+        self.push(Subject::File, value);
+    }
+
+    pub fn author (&mut self, email: &str, value: f64)
+    {
+        self.push(Subject::Author(email.to_string()), value);
+    }
+
+    fn push (&mut self, subject: Subject, value: f64)
+    {
+        self.out.push(Measurement {
+            metric: self.metric.to_string(),
+            path: self.path.to_string(),
+            subject,
+            value,
+        });
+    }
+}
+
+pub trait Metric: Sync {
+    /// Name the values are reported under
+    fn name (&self) -> &str;
+
+    /// Emit the values for `file`
+    fn measure (&self, file: &FileReport, ctx: &Context, out: &mut Sink);
+}
+
+/// The registered metrics
+#[derive(Default)]
+pub struct Metrics {
+    metrics: Vec<Box<dyn Metric>>,
+}
+
+impl Metrics {
+    pub fn new () -> Metrics
+    {
+        Metrics::default()
+    }
+
+    /// The metrics that ship with the crate
+    pub fn builtin () -> Metrics
+    {
+        let mut metrics = Metrics::new();
+        metrics.register(Lines)
+            .register(Share)
+            .register(BusFactor)
+            .register(Age);
+        metrics
+    }
+
+    pub fn register<M: Metric + 'static> (&mut self, metric: M) -> &mut Metrics
+    {
+        self.metrics.push(Box::new(metric));
+        self
+    }
+
+    pub fn names (&self) -> Vec<&str>
+    {
+        self.metrics.iter().map(|m| m.name()).collect()
+    }
+
+    /// Keep only the metrics named in `names`
+    pub fn retain (&mut self, names: &[String])
+    {
+        self.metrics.retain(|m| names.iter().any(|n| n == m.name()));
+    }
+
+    /// Run every metric over every file of `report`
+    pub fn run (&self, report: &RepoReport, ctx: &Context) -> Vec<Measurement>
+    {
+        let mut out = Vec::new();
+        for file in &report.files
+        {
+            for metric in &self.metrics
+            {
+                let mut sink = Sink { metric: metric.name(), path: &file.path, out: &mut out };
+                metric.measure(file, ctx, &mut sink);
+            }
+        }
+        out
+    }
+}
+
+/// Lines owned by each author
+pub struct Lines;
+
+impl Metric for Lines {
+    fn name (&self) -> &str
+    {
+        "lines"
+    }
+
+    fn measure (&self, file: &FileReport, _ctx: &Context, out: &mut Sink)
+    {
+        for owner in file.ranked_owners()
+        {
+            out.author(&owner.email, owner.lines() as f64);
+        }
+    }
+}
+
+/// Percentage of the file owned by each author
+pub struct Share;
+
+impl Metric for Share {
+    fn name (&self) -> &str
+    {
+        "share"
+    }
+
+    fn measure (&self, file: &FileReport, _ctx: &Context, out: &mut Sink)
+    {
+        let total = file.lines();
+        for owner in file.ranked_owners()
+        {
+            out.author(&owner.email, crate::percent(owner.lines(), total));
+        }
+    }
+}
+
+/// Fewest authors owning more than half of the file
+pub struct BusFactor;
+
+impl Metric for BusFactor {
+    fn name (&self) -> &str
+    {
+        "bus_factor"
+    }
+
+    fn measure (&self, file: &FileReport, _ctx: &Context, out: &mut Sink)
+    {
+        out.file(file.bus_factor() as f64);
+    }
+}
+
+/// Average age in days of the lines each author owns
+pub struct Age;
+
+impl Metric for Age {
+    fn name (&self) -> &str
+    {
+        "age_days"
+    }
+
+    fn measure (&self, file: &FileReport, ctx: &Context, out: &mut Sink)
+    {
+        for owner in file.ranked_owners()
+        {
+            let lines = owner.lines();
+            if lines == 0 {
+                continue;
+            }
+            let seconds: f64 = owner.commits.values()
+                .map(|c| (ctx.now - c.time).max(0) as f64 * c.lines as f64)
+                .sum();
+            out.author(&owner.email, seconds / DAY / lines as f64);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Commits that changed a lot of lines without anyone learning about them
+//!
+//! Formatter runs and codemods rewrite whole files, which makes whoever ran
+//! them the owner of everything they touched. A commit counts as noise when
+//! its summary says it is a formatting or codemod sweep, or when it rewrote
+//! nearly every line of the files it modified while leaving their length
+//! about the same.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, Delta, DiffOptions, Oid, Repository};
+
+use crate::error::Error;
+use crate::regex::Regex;
+
+/// Summaries of formatting and codemod commits
+const MESSAGES: &str = "(?i)(rustfmt|cargo fmt|clang-format|gofmt|goimports|prettier|\
+                        autopep8|yapf|run black|apply black|isort|reformat|codemod|\
+                        format code|formatting only|fix formatting|whitespace only)";
+
+/// Share of the old lines of the modified files a commit must replace
+const CHURN: f64 = 0.9;
+
+/// Smallest number of old lines for the churn heuristic, below which a
+/// rewrite is as likely to be real work
+const MIN_CHURN_LINES: usize = 50;
+
+/// Why a commit was found to be noise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    Message,
+    Churn,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Message => write!(f, "message"),
+            Reason::Churn => write!(f, "churn"),
+        }
+    }
+}
+
+pub struct Noise {
+    pub oid: Oid,
+    pub reason: Reason,
+    pub summary: String,
+}
+
+/// The noise commits in the history of `rev`, or of HEAD, newest first
+pub fn detect (repo: &Repository, rev: Option<&str>) -> Result<Vec<Noise>, Error>
+{
+    let messages = Regex::new(MESSAGES).expect("noise patterns are valid");
+    let start = repo.revparse_single(rev.unwrap_or("HEAD"))?.peel_to_commit()?.id();
+
+    let mut walk = repo.revwalk()?;
+    walk.push(start)?;
+    let mut noise = Vec::new();
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        // A merge only changes what its parents disagreed on
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let summary = commit.summary().unwrap_or("").to_string();
+        let reason = if messages.is_match(&summary) {
+            Reason::Message
+        } else if is_churn(repo, &commit)? {
+            Reason::Churn
+        } else {
+            continue;
+        };
+        noise.push(Noise { oid: commit.id(), reason, summary });
+    }
+    Ok(noise)
+}
+
+/// Whether `commit` replaced nearly all lines of the files it modified
+fn is_churn (repo: &Repository, commit: &Commit) -> Result<bool, Error>
+{
+    let parent = match commit.parents().next() {
+        Some(parent) => parent.tree()?,
+        None => return Ok(false),
+    };
+    let diff = repo.diff_tree_to_tree(Some(&parent), Some(&commit.tree()?),
+                                      Some(DiffOptions::new().ignore_submodules(true)))?;
+
+    let (mut old_lines, mut deleted, mut added) = (0, 0, 0);
+    for (i, delta) in diff.deltas().enumerate()
+    {
+        if delta.status() != Delta::Modified {
+            continue;
+        }
+        let blob = repo.find_blob(delta.old_file().id())?;
+        if blob.is_binary() {
+            continue;
+        }
+        old_lines += blob.content().iter().filter(|&&b| b == b'\n').count();
+        if let Some(patch) = git2::Patch::from_diff(&diff, i)? {
+            let (_, additions, deletions) = patch.line_stats()?;
+            added += additions;
+            deleted += deletions;
+        }
+    }
+    if old_lines < MIN_CHURN_LINES {
+        return Ok(false);
+    }
+    let size_kept = (added as f64 - deleted as f64).abs() <= (1.0 - CHURN) * deleted as f64;
+    Ok(deleted as f64 >= CHURN * old_lines as f64 && size_kept)
+}
+
+/// Write `noise`, followed by the contents of `extra` if given, as a file
+/// for `git blame --ignore-revs-file` at `path`
+pub fn write_ignore_revs (path: &Path, noise: &[Noise], extra: Option<&PathBuf>) -> Result<(), Error>
+{
+    let mut text = String::new();
+    for commit in noise
+    {
+        text.push_str(&format!("# {}: {}\n{}\n", commit.reason, commit.summary, commit.oid));
+    }
+    if let Some(extra) = extra {
+        text.push_str(&fs::read_to_string(extra)?);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! Telling a chat channel about files that became a knowledge risk
+//!
+//! Each notifying run remembers the bus factor of every file in
+//! `.git/whoknows-cache/notified`, and the next one only speaks up about
+//! files that have dropped below their minimum since, so that a weekly run
+//! reads as "what got worse this week".
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use git2::Repository;
+
+use whoknows::cache::Cache;
+use whoknows::config::Config;
+use whoknows::json::Value;
+use whoknows::{date, percent, Error, FileReport, RepoReport};
+
+use crate::http::Request;
+
+/// Name of the state file in the cache directory
+const STATE: &str = "notified";
+
+/// Bus factor below which a file is a risk when no threshold says
+/// otherwise
+const MIN_BUS_FACTOR: usize = 2;
+
+/// A Slack incoming webhook payload
+const TEMPLATE: &str = r#"{"text": "{{text}}"}"#;
+
+/// Bus factors by path as of a time
+struct State {
+    time: i64,
+    bus_factors: BTreeMap<String, usize>,
+}
+
+impl State {
+    fn load (path: &Path) -> Option<State>
+    {
+        let text = fs::read_to_string(path).ok()?;
+        let mut lines = text.lines();
+        let time = lines.next()?.parse().ok()?;
+        let bus_factors = lines
+            .filter_map(|line| line.split_once('\t'))
+            .filter_map(|(bus_factor, path)| Some((path.to_string(), bus_factor.parse().ok()?)))
+            .collect();
+        Some(State { time, bus_factors })
+    }
+
+    fn store (&self, path: &Path) -> Result<(), Error>
+    {
+        let mut text = format!("{}\n", self.time);
+        for (file, bus_factor) in &self.bus_factors
+        {
+            text.push_str(&format!("{}\t{}\n", bus_factor, file));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Post the files of `report` whose bus factor dropped below their
+/// minimum since the previous notification to the webhook at `url`, as
+/// `template` or a Slack message
+pub fn notify (
+    repo: &Repository,
+    url: &str,
+    template: Option<&Path>,
+    report: &RepoReport,
+    config: &Config,
+    quiet: bool
+) -> Result<(), Error>
+{
+    let path = Cache::new(repo).dir().join(STATE);
+    let previous = State::load(&path);
+    let min_bus_factor = |file: &FileReport| {
+        config.thresholds_for(Path::new(&file.path)).min_bus_factor.unwrap_or(MIN_BUS_FACTOR)
+    };
+    let dropped: Vec<&FileReport> = report.files.iter()
+        .filter(|file| file.bus_factor() < min_bus_factor(file))
+        .filter(|file| {
+            let before = previous.as_ref().and_then(|p| p.bus_factors.get(&file.path));
+            before.is_none_or(|&before| before >= min_bus_factor(file))
+        })
+        .collect();
+
+    if dropped.is_empty() {
+        if !quiet {
+            eprintln!("notify: no file dropped to a low bus factor");
+        }
+    } else {
+        let template = match template {
+            Some(template) => fs::read_to_string(template)?,
+            None => TEMPLATE.to_string(),
+        };
+        let since = previous.as_ref().map(|p| p.time);
+        Request::post_json(url, payload(&template, repo, &dropped, since)).send()?;
+        if !quiet {
+            eprintln!("notify: posted {} files", dropped.len());
+        }
+    }
+
+    let mut state = previous.unwrap_or(State { time: 0, bus_factors: BTreeMap::new() });
+    state.time = date::now();
+    for file in &report.files
+    {
+        state.bus_factors.insert(file.path.clone(), file.bus_factor());
+    }
+    state.store(&path)
+}
+
+/// `template` with `{{text}}`, `{{files}}`, `{{count}}`, `{{repo}}` and
+/// `{{since}}` replaced by their values, escaped to go inside a JSON string
+fn payload (template: &str, repo: &Repository, files: &[&FileReport], since: Option<i64>) -> String
+{
+    let name = repo.workdir().unwrap_or_else(|| repo.path())
+        .file_name().map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let since_text = match since {
+        Some(time) => format!(" since {}", date::format_date(time)),
+        None => String::new(),
+    };
+
+    let mut list = String::new();
+    for file in files
+    {
+        let owner = match file.ranked_owners().first() {
+            Some(owner) => format!(", mostly {} ({:.0}%)", owner.name, percent(owner.lines(), file.lines())),
+            None => String::new(),
+        };
+        list.push_str(&format!("• {}: bus factor {}{}\n", file.path, file.bus_factor(), owner));
+    }
+    let text = format!("{} files in {} dropped to a low bus factor{}:\n{}",
+                       files.len(), name, since_text, list);
+
+    template
+        .replace("{{text}}", &escape(text.trim_end()))
+        .replace("{{files}}", &escape(list.trim_end()))
+        .replace("{{count}}", &files.len().to_string())
+        .replace("{{repo}}", &escape(&name))
+        .replace("{{since}}", &since.map(date::format_date).unwrap_or_default())
+}
+
+/// `s` as the inside of a JSON string
+fn escape (s: &str) -> String
+{
+    let quoted = Value::from(s).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
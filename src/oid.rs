@@ -0,0 +1,68 @@
+//! Commit ids, those of libgit2 with the `git` feature
+//!
+//! Without that feature nothing reads a repository: commit ids only come
+//! from blame output and are compared and written back, for which an array
+//! of bytes does, and libgit2, which builds for no wasm32 target, is left
+//! out.
+
+#[cfg(feature = "git")]
+pub use git2::Oid;
+
+#[cfg(not(feature = "git"))]
+pub use self::standalone::Oid;
+
+#[cfg(not(feature = "git"))]
+mod standalone {
+    use std::fmt;
+
+    use crate::error::Error;
+
+    /// The id of a commit, as many bytes as libgit2 keeps
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+    pub struct Oid([u8; 20]);
+
+    impl Oid {
+        /// Parse a hex id, which like libgit2 takes a prefix of one as
+        /// the id padded with zeros
+        #[allow(clippy::should_implement_trait)]
+        pub fn from_str (hex: &str) -> Result<Oid, Error>
+        {
+            let invalid = || Error::Blame(format!("invalid commit id '{}'", hex));
+            if hex.is_empty() || hex.len() > 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            let mut bytes = [0; 20];
+            for (i, digit) in hex.bytes().enumerate()
+            {
+                let value = (digit as char).to_digit(16).ok_or_else(invalid)? as u8;
+                bytes[i / 2] |= if i % 2 == 0 { value << 4 } else { value };
+            }
+            Ok(Oid(bytes))
+        }
+
+        pub fn zero () -> Oid
+        {
+            Oid([0; 20])
+        }
+
+        pub fn is_zero (&self) -> bool
+        {
+            self.0 == [0; 20]
+        }
+
+        pub fn as_bytes (&self) -> &[u8]
+        {
+            &self.0
+        }
+    }
+
+    impl fmt::Display for Oid {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for byte in &self.0
+            {
+                write!(f, "{:02x}", byte)?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Where reports are written
+//!
+//! Reports go to stdout, or to the file of `--output`, through `gzip` or
+//! `zstd` for a name ending in `.gz` or `.zst`, so that every format lands
+//! there as it would on the terminal. Repository-wide data of a monorepo
+//! easily runs to hundreds of megabytes, which CI artifacts are better off
+//! without. What is written can be kept as well, for `--copy`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use whoknows::Error;
+
+/// What reports are written to, stdout unless `redirect` says otherwise
+pub struct Output {
+    sink: Sink,
+    /// Everything written since `keep`
+    kept: Option<Vec<u8>>,
+}
+
+enum Sink {
+    Stdout(io::Stdout),
+    File(BufWriter<File>),
+    Compressed(Compressor),
+}
+
+/// A compressor writing to a file, waited for when dropped so that the
+/// file is complete however the run ends
+struct Compressor {
+    tool: &'static str,
+    path: PathBuf,
+    child: Child,
+    /// Closed by `finish` to let the compressor finish
+    stdin: Option<BufWriter<ChildStdin>>,
+}
+
+/// The command compressing to a file named `path`, if its extension asks
+/// for one
+fn compressor (path: &Path) -> Option<(&'static str, &'static [&'static str])>
+{
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => Some(("gzip", &["-c"])),
+        Some("zst") => Some(("zstd", &["-q", "-c"])),
+        _ => None,
+    }
+}
+
+impl Output {
+    pub fn stdout () -> Output
+    {
+        Output { sink: Sink::Stdout(io::stdout()), kept: None }
+    }
+
+    /// Write to `path` from now on instead
+    pub fn redirect (&mut self, path: &Path) -> Result<(), Error>
+    {
+        self.flush()?;
+        let file = File::create(path)
+            .map_err(|e| Error::Export(format!("cannot create {}: {}", path.display(), e)))?;
+        self.sink = match compressor(path) {
+            Some((tool, args)) => {
+                let mut child = Command::new(tool).args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(file)
+                    .spawn()
+                    .map_err(|e| Error::Export(format!("cannot run {} for {}: {}", tool, path.display(), e)))?;
+                let stdin = child.stdin.take().map(BufWriter::new);
+                Sink::Compressed(Compressor { tool, path: path.to_path_buf(), child, stdin })
+            }
+            None => Sink::File(BufWriter::new(file)),
+        };
+        Ok(())
+    }
+
+    /// Keep everything written from now on, see `finish`
+    pub fn keep (&mut self)
+    {
+        self.kept = Some(Vec::new());
+    }
+
+    /// Flush what is left and wait for the file to be complete; what was
+    /// written since `keep`, if it was called
+    pub fn finish (&mut self) -> Result<Option<Vec<u8>>, Error>
+    {
+        self.flush()?;
+        if let Sink::Compressed(compressor) = &mut self.sink {
+            compressor.finish()?;
+        }
+        Ok(self.kept.take())
+    }
+}
+
+impl Write for Output {
+    fn write (&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        let written = match &mut self.sink {
+            Sink::Stdout(stdout) => stdout.write(buf)?,
+            Sink::File(file) => file.write(buf)?,
+            Sink::Compressed(compressor) => match &mut compressor.stdin {
+                Some(stdin) => stdin.write(buf)?,
+                None => return Err(io::Error::new(io::ErrorKind::BrokenPipe, "output already finished")),
+            },
+        };
+        if let Some(kept) = &mut self.kept {
+            kept.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush (&mut self) -> io::Result<()>
+    {
+        match &mut self.sink {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::File(file) => file.flush(),
+            Sink::Compressed(compressor) => compressor.stdin.as_mut().map_or(Ok(()), Write::flush),
+        }
+    }
+}
+
+impl Compressor {
+    /// Close the input of the compressor and wait for it to exit
+    fn finish (&mut self) -> Result<(), Error>
+    {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(Error::Export(format!("{} failed with {} writing {}",
+                                             self.tool, status, self.path.display())));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Compressor {
+    fn drop (&mut self)
+    {
+        let _ = self.finish();
+    }
+}
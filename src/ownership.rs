@@ -0,0 +1,628 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::blame::Hunk;
+use crate::date;
+use crate::mailmap::Mailmap;
+use crate::oid::Oid;
+
+/// Seconds in a day
+const DAY: f64 = 86_400.0;
+
+/// Who owns how many lines of one file
+#[derive(Clone)]
+pub struct FileReport {
+    pub path: String,
+    /// Owners by email, see `owner_key`
+    pub owners: HashMap<String, Owner>,
+    /// Other paths the file had in commits its lines survive from, where
+    /// blame followed it across a rename
+    pub renamed_from: Vec<String>,
+    /// The lines of the file in order, as blamed; empty for reports put
+    /// together from totals, such as snapshots and merged reports
+    pub spans: Vec<Span>,
+}
+
+/// A run of consecutive lines of a file last changed by the same commit
+#[derive(Clone, Copy)]
+pub struct Span {
+    /// Its first line, counting from 1
+    pub start: usize,
+    pub lines: usize,
+    pub commit: Oid,
+}
+
+impl FileReport {
+    pub fn new (path: &str) -> FileReport
+    {
+        FileReport {
+            path: path.to_string(),
+            owners: HashMap::new(),
+            renamed_from: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Add a hunk of blame, which comes after those added before it
+    pub fn add_blamed (&mut self, hunk: &Hunk)
+    {
+        if hunk.lines == 0 {
+            return;
+        }
+        let start = self.spans.last().map_or(1, |span| span.start + span.lines);
+        self.spans.push(Span { start, lines: hunk.lines, commit: hunk.commit });
+        self.add_hunk(hunk);
+    }
+
+    /// The owner of `line`, counting from 1; `None` past the end, for
+    /// lines of commits left out of the report and without `spans`
+    pub fn owner_of (&self, line: usize) -> Option<&Owner>
+    {
+        let i = self.spans.partition_point(|span| span.start + span.lines <= line);
+        let span = self.spans.get(i).filter(|span| span.start <= line)?;
+        self.owner_by_commit(span.commit)
+    }
+
+    /// The owners of `lines`, counting from 1, by runs of lines last
+    /// changed by the same commit, in order; runs are cut to `lines` and
+    /// those `owner_of` has no owner for are left out
+    pub fn owners_of (&self, lines: RangeInclusive<usize>) -> Vec<(RangeInclusive<usize>, &Owner)>
+    {
+        let first = self.spans.partition_point(|span| span.start + span.lines <= *lines.start());
+        self.spans[first..].iter()
+            .take_while(|span| span.start <= *lines.end())
+            .filter(|span| span.lines > 0)
+            .filter_map(|span| {
+                let run = span.start.max(*lines.start())..=(span.start + span.lines - 1).min(*lines.end());
+                Some((run, self.owner_by_commit(span.commit)?))
+            })
+            .collect()
+    }
+
+    fn owner_by_commit (&self, commit: Oid) -> Option<&Owner>
+    {
+        self.owners.values().find(|owner| owner.commits.contains_key(&commit))
+    }
+
+    /// Add the lines of a hunk of blame; one without any, as libgit2 has
+    /// for an empty file, owns nothing and makes nobody an owner
+    pub fn add_hunk (
+        &mut self,
+        commit: &Hunk
+    )
+    {
+        if commit.lines == 0 {
+            return;
+        }
+        if let Some(origin) = commit.origin {
+            if origin != self.path && !self.renamed_from.iter().any(|path| path == origin) {
+                self.renamed_from.push(origin.to_string());
+            }
+        }
+        // Only allocate the first time an author turns up
+        let key = owner_key(commit.name, commit.email);
+        match self.owners.get_mut(key.as_ref()) {
+            Some(owner) => owner.add_hunk(commit),
+            None => {
+                let mut owner = Owner::new(commit);
+                owner.add_hunk(commit);
+                self.owners.insert(key.into_owned(), owner);
+            }
+        }
+    }
+
+    pub fn lines (&self) -> usize
+    {
+        self.owners.values().map(Owner::lines).sum::<usize>()
+    }
+
+    /// Owners ordered by score, largest first; without decay the score is
+    /// the number of lines owned. Ties go by lines, then name and email, so
+    /// that owners keep their place from one run to the next
+    pub fn ranked_owners (&self) -> Vec<&Owner>
+    {
+        let mut owners: Vec<&Owner> = self.owners.values().collect();
+        owners.sort_by(|a, b| b.score.partial_cmp(&a.score)
+                       .unwrap_or(std::cmp::Ordering::Equal)
+                       .then_with(|| b.lines().cmp(&a.lines()))
+                       .then_with(|| a.name.cmp(&b.name))
+                       .then_with(|| a.email.cmp(&b.email)));
+        owners
+    }
+
+    /// Add the ownership of `other`, as if its lines were part of this file
+    pub fn merge (&mut self, other: &FileReport)
+    {
+        for (email, owner) in sorted(&other.owners)
+        {
+            let merged = self.owners.entry(email.clone()).or_insert_with(|| Owner {
+                name: owner.name.clone(),
+                email: owner.email.clone(),
+                commits: HashMap::new(),
+                score: 0.0,
+            });
+            for (oid, contribution) in &owner.commits
+            {
+                merged.commits.entry(*oid)
+                    .or_insert(Contribution { lines: 0, time: contribution.time })
+                    .lines += contribution.lines;
+            }
+            merged.score += owner.score;
+        }
+    }
+
+    /// The same ownership with every identity replaced by its canonical
+    /// one, merging owners that turn out to be the same person
+    ///
+    /// An owner merged from identities of several names goes by the name
+    /// the mailmap gives, else by the one with the most lines and else by
+    /// the first in order, whatever order the identities come in.
+    pub fn remap (self, mailmap: &Mailmap) -> FileReport
+    {
+        let mut report = FileReport::new(&self.path);
+        report.renamed_from = self.renamed_from.clone();
+        report.spans = self.spans.clone();
+        // Every name of every owner, with whether the mailmap gives it and
+        // its lines
+        let mut names: BTreeMap<String, BTreeMap<String, (bool, usize)>> = BTreeMap::new();
+        for (_, owner) in sorted(&self.owners)
+        {
+            let (name, email) = mailmap.resolve(&owner.name, &owner.email);
+            let proper = mailmap.proper_name(&owner.name, &owner.email).is_some();
+            let named = names.entry(owner_key(name, email).into_owned()).or_default()
+                .entry(normalize_name(name)).or_default();
+            named.0 |= proper;
+            named.1 += owner.lines();
+            for (commit, contribution) in sorted(&owner.commits)
+            {
+                report.add_hunk(&Hunk {
+                    commit: *commit,
+                    name,
+                    email,
+                    time: contribution.time,
+                    lines: contribution.lines,
+                    origin: None,
+                });
+            }
+        }
+        for (key, names) in names
+        {
+            let best = names.into_iter()
+                .max_by(|(a, (a_proper, a_lines)), (b, (b_proper, b_lines))| {
+                    a_proper.cmp(b_proper).then_with(|| a_lines.cmp(b_lines)).then_with(|| b.cmp(a))
+                });
+            if let (Some(owner), Some((name, _))) = (report.owners.get_mut(&key), best) {
+                owner.name = name;
+            }
+        }
+        report
+    }
+
+    /// Keep only the lines of commits authored within `since..=until`,
+    /// dropping owners left without any
+    pub fn window (&mut self, since: Option<i64>, until: Option<i64>)
+    {
+        self.retain_commits(|_, c| since.is_none_or(|s| c.time >= s) && until.is_none_or(|u| c.time <= u));
+    }
+
+    /// Keep only the lines of the commits for which `keep` returns true,
+    /// dropping owners left without any
+    pub fn retain_commits<F: FnMut(&Oid, &Contribution) -> bool> (&mut self, mut keep: F)
+    {
+        for owner in self.owners.values_mut()
+        {
+            owner.commits.retain(|oid, c| keep(oid, c));
+            owner.score = owner.lines() as f64;
+        }
+        self.owners.retain(|_, owner| !owner.commits.is_empty());
+    }
+
+    /// Keep only the owners for whom `keep` returns true
+    pub fn retain_owners<F: FnMut(&Owner) -> bool> (&mut self, mut keep: F)
+    {
+        self.owners.retain(|_, owner| keep(owner));
+    }
+
+    /// Weigh every line by its age, halving its worth every `half_life`
+    /// days as of `now`
+    pub fn decay (&mut self, half_life: f64, now: i64)
+    {
+        for owner in self.owners.values_mut()
+        {
+            owner.score = owner.commits.values()
+                .map(|c| {
+                    let age = (now - c.time).max(0) as f64 / DAY;
+                    c.lines as f64 * 0.5f64.powf(age / half_life)
+                })
+                .sum();
+        }
+    }
+
+    pub fn bus_factor (&self) -> usize
+    {
+        bus_factor(self.owners.values().map(Owner::lines))
+    }
+
+    /// The owners whose lines differ from those in `base`, the same file
+    /// elsewhere, largest difference first
+    pub fn differences (&self, base: &FileReport) -> Vec<Difference>
+    {
+        let mut differences: Vec<Difference> = Vec::new();
+        let mut seen = HashSet::new();
+        for (key, owner) in self.owners.iter().chain(base.owners.iter())
+        {
+            if !seen.insert(key) {
+                continue;
+            }
+            let lines = |report: &FileReport| report.owners.get(key).map_or(0, Owner::lines);
+            let (before, after) = (lines(base), lines(self));
+            if before != after {
+                differences.push(Difference { name: owner.name.clone(), email: owner.email.clone(), before, after });
+            }
+        }
+        differences.sort_by(|a, b| b.change().abs().cmp(&a.change().abs()).then_with(|| a.email.cmp(&b.email)));
+        differences
+    }
+}
+
+/// The entries of `map` in the order of their keys, so that what is built
+/// from them does not depend on the order of a hash map
+fn sorted<K: Ord, V> (map: &HashMap<K, V>) -> Vec<(&K, &V)>
+{
+    let mut entries: Vec<(&K, &V)> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// How the lines of one owner differ between two versions of a file
+pub struct Difference {
+    pub name: String,
+    pub email: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl Difference {
+    pub fn change (&self) -> i64
+    {
+        self.after as i64 - self.before as i64
+    }
+}
+
+/// `email` trimmed, without the angle brackets of an `author-mail` line, in
+/// NFC and lower cased, so that the spellings of one address make one
+/// owner; most are that already and are not copied
+pub fn normalize_email (email: &str) -> Cow<'_, str>
+{
+    let bare = email.trim().trim_start_matches('<').trim_end_matches('>').trim();
+    let plain = bare.bytes().all(|b| b.is_ascii() && !b.is_ascii_uppercase());
+    if plain && bare == email {
+        Cow::Borrowed(email)
+    } else {
+        Cow::Owned(bare.nfc().collect::<String>().to_lowercase())
+    }
+}
+
+/// What tells owners apart: the normalized email, or for commits that have
+/// none, the lower cased name, so that everyone without an email does not
+/// turn into one owner
+pub fn owner_key<'a> (name: &str, email: &'a str) -> Cow<'a, str>
+{
+    match normalize_email(email) {
+        email if email.is_empty() => Cow::Owned(normalize_name(name).to_lowercase()),
+        email => email,
+    }
+}
+
+/// `name` trimmed and in NFC, so that "José" is the same however it was
+/// composed
+pub fn normalize_name (name: &str) -> String
+{
+    name.trim().nfc().collect()
+}
+
+/// Fewest owners who together own more than half of the lines; 0 without
+/// any lines, which nobody can be missed for
+pub fn bus_factor<I: Iterator<Item = usize>> (lines: I) -> usize
+{
+    let mut lines: Vec<usize> = lines.collect();
+    lines.sort_unstable_by(|a, b| b.cmp(a));
+    let total = lines.iter().sum::<usize>();
+    if total == 0 {
+        return 0;
+    }
+
+    let mut owned = 0;
+    for (count, owner_lines) in lines.iter().enumerate()
+    {
+        if owned * 2 > total {
+            return count;
+        }
+        owned += owner_lines;
+    }
+    lines.len()
+}
+
+/// `part` as a percentage of `total`, 0 for an empty total
+pub fn percent (part: usize, total: usize) -> f64
+{
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// The lines someone owns through one commit
+#[derive(Clone, Copy)]
+pub struct Contribution {
+    pub lines: usize,
+    /// Author time of the commit, seconds since the epoch
+    pub time: i64,
+}
+
+/// Someone owning lines of a file
+#[derive(Clone)]
+pub struct Owner {
+    pub name: String,
+    pub email: String,
+    /// Lines owned through each commit
+    pub commits: HashMap<Oid, Contribution>,
+    /// Weighted lines used for ranking, see `FileReport::decay`
+    pub score: f64,
+}
+
+impl Owner {
+    pub fn new (commit: &Hunk) -> Owner
+    {
+        Owner {
+            name: normalize_name(commit.name),
+            email: normalize_email(commit.email).into_owned(),
+            commits: HashMap::new(),
+            score: 0.0,
+        }
+    }
+
+    pub fn add_hunk (
+        &mut self,
+        commit: &Hunk
+    )
+    {
+        self.commits.entry(commit.commit)
+            .or_insert(Contribution { lines: 0, time: commit.time })
+            .lines += commit.lines;
+        self.score += commit.lines as f64;
+    }
+
+    pub fn lines (&self) -> usize
+    {
+       self.commits.values().map(|c| c.lines).sum::<usize>()
+    }
+
+    /// Surviving lines per commit they survive from: low for someone who
+    /// grew the file through many small changes, high for one big import
+    pub fn lines_per_commit (&self) -> f64
+    {
+        match self.commits.len() {
+            0 => 0.0,
+            commits => self.lines() as f64 / commits as f64,
+        }
+    }
+
+    /// Age of the newest, the median and the oldest line as of `now`, in
+    /// seconds; `None` without any lines
+    pub fn line_ages (&self, now: i64) -> Option<(i64, i64, i64)>
+    {
+        let mut ages: Vec<(i64, usize)> = self.commits.values()
+            .map(|c| ((now - c.time).max(0), c.lines))
+            .filter(|&(_, lines)| lines > 0)
+            .collect();
+        ages.sort_unstable();
+        let (newest, oldest) = (ages.first()?.0, ages.last()?.0);
+        // The age of the line in the middle when all of them are in order
+        let middle = self.lines() / 2;
+        let mut seen = 0;
+        let median = ages.iter()
+            .find(|&&(_, lines)| {
+                seen += lines;
+                seen > middle
+            })
+            .map_or(oldest, |&(age, _)| age);
+        Some((newest, median, oldest))
+    }
+
+    /// How many of the commits the lines survive from were authored in
+    /// each quarter, see `date::quarter`
+    pub fn commits_by_quarter (&self) -> BTreeMap<i64, usize>
+    {
+        let mut quarters = BTreeMap::new();
+        for contribution in self.commits.values()
+        {
+            *quarters.entry(date::quarter(contribution.time)).or_insert(0) += 1;
+        }
+        quarters
+    }
+
+    /// The earliest commit any of the lines survive from, with its time
+    pub fn oldest_commit (&self) -> Option<(Oid, i64)>
+    {
+        self.commits.iter()
+            .filter(|(_, c)| c.lines > 0)
+            .map(|(oid, c)| (*oid, c.time))
+            .min_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)))
+    }
+
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} <{}>: Lines: {} Count: {}",
+               self.name,
+               self.email,
+               self.lines(),
+               self.commits.len())
+    }
+
+}
+
+/// The analysis of several files at one revision
+pub struct RepoReport {
+    /// Commit the files were analyzed at, if the repository has one
+    pub revision: Option<Oid>,
+    pub files: Vec<FileReport>,
+    /// The analysis was cancelled before every file was done
+    pub partial: bool,
+}
+
+impl RepoReport {
+    pub fn lines (&self) -> usize
+    {
+        self.files.iter().map(FileReport::lines).sum::<usize>()
+    }
+
+    /// Lines owned across all files, by `owner_key`
+    pub fn owners (&self) -> HashMap<&str, usize>
+    {
+        let mut owners = HashMap::new();
+        for file in &self.files
+        {
+            for (key, owner) in &file.owners
+            {
+                *owners.entry(key.as_str()).or_insert(0) += owner.lines();
+            }
+        }
+        owners
+    }
+
+    /// Number of distinct commits that lines survive from
+    pub fn commits (&self) -> usize
+    {
+        let mut commits: HashSet<&Oid> = HashSet::new();
+        for file in &self.files
+        {
+            for owner in file.owners.values()
+            {
+                commits.extend(owner.commits.keys());
+            }
+        }
+        commits.len()
+    }
+
+    pub fn bus_factor (&self) -> usize
+    {
+        bus_factor(self.owners().values().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk<'a> (commit: u8, name: &'a str, email: &'a str, lines: usize) -> Hunk<'a>
+    {
+        Hunk { commit: Oid::from_str(&format!("{:02x}", commit).repeat(20)).unwrap(), name, email, time: 0, lines, origin: None }
+    }
+
+    #[test]
+    fn bus_factor_of_nothing ()
+    {
+        assert_eq!(bus_factor(std::iter::empty()), 0);
+        assert_eq!(bus_factor(vec![0, 0].into_iter()), 0);
+    }
+
+    #[test]
+    fn bus_factor_counts_owners_past_half ()
+    {
+        assert_eq!(bus_factor(vec![10].into_iter()), 1);
+        assert_eq!(bus_factor(vec![5, 5].into_iter()), 2);
+        assert_eq!(bus_factor(vec![6, 4].into_iter()), 1);
+        assert_eq!(bus_factor(vec![1, 3, 1, 1].into_iter()), 2);
+    }
+
+    #[test]
+    fn empty_file ()
+    {
+        let mut file = FileReport::new("empty.txt");
+        file.add_blamed(&hunk(1, "Alice", "alice@example.com", 0));
+        assert!(file.owners.is_empty());
+        assert!(file.spans.is_empty());
+        assert_eq!(file.lines(), 0);
+        assert_eq!(file.bus_factor(), 0);
+        assert_eq!(file.owner_of(1).map(|owner| owner.name.as_str()), None);
+    }
+
+    #[test]
+    fn single_line ()
+    {
+        let mut file = FileReport::new("one.txt");
+        file.add_blamed(&hunk(1, "Alice", "alice@example.com", 1));
+        assert_eq!(file.lines(), 1);
+        assert_eq!(file.bus_factor(), 1);
+        assert_eq!(file.owner_of(1).map(|owner| owner.name.as_str()), Some("Alice"));
+        assert_eq!(percent(file.lines(), file.lines()), 100.0);
+    }
+
+    #[test]
+    fn spellings_of_an_email_are_one_owner ()
+    {
+        let mut file = FileReport::new("a.txt");
+        file.add_hunk(&hunk(1, "Alice", "alice@example.com", 2));
+        file.add_hunk(&hunk(2, "Alice", " <Alice@Example.COM> ", 3));
+        assert_eq!(file.owners.len(), 1);
+        assert_eq!(file.owners["alice@example.com"].lines(), 5);
+    }
+
+    #[test]
+    fn normalized_emails ()
+    {
+        assert!(matches!(normalize_email("alice@example.com"), Cow::Borrowed(_)));
+        assert_eq!(normalize_email("<Alice@Example.com>"), "alice@example.com");
+        assert_eq!(normalize_email("  bob@example.com "), "bob@example.com");
+        // The decomposed é composes
+        assert_eq!(normalize_email("Jose\u{301}@example.com"), "jos\u{e9}@example.com");
+    }
+
+    #[test]
+    fn owner_keys ()
+    {
+        assert_eq!(owner_key("Alice", "Alice@Example.com"), "alice@example.com");
+        // Without an email, people are told apart by name
+        assert_eq!(owner_key(" Alice ", ""), "alice");
+        assert_eq!(owner_key("Bob", "<>"), "bob");
+        assert_ne!(owner_key("Alice", ""), owner_key("Bob", ""));
+    }
+
+    #[test]
+    fn percent_of_nothing ()
+    {
+        assert_eq!(percent(0, 0), 0.0);
+        assert_eq!(percent(1, 4), 25.0);
+    }
+
+    #[test]
+    fn remapped_names ()
+    {
+        let mailmap = Mailmap::parse("Z <z@x.org> <zed@x.org>\n");
+        // Hash maps iterate in another order every time
+        for _ in 0..20
+        {
+            let mut file = FileReport::new("a.txt");
+            file.add_hunk(&hunk(1, "Zed", "zed@x.org", 1));
+            file.add_hunk(&hunk(2, "Zee", "Z@X.org", 5));
+            file.add_hunk(&hunk(3, "Bea", "b@x.org", 2));
+            file.add_hunk(&hunk(4, "Bee", "B@x.org", 2));
+            file.add_hunk(&hunk(5, "Bo", "B@X.org", 1));
+            let file = file.remap(&mailmap);
+            assert_eq!(file.owners.len(), 2);
+            // The name of the mailmap over the one with more lines
+            assert_eq!(file.owners["z@x.org"].name, "Z");
+            assert_eq!(file.owners["z@x.org"].lines(), 6);
+            // The most lines, then the first name
+            assert_eq!(file.owners["b@x.org"].name, "Bea");
+        }
+    }
+}
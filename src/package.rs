@@ -0,0 +1,141 @@
+//! Ownership by package rather than by directory
+//!
+//! In a monorepo teams own packages, which need not line up with any one
+//! level of directories. A directory is the root of a package when it has
+//! a `Cargo.toml` with a `[package]` table, as the members of a Cargo
+//! workspace do, a `package.json`, or a Bazel `BUILD` or `BUILD.bazel`
+//! file. Every file belongs to the package of the closest such directory
+//! above it, files outside of any to none.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use git2::{ObjectType, Repository, Tree};
+
+use crate::error::Error;
+use crate::json;
+use crate::{bus_factor, FileReport};
+
+/// The manifests that make a directory a package, in order of precedence
+const MANIFESTS: [(&str, &str); 4] = [
+    ("Cargo.toml", "cargo"),
+    ("package.json", "npm"),
+    ("BUILD.bazel", "bazel"),
+    ("BUILD", "bazel"),
+];
+
+/// Ownership of every tracked line of a package
+pub struct Package {
+    /// Its directory, `.` for the root of the work tree
+    pub path: String,
+    pub name: String,
+    /// `cargo`, `npm` or `bazel`
+    pub kind: &'static str,
+    /// Lines per owner email
+    pub owners: HashMap<String, usize>,
+}
+
+impl Package {
+    pub fn lines (&self) -> usize
+    {
+        self.owners.values().sum::<usize>()
+    }
+
+    pub fn bus_factor (&self) -> usize
+    {
+        bus_factor(self.owners.values().cloned())
+    }
+}
+
+/// Roll file ownership up into the packages of the tree of `rev`, HEAD if
+/// not given; packages are ordered by path
+pub fn packages (repo: &Repository, rev: Option<&str>, files: &[FileReport]) -> Result<Vec<Package>, Error>
+{
+    let tree = repo.revparse_single(rev.unwrap_or("HEAD"))?.peel_to_tree()?;
+    // Whether each directory is a package, and which
+    let mut found: HashMap<String, Option<(String, &'static str)>> = HashMap::new();
+    let mut packages: BTreeMap<String, Package> = BTreeMap::new();
+    for file in files
+    {
+        for dir in Path::new(&file.path).ancestors().skip(1)
+        {
+            let path = match dir.to_str() {
+                Some("") => ".".to_string(),
+                _ => dir.display().to_string(),
+            };
+            if !found.contains_key(&path) {
+                found.insert(path.clone(), package(repo, &tree, dir)?);
+            }
+            if let Some((name, kind)) = &found[&path] {
+                let entry = packages.entry(path.clone()).or_insert_with(|| Package {
+                    path,
+                    name: name.clone(),
+                    kind,
+                    owners: HashMap::new(),
+                });
+                for (key, owner) in &file.owners
+                {
+                    *entry.owners.entry(key.clone()).or_insert(0) += owner.lines();
+                }
+                break;
+            }
+        }
+    }
+    Ok(packages.into_values().collect())
+}
+
+/// The name and kind of the package `dir` is the root of, if any
+fn package (repo: &Repository, tree: &Tree, dir: &Path) -> Result<Option<(String, &'static str)>, Error>
+{
+    for (manifest, kind) in MANIFESTS
+    {
+        let entry = match tree.get_path(&dir.join(manifest)) {
+            Ok(entry) if entry.kind() == Some(ObjectType::Blob) => entry,
+            _ => continue,
+        };
+        let blob = repo.find_blob(entry.id())?;
+        let text = String::from_utf8_lossy(blob.content());
+        let name = match kind {
+            "cargo" => match crate_name(&text) {
+                Some(name) if !name.is_empty() => name,
+                Some(_) => dir.display().to_string(),
+                // The root of a virtual workspace, whose members are the packages
+                None => continue,
+            },
+            "npm" => match json::parse(&text).ok().and_then(|json| json.get("name").and_then(|n| n.as_str()).map(String::from)) {
+                Some(name) => name,
+                None => dir.display().to_string(),
+            },
+            _ => format!("//{}", dir.display()),
+        };
+        return Ok(Some((name, kind)));
+    }
+    Ok(None)
+}
+
+/// The `name` of the `[package]` table of a `Cargo.toml`, `None` without
+/// that table
+fn crate_name (text: &str) -> Option<String>
+{
+    let mut in_package = false;
+    let mut name = None;
+    for line in text.lines().map(str::trim)
+    {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            if in_package {
+                name = Some(String::new());
+            }
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "name" {
+                name = Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    name
+}
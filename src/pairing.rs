@@ -0,0 +1,118 @@
+//! Whom to pair with the only expert of a file
+//!
+//! For every file that depends on one person the best second owner is
+//! whoever already owns most code close to it: lines of the file itself
+//! count most, then those of files in the same directory, halving with
+//! every step through the tree and doubling for files of the same kind.
+//! Authors whose lines there are all older than `INACTIVE_DAYS` are passed
+//! over.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::risk::{Risk, INACTIVE_DAYS};
+use crate::{owner_key, percent, FileReport, Owner};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Someone to share a file with
+pub struct Candidate {
+    pub name: String,
+    pub email: String,
+    /// Lines owned close to the file, weighed by how close
+    pub affinity: f64,
+    /// The file they own the closest lines of
+    pub closest: String,
+}
+
+pub struct Pairing {
+    pub path: String,
+    /// Name of the only owner that counts
+    pub owner: String,
+    /// Share of the lines of that owner, 0 to 1
+    pub share: f64,
+    /// None if nobody active owns code anywhere close
+    pub candidate: Option<Candidate>,
+}
+
+/// Steps through the directory tree from the directory of `a` to that of
+/// `b`
+fn distance (a: &Path, b: &Path) -> usize
+{
+    let a: Vec<_> = a.parent().map(|p| p.components().collect()).unwrap_or_default();
+    let b: Vec<_> = b.parent().map(|p| p.components().collect()).unwrap_or_default();
+    let common = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    a.len() + b.len() - 2 * common
+}
+
+/// How close the code of `other` is to that of `file`, 0 to 2
+fn similarity (file: &str, other: &str) -> f64
+{
+    if file == other {
+        return 2.0;
+    }
+    let (file, other) = (Path::new(file), Path::new(other));
+    let kind = if file.extension() == other.extension() { 1.0 } else { 0.5 };
+    kind * 0.5f64.powi(distance(file, other) as i32 + 1)
+}
+
+/// A second owner for every file of `files` with a bus factor of 1, in
+/// the order of `risks`, as of `now`
+pub fn suggest (files: &[FileReport], risks: &[Risk], now: i64) -> Vec<Pairing>
+{
+    let by_path: HashMap<&str, &FileReport> = files.iter().map(|f| (f.path.as_str(), f)).collect();
+    let active = |owner: &Owner| {
+        owner.commits.values().any(|c| c.time >= now - INACTIVE_DAYS * DAY)
+    };
+
+    let mut pairings = Vec::new();
+    for risk in risks
+    {
+        let file = match by_path.get(risk.path.as_str()) {
+            Some(file) if file.lines() > 0 && file.bus_factor() == 1 => file,
+            _ => continue,
+        };
+        let owners = file.ranked_owners();
+        let owner = match owners.first() {
+            Some(owner) => owner,
+            None => continue,
+        };
+        let expert = owner_key(&owner.name, &owner.email);
+
+        // Affinity and closest file of everyone else who is still around
+        let mut candidates: HashMap<&str, Candidate> = HashMap::new();
+        for other in files
+        {
+            let weight = similarity(&file.path, &other.path);
+            for (key, them) in &other.owners
+            {
+                if key.as_str() == expert.as_ref() || !active(them) {
+                    continue;
+                }
+                let affinity = them.lines() as f64 * weight;
+                let candidate = candidates.entry(key).or_insert_with(|| Candidate {
+                    name: them.name.clone(),
+                    email: them.email.clone(),
+                    affinity: 0.0,
+                    closest: other.path.clone(),
+                });
+                if weight > similarity(&file.path, &candidate.closest) {
+                    candidate.closest = other.path.clone();
+                }
+                candidate.affinity += affinity;
+            }
+        }
+        let candidate = candidates.into_values()
+            .max_by(|a, b| a.affinity.partial_cmp(&b.affinity)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| b.email.cmp(&a.email)));
+
+        pairings.push(Pairing {
+            path: file.path.clone(),
+            owner: owner.name.clone(),
+            share: percent(owner.lines(), file.lines()) / 100.0,
+            candidate,
+        });
+    }
+    pairings
+}
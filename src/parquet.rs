@@ -0,0 +1,406 @@
+//! Minimal Parquet writer for the ownership table
+//!
+//! Writes a single row group of required, PLAIN encoded, uncompressed
+//! columns, which every Parquet reader understands and which keeps the
+//! writer free of the arrow dependency tree.
+
+use std::io::{self, Write};
+
+use crate::{percent, FileReport, Owner};
+
+const MAGIC: &[u8] = b"PAR1";
+
+// parquet.thrift enums
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REPETITION_REQUIRED: i32 = 0;
+const CONVERTED_UTF8: i32 = 0;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+pub enum Values {
+    Utf8(Vec<String>),
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+}
+
+impl Values {
+    fn len (&self) -> usize
+    {
+        match self {
+            Values::Utf8(v) => v.len(),
+            Values::Int64(v) => v.len(),
+            Values::Double(v) => v.len(),
+        }
+    }
+
+    fn physical_type (&self) -> i32
+    {
+        match self {
+            Values::Utf8(_) => TYPE_BYTE_ARRAY,
+            Values::Int64(_) => TYPE_INT64,
+            Values::Double(_) => TYPE_DOUBLE,
+        }
+    }
+
+    fn plain (&self) -> Vec<u8>
+    {
+        let mut out = Vec::new();
+        match self {
+            Values::Utf8(values) => {
+                for v in values
+                {
+                    out.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                    out.extend_from_slice(v.as_bytes());
+                }
+            }
+            Values::Int64(values) => {
+                for v in values
+                {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Values::Double(values) => {
+                for v in values
+                {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub values: Values,
+}
+
+/// Write `columns`, which must all have the same length, as a Parquet file
+pub fn write<W: Write> (mut out: W, columns: &[Column]) -> io::Result<()>
+{
+    let rows = columns.first().map(|c| c.values.len()).unwrap_or(0);
+    debug_assert!(columns.iter().all(|c| c.values.len() == rows));
+
+    out.write_all(MAGIC)?;
+    let mut offset = MAGIC.len() as i64;
+
+    // (data_page_offset, size) of every column chunk
+    let mut chunks = Vec::new();
+    if rows > 0 {
+        for column in columns
+        {
+            let data = column.values.plain();
+            let mut header = Compact::new();
+            header.i32(1, PAGE_DATA);
+            header.i32(2, data.len() as i32);
+            header.i32(3, data.len() as i32);
+            header.begin_struct(5);
+            header.i32(1, rows as i32);
+            header.i32(2, ENCODING_PLAIN);
+            header.i32(3, ENCODING_RLE);
+            header.i32(4, ENCODING_RLE);
+            header.end_struct();
+            header.stop();
+
+            out.write_all(&header.buf)?;
+            out.write_all(&data)?;
+            let size = (header.buf.len() + data.len()) as i64;
+            chunks.push((offset, size));
+            offset += size;
+        }
+    }
+
+    let mut meta = Compact::new();
+    meta.i32(1, 1);
+
+    meta.begin_list(2, CT_STRUCT, columns.len() + 1);
+    meta.begin_element();
+    meta.binary(4, b"schema");
+    meta.i32(5, columns.len() as i32);
+    meta.end_struct();
+    for column in columns
+    {
+        meta.begin_element();
+        meta.i32(1, column.values.physical_type());
+        meta.i32(3, REPETITION_REQUIRED);
+        meta.binary(4, column.name.as_bytes());
+        if let Values::Utf8(_) = column.values {
+            meta.i32(6, CONVERTED_UTF8);
+        }
+        meta.end_struct();
+    }
+
+    meta.i64(3, rows as i64);
+
+    if rows > 0 {
+        meta.begin_list(4, CT_STRUCT, 1);
+        meta.begin_element();
+        meta.begin_list(1, CT_STRUCT, columns.len());
+        for (column, (page_offset, size)) in columns.iter().zip(&chunks)
+        {
+            meta.begin_element();
+            meta.i64(2, *page_offset);
+            meta.begin_struct(3);
+            meta.i32(1, column.values.physical_type());
+            meta.begin_list(2, CT_I32, 2);
+            meta.element_i32(ENCODING_PLAIN);
+            meta.element_i32(ENCODING_RLE);
+            meta.begin_list(3, CT_BINARY, 1);
+            meta.element_binary(column.name.as_bytes());
+            meta.i32(4, CODEC_UNCOMPRESSED);
+            meta.i64(5, rows as i64);
+            meta.i64(6, *size);
+            meta.i64(7, *size);
+            meta.i64(9, *page_offset);
+            meta.end_struct();
+            meta.end_struct();
+        }
+        meta.i64(2, chunks.iter().map(|(_, size)| size).sum());
+        meta.i64(3, rows as i64);
+        meta.end_struct();
+    } else {
+        meta.begin_list(4, CT_STRUCT, 0);
+    }
+
+    meta.binary(6, concat!("git-whoknows version ", env!("CARGO_PKG_VERSION")).as_bytes());
+    meta.stop();
+
+    out.write_all(&meta.buf)?;
+    out.write_all(&(meta.buf.len() as u32).to_le_bytes())?;
+    out.write_all(MAGIC)?;
+    Ok(())
+}
+
+/// What an owner of a file is labeled as
+pub type Label<'a> = &'a dyn Fn(&FileReport, &Owner) -> String;
+
+/// The ownership table, one row per (file, author) pair, with a column of
+/// the `expertise` that `label` gives each if there is one
+pub fn write_files<W: Write> (
+    out: W,
+    files: &[FileReport],
+    label: Option<Label>
+) -> io::Result<()>
+{
+    let mut path = Vec::new();
+    let mut name = Vec::new();
+    let mut email = Vec::new();
+    let mut lines = Vec::new();
+    let mut commits = Vec::new();
+    let mut share = Vec::new();
+    let mut expertise = Vec::new();
+    for file in files
+    {
+        let total = file.lines();
+        for owner in file.ranked_owners()
+        {
+            path.push(file.path.clone());
+            name.push(owner.name.clone());
+            email.push(owner.email.clone());
+            lines.push(owner.lines() as i64);
+            commits.push(owner.commits.len() as i64);
+            share.push(percent(owner.lines(), total));
+            if let Some(label) = label {
+                expertise.push(label(file, owner));
+            }
+        }
+    }
+
+    let mut columns = vec![
+        Column { name: "path", values: Values::Utf8(path) },
+        Column { name: "name", values: Values::Utf8(name) },
+        Column { name: "email", values: Values::Utf8(email) },
+        Column { name: "lines", values: Values::Int64(lines) },
+        Column { name: "commits", values: Values::Int64(commits) },
+        Column { name: "share", values: Values::Double(share) },
+    ];
+    if label.is_some() {
+        columns.push(Column { name: "expertise", values: Values::Utf8(expertise) });
+    }
+    write(out, &columns)
+}
+
+// Thrift compact protocol type ids
+const CT_I32: u8 = 5;
+const CT_I64: u8 = 6;
+const CT_BINARY: u8 = 8;
+const CT_LIST: u8 = 9;
+const CT_STRUCT: u8 = 12;
+
+/// Just enough of the thrift compact protocol to encode parquet metadata
+struct Compact {
+    buf: Vec<u8>,
+    last_field: Vec<i16>,
+}
+
+impl Compact {
+    fn new () -> Compact
+    {
+        Compact {
+            buf: Vec::new(),
+            last_field: vec![0],
+        }
+    }
+
+    fn varint (&mut self, mut v: u64)
+    {
+        while v >= 0x80 {
+            self.buf.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        self.buf.push(v as u8);
+    }
+
+    fn zigzag (&mut self, v: i64)
+    {
+        self.varint(((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    fn field (&mut self, id: i16, kind: u8)
+    {
+        let last = self.last_field.last_mut().unwrap();
+        let delta = id - *last;
+        if delta > 0 && delta <= 15 {
+            self.buf.push(((delta as u8) << 4) | kind);
+        } else {
+            self.buf.push(kind);
+            self.zigzag(id as i64);
+        }
+        *self.last_field.last_mut().unwrap() = id;
+    }
+
+    fn i32 (&mut self, id: i16, v: i32)
+    {
+        self.field(id, CT_I32);
+        self.zigzag(v as i64);
+    }
+
+    fn i64 (&mut self, id: i16, v: i64)
+    {
+        self.field(id, CT_I64);
+        self.zigzag(v);
+    }
+
+    fn binary (&mut self, id: i16, v: &[u8])
+    {
+        self.field(id, CT_BINARY);
+        self.element_binary(v);
+    }
+
+    fn begin_struct (&mut self, id: i16)
+    {
+        self.field(id, CT_STRUCT);
+        self.last_field.push(0);
+    }
+
+    fn end_struct (&mut self)
+    {
+        self.stop();
+        self.last_field.pop();
+    }
+
+    fn stop (&mut self)
+    {
+        self.buf.push(0);
+    }
+
+    /// Start a list of `len` items of thrift type `kind`; struct items are
+    /// each opened with `begin_element`
+    fn begin_list (&mut self, id: i16, kind: u8, len: usize)
+    {
+        self.field(id, CT_LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | kind);
+        } else {
+            self.buf.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    fn begin_element (&mut self)
+    {
+        self.last_field.push(0);
+    }
+
+    fn element_i32 (&mut self, v: i32)
+    {
+        self.zigzag(v as i64);
+    }
+
+    fn element_binary (&mut self, v: &[u8])
+    {
+        self.varint(v.len() as u64);
+        self.buf.extend_from_slice(v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_int64_column ()
+    {
+        let columns = [Column { name: "n", values: Values::Int64(vec![1, 2]) }];
+        let mut out = Vec::new();
+        write(&mut out, &columns).unwrap();
+
+        let mut expected = MAGIC.to_vec();
+        // PageHeader: DATA_PAGE of 16 bytes, uncompressed and compressed
+        expected.extend_from_slice(&[0x15, 0x00, 0x15, 0x20, 0x15, 0x20]);
+        // DataPageHeader: 2 values, PLAIN, RLE levels
+        expected.extend_from_slice(&[0x2c, 0x15, 0x04, 0x15, 0x00, 0x15, 0x06, 0x15, 0x06, 0x00, 0x00]);
+        expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut footer = vec![
+            // version 1
+            0x15, 0x02,
+            // schema: the root with 1 child, then required INT64 "n"
+            0x19, 0x2c,
+            0x48, 0x06, b's', b'c', b'h', b'e', b'm', b'a', 0x15, 0x02, 0x00,
+            0x15, 0x04, 0x25, 0x00, 0x18, 0x01, b'n', 0x00,
+            // num_rows 2
+            0x16, 0x04,
+            // row_groups: one, of one column chunk at offset 4
+            0x19, 0x1c, 0x19, 0x1c, 0x26, 0x08,
+            // ColumnMetaData: INT64, [PLAIN, RLE], ["n"], UNCOMPRESSED, 2
+            // values, 33 bytes with the page header, data page at 4
+            0x1c, 0x15, 0x04, 0x19, 0x25, 0x00, 0x06, 0x19, 0x18, 0x01, b'n',
+            0x15, 0x00, 0x16, 0x04, 0x16, 0x42, 0x16, 0x42, 0x26, 0x08, 0x00, 0x00,
+            // total_byte_size 33, num_rows 2
+            0x16, 0x42, 0x16, 0x04, 0x00,
+        ];
+        let created_by = concat!("git-whoknows version ", env!("CARGO_PKG_VERSION"));
+        footer.extend_from_slice(&[0x28, created_by.len() as u8]);
+        footer.extend_from_slice(created_by.as_bytes());
+        footer.push(0x00);
+
+        expected.extend_from_slice(&footer);
+        expected.extend_from_slice(&(footer.len() as u32).to_le_bytes());
+        expected.extend_from_slice(MAGIC);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn empty_table ()
+    {
+        let columns = [Column { name: "s", values: Values::Utf8(Vec::new()) }];
+        let mut out = Vec::new();
+        write(&mut out, &columns).unwrap();
+
+        // No pages; a required UTF8 column, no rows and no row groups
+        let schema = [0x19, 0x2c,
+                      0x48, 0x06, b's', b'c', b'h', b'e', b'm', b'a', 0x15, 0x02, 0x00,
+                      0x15, 0x0c, 0x25, 0x00, 0x18, 0x01, b's', 0x25, 0x00, 0x00];
+        assert_eq!(&out[..6], &[b'P', b'A', b'R', b'1', 0x15, 0x02]);
+        assert_eq!(&out[6..6 + schema.len()], &schema);
+        assert_eq!(&out[6 + schema.len()..6 + schema.len() + 4], &[0x16, 0x00, 0x19, 0x0c]);
+        let footer = u32::from_le_bytes([out[out.len() - 8], out[out.len() - 7], out[out.len() - 6], out[out.len() - 5]]);
+        assert_eq!(footer as usize, out.len() - 12);
+        assert_eq!(&out[out.len() - 4..], MAGIC);
+    }
+}
@@ -0,0 +1,291 @@
+//! Choosing files to analyze from a list narrowed down by typing
+//!
+//! Typing keeps the files whose path holds the typed characters in order,
+//! best matches first: runs of characters, the starts of path components
+//! and words and short paths are preferred. Up and down move through the
+//! list, Tab marks several files, Enter takes the marked files or else the
+//! one under the cursor, Esc and Ctrl-C give up. The list is drawn on the
+//! terminal itself, not on stdout, so the report can still be redirected.
+
+use std::io::{Read, Write};
+
+use whoknows::Error;
+
+/// Most files listed at once
+const ROWS: usize = 20;
+
+/// How well `query` matches `path`, higher is better, or None when the
+/// characters of `query` do not all appear in `path` in order; case only
+/// matters when `query` has upper case
+pub fn score (query: &str, path: &str) -> Option<i64>
+{
+    let sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| if sensitive { c } else { c.to_ascii_lowercase() };
+    let chars: Vec<char> = path.chars().collect();
+
+    let mut score = 0;
+    let mut at = 0;
+    let mut last: Option<usize> = None;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()).map(fold)
+    {
+        let found = (at..chars.len()).find(|&i| fold(chars[i]) == wanted)?;
+        score += 16;
+        match last {
+            Some(last) if last + 1 == found => score += 24,
+            Some(last) => score -= (found - last - 1).min(16) as i64,
+            None => score -= found.min(16) as i64,
+        }
+        let boundary = found == 0 || matches!(chars[found - 1], '/' | '_' | '-' | '.' | ' ');
+        if boundary {
+            score += 20;
+        }
+        last = Some(found);
+        at = found + 1;
+    }
+    // The file name matters more than the directories leading to it
+    if let (Some(last), Some(slash)) = (last, chars.iter().rposition(|&c| c == '/')) {
+        if last > slash {
+            score += 8;
+        }
+    }
+    Some(score * 8 - chars.len() as i64)
+}
+
+/// The indices of `paths` matching `query`, best first
+fn matching (paths: &[String], query: &str) -> Vec<usize>
+{
+    let mut matches: Vec<(i64, usize)> = paths.iter().enumerate()
+        .filter_map(|(i, path)| score(query, path).map(|score| (score, i)))
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    matches.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Whether files can be picked without asking for it: both stdin and
+/// stdout are a terminal
+#[cfg(unix)]
+pub fn interactive () -> bool
+{
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+pub fn interactive () -> bool
+{
+    false
+}
+
+/// The terminal in raw mode, back to how it was when dropped
+#[cfg(unix)]
+struct Terminal {
+    tty: std::fs::File,
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl Terminal {
+    fn open () -> Result<Terminal, Error>
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty")
+            .map_err(|e| Error::Usage(format!("--pick needs a terminal: {}", e)))?;
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut saved) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut terminal = Terminal { tty, saved };
+        // The alternate screen leaves the scrollback as it was
+        terminal.tty.write_all(b"\x1b[?1049h")?;
+        Ok(terminal)
+    }
+
+    /// Rows and columns
+    fn size (&self) -> (usize, usize)
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+        if ret == 0 && size.ws_row > 0 && size.ws_col > 0 {
+            (size.ws_row as usize, size.ws_col as usize)
+        } else {
+            (24, 80)
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Terminal {
+    fn drop (&mut self)
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let _ = self.tty.write_all(b"\x1b[?1049l");
+        let _ = self.tty.flush();
+        unsafe {
+            libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.saved);
+        }
+    }
+}
+
+/// What a key press asks for
+enum Key {
+    Text(String),
+    Erase,
+    Clear,
+    Up,
+    Down,
+    Mark,
+    Accept,
+    Cancel,
+    Other,
+}
+
+/// The keys pressed in `bytes`, one read from the terminal
+fn keys (bytes: &[u8]) -> Vec<Key>
+{
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while let Some(&byte) = rest.first()
+    {
+        let (key, used) = match rest {
+            [0x1b, b'[' | b'O', b'A', ..] => (Key::Up, 3),
+            [0x1b, b'[' | b'O', b'B', ..] => (Key::Down, 3),
+            // Other sequences end in their first letter or tilde
+            [0x1b, b'[' | b'O', sequence @ ..] => {
+                let end = sequence.iter().position(|b| (0x40..=0x7e).contains(b)).map_or(sequence.len(), |at| at + 1);
+                (Key::Other, 2 + end)
+            }
+            [0x1b, ..] => (Key::Cancel, 1),
+            _ => match byte {
+                b'\r' | b'\n' => (Key::Accept, 1),
+                0x03 | 0x04 | 0x07 => (Key::Cancel, 1),
+                0x7f | 0x08 => (Key::Erase, 1),
+                0x15 => (Key::Clear, 1),
+                b'\t' => (Key::Mark, 1),
+                0x10 | 0x0b => (Key::Up, 1),
+                0x0e => (Key::Down, 1),
+                _ if byte < 0x20 => (Key::Other, 1),
+                _ => {
+                    let used = rest.iter().position(|&b| b < 0x20 || b == 0x7f).unwrap_or(rest.len());
+                    (Key::Text(String::from_utf8_lossy(&rest[..used]).into_owned()), used)
+                }
+            },
+        };
+        keys.push(key);
+        rest = &rest[used..];
+    }
+    keys
+}
+
+/// `text` cut to `width` columns
+fn fit (text: &str, width: usize) -> String
+{
+    use unicode_width::UnicodeWidthChar;
+
+    let mut used = 0;
+    text.chars()
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+/// Let the user choose among `paths` on the terminal
+#[cfg(unix)]
+pub fn pick (paths: &[String]) -> Result<Vec<String>, Error>
+{
+    if paths.is_empty() {
+        return Err(Error::Path("no tracked files to pick from".to_string()));
+    }
+    let mut terminal = Terminal::open()?;
+    let mut query = String::new();
+    let mut marked: Vec<usize> = Vec::new();
+    let mut cursor = 0;
+    let mut matches = matching(paths, &query);
+    loop
+    {
+        let (rows, columns) = terminal.size();
+        let shown = matches.len().min(ROWS).min(rows.saturating_sub(2).max(1));
+        cursor = cursor.min(matches.len().saturating_sub(1));
+        let first = (cursor + 1).saturating_sub(shown);
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        for (row, &i) in matches.iter().enumerate().skip(first).take(shown)
+        {
+            let mark = if marked.contains(&i) { '*' } else { ' ' };
+            let line = fit(&format!("{} {}", mark, paths[i]), columns.saturating_sub(2));
+            if row == cursor {
+                screen.push_str(&format!("\x1b[7m>{}\x1b[0m\r\n", line));
+            } else {
+                screen.push_str(&format!(" {}\r\n", line));
+            }
+        }
+        screen.push_str(&format!("  {}/{}", matches.len(), paths.len()));
+        if !marked.is_empty() {
+            screen.push_str(&format!(" ({} marked)", marked.len()));
+        }
+        screen.push_str(&format!("\r\n> {}", fit(&query, columns.saturating_sub(3))));
+        terminal.tty.write_all(screen.as_bytes())?;
+        terminal.tty.flush()?;
+
+        let mut buffer = [0; 64];
+        let read = terminal.tty.read(&mut buffer)?;
+        let before = query.clone();
+        let last = matches.len().saturating_sub(1);
+        for key in keys(&buffer[..read])
+        {
+            match key {
+                Key::Text(text) => query.push_str(&text),
+                Key::Erase => {
+                    query.pop();
+                }
+                Key::Clear => query.clear(),
+                Key::Up => cursor = cursor.saturating_sub(1),
+                Key::Down => cursor = (cursor + 1).min(last),
+                Key::Mark => {
+                    if let Some(&i) = matches.get(cursor) {
+                        match marked.iter().position(|&m| m == i) {
+                            Some(at) => {
+                                marked.remove(at);
+                            }
+                            None => marked.push(i),
+                        }
+                        cursor = (cursor + 1).min(last);
+                    }
+                }
+                Key::Accept => {
+                    if marked.is_empty() {
+                        marked.extend(matches.get(cursor));
+                    }
+                    if !marked.is_empty() {
+                        return Ok(marked.into_iter().map(|i| paths[i].clone()).collect());
+                    }
+                }
+                Key::Cancel => return Err(Error::Usage("no file picked".to_string())),
+                Key::Other => {}
+            }
+        }
+        if query == before {
+            continue;
+        }
+        matches = matching(paths, &query);
+        cursor = 0;
+    }
+}
+
+#[cfg(not(unix))]
+pub fn pick (_paths: &[String]) -> Result<Vec<String>, Error>
+{
+    Err(Error::Usage("--pick needs a Unix terminal".to_string()))
+}
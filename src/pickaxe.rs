@@ -0,0 +1,68 @@
+//! Who knows about a concept rather than a file
+//!
+//! git's pickaxe finds the commits that added or removed code matching a
+//! string, as `git log -S`, or that changed lines matching a regular
+//! expression, as `git log -G`, wherever in the repository that code is.
+//! Their authors are the people to ask about it.
+
+use std::collections::{HashMap, HashSet};
+
+use git2::Repository;
+
+use crate::blame::unquote;
+use crate::error::Error;
+use crate::git;
+use crate::ownership::{normalize_email, owner_key};
+
+/// What one author did to code matching the pattern
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+    /// Distinct files those commits changed
+    pub files: usize,
+    /// Author time of their latest such commit, seconds since the epoch
+    pub last: i64,
+}
+
+/// Everyone who authored commits up to `rev` that changed code matching
+/// `pattern`, a string or with `regex` a regular expression, most commits
+/// first; `mailmap` has git map their identities
+pub fn search (repo: &Repository, pattern: &str, regex: bool, rev: &str, mailmap: bool) -> Result<Vec<Author>, Error>
+{
+    let format = if mailmap { "--format=%x00%aN%x1f%aE%x1f%at" } else { "--format=%x00%an%x1f%ae%x1f%at" };
+    let pickaxe = format!("{}{}", if regex { "-G" } else { "-S" }, pattern);
+    let output = git::run(repo, &["log".as_ref(), "--no-color".as_ref(), "--name-only".as_ref(), format.as_ref(),
+                                  pickaxe.as_ref(), rev.as_ref(), "--".as_ref()])?;
+
+    let mut authors: HashMap<String, (Author, HashSet<String>)> = HashMap::new();
+    for commit in output.split('\0').skip(1)
+    {
+        let (header, files) = commit.split_once('\n').unwrap_or((commit, ""));
+        let fields: Vec<&str> = header.split('\x1f').collect();
+        let (name, email, time) = match fields.as_slice() {
+            [name, email, time] => (name.trim(), normalize_email(email).into_owned(), time.parse::<i64>().unwrap_or(0)),
+            _ => continue,
+        };
+        let (author, paths) = authors.entry(owner_key(name, &email).into_owned()).or_insert_with(|| (Author {
+            name: name.to_string(),
+            email,
+            commits: 0,
+            files: 0,
+            last: time,
+        }, HashSet::new()));
+        author.commits += 1;
+        author.last = author.last.max(time);
+        for file in files.lines().filter(|line| !line.is_empty())
+        {
+            paths.insert(unquote(file).into_owned());
+        }
+        author.files = paths.len();
+    }
+
+    let mut authors: Vec<Author> = authors.into_values().map(|(author, _)| author).collect();
+    authors.sort_by(|a, b| b.commits.cmp(&a.commits)
+                    .then_with(|| b.files.cmp(&a.files))
+                    .then_with(|| a.email.cmp(&b.email)));
+    Ok(authors)
+}
@@ -0,0 +1,151 @@
+//! What differs between operating systems
+//!
+//! On Windows, git is often installed without being on the `PATH` of
+//! every shell, `canonicalize` returns verbatim paths such as
+//! `\\?\C:\src\repo` that git and people do not take, and the console only
+//! understands the escape sequences of the progress bar once asked to.
+//! Elsewhere these are no-ops. Temporary directories are private to their
+//! user where permissions can say so.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The git to run: `WHOKNOWS_GIT` if set, else `git` from the `PATH`, or
+/// on Windows from where Git for Windows installs to when it is not there
+pub fn git () -> &'static OsString
+{
+    static GIT: OnceLock<OsString> = OnceLock::new();
+    GIT.get_or_init(|| match std::env::var_os("WHOKNOWS_GIT") {
+        Some(git) if !git.is_empty() => git,
+        _ => find_git().unwrap_or_else(|| "git".into()),
+    })
+}
+
+#[cfg(windows)]
+fn find_git () -> Option<OsString>
+{
+    let on_path = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join("git.exe").is_file()))
+        .unwrap_or(false);
+    if on_path {
+        return None;
+    }
+    let roots = [
+        ("ProgramFiles", r"Git\cmd\git.exe"),
+        ("ProgramFiles(x86)", r"Git\cmd\git.exe"),
+        ("LOCALAPPDATA", r"Programs\Git\cmd\git.exe"),
+    ];
+    roots.iter()
+        .filter_map(|(var, exe)| Some(PathBuf::from(std::env::var_os(var)?).join(exe)))
+        .find(|exe| exe.is_file())
+        .map(PathBuf::into_os_string)
+}
+
+#[cfg(not(windows))]
+fn find_git () -> Option<OsString>
+{
+    None
+}
+
+/// `path` made absolute with every link resolved, as by
+/// `Path::canonicalize`, but never in verbatim form: `\\?\C:\x` is
+/// `C:\x` and `\\?\UNC\server\share` is `\\server\share`
+pub fn canonicalize (path: &Path) -> io::Result<PathBuf>
+{
+    path.canonicalize().map(simplify)
+}
+
+#[cfg(windows)]
+fn simplify (path: PathBuf) -> PathBuf
+{
+    let text = match path.to_str() {
+        Some(text) => text,
+        None => return path,
+    };
+    if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{}", unc));
+    }
+    match text.strip_prefix(r"\\?\") {
+        // Only a drive letter path has a plain form; others, such as
+        // volume GUIDs, stay as they are
+        Some(plain) if plain.as_bytes().get(1) == Some(&b':') => PathBuf::from(plain),
+        _ => path,
+    }
+}
+
+#[cfg(not(windows))]
+fn simplify (path: PathBuf) -> PathBuf
+{
+    path
+}
+
+/// A new directory in the temporary directory, named after `prefix` and
+/// this process; created exclusively, so that nothing someone else put
+/// there under the same name is ever used, and only for this user to enter
+pub fn temp_dir (prefix: &str) -> io::Result<PathBuf>
+{
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    for attempt in 0..100
+    {
+        let dir = std::env::temp_dir().join(format!("{}-{}-{}-{}", prefix, std::process::id(), nanos, attempt));
+        match create_private_dir(&dir) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            created => return created.map(|()| dir),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("no unused name for a {} directory", prefix)))
+}
+
+#[cfg(unix)]
+fn create_private_dir (dir: &Path) -> io::Result<()>
+{
+    use std::os::unix::fs::DirBuilderExt;
+
+    fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir (dir: &Path) -> io::Result<()>
+{
+    fs::create_dir(dir)
+}
+
+/// Have the console of stdout and stderr interpret escape sequences, as
+/// terminals elsewhere always do
+#[cfg(windows)]
+pub fn enable_ansi ()
+{
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle (handle: u32) -> *mut c_void;
+        fn GetConsoleMode (console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode (console: *mut c_void, mode: u32) -> i32;
+    }
+
+    for handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE]
+    {
+        // Not a console, such as a pipe, when the mode cannot be read
+        unsafe {
+            let console = GetStdHandle(handle);
+            let mut mode = 0;
+            if GetConsoleMode(console, &mut mode) != 0 {
+                SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn enable_ansi ()
+{
+}
@@ -0,0 +1,54 @@
+//! Output for scripts
+//!
+//! The tables are written for people and change whenever that helps them.
+//! What `--porcelain` writes is a contract instead: each version keeps its
+//! format for as long as the major version of the crate stays the same,
+//! and anything new comes as a new version next to it.
+//!
+//! Version 1 starts with the line `porcelain v1`, followed by one block per
+//! file, each ending in an empty line:
+//!
+//! ```text
+//! file src/main.rs
+//! lines 120
+//! bus-factor 1
+//! owner 100 83.3 4 <a@example.com> Alice Example
+//! owner 20 16.7 1 <b@example.com> Bob
+//! ```
+//!
+//! Owners come most lines first, with their lines, their share of the file
+//! in percent to one decimal, the commits their lines survive from, their
+//! email in angle brackets and their name up to the end of the line. A
+//! path or name holding a double quote, a backslash or a control character
+//! is quoted as git quotes paths.
+
+use crate::blame::quote;
+use crate::{percent, FileReport};
+
+/// The versions there are, the latest last
+pub const VERSIONS: &[&str] = &["v1"];
+
+/// `files` in version 1
+pub fn v1 (files: &[FileReport]) -> String
+{
+    let mut out = String::from("porcelain v1\n");
+    for file in files
+    {
+        out.push_str(&format!("file {}\n", quote(&file.path)));
+        out.push_str(&format!("lines {}\n", file.lines()));
+        out.push_str(&format!("bus-factor {}\n", file.bus_factor()));
+        let mut owners = file.ranked_owners();
+        owners.sort_by(|a, b| b.lines().cmp(&a.lines()).then_with(|| a.email.cmp(&b.email)));
+        for owner in owners.into_iter().filter(|owner| owner.lines() > 0)
+        {
+            out.push_str(&format!("owner {} {:.1} {} <{}> {}\n",
+                                  owner.lines(),
+                                  percent(owner.lines(), file.lines()),
+                                  owner.commits.len(),
+                                  quote(&owner.email),
+                                  quote(&owner.name)));
+        }
+        out.push('\n');
+    }
+    out
+}
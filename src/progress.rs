@@ -0,0 +1,187 @@
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use whoknows::json::Value;
+use whoknows::Error;
+
+/// Width of the bar itself
+const BAR: usize = 24;
+
+/// Longest path shown next to the bar
+const PATH: usize = 40;
+
+/// How progress is shown
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProgressFormat {
+    /// A bar on a terminal
+    Bar,
+    /// One JSON object per event, for programs to follow
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ProgressFormat, String> {
+        match s {
+            "bar" => Ok(ProgressFormat::Bar),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(format!("unknown progress format '{}'", s)),
+        }
+    }
+}
+
+/// A single line progress display on stderr, shared by the blame workers,
+/// or the events of the analysis as JSON lines
+pub struct Progress {
+    enabled: bool,
+    json: bool,
+    total: usize,
+    start: Instant,
+    done: Mutex<usize>,
+}
+
+impl Progress {
+    /// Progress over `total` files, hidden when `enabled` is false or when
+    /// stderr is not a terminal; events as JSON are written either way
+    pub fn new (total: usize, enabled: bool, format: ProgressFormat) -> Progress
+    {
+        let json = format == ProgressFormat::Json;
+        let progress = Progress {
+            enabled: enabled && !json && stderr_is_tty(),
+            json,
+            total,
+            start: Instant::now(),
+            done: Mutex::new(0),
+        };
+        progress.event("discovered", vec![("files", total.into())]);
+        progress
+    }
+
+    /// Remove the progress line before the report is printed
+    pub fn clear (&self)
+    {
+        if self.enabled {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+        let done = *self.done.lock().unwrap();
+        self.event("done", vec![
+            ("files", done.into()),
+            ("total", self.total.into()),
+            ("seconds", self.start.elapsed().as_secs_f64().into()),
+        ]);
+    }
+
+    /// Write `{"event": <name>, <fields>}` as a line of its own, if events
+    /// are wanted
+    fn event (&self, name: &str, mut fields: Vec<(&str, Value)>)
+    {
+        if !self.json {
+            return;
+        }
+        fields.insert(0, ("event", name.into()));
+        // A line at a time, so that the workers do not interleave theirs
+        let mut stderr = std::io::stderr().lock();
+        let _ = writeln!(stderr, "{}", Value::object(fields));
+    }
+
+    fn draw (&self, done: usize, path: &str)
+    {
+        let filled = (done * BAR).checked_div(self.total).unwrap_or(BAR);
+        let eta = match done {
+            0 => "--:--".to_string(),
+            _ => {
+                let per_file = self.start.elapsed() / done as u32;
+                format_duration(per_file * (self.total - done) as u32)
+            }
+        };
+
+        eprint!("\r\x1b[2K[{}{}] {}/{} ETA {} {}",
+                "=".repeat(filled),
+                " ".repeat(BAR - filled),
+                done,
+                self.total,
+                eta,
+                shorten(path));
+        let _ = std::io::stderr().flush();
+    }
+}
+
+impl whoknows::Observer for Progress {
+    fn started(&self, path: &Path) {
+        if self.enabled {
+            let done = self.done.lock().unwrap();
+            self.draw(*done, &path.display().to_string());
+        }
+        self.event("started", vec![("path", path.display().to_string().into())]);
+    }
+
+    fn finished(&self, path: &Path) {
+        let mut done = self.done.lock().unwrap();
+        *done += 1;
+        if self.enabled {
+            self.draw(*done, &path.display().to_string());
+        }
+        self.event("finished", vec![
+            ("path", path.display().to_string().into()),
+            ("done", (*done).into()),
+            ("total", self.total.into()),
+        ]);
+    }
+
+    fn failed(&self, path: &Path, error: &Error) {
+        self.event("failed", vec![
+            ("path", path.display().to_string().into()),
+            ("kind", error.kind().into()),
+            ("message", error.message().into()),
+        ]);
+    }
+
+    fn cached(&self, path: &Path, hit: bool) {
+        self.event("cache", vec![
+            ("path", path.display().to_string().into()),
+            ("hit", hit.into()),
+        ]);
+    }
+
+    fn cancelled(&self) -> bool {
+        crate::interrupt::interrupted()
+    }
+}
+
+fn format_duration (d: Duration) -> String
+{
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Keep the end of long paths, which is the part that tells files apart
+fn shorten (path: &str) -> String
+{
+    let chars: Vec<char> = path.chars().collect();
+    if chars.len() <= PATH {
+        return path.to_string();
+    }
+    let tail: String = chars[chars.len() - (PATH - 1)..].iter().collect();
+    format!("…{}", tail)
+}
+
+#[cfg(unix)]
+fn stderr_is_tty () -> bool
+{
+    unsafe { libc::isatty(libc::STDERR_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stderr_is_tty () -> bool
+{
+    false
+}
@@ -0,0 +1,96 @@
+use std::io::Write;
+
+use git2::Repository;
+
+use crate::directory::{directories, Directory};
+use crate::date;
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::roster::active_authors;
+use crate::FileReport;
+
+/// Authors without a commit in this many days no longer count as owners
+const ORPHAN_AFTER_DAYS: i64 = 365;
+
+/// Write the analysis of `rev` (HEAD if not given) as gauges in the
+/// Prometheus text exposition format, telling owners apart after `mailmap`
+pub fn write<W: Write> (
+    mut out: W,
+    repo: &Repository,
+    rev: Option<&str>,
+    mailmap: &Mailmap,
+    files: &[FileReport]
+) -> Result<(), Error>
+{
+    let now = date::now();
+    let active = active_authors(repo, rev, mailmap, now - ORPHAN_AFTER_DAYS * 24 * 60 * 60)?;
+    let dirs = directories(files);
+
+    let orphaned = |owners: &mut dyn Iterator<Item = (&String, usize)>| -> usize {
+        owners.filter(|(key, _)| !active.contains(*key))
+            .map(|(_, lines)| lines)
+            .sum()
+    };
+
+    gauge(&mut out, "whoknows_file_lines", "Lines in the file", files,
+          |f| f.lines() as f64)?;
+    gauge(&mut out, "whoknows_file_bus_factor",
+          "Fewest authors owning more than half of the file", files,
+          |f| f.bus_factor() as f64)?;
+    gauge(&mut out, "whoknows_file_top_owner_ratio",
+          "Share of the file owned by its largest owner", files,
+          |f| ratio(f.ranked_owners().first().map(|o| o.lines()).unwrap_or(0), f.lines()))?;
+    gauge(&mut out, "whoknows_file_orphaned_lines",
+          "Lines owned by authors without a commit in the last year", files,
+          |f| orphaned(&mut f.owners.iter().map(|(key, o)| (key, o.lines()))) as f64)?;
+
+    dir_gauge(&mut out, "whoknows_directory_lines", "Lines in the directory", &dirs,
+              |d| d.lines() as f64)?;
+    dir_gauge(&mut out, "whoknows_directory_bus_factor",
+              "Fewest authors owning more than half of the directory", &dirs,
+              |d| d.bus_factor() as f64)?;
+    dir_gauge(&mut out, "whoknows_directory_top_owner_ratio",
+              "Share of the directory owned by its largest owner", &dirs,
+              |d| ratio(d.top_owner_lines(), d.lines()))?;
+    dir_gauge(&mut out, "whoknows_directory_orphaned_lines",
+              "Lines owned by authors without a commit in the last year", &dirs,
+              |d| orphaned(&mut d.owners.iter().map(|(e, l)| (e, *l))) as f64)?;
+    Ok(())
+}
+
+fn gauge<W, F> (out: &mut W, name: &str, help: &str, files: &[FileReport], value: F)
+    -> Result<(), Error>
+    where W: Write, F: Fn(&FileReport) -> f64
+{
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} gauge", name)?;
+    for file in files
+    {
+        writeln!(out, "{}{{path=\"{}\"}} {}", name, escape(&file.path), value(file))?;
+    }
+    Ok(())
+}
+
+fn dir_gauge<W, F> (out: &mut W, name: &str, help: &str, dirs: &[Directory], value: F)
+    -> Result<(), Error>
+    where W: Write, F: Fn(&Directory) -> f64
+{
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} gauge", name)?;
+    for dir in dirs
+    {
+        writeln!(out, "{}{{directory=\"{}\"}} {}", name, escape(&dir.path), value(dir))?;
+    }
+    Ok(())
+}
+
+fn ratio (part: usize, total: usize) -> f64
+{
+    crate::percent(part, total) / 100.0
+}
+
+/// Escape a label value
+fn escape (value: &str) -> String
+{
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,123 @@
+//! Answering ownership questions of an editor over stdin and stdout
+//!
+//! Every line of input is a JSON request such as
+//! `{"file": "src/main.rs", "line": 120}` and gets one line of JSON in
+//! return, in order. An `id` in the request is echoed in the response. The
+//! blame of every file asked about is kept until HEAD moves, so that asking
+//! about the next line as the cursor moves costs nothing.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use git2::{Oid, Repository};
+
+use whoknows::json::{self, Value};
+use whoknows::{Error, FileReport, Options, Region};
+
+struct Blamed {
+    report: FileReport,
+    regions: Vec<Region>,
+}
+
+/// Answer requests on stdin until it is closed
+pub fn serve (repo: &Repository, opts: &Options) -> Result<(), Error>
+{
+    let mut known: HashMap<(Oid, PathBuf), Blamed> = HashMap::new();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for line in stdin.lock().lines()
+    {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (id, response) = match json::parse(&line) {
+            Ok(request) => {
+                let id = request.get("id").cloned();
+                let response = answer(repo, opts, &mut known, &request)
+                    .unwrap_or_else(|e| Value::object(vec![("error", e.to_string().into())]));
+                (id, response)
+            }
+            Err(e) => (None, Value::object(vec![("error", e.into())])),
+        };
+        let response = match (id, response) {
+            (Some(id), Value::Object(mut members)) => {
+                members.insert(0, ("id".to_string(), id));
+                Value::Object(members)
+            }
+            (_, response) => response,
+        };
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn answer (
+    repo: &Repository,
+    opts: &Options,
+    known: &mut HashMap<(Oid, PathBuf), Blamed>,
+    request: &Value
+) -> Result<Value, Error>
+{
+    let file = request.get("file").and_then(Value::as_str)
+        .ok_or_else(|| Error::Usage("missing \"file\"".to_string()))?;
+    let path = if Path::new(file).is_absolute() {
+        whoknows::repo_relative(repo, Path::new(file))?
+    } else {
+        PathBuf::from(file)
+    };
+    let head = repo.head()?.peel_to_commit()?;
+    if head.tree()?.get_path(&path).is_err() {
+        return Err(Error::Untracked(path.display().to_string()));
+    }
+
+    let key = (head.id(), path.clone());
+    if !known.contains_key(&key) {
+        // Whatever HEAD moved away from is not coming back
+        known.retain(|(oid, _), _| *oid == head.id());
+        let blamed = Blamed {
+            report: whoknows::analyze_file(repo, &path, opts)?,
+            regions: whoknows::blame_regions(repo, &path, opts)?,
+        };
+        known.insert(key.clone(), blamed);
+    }
+    let blamed = &known[&key];
+
+    let mut members = vec![("file".to_string(), path.display().to_string().into())];
+    if let Some(line) = request.get("line") {
+        let line = line.as_f64().filter(|l| *l >= 1.0 && l.fract() == 0.0)
+            .ok_or_else(|| Error::Usage("\"line\" must be a positive integer".to_string()))? as usize;
+        let region = blamed.regions.iter()
+            .find(|r| (r.line..r.line + r.lines).contains(&line))
+            .ok_or_else(|| Error::Path(format!("{} has no line {}", path.display(), line)))?;
+        members.push(("line".to_string(), line.into()));
+        members.push(("author".to_string(), Value::object(vec![
+            ("name", region.name.as_str().into()),
+            ("email", region.email.as_str().into()),
+        ])));
+        members.push(("commit".to_string(), commit(repo, region)));
+    }
+    if let Value::Object(report) = json::file_report(&blamed.report) {
+        members.extend(report.into_iter().filter(|(key, _)| key != "path"));
+    }
+    Ok(Value::Object(members))
+}
+
+/// The commit a line came from, or null for uncommitted lines
+fn commit (repo: &Repository, region: &Region) -> Value
+{
+    if region.commit.is_zero() {
+        return Value::Null;
+    }
+    let summary = repo.find_commit(region.commit).ok()
+        .and_then(|c| c.summary().map(str::to_string))
+        .unwrap_or_default();
+    Value::object(vec![
+        ("id", region.commit.to_string().into()),
+        ("summary", summary.into()),
+        ("time", (region.time as f64).into()),
+    ])
+}
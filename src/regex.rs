@@ -0,0 +1,428 @@
+//! Small regular expressions for matching names and emails
+//!
+//! Literals, `.`, classes like `[a-z]` and `[^0-9]`, the escapes `\d`, `\w`
+//! and `\s`, anchors `^` and `$`, groups, alternation with `|` and the
+//! greedy quantifiers `*`, `+`, `?` and `{n,m}`. A leading `(?i)` makes the
+//! whole expression case insensitive. Matching backtracks, which is plenty
+//! for strings this short.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone)]
+pub struct Regex {
+    source: String,
+    alternatives: Vec<Vec<Node>>,
+    ignore_case: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+impl Regex {
+    pub fn new (source: &str) -> Result<Regex, String>
+    {
+        let (ignore_case, pattern) = match source.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, source),
+        };
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0 };
+        let alternatives = parser.alternatives()
+            .map_err(|e| format!("invalid regex '{}': {}", source, e))?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("invalid regex '{}': unmatched ')'", source));
+        }
+        Ok(Regex { source: source.to_string(), alternatives, ignore_case })
+    }
+
+    pub fn as_str (&self) -> &str
+    {
+        &self.source
+    }
+
+    /// Whether the expression matches anywhere in `text`
+    pub fn is_match (&self, text: &str) -> bool
+    {
+        let text: Vec<char> = if self.ignore_case {
+            text.chars().flat_map(char::to_lowercase).collect()
+        } else {
+            text.chars().collect()
+        };
+        let m = Matcher { text: &text, ignore_case: self.ignore_case };
+        (0..=text.len()).any(|start| m.alternatives(&self.alternatives, start, &mut |_| true))
+    }
+}
+
+impl FromStr for Regex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Regex, String> {
+        Regex::new(s)
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Regex({:?})", self.source)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek (&self) -> Option<char>
+    {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next (&mut self) -> Result<char, String>
+    {
+        let c = self.peek().ok_or_else(|| "unexpected end".to_string())?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    /// `a|b|c` up to a closing parenthesis or the end
+    fn alternatives (&mut self) -> Result<Vec<Vec<Node>>, String>
+    {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence (&mut self) -> Result<Vec<Node>, String>
+    {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn atom (&mut self) -> Result<Node, String>
+    {
+        Ok(match self.next()? {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                // Non-capturing groups are all there is anyway
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let group = self.alternatives()?;
+                if self.peek() != Some(')') {
+                    return Err("unclosed '('".to_string());
+                }
+                self.pos += 1;
+                Node::Group(group)
+            }
+            '[' => self.class()?,
+            '\\' => self.escape()?,
+            c @ ('*' | '+' | '?') => return Err(format!("nothing to repeat before '{}'", c)),
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape (&mut self) -> Result<Node, String>
+    {
+        let class = |ranges: &[(char, char)], negated| Node::Class { ranges: ranges.to_vec(), negated };
+        Ok(match self.next()? {
+            'd' => class(DIGIT, false),
+            'D' => class(DIGIT, true),
+            'w' => class(WORD, false),
+            'W' => class(WORD, true),
+            's' => class(SPACE, false),
+            'S' => class(SPACE, true),
+            c => Node::Char(c),
+        })
+    }
+
+    fn class (&mut self) -> Result<Node, String>
+    {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().map_err(|_| "unclosed '['".to_string())?;
+            if c == ']' && !first {
+                return Ok(Node::Class { ranges, negated });
+            }
+            first = false;
+            let low = if c == '\\' {
+                match self.next()? {
+                    'd' => { ranges.extend_from_slice(DIGIT); continue; }
+                    'w' => { ranges.extend_from_slice(WORD); continue; }
+                    's' => { ranges.extend_from_slice(SPACE); continue; }
+                    c => c,
+                }
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let high = self.next()?;
+                if high < low {
+                    return Err(format!("invalid range '{}-{}'", low, high));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+    }
+
+    fn quantified (&mut self, node: Node) -> Result<Node, String>
+    {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.counted(node),
+            _ => return Ok(node),
+        };
+        self.pos += 1;
+        Ok(Node::Repeat { node: Box::new(node), min, max })
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}`
+    fn counted (&mut self, node: Node) -> Result<Node, String>
+    {
+        let start = self.pos;
+        let end = self.chars[start..].iter().position(|&c| c == '}')
+            .ok_or_else(|| "unclosed '{'".to_string())? + start;
+        let spec: String = self.chars[start + 1..end].iter().collect();
+        let number = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("invalid count '{{{}}}'", spec));
+        let (min, max) = match spec.find(',') {
+            None => { let n = number(&spec)?; (n, Some(n)) }
+            Some(i) if spec[i + 1..].trim().is_empty() => (number(&spec[..i])?, None),
+            Some(i) => (number(&spec[..i])?, Some(number(&spec[i + 1..])?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid count '{{{}}}'", spec));
+        }
+        self.pos = end + 1;
+        Ok(Node::Repeat { node: Box::new(node), min, max })
+    }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')];
+
+/// Continuation passing backtracking: every function calls `k` with each
+/// position a match could end at until `k` accepts one
+struct Matcher<'t> {
+    text: &'t [char],
+    ignore_case: bool,
+}
+
+impl<'t> Matcher<'t> {
+    fn alternatives (&self, alternatives: &[Vec<Node>], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool
+    {
+        alternatives.iter().any(|nodes| self.sequence(nodes, pos, k))
+    }
+
+    fn sequence (&self, nodes: &[Node], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool
+    {
+        match nodes.split_first() {
+            None => k(pos),
+            Some((node, rest)) => self.node(node, pos, &mut |next| self.sequence(rest, next, k)),
+        }
+    }
+
+    fn node (&self, node: &Node, pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool
+    {
+        match node {
+            Node::Start => pos == 0 && k(pos),
+            Node::End => pos == self.text.len() && k(pos),
+            Node::Group(alternatives) => self.alternatives(alternatives, pos, k),
+            Node::Repeat { node, min, max } => self.repeat(node, *min, *max, 0, pos, k),
+            _ => match self.text.get(pos) {
+                Some(&c) if self.single(node, c) => k(pos + 1),
+                _ => false,
+            },
+        }
+    }
+
+    /// Greedy: try one more repetition before settling for `count`
+    fn repeat (
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        count: usize,
+        pos: usize,
+        k: &mut dyn FnMut(usize) -> bool
+    ) -> bool
+    {
+        if max.is_none_or(|max| count < max) {
+            // An empty repetition would loop forever
+            let more = self.node(node, pos, &mut |next| {
+                next != pos && self.repeat(node, min, max, count + 1, next, k)
+            });
+            if more {
+                return true;
+            }
+        }
+        count >= min && k(pos)
+    }
+
+    /// Whether a single character node matches `c`
+    fn single (&self, node: &Node, c: char) -> bool
+    {
+        match node {
+            Node::Any => c != '\n',
+            Node::Char(expected) => self.fold(*expected) == c,
+            Node::Class { ranges, negated } => {
+                let inside = ranges.iter().any(|&(low, high)| {
+                    (low..=high).contains(&c)
+                        || (self.ignore_case && (low..=high).contains(&c.to_ascii_uppercase()))
+                });
+                inside != *negated
+            }
+            _ => false,
+        }
+    }
+
+    /// `c` as it appears in the text, which is lowercased when ignoring case
+    fn fold (&self, c: char) -> char
+    {
+        if self.ignore_case {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches (pattern: &str, text: &str) -> bool
+    {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn literals_match_anywhere ()
+    {
+        assert!(matches("bot", "dependabot[bot]"));
+        assert!(!matches("bot", "Robert"));
+        assert!(matches("", "anything"));
+        assert!(matches("a.c", "xabcx"));
+        assert!(!matches("a.c", "a\nc"));
+        assert!(matches(r"a\.c", "a.c"));
+        assert!(!matches(r"a\.c", "abc"));
+    }
+
+    #[test]
+    fn anchors ()
+    {
+        assert!(matches("^ci-", "ci-runner"));
+        assert!(!matches("^ci-", "not-ci-runner"));
+        assert!(matches("@example\\.com$", "jane@example.com"));
+        assert!(!matches("@example\\.com$", "jane@example.com.evil"));
+        assert!(matches("^$", ""));
+        assert!(!matches("^$", "x"));
+        assert!(matches("^(a|b)$", "b"));
+    }
+
+    #[test]
+    fn classes ()
+    {
+        assert!(matches("^[a-c]+$", "abcab"));
+        assert!(!matches("^[a-c]+$", "abd"));
+        assert!(matches("^[^0-9]+$", "abc"));
+        assert!(!matches("^[^0-9]+$", "ab1"));
+        assert!(matches(r"^\d\d$", "42"));
+        assert!(!matches(r"^\D", "4"));
+        assert!(matches(r"^\w+\s\w+$", "Jane Doe"));
+        assert!(!matches(r"^\S+$", "Jane Doe"));
+        assert!(matches(r"^[\d.-]+$", "1.2-3"));
+        assert!(matches("^[]a]+$", "]a]"));
+        assert!(matches("^[a-]+$", "a-a"));
+    }
+
+    #[test]
+    fn alternation_and_groups ()
+    {
+        assert!(matches("^(bot|ci)@", "ci@corp.com"));
+        assert!(!matches("^(bot|ci)@", "cd@corp.com"));
+        assert!(matches("^a|b$", "ax"));
+        assert!(matches("^a|b$", "xb"));
+        assert!(!matches("^a|b$", "xa"));
+        assert!(matches("^(?:ab)+$", "ababab"));
+        assert!(matches("^((a|b)c)+$", "acbc"));
+        assert!(matches("^(|x)y$", "y"));
+    }
+
+    #[test]
+    fn repetition ()
+    {
+        assert!(matches("^ab*c$", "ac"));
+        assert!(matches("^ab*c$", "abbbc"));
+        assert!(!matches("^ab+c$", "ac"));
+        assert!(matches("^ab?c$", "abc"));
+        assert!(!matches("^ab?c$", "abbc"));
+        assert!(matches("^a{3}$", "aaa"));
+        assert!(!matches("^a{3}$", "aaaa"));
+        assert!(matches("^a{2,}$", "aaaaa"));
+        assert!(!matches("^a{2,}$", "a"));
+        assert!(matches("^a{1,2}b$", "aab"));
+        assert!(!matches("^a{1,2}b$", "aaab"));
+        // Greedy repetition gives back what the rest needs
+        assert!(matches("^.*@.*\\.com$", "a@b.c@d.com"));
+        // Repeating what can match nothing terminates
+        assert!(matches("^(a*)*$", "aaa"));
+        assert!(!matches("^(a*)*$", "aab"));
+    }
+
+    #[test]
+    fn ignoring_case ()
+    {
+        assert!(matches("(?i)^jane", "JANE Doe"));
+        assert!(matches("(?i)^[a-z]+$", "MiXeD"));
+        assert!(!matches("^jane", "JANE"));
+        assert!(matches("(?i)^émile$", "ÉMILE"));
+    }
+
+    #[test]
+    fn invalid_patterns ()
+    {
+        let error = |pattern: &str| Regex::new(pattern).err().unwrap();
+        assert_eq!(error("(ab"), "invalid regex '(ab': unclosed '('");
+        assert_eq!(error("ab)"), "invalid regex 'ab)': unmatched ')'");
+        assert_eq!(error("[ab"), "invalid regex '[ab': unclosed '['");
+        assert_eq!(error("[z-a]"), "invalid regex '[z-a]': invalid range 'z-a'");
+        assert_eq!(error("*a"), "invalid regex '*a': nothing to repeat before '*'");
+        assert_eq!(error("a|+"), "invalid regex 'a|+': nothing to repeat before '+'");
+        assert_eq!(error("a{2"), "invalid regex 'a{2': unclosed '{'");
+        assert_eq!(error("a{x}"), "invalid regex 'a{x}': invalid count '{x}'");
+        assert_eq!(error("a{3,1}"), "invalid regex 'a{3,1}': invalid count '{3,1}'");
+        assert_eq!(error("a\\"), "invalid regex 'a\\': unexpected end");
+        assert_eq!("^x$".parse::<Regex>().unwrap().as_str(), "^x$");
+    }
+}
@@ -0,0 +1,139 @@
+//! Analyzing a repository that is only known by its URL
+//!
+//! The repository is cloned into a temporary directory without the
+//! contents of any file but the requested ones, and with only as much
+//! history as blame needs: starting from the latest commits, it is deepened
+//! until none of the requested paths is there yet in the oldest commits
+//! fetched, so that no line is blamed on where the history was cut off.
+//! Blame has to run `git`, which fetches what else it needs on its own.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use whoknows::{platform, Error};
+
+/// Commits fetched at first, doubled with every deepening
+const DEPTH: usize = 50;
+
+/// Files read from the work tree besides the requested ones
+const SETTINGS: &[&str] = &[".mailmap", ".git-whoknows.toml", ".whoknowsignore"];
+
+/// Whether `arg` names a remote repository rather than a local path
+pub fn is_url (arg: &str) -> bool
+{
+    if arg.contains("://") {
+        return true;
+    }
+    // user@host:path, as ssh has it
+    match (arg.find('@'), arg.find(':'), arg.find('/')) {
+        (Some(at), Some(colon), slash) => at < colon && slash.is_none_or(|slash| colon < slash),
+        _ => false,
+    }
+}
+
+/// A clone in a directory of its own, removed again when dropped
+pub struct Clone {
+    pub dir: PathBuf,
+}
+
+impl Clone {
+    /// Clone `url` with the files at `paths`, relative to its root, or
+    /// with every file if there are none
+    pub fn new (url: &str, paths: &[PathBuf], quiet: bool) -> Result<Clone, Error>
+    {
+        let dir = platform::temp_dir("git-whoknows")
+            .map_err(|e| Error::Remote(format!("cannot create a directory to clone {} into: {}", url, e)))?;
+        let clone = Clone { dir };
+        if !quiet {
+            eprintln!("cloning {}", url);
+        }
+        let dir = clone.dir.to_string_lossy().into_owned();
+        git(None, &["clone", "--quiet", "--filter=blob:none", "--no-checkout",
+                    &format!("--depth={}", DEPTH), "--", url, &dir])?;
+        // libgit2 refuses to open anything newer than version 0, and git
+        // still knows the clone is partial without the version telling it
+        git(Some(&clone.dir), &["config", "core.repositoryformatversion", "0"])?;
+        if !paths.is_empty() {
+            let mut set = vec!["sparse-checkout".to_string(), "set".to_string(), "--no-cone".to_string()];
+            set.extend(SETTINGS.iter().map(|file| format!("/{}", file)));
+            set.extend(paths.iter().map(|path| format!("/{}", path.display())));
+            git(Some(&clone.dir), &set.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        git(Some(&clone.dir), &["checkout", "--quiet"])?;
+        clone.deepen(paths, quiet)?;
+        Ok(clone)
+    }
+
+    /// Fetch more history until `paths` did not exist yet at any of the
+    /// commits it was cut off at
+    fn deepen (&self, paths: &[PathBuf], quiet: bool) -> Result<(), Error>
+    {
+        let mut depth = DEPTH;
+        // Without any paths every file counts
+        let paths: Vec<String> = match paths {
+            [] => vec![String::new()],
+            paths => paths.iter().map(|path| path.display().to_string()).collect(),
+        };
+        while let Ok(shallow) = fs::read_to_string(self.dir.join(".git").join("shallow")) {
+            let cut = shallow.lines().any(|commit| {
+                paths.iter().any(|path| exists(&self.dir, commit.trim(), path))
+            });
+            if !cut {
+                break;
+            }
+            if !quiet {
+                eprintln!("cloning: fetching {} more commits", depth);
+            }
+            git(Some(&self.dir), &["fetch", "--quiet", &format!("--deepen={}", depth)])?;
+            depth *= 2;
+        }
+        Ok(())
+    }
+
+    /// `paths` as paths into the clone, or its root if there are none
+    pub fn paths (&self, paths: &[PathBuf]) -> Vec<PathBuf>
+    {
+        match paths {
+            [] => vec![self.dir.clone()],
+            paths => paths.iter().map(|path| self.dir.join(path)).collect(),
+        }
+    }
+}
+
+impl Drop for Clone {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Whether `path` is in the tree of `commit`, which only needs the trees
+/// that were fetched and not the blobs that were not
+fn exists (dir: &Path, commit: &str, path: &str) -> bool
+{
+    let mut cmd = Command::new(platform::git());
+    cmd.arg("-C").arg(dir).args(["ls-tree", commit]);
+    if !path.is_empty() {
+        cmd.arg("--").arg(path);
+    }
+    cmd.stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+fn git (dir: Option<&Path>, args: &[&str]) -> Result<(), Error>
+{
+    let mut cmd = Command::new(platform::git());
+    if let Some(dir) = dir {
+        cmd.arg("-C").arg(dir);
+    }
+    let output = cmd.args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::Remote(format!("could not run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::Remote(format!("git {}: {}", args[0],
+                                         String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(())
+}
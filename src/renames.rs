@@ -0,0 +1,66 @@
+//! The names a file had before
+//!
+//! Blame follows a file through its renames, so the owners of a file may
+//! have written their lines under another name, in another directory.
+//! Every rename on the way is found as `git log --follow --name-status`
+//! finds it, so that numbers spanning several names can be read as such.
+
+use std::path::Path;
+use std::process::Command;
+
+use git2::{Oid, Repository};
+
+use crate::blame::unquote;
+use crate::error::Error;
+use crate::git::{self, output};
+
+/// A commit that gave the file another name
+pub struct Rename {
+    pub commit: Oid,
+    /// Author time, seconds since the epoch
+    pub time: i64,
+    pub from: String,
+    pub to: String,
+}
+
+/// The `git log` that `chain` runs
+pub fn command (repo: &Repository, path: &Path, rev: &str) -> Result<Command, Error>
+{
+    git::command(repo, &["log".as_ref(), "--no-color".as_ref(), "--follow".as_ref(), "--name-status".as_ref(),
+                         "--format=%x00%H%x1f%at".as_ref(), rev.as_ref(), "--".as_ref(), path.as_os_str()])
+}
+
+/// Every rename of `path` up to `rev`, oldest first
+pub fn chain (repo: &Repository, path: &Path, rev: &str) -> Result<Vec<Rename>, Error>
+{
+    let output = output(command(repo, path, rev)?)?;
+    let mut renames = Vec::new();
+    for commit in output.split('\0').skip(1)
+    {
+        let (header, changes) = commit.split_once('\n').unwrap_or((commit, ""));
+        let (commit, time) = match header.split_once('\x1f') {
+            Some((hash, time)) => match Oid::from_str(hash) {
+                Ok(oid) => (oid, time.trim().parse::<i64>().unwrap_or(0)),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        // R<similarity> <from> <to>, tab separated
+        for line in changes.lines()
+        {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if let [status, from, to] = fields.as_slice() {
+                if status.starts_with('R') {
+                    renames.push(Rename {
+                        commit,
+                        time,
+                        from: unquote(from).into_owned(),
+                        to: unquote(to).into_owned(),
+                    });
+                }
+            }
+        }
+    }
+    renames.reverse();
+    Ok(renames)
+}
@@ -0,0 +1,139 @@
+//! What changed between two saved reports
+//!
+//! Reports saved with `--format json`, or snapshots as kept in the notes,
+//! are compared without blaming anything again: who owns lines now and did
+//! not before, who no longer owns any, and which files lost knowledge
+//! holders. Comparing the report of this week with that of last week is a
+//! matter of reading two files.
+
+use std::collections::HashMap;
+
+use git2::{ObjectType, Oid};
+
+use crate::blame::Hunk;
+use crate::error::Error;
+use crate::json::{self, Value};
+use crate::snapshot::Snapshot;
+use crate::{owner_key, FileReport};
+
+/// The files of a report saved with `--format json`, of one with a file
+/// per line, or of a snapshot
+pub fn decode (text: &str) -> Result<Vec<FileReport>, Error>
+{
+    let invalid = |what: &str| Error::Export(format!("not a whoknows report: {}", what));
+    let value = match json::parse(text) {
+        Ok(value) => value,
+        // One file per line, as jobs append their results
+        Err(_) if text.trim().lines().count() > 1 => {
+            return text.lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(n, line)| {
+                    let file = json::parse(line).map_err(|e| invalid(&format!("line {}: {}", n + 1, e)))?;
+                    decode_file(&file).map_err(|what| invalid(&format!("line {}: {}", n + 1, what)))
+                })
+                .collect();
+        }
+        Err(e) => return Err(invalid(&e)),
+    };
+    let files = match value.as_array() {
+        Some(files) => files,
+        None if value.get("version").is_some() => return Ok(Snapshot::decode(text)?.files),
+        None if value.get("path").is_some() => std::slice::from_ref(&value),
+        None => return Err(invalid("neither an array of files nor a snapshot")),
+    };
+    files.iter().map(|file| decode_file(file).map_err(|what| invalid(&what))).collect()
+}
+
+/// One file of a report
+fn decode_file (file: &Value) -> Result<FileReport, String>
+{
+    let path = file.get("path").and_then(Value::as_str).ok_or_else(|| "a file without path".to_string())?;
+    let mut report = FileReport::new(path);
+    for owner in file.get("owners").and_then(Value::as_array).unwrap_or_default()
+    {
+        let name = owner.get("name").and_then(Value::as_str).unwrap_or("");
+        let email = owner.get("email").and_then(Value::as_str).unwrap_or("");
+        let lines = owner.get("lines").and_then(Value::as_f64).unwrap_or(0.0) as usize;
+        // Reports only keep how many commits there were; as many stand-ins
+        // keep that count when owners are merged
+        let commits = owner.get("commits").and_then(Value::as_f64).unwrap_or(1.0).max(1.0) as usize;
+        for i in 0..commits
+        {
+            let stand_in = format!("{}\n{}\n{}", path, email, i);
+            let commit = Oid::hash_object(ObjectType::Blob, stand_in.as_bytes()).map_err(|e| e.message().to_string())?;
+            let lines = lines / commits + usize::from(i < lines % commits);
+            report.add_hunk(&Hunk { commit, name, email, time: 0, lines, origin: None });
+        }
+    }
+    Ok(report)
+}
+
+/// Someone's lines over a whole report
+#[derive(Clone)]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+}
+
+/// A file whose bus factor went down
+pub struct Regression {
+    pub path: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+pub struct ReportDiff {
+    /// Owners of lines after who owned none before, most lines first
+    pub joined: Vec<Person>,
+    /// Owners of lines before who own none after, most lines first
+    pub departed: Vec<Person>,
+    /// Files of both reports whose bus factor went down, by path
+    pub regressions: Vec<Regression>,
+}
+
+/// Everyone owning lines of `files` by `owner_key`
+fn people (files: &[FileReport]) -> HashMap<String, Person>
+{
+    let mut people: HashMap<String, Person> = HashMap::new();
+    for owner in files.iter().flat_map(|file| file.owners.values()).filter(|owner| owner.lines() > 0)
+    {
+        people.entry(owner_key(&owner.name, &owner.email).into_owned())
+            .or_insert_with(|| Person { name: owner.name.clone(), email: owner.email.clone(), lines: 0 })
+            .lines += owner.lines();
+    }
+    people
+}
+
+/// Those of `people` not among `others`, most lines first
+fn missing (people: &HashMap<String, Person>, others: &HashMap<String, Person>) -> Vec<Person>
+{
+    let mut missing: Vec<Person> = people.iter()
+        .filter(|(key, _)| !others.contains_key(*key))
+        .map(|(_, person)| person.clone())
+        .collect();
+    missing.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+    missing
+}
+
+pub fn compare (before: &[FileReport], after: &[FileReport]) -> ReportDiff
+{
+    let (old, new) = (people(before), people(after));
+    let joined = missing(&new, &old);
+    let departed = missing(&old, &new);
+
+    let old: HashMap<&str, &FileReport> = before.iter().map(|file| (file.path.as_str(), file)).collect();
+    let mut regressions: Vec<Regression> = after.iter()
+        .filter_map(|file| {
+            let before = old.get(file.path.as_str())?.bus_factor();
+            (file.bus_factor() < before).then(|| Regression {
+                path: file.path.clone(),
+                before,
+                after: file.bus_factor(),
+            })
+        })
+        .collect();
+    regressions.sort_by(|a, b| a.path.cmp(&b.path));
+    ReportDiff { joined, departed, regressions }
+}
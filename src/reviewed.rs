@@ -0,0 +1,114 @@
+//! Who reviewed the code that survives
+//!
+//! In many teams whoever reviewed a change knows the code about as well as
+//! whoever wrote it. `Reviewed-by:` and `Acked-by:` trailers of the commits
+//! a file owns lines through credit those lines to the reviewers as well,
+//! leaving the authors' ownership as it is.
+//!
+//! In kernel-style workflows patches reach the tree through maintainers
+//! who add their `Signed-off-by:` on the way, and that chain says who
+//! shepherds the code as much as authorship does. Those are credited the
+//! same way, but apart from the reviewers.
+
+use std::collections::HashMap;
+
+use crate::commits::Commits;
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::ownership::{normalize_email, normalize_name, owner_key};
+use crate::FileReport;
+
+/// Trailers that name someone who reviewed a commit
+const REVIEW_TRAILERS: &[&str] = &["reviewed-by", "acked-by"];
+
+/// Trailers that name someone who passed a commit on towards the tree
+const SIGNOFF_TRAILERS: &[&str] = &["signed-off-by"];
+
+/// Someone who reviewed, or signed off, commits that lines of a file
+/// survive from
+pub struct Reviewer {
+    pub name: String,
+    pub email: String,
+    /// Surviving lines of the commits they reviewed or signed off
+    pub lines: usize,
+    pub commits: usize,
+}
+
+/// `Name <email>`, or either on its own, of a trailer value
+pub fn identity (value: &str) -> (&str, &str)
+{
+    match value.rsplit_once('<') {
+        Some((name, email)) => (name.trim(), email.trim_end().trim_end_matches('>')),
+        None if value.contains('@') => ("", value.trim()),
+        None => (value.trim(), ""),
+    }
+}
+
+/// Everyone the commits of `file` name as their reviewers, merged through
+/// `mailmap`, most lines first; nobody is credited for reviewing their
+/// own commit
+pub fn reviewers (file: &FileReport, commits: &mut Commits, mailmap: &Mailmap) -> Result<Vec<Reviewer>, Error>
+{
+    credited(file, commits, mailmap, REVIEW_TRAILERS)
+}
+
+/// Everyone who signed off the commits of `file` other than their authors,
+/// merged through `mailmap`, most lines first
+pub fn shepherds (file: &FileReport, commits: &mut Commits, mailmap: &Mailmap) -> Result<Vec<Reviewer>, Error>
+{
+    credited(file, commits, mailmap, SIGNOFF_TRAILERS)
+}
+
+/// Everyone named by one of `trailers` of the commits of `file` but their
+/// authors, most lines first
+fn credited (
+    file: &FileReport,
+    commits: &mut Commits,
+    mailmap: &Mailmap,
+    trailers: &[&str]
+) -> Result<Vec<Reviewer>, Error>
+{
+    let mut reviewers: HashMap<String, Reviewer> = HashMap::new();
+    for owner in file.owners.values()
+    {
+        for (oid, contribution) in &owner.commits
+        {
+            // Uncommitted lines have no message
+            if oid.is_zero() {
+                continue;
+            }
+            let commit = commits.get(*oid)?;
+            // By the commit rather than the owner, which may go by another
+            // name in the report
+            let (name, email) = mailmap.resolve(&commit.author_name, &commit.author_email);
+            let author = owner_key(name, email).into_owned();
+            // Reviewed and acked by the same person is one review
+            let mut credited = Vec::new();
+            for (key, value) in commit.trailers()
+            {
+                if !trailers.contains(&key.to_ascii_lowercase().as_str()) {
+                    continue;
+                }
+                let (name, email) = identity(value);
+                let (name, email) = mailmap.resolve(name, email);
+                let reviewer_key = owner_key(name, email);
+                if reviewer_key.is_empty() || reviewer_key == author.as_str() || credited.contains(&reviewer_key) {
+                    continue;
+                }
+                credited.push(reviewer_key.clone());
+                let reviewer = reviewers.entry(reviewer_key.into_owned()).or_insert_with(|| Reviewer {
+                    name: normalize_name(name),
+                    email: normalize_email(email).into_owned(),
+                    lines: 0,
+                    commits: 0,
+                });
+                reviewer.lines += contribution.lines;
+                reviewer.commits += 1;
+            }
+        }
+    }
+
+    let mut reviewers: Vec<Reviewer> = reviewers.into_values().collect();
+    reviewers.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+    Ok(reviewers)
+}
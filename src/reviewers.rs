@@ -0,0 +1,138 @@
+//! Who should review a change
+//!
+//! The people who own the most lines of the code a change touches are the
+//! ones most likely to spot what it breaks. Only the old side of a diff
+//! counts: a new file has nobody to ask yet.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use git2::{DiffOptions, Oid, Repository};
+
+use crate::blame::{unquote, Hunk};
+use crate::error::Error;
+use crate::{FileReport, Options};
+
+pub struct Reviewer {
+    pub name: String,
+    pub email: String,
+    /// Lines owned across the touched files
+    pub lines: usize,
+    /// The commit through which the most of those lines are owned, for
+    /// looking the author up elsewhere
+    pub commit: Oid,
+}
+
+/// Paths on the old side of a unified diff, which is the code somebody
+/// already knows; deleted and modified files count, added ones do not
+pub fn touched_files (diff: &str) -> Vec<String>
+{
+    let mut files = Vec::new();
+    let mut lines = diff.lines().peekable();
+    while let Some(line) = lines.next() {
+        // A removed line starting with "-- " looks just like a file header,
+        // only the "+++ " that follows a real one tells them apart
+        let header = lines.peek().is_some_and(|next| next.starts_with("+++ "));
+        if let Some(path) = line.strip_prefix("--- ").filter(|_| header) {
+            let path = unquote(path.split('\t').next().unwrap_or(path));
+            if path == "/dev/null" {
+                continue;
+            }
+            let path = path.strip_prefix("a/").unwrap_or(&path).to_string();
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// The owners of `files` by the lines they own in all of them, most first
+pub fn rank<'a, I> (files: I) -> Vec<Reviewer>
+    where I: IntoIterator<Item = &'a FileReport>
+{
+    let mut reviewers: HashMap<String, (Reviewer, usize)> = HashMap::new();
+    for file in files
+    {
+        for (key, owner) in &file.owners
+        {
+            let (reviewer, best) = reviewers.entry(key.clone())
+                .or_insert_with(|| (Reviewer {
+                    name: owner.name.clone(),
+                    email: owner.email.clone(),
+                    lines: 0,
+                    commit: Oid::zero(),
+                }, 0));
+            reviewer.lines += owner.lines();
+            for (oid, contribution) in &owner.commits
+            {
+                if contribution.lines > *best {
+                    reviewer.commit = *oid;
+                    *best = contribution.lines;
+                }
+            }
+        }
+    }
+
+    let mut ranked: Vec<Reviewer> = reviewers.into_iter().map(|(_, (r, _))| r).collect();
+    ranked.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+    ranked
+}
+
+/// Who owned the lines `commit` changed or removed, blamed as of its first
+/// parent, one report per file it modified
+pub fn changed_by (repo: &Repository, commit: Oid, opts: &Options) -> Result<Vec<FileReport>, Error>
+{
+    let commit = repo.find_commit(commit)?;
+    let parent = match commit.parents().next() {
+        Some(parent) => parent,
+        // A root commit has nobody's code to change
+        None => return Ok(Vec::new()),
+    };
+    let mut diff_opts = DiffOptions::new();
+    diff_opts.context_lines(0);
+    let diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&commit.tree()?), Some(&mut diff_opts))?;
+
+    // Old side line ranges by path, in the order of the diff
+    let mut ranges: Vec<(PathBuf, Vec<(usize, usize)>)> = Vec::new();
+    diff.foreach(&mut |_, _| true, None, Some(&mut |delta, hunk| {
+        if let (Some(path), true) = (delta.old_file().path(), hunk.old_lines() > 0) {
+            let range = (hunk.old_start() as usize, hunk.old_lines() as usize);
+            match ranges.last_mut() {
+                Some((last, file_ranges)) if last == path => file_ranges.push(range),
+                _ => ranges.push((path.to_path_buf(), vec![range])),
+            }
+        }
+        true
+    }), None)?;
+
+    let mut opts = opts.clone();
+    opts.blame.rev = Some(parent.id().to_string());
+    let mut files = Vec::new();
+    for (path, file_ranges) in ranges
+    {
+        let mut report = FileReport::new(&path.to_string_lossy());
+        for region in crate::blame_regions(repo, &path, &opts)?
+        {
+            let lines: usize = file_ranges.iter()
+                .map(|&(start, count)| {
+                    let from = region.line.max(start);
+                    let to = (region.line + region.lines).min(start + count);
+                    to.saturating_sub(from)
+                })
+                .sum();
+            if lines > 0 {
+                report.add_hunk(&Hunk {
+                    commit: region.commit,
+                    name: &region.name,
+                    email: &region.email,
+                    time: region.time,
+                    lines,
+                    origin: None,
+                });
+            }
+        }
+        files.push(report);
+    }
+    Ok(files)
+}
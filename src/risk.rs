@@ -0,0 +1,109 @@
+//! Which files would hurt most if their expert were gone
+//!
+//! A file is a risk when it keeps changing, when few people own it and
+//! when those people have stopped committing. The score multiplies the
+//! three, so that a stable file or one shared by many ranks low however
+//! stale its owners are.
+
+use std::collections::{HashMap, HashSet};
+
+use git2::{Repository, Sort};
+
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::ownership::owner_key;
+use crate::FileReport;
+
+/// Commits in this many days count as recent changes
+pub const RECENT_DAYS: i64 = 90;
+
+/// Authors without a commit in this many days count as gone
+pub const INACTIVE_DAYS: i64 = 365;
+
+const DAY: i64 = 24 * 60 * 60;
+
+pub struct Risk {
+    pub path: String,
+    /// Commits that changed the file in the last `RECENT_DAYS`
+    pub churn: usize,
+    /// Share of the lines owned by the largest owner, 0 to 1
+    pub concentration: f64,
+    /// Share of the lines owned by authors without a commit in the last
+    /// `INACTIVE_DAYS`, 0 to 1
+    pub inactivity: f64,
+    /// `churn * concentration * (1 + inactivity)`
+    pub score: f64,
+}
+
+/// The risk of every file of `files`, highest first, from the history of
+/// `rev` (HEAD if not given) as of `now`, identities merged by `mailmap`
+pub fn assess (
+    repo: &Repository,
+    files: &[FileReport],
+    rev: Option<&str>,
+    mailmap: &Mailmap,
+    now: i64
+) -> Result<Vec<Risk>, Error>
+{
+    let paths: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    let mut churn: HashMap<String, usize> = HashMap::new();
+    let mut active = HashSet::new();
+
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME)?;
+    match rev {
+        Some(rev) => walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?,
+        None => walk.push_head()?,
+    }
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        let time = commit.time().seconds();
+        if time < now - INACTIVE_DAYS * DAY {
+            break;
+        }
+        let author = commit.author();
+        let name = String::from_utf8_lossy(author.name_bytes());
+        let email = String::from_utf8_lossy(author.email_bytes());
+        let (name, email) = mailmap.resolve(&name, &email);
+        active.insert(owner_key(name, email).into_owned());
+        if time < now - RECENT_DAYS * DAY {
+            continue;
+        }
+        let parent = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?;
+        for delta in diff.deltas()
+        {
+            if let Some(path) = delta.new_file().path().and_then(|p| p.to_str()) {
+                if paths.contains(path) {
+                    *churn.entry(path.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    let mut risks: Vec<Risk> = files.iter().map(|file| {
+        let lines = file.lines();
+        let share = |part: usize| crate::percent(part, lines) / 100.0;
+        let churn = churn.get(&file.path).copied().unwrap_or(0);
+        let concentration = share(file.ranked_owners().first().map_or(0, |owner| owner.lines()));
+        let inactivity = share(file.owners.iter()
+                               .filter(|(key, _)| !active.contains(key.as_str()))
+                               .map(|(_, owner)| owner.lines())
+                               .sum());
+        Risk {
+            path: file.path.clone(),
+            churn,
+            concentration,
+            inactivity,
+            score: churn as f64 * concentration * (1.0 + inactivity),
+        }
+    }).collect();
+    risks.sort_by(|a, b| b.score.partial_cmp(&a.score)
+                  .unwrap_or(std::cmp::Ordering::Equal)
+                  .then_with(|| a.path.cmp(&b.path)));
+    Ok(risks)
+}
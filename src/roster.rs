@@ -0,0 +1,137 @@
+//! Who is still around to ask
+//!
+//! Lines owned by someone who has left are lines nobody knows, however
+//! high the bus factor looks. A roster lists the people who are still
+//! here, by email or by name; everyone else counts as departed.
+//!
+//! ```toml
+//! active = [
+//!     "alice@corp.com",
+//!     "Bob Builder",
+//! ]
+//! ```
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use git2::Repository;
+
+use crate::blame::Hunk;
+use crate::error::Error;
+use crate::mailmap::Mailmap;
+use crate::toml;
+use crate::{owner_key, FileReport, Owner};
+
+/// The owner that lines of authors who are not active are folded into
+pub const INACTIVE: &str = "inactive owners";
+
+pub struct Roster {
+    /// Lower cased emails and names of everyone still around
+    active: Vec<String>,
+}
+
+impl Roster {
+    pub fn from_file (path: &Path) -> Result<Roster, Error>
+    {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Roster::parse(&text))
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn parse (text: &str) -> Result<Roster, String>
+    {
+        let mut active = Vec::new();
+        for (key, value) in &toml::parse(text)?
+        {
+            match key.as_str() {
+                "active" => {
+                    let items = value.as_array().ok_or("'active' must be an array of strings")?;
+                    for item in items
+                    {
+                        let item = item.as_str().ok_or("'active' must be an array of strings")?;
+                        active.push(item.trim().to_lowercase());
+                    }
+                }
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+        Ok(Roster { active })
+    }
+
+    /// Whether `owner` is on the roster, by email or by name
+    pub fn is_active (&self, owner: &Owner) -> bool
+    {
+        let name = owner.name.to_lowercase();
+        self.active.iter().any(|a| *a == owner.email || *a == name)
+    }
+
+    /// Lines of `file` owned by people who are not on the roster
+    pub fn departed_lines (&self, file: &FileReport) -> usize
+    {
+        file.owners.values().filter(|o| !self.is_active(o)).map(Owner::lines).sum()
+    }
+}
+
+/// Everyone who authored a commit of the history of `rev` (HEAD if not
+/// given) since `since`, by `owner_key` after `mailmap`; the whole history
+/// is looked at, as merges of older work and skewed clocks leave commit
+/// times out of order
+pub fn active_authors (
+    repo: &Repository,
+    rev: Option<&str>,
+    mailmap: &Mailmap,
+    since: i64
+) -> Result<HashSet<String>, Error>
+{
+    let mut walk = repo.revwalk()?;
+    match rev {
+        Some(rev) => walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?,
+        None => walk.push_head()?,
+    }
+    let mut active = HashSet::new();
+    for oid in walk
+    {
+        let commit = repo.find_commit(oid?)?;
+        if commit.time().seconds() < since {
+            continue;
+        }
+        let author = commit.author();
+        let name = String::from_utf8_lossy(author.name_bytes());
+        let email = String::from_utf8_lossy(author.email_bytes());
+        let (name, email) = mailmap.resolve(&name, &email);
+        active.insert(owner_key(name, email).into_owned());
+    }
+    Ok(active)
+}
+
+/// Fold the owners of `file` who are not in `active` into one owner named
+/// `INACTIVE`, keeping their lines and score
+pub fn fold_inactive (file: &mut FileReport, active: &HashSet<String>)
+{
+    let keys: Vec<String> = file.owners.keys().filter(|key| !active.contains(*key)).cloned().collect();
+    let inactive: Vec<Owner> = keys.iter().filter_map(|key| file.owners.remove(key)).collect();
+    if inactive.is_empty() {
+        return;
+    }
+    let mut score = 0.0;
+    for owner in &inactive
+    {
+        score += owner.score;
+        for (commit, contribution) in &owner.commits
+        {
+            file.add_hunk(&Hunk {
+                commit: *commit,
+                name: INACTIVE,
+                email: "",
+                time: contribution.time,
+                lines: contribution.lines,
+                origin: None,
+            });
+        }
+    }
+    if let Some(folded) = file.owners.get_mut(owner_key(INACTIVE, "").as_ref()) {
+        folded.score = score;
+    }
+}
@@ -0,0 +1,160 @@
+//! The contract of the JSON we write
+//!
+//! One JSON Schema describes the answers of `--query-server`, of `serve`,
+//! the reports of `--format json` and the failures of `--errors json`,
+//! each under `$defs`. It is written
+//! by hand next to the code producing the output, and carries the version
+//! of the crate so that consumers can tell which output it describes.
+
+use crate::json::Value;
+
+fn typed (kind: &str) -> Value
+{
+    Value::object(vec![("type", kind.into())])
+}
+
+fn described (kind: &str, description: &str) -> Value
+{
+    Value::object(vec![("type", kind.into()), ("description", description.into())])
+}
+
+/// Any JSON value
+fn described_any (description: &str) -> Value
+{
+    Value::object(vec![("description", description.into())])
+}
+
+fn reference (name: &str) -> Value
+{
+    Value::object(vec![("$ref", format!("#/$defs/{}", name).into())])
+}
+
+fn array_of (items: Value) -> Value
+{
+    Value::object(vec![("type", "array".into()), ("items", items)])
+}
+
+fn nullable (schema: Value) -> Value
+{
+    Value::object(vec![("anyOf", Value::Array(vec![schema, typed("null")]))])
+}
+
+/// An object with exactly `properties`, all of them required unless in
+/// `optional`
+fn object (properties: Vec<(&str, Value)>, optional: &[&str]) -> Value
+{
+    let required = properties.iter()
+        .filter(|(name, _)| !optional.contains(name))
+        .map(|(name, _)| Value::from(*name))
+        .collect();
+    Value::object(vec![
+        ("type", "object".into()),
+        ("properties", Value::object(properties)),
+        ("required", Value::Array(required)),
+        ("additionalProperties", false.into()),
+    ])
+}
+
+fn owner () -> Value
+{
+    object(vec![
+        ("name", typed("string")),
+        ("email", typed("string")),
+        ("lines", typed("integer")),
+        ("share", described("number", "percentage of the lines of the file")),
+        ("commits", described("integer", "commits the lines survive from")),
+        ("lines_per_commit", described("number", "surviving lines per commit they survive from")),
+        ("score", described("number", "weighted lines the owners are ranked by")),
+        ("expertise", described("string", "label of the share of the file, with --expertise")),
+    ], &["expertise"])
+}
+
+/// The members of a file report other than its path
+fn ownership () -> Vec<(&'static str, Value)>
+{
+    vec![
+        ("lines", typed("integer")),
+        ("bus_factor", typed("integer")),
+        ("owners", array_of(reference("owner"))),
+    ]
+}
+
+fn file_report () -> Value
+{
+    let mut properties = vec![("path", typed("string"))];
+    properties.extend(ownership());
+    properties.push(("renames", array_of(object(vec![
+        ("commit", typed("string")),
+        ("time", described("integer", "author time, seconds since the epoch")),
+        ("from", typed("string")),
+        ("to", typed("string")),
+    ], &[]))));
+    object(properties, &["renames"])
+}
+
+fn query_answer () -> Value
+{
+    let mut properties = vec![
+        ("id", described_any("copied from the request")),
+        ("file", typed("string")),
+        ("line", typed("integer")),
+        ("author", object(vec![("name", typed("string")), ("email", typed("string"))], &[])),
+        ("commit", nullable(object(vec![
+            ("id", typed("string")),
+            ("summary", typed("string")),
+            ("time", described("integer", "author time, seconds since the epoch")),
+        ], &[]))),
+    ];
+    properties.extend(ownership());
+    object(properties, &["id", "line", "author", "commit"])
+}
+
+fn reviewers () -> Value
+{
+    object(vec![
+        ("files", array_of(typed("string"))),
+        ("reviewers", array_of(object(vec![
+            ("name", typed("string")),
+            ("email", typed("string")),
+            ("lines", typed("integer")),
+        ], &[]))),
+    ], &[])
+}
+
+/// A failure of `serve` or `--query-server`
+fn failure () -> Value
+{
+    object(vec![("id", described_any("copied from the request")), ("error", typed("string"))], &["id"])
+}
+
+/// A failure reported with `--errors json`
+fn error () -> Value
+{
+    object(vec![("error", object(vec![
+        ("code", described("integer", "the exit status")),
+        ("kind", typed("string")),
+        ("path", nullable(typed("string"))),
+        ("message", typed("string")),
+    ], &[]))], &[])
+}
+
+/// The JSON Schema of every JSON output
+pub fn schema () -> Value
+{
+    let version = env!("CARGO_PKG_VERSION");
+    Value::object(vec![
+        ("$schema", "https://json-schema.org/draft/2020-12/schema".into()),
+        ("$id", format!("urn:git-whoknows:schema:{}", version).into()),
+        ("title", "git-whoknows output".into()),
+        ("version", version.into()),
+        ("$defs", Value::object(vec![
+            ("owner", owner()),
+            ("file_report", file_report()),
+            ("report", array_of(reference("file_report"))),
+            ("query_answer", query_answer()),
+            ("reviewers", reviewers()),
+            ("failure", failure()),
+            ("error", error()),
+        ])),
+    ])
+}
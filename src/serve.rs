@@ -0,0 +1,307 @@
+//! A small HTTP server answering ownership questions as JSON
+//!
+//! Connections are handled by a fixed number of threads, each request with
+//! its own handle on the repository; connections that find every thread
+//! busy and the queue full are turned away. Reports are kept in memory
+//! keyed by the HEAD they were made at, so repeated questions are answered
+//! without blaming again, and dropped once HEAD has moved on.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use git2::{Oid, Repository};
+
+use whoknows::json::{self, Value};
+use whoknows::reviewers;
+use whoknows::{Error, FileReport, Options};
+
+/// Threads answering requests
+const WORKERS: usize = 8;
+
+/// Connections waiting for a thread before new ones are turned away
+const QUEUE: usize = 32;
+
+/// Time a client has to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest request line and headers accepted, together
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// Largest request body accepted, diffs included
+const MAX_BODY: usize = 1024 * 1024;
+
+struct Server {
+    git_dir: PathBuf,
+    workdir: Option<PathBuf>,
+    opts: Options,
+    /// An email as responses may show it
+    shown_email: fn(&str) -> String,
+    reports: Mutex<HashMap<(Oid, String), Arc<FileReport>>>,
+}
+
+struct Response {
+    status: &'static str,
+    body: Value,
+}
+
+impl Response {
+    fn ok (body: Value) -> Response
+    {
+        Response { status: "200 OK", body }
+    }
+
+    fn error (status: &'static str, message: &str) -> Response
+    {
+        Response {
+            status,
+            body: Value::object(vec![("error", message.into())]),
+        }
+    }
+}
+
+/// Answer requests on `listen` until the process is stopped, showing
+/// emails as `shown_email` has them
+pub fn serve (repo: &Repository, listen: &str, opts: Options, shown_email: fn(&str) -> String, quiet: bool)
+              -> Result<(), Error>
+{
+    let listener = TcpListener::bind(listen)?;
+    if !quiet {
+        eprintln!("listening on http://{}", listener.local_addr()?);
+    }
+
+    let server = Arc::new(Server {
+        git_dir: repo.path().to_path_buf(),
+        workdir: repo.workdir().map(Path::to_path_buf),
+        opts,
+        shown_email,
+        reports: Mutex::new(HashMap::new()),
+    });
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(QUEUE);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS
+    {
+        let server = Arc::clone(&server);
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || loop {
+            let stream = match receiver.lock().unwrap().recv() {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            // A client hanging up early is its own problem
+            let _ = handle(&server, stream);
+        });
+    }
+
+    for stream in listener.incoming()
+    {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        if let Err(TrySendError::Full(stream)) = sender.try_send(stream) {
+            let _ = respond(stream, Response::error("503 Service Unavailable", "too many requests, try again later"));
+        }
+    }
+    Ok(())
+}
+
+/// Reads that fail once the client has had `REQUEST_TIMEOUT` to send its
+/// request, however slowly it trickles in
+struct Deadline {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline {
+    fn read (&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request took too long"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+fn handle (server: &Server, stream: TcpStream) -> io::Result<()>
+{
+    let mut reader = BufReader::new(Deadline {
+        stream: stream.try_clone()?,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+
+    // Everything up to the body, as long as it stays within MAX_HEAD
+    let mut head = (&mut reader).take(MAX_HEAD);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("").to_string();
+
+    let mut length = 0;
+    let mut complete = false;
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header)? == 0 || !header.ends_with('\n') {
+            break;
+        }
+        if header.trim().is_empty() {
+            complete = true;
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let response = if !complete && head.limit() == 0 {
+        Response::error("431 Request Header Fields Too Large", "request headers are too large")
+    } else if length > MAX_BODY {
+        Response::error("413 Payload Too Large", "request body is too large")
+    } else {
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        route(server, &method, &target, &String::from_utf8_lossy(&body))
+    };
+    respond(stream, response)
+}
+
+fn respond (mut stream: TcpStream, response: Response) -> io::Result<()>
+{
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let body = response.body.to_string();
+    write!(stream, "HTTP/1.1 {}\r\n\
+                    Content-Type: application/json\r\n\
+                    Content-Length: {}\r\n\
+                    Connection: close\r\n\r\n{}",
+           response.status, body.len(), body)?;
+    stream.flush()
+}
+
+fn route (server: &Server, method: &str, target: &str, body: &str) -> Response
+{
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (target, ""),
+    };
+
+    match (method, path) {
+        ("GET", "/ownership") => match query_param(query, "path") {
+            Some(file) => match server.report(&file) {
+                Ok(report) => Response::ok(json::file_report(&server.shown(&report))),
+                Err(response) => response,
+            },
+            None => Response::error("400 Bad Request", "missing path parameter"),
+        },
+        ("POST", "/reviewers") => server.reviewers(body),
+        (_, "/ownership") | (_, "/reviewers") =>
+            Response::error("405 Method Not Allowed", "method not allowed"),
+        _ => Response::error("404 Not Found", "no such endpoint"),
+    }
+}
+
+impl Server {
+    /// Ownership of `path` at the current HEAD, from memory when possible
+    fn report (&self, path: &str) -> Result<Arc<FileReport>, Response>
+    {
+        let internal = |e: Error| Response::error("500 Internal Server Error", &e.to_string());
+        let repo = whoknows::reopen(&self.git_dir, self.workdir.as_deref()).map_err(|e| internal(e.into()))?;
+        let head = repo.head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|e| internal(e.into()))?;
+        if head.tree().and_then(|tree| tree.get_path(Path::new(path))).is_err() {
+            return Err(Response::error("404 Not Found", &format!("{} is not tracked", path)));
+        }
+
+        let key = (head.id(), path.to_string());
+        if let Some(report) = self.reports.lock().unwrap().get(&key) {
+            return Ok(Arc::clone(report));
+        }
+        let report = Arc::new(whoknows::analyze_file(&repo, Path::new(path), &self.opts)
+                              .map_err(internal)?);
+        let mut reports = self.reports.lock().unwrap();
+        // Nothing asks for the reports of an earlier HEAD again
+        reports.retain(|(commit, _), _| *commit == head.id());
+        reports.insert(key, Arc::clone(&report));
+        Ok(report)
+    }
+
+    /// `report` with the emails of its owners as responses may show them
+    fn shown (&self, report: &FileReport) -> FileReport
+    {
+        let mut report = report.clone();
+        for owner in report.owners.values_mut()
+        {
+            owner.email = (self.shown_email)(&owner.email);
+        }
+        report
+    }
+
+    /// Authors owning the most lines of the files a unified diff touches
+    fn reviewers (&self, diff: &str) -> Response
+    {
+        let files = reviewers::touched_files(diff);
+        let mut reports = Vec::new();
+        for file in &files
+        {
+            match self.report(file) {
+                Ok(report) => reports.push(report),
+                // New files have nobody to ask yet
+                Err(ref response) if response.status.starts_with("404") => {}
+                Err(response) => return response,
+            }
+        }
+
+        let reviewers = reviewers::rank(reports.iter().map(|r| &**r)).into_iter()
+            .map(|reviewer| Value::object(vec![
+                ("name", reviewer.name.into()),
+                ("email", (self.shown_email)(&reviewer.email).into()),
+                ("lines", reviewer.lines.into()),
+            ]))
+            .collect();
+        Response::ok(Value::object(vec![
+            ("files", Value::Array(files.into_iter().map(Value::from).collect())),
+            ("reviewers", Value::Array(reviewers)),
+        ]))
+    }
+}
+
+/// The percent-decoded value of `name` in a query string
+fn query_param (query: &str, name: &str) -> Option<String>
+{
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+fn percent_decode (s: &str) -> String
+{
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => match s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(b) => {
+                    out.push(b);
+                    i += 2;
+                }
+                None => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
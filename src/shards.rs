@@ -0,0 +1,41 @@
+//! One report from the reports of several runs
+//!
+//! A CI matrix may split a monorepo by directory and analyze each part in a
+//! job of its own. The reports the jobs save are combined into one for the
+//! whole repository: every identity mapped once more through the same
+//! `.mailmap` and identities, so that someone who shows up under an old
+//! email in one shard counts as one owner, and every file taken once.
+
+use std::collections::BTreeMap;
+
+use crate::mailmap::Mailmap;
+use crate::FileReport;
+
+/// The files of all shards
+pub struct Merged {
+    /// By path
+    pub files: Vec<FileReport>,
+    /// Paths more than one shard had, taken from the last of them
+    pub overlaps: Vec<String>,
+}
+
+/// Combine `shards`, given in order, with identities remapped through
+/// `mailmap`
+pub fn merge (shards: Vec<Vec<FileReport>>, mailmap: &Mailmap) -> Merged
+{
+    let mut files: BTreeMap<String, FileReport> = BTreeMap::new();
+    let mut overlaps = Vec::new();
+    for file in shards.into_iter().flatten()
+    {
+        let file = file.remap(mailmap);
+        if let Some(earlier) = files.insert(file.path.clone(), file) {
+            overlaps.push(earlier.path);
+        }
+    }
+    overlaps.sort();
+    overlaps.dedup();
+    Merged {
+        files: files.into_values().collect(),
+        overlaps,
+    }
+}
@@ -0,0 +1,157 @@
+//! How much of everyone's code comes from signed commits
+//!
+//! Organizations with provenance requirements want the code on critical
+//! paths to come from signed commits. git checks the signature of every
+//! commit lines survive from, as `%G?` of `git log`, in a single run, and
+//! each author's lines are split by how their commits fared. Checking needs
+//! the keys of the signers in the keyring of gpg, or the allowed signers of
+//! ssh; signatures that cannot be checked without them are told apart from
+//! missing ones.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use git2::{Oid, Repository};
+
+use crate::error::Error;
+use crate::platform;
+use crate::{owner_key, FileReport};
+
+/// What git makes of the signature of a commit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    /// A good signature, by a key that may have expired since
+    Good,
+    /// Signed, but without the key or allowed signers to check it with
+    Unchecked,
+    /// A bad signature or a revoked key
+    Bad,
+    Unsigned,
+}
+
+impl Status {
+    /// The status a `%G?` letter stands for
+    fn from_letter (letter: &str) -> Status
+    {
+        match letter {
+            "G" | "U" | "X" | "Y" => Status::Good,
+            "E" => Status::Unchecked,
+            "B" | "R" => Status::Bad,
+            _ => Status::Unsigned,
+        }
+    }
+}
+
+/// The `git log` that `statuses` feeds the commits to check
+pub fn command (repo: &Repository) -> Command
+{
+    let mut cmd = Command::new(platform::git());
+    cmd.arg("--git-dir").arg(repo.path())
+        .arg("log").arg("--no-walk=unsorted").arg("--stdin").arg("--format=%H %G?");
+    cmd
+}
+
+/// The signature status of each of `oids`, checked by one `git log`
+pub fn statuses (repo: &Repository, oids: &[Oid]) -> Result<HashMap<Oid, Status>, Error>
+{
+    if oids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut child = command(repo)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Blame(format!("could not run git: {}", e)))?;
+
+    // Both fed and drained on the side, so that neither pipe can fill up
+    // while git waits on the other
+    let input: String = oids.iter().map(|oid| format!("{}\n", oid)).collect();
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let mut stderr = child.stderr.take().unwrap();
+    let errors = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+
+    let mut statuses = HashMap::new();
+    for line in BufReader::new(child.stdout.take().unwrap()).lines()
+    {
+        let line = line?;
+        if let Some((hash, letter)) = line.split_once(' ') {
+            if let Ok(oid) = Oid::from_str(hash) {
+                let status = match Status::from_letter(letter.trim()) {
+                    // git says so of ssh signatures without allowed signers
+                    // to check them against
+                    Status::Unsigned if repo.extract_signature(&oid, None).is_ok() => Status::Unchecked,
+                    status => status,
+                };
+                statuses.insert(oid, status);
+            }
+        }
+    }
+    let _ = writer.join();
+    let status = child.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        return Err(Error::Blame(format!("checking signatures: {}", errors.trim())));
+    }
+    Ok(statuses)
+}
+
+/// Someone's lines over a report, split by the signatures of their commits
+pub struct Signer {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+    /// Lines of commits with a good signature
+    pub signed: usize,
+    /// Lines of signed commits whose signature could not be checked
+    pub unchecked: usize,
+}
+
+/// The signature status of every commit the lines of `files` survive from
+pub fn check (repo: &Repository, files: &[FileReport]) -> Result<HashMap<Oid, Status>, Error>
+{
+    let oids: HashSet<Oid> = files.iter()
+        .flat_map(|file| file.owners.values())
+        .flat_map(|owner| owner.commits.keys().copied())
+        // Uncommitted lines are not signed by anyone yet
+        .filter(|oid| !oid.is_zero())
+        .collect();
+    statuses(repo, &oids.into_iter().collect::<Vec<_>>())
+}
+
+/// Every owner of lines of `files`, with how many of them `statuses` has
+/// as signed, most lines first
+pub fn signers (files: &[FileReport], statuses: &HashMap<Oid, Status>) -> Vec<Signer>
+{
+    let mut signers: HashMap<String, Signer> = HashMap::new();
+    for owner in files.iter().flat_map(|file| file.owners.values()).filter(|owner| owner.lines() > 0)
+    {
+        let signer = signers.entry(owner_key(&owner.name, &owner.email).into_owned())
+            .or_insert_with(|| Signer {
+                name: owner.name.clone(),
+                email: owner.email.clone(),
+                lines: 0,
+                signed: 0,
+                unchecked: 0,
+            });
+        for (oid, contribution) in &owner.commits
+        {
+            signer.lines += contribution.lines;
+            match statuses.get(oid) {
+                Some(Status::Good) => signer.signed += contribution.lines,
+                Some(Status::Unchecked) => signer.unchecked += contribution.lines,
+                _ => {}
+            }
+        }
+    }
+    let mut signers: Vec<Signer> = signers.into_values().collect();
+    signers.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+    signers
+}
@@ -0,0 +1,167 @@
+//! Ownership recorded in the repository itself
+//!
+//! A snapshot is the lines of every owner of every file as of one commit,
+//! kept as a git note on that commit under `NOTES_REF`. Notes travel with
+//! `git push` and `git fetch` of that ref like any other, so how knowledge
+//! moved between two releases can be read back long after either was
+//! analyzed, without any file kept next to the repository.
+
+use std::collections::BTreeMap;
+
+use git2::{Oid, Repository, Signature};
+
+use crate::blame::Hunk;
+use crate::error::Error;
+use crate::json::{self, Value};
+use crate::{date, Difference, FileReport};
+
+/// Where the notes are kept
+pub const NOTES_REF: &str = "refs/notes/whoknows";
+
+/// Format of the note, raised when it changes incompatibly
+const VERSION: f64 = 1.0;
+
+pub struct Snapshot {
+    /// The commit analyzed
+    pub commit: Oid,
+    /// When it was saved, seconds since the epoch
+    pub saved: i64,
+    /// Every file with the lines of its owners; commits are not kept
+    pub files: Vec<FileReport>,
+}
+
+impl Snapshot {
+    pub fn new (commit: Oid, files: &[FileReport]) -> Snapshot
+    {
+        let files = files.iter().map(|file| {
+            let mut summary = FileReport::new(&file.path);
+            for owner in file.owners.values()
+            {
+                add_owner(&mut summary, &owner.name, &owner.email, owner.lines());
+            }
+            summary
+        });
+        Snapshot { commit, saved: date::now(), files: files.collect() }
+    }
+
+    /// The snapshot as the text of its note, files and owners in a stable
+    /// order so that equal snapshots make equal notes
+    pub fn encode (&self) -> String
+    {
+        let mut files: Vec<&FileReport> = self.files.iter().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let files = files.into_iter()
+            .map(|file| {
+                let owners = file.ranked_owners().into_iter()
+                    .filter(|owner| owner.lines() > 0)
+                    .map(|owner| Value::Array(vec![
+                        owner.name.as_str().into(),
+                        owner.email.as_str().into(),
+                        owner.lines().into(),
+                    ]))
+                    .collect();
+                Value::object(vec![("path", file.path.as_str().into()), ("owners", Value::Array(owners))])
+            })
+            .collect();
+        Value::object(vec![
+            ("version", VERSION.into()),
+            ("commit", self.commit.to_string().into()),
+            ("saved", (self.saved as f64).into()),
+            ("files", Value::Array(files)),
+        ]).to_string()
+    }
+
+    pub fn decode (text: &str) -> Result<Snapshot, Error>
+    {
+        let invalid = |what: &str| Error::Export(format!("not a whoknows snapshot: {}", what));
+        let value = json::parse(text).map_err(|e| invalid(&e))?;
+        if value.get("version").and_then(Value::as_f64) != Some(VERSION) {
+            return Err(invalid("unknown version"));
+        }
+        let commit = value.get("commit").and_then(Value::as_str)
+            .and_then(|hex| Oid::from_str(hex).ok())
+            .ok_or_else(|| invalid("no commit"))?;
+        let saved = value.get("saved").and_then(Value::as_f64).unwrap_or(0.0) as i64;
+        let mut files = Vec::new();
+        for file in value.get("files").and_then(Value::as_array).ok_or_else(|| invalid("no files"))?
+        {
+            let path = file.get("path").and_then(Value::as_str).ok_or_else(|| invalid("a file without path"))?;
+            let mut report = FileReport::new(path);
+            for owner in file.get("owners").and_then(Value::as_array).unwrap_or_default()
+            {
+                match owner.as_array() {
+                    Some([name, email, lines]) => add_owner(
+                        &mut report,
+                        name.as_str().unwrap_or(""),
+                        email.as_str().unwrap_or(""),
+                        lines.as_f64().unwrap_or(0.0) as usize,
+                    ),
+                    _ => return Err(invalid("an owner is not [name, email, lines]")),
+                }
+            }
+            files.push(report);
+        }
+        Ok(Snapshot { commit, saved, files })
+    }
+}
+
+/// Give `lines` of `file` to an owner
+fn add_owner (file: &mut FileReport, name: &str, email: &str, lines: usize)
+{
+    file.add_hunk(&Hunk { commit: Oid::zero(), name, email, time: 0, lines, origin: None });
+}
+
+/// Keep `snapshot` as the note of its commit, replacing any there is
+pub fn save (repo: &Repository, snapshot: &Snapshot) -> Result<Oid, Error>
+{
+    let signature = repo.signature().or_else(|_| Signature::now("git-whoknows", "git-whoknows@localhost"))?;
+    let note = repo.note(&signature, &signature, Some(NOTES_REF), snapshot.commit, &snapshot.encode(), true)?;
+    Ok(note)
+}
+
+/// The snapshot saved on `commit`
+pub fn load (repo: &Repository, commit: Oid) -> Result<Snapshot, Error>
+{
+    let note = repo.find_note(Some(NOTES_REF), commit).map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound => Error::Usage(format!("no snapshot saved on {}", commit)),
+        _ => e.into(),
+    })?;
+    Snapshot::decode(note.message().unwrap_or(""))
+}
+
+/// How the ownership of one file moved between two snapshots
+pub struct Drift {
+    pub path: String,
+    /// Bus factor before and after, None where the file was not there
+    pub bus_factor: (Option<usize>, Option<usize>),
+    pub differences: Vec<Difference>,
+}
+
+/// The files whose ownership differs from `before` to `after`, by path
+pub fn diff (before: &Snapshot, after: &Snapshot) -> Vec<Drift>
+{
+    let mut paths: BTreeMap<&str, (Option<&FileReport>, Option<&FileReport>)> = BTreeMap::new();
+    for file in &before.files
+    {
+        paths.entry(&file.path).or_default().0 = Some(file);
+    }
+    for file in &after.files
+    {
+        paths.entry(&file.path).or_default().1 = Some(file);
+    }
+    let mut drifts = Vec::new();
+    for (path, (old, new)) in paths
+    {
+        let empty = FileReport::new(path);
+        let differences = new.unwrap_or(&empty).differences(old.unwrap_or(&empty));
+        if differences.is_empty() && old.is_some() == new.is_some() {
+            continue;
+        }
+        drifts.push(Drift {
+            path: path.to_string(),
+            bus_factor: (old.map(FileReport::bus_factor), new.map(FileReport::bus_factor)),
+            differences,
+        });
+    }
+    drifts
+}
@@ -0,0 +1,122 @@
+//! The history of one function, or of any range of lines
+//!
+//! git finds a function by the funcname rules of its diff driver, as for
+//! `git log -L :<name>:<path>`, and follows it through every commit that
+//! changed it, which is what someone who once wrote most of it but whose
+//! lines have since been rewritten still counts for. Ranges as `git log
+//! -L <start>,<end>:<path>` takes them are followed the same way.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+use git2::{Oid, Repository};
+
+use crate::error::Error;
+use crate::git;
+use crate::ownership::{normalize_email, owner_key};
+
+/// What one author did to a function over its history
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// Author time of their latest change, seconds since the epoch
+    pub last: i64,
+}
+
+/// The lines `name` takes up in `path` as of `rev`, first line and count
+pub fn lines (repo: &Repository, name: &str, path: &Path, rev: &str) -> Result<(usize, usize), Error>
+{
+    range_lines(repo, &format!(":{}", name), path, rev).map_err(|e| match e {
+        Error::Blame(message) if message.contains("no match") => {
+            Error::Path(format!("no function '{}' in {}", name, path.display()))
+        }
+        Error::Path(_) => Error::Path(format!("no function '{}' in {}", name, path.display())),
+        e => e,
+    })
+}
+
+/// The lines `range` takes up in `path` as of `rev`, first line and count,
+/// for a range as `git blame -L` takes it such as `10,40` or
+/// `/^fn main/,+20`
+pub fn range_lines (repo: &Repository, range: &str, path: &Path, rev: &str) -> Result<(usize, usize), Error>
+{
+    let option = format!("-L{}", range);
+    let output = git::run(repo, &["blame".as_ref(), "--porcelain".as_ref(), option.as_ref(), rev.as_ref(), "--".as_ref(),
+                                  path.as_os_str()])?;
+    let mut found: Option<(usize, usize)> = None;
+    for line in output.lines()
+    {
+        // <commit> <original line> <final line>[ <lines in group>]
+        let fields: Vec<&str> = line.split(' ').collect();
+        let header = fields.len() >= 3 && fields[0].len() == 40 && Oid::from_str(fields[0]).is_ok();
+        if let (true, Some(final_line)) = (header, fields.get(2).and_then(|f| f.parse::<usize>().ok())) {
+            found = Some(match found {
+                Some((first, last)) => (first.min(final_line), last.max(final_line)),
+                None => (final_line, final_line),
+            });
+        }
+    }
+    found.map(|(first, last)| (first, last - first + 1))
+        .ok_or_else(|| Error::Path(format!("no lines {} in {}", range, path.display())))
+}
+
+/// Everyone who changed `name` in `path` up to `rev`, most lines added
+/// first; `mailmap` has git map their identities
+pub fn history (repo: &Repository, name: &str, path: &Path, rev: &str, mailmap: bool) -> Result<Vec<Author>, Error>
+{
+    range_history(repo, &format!(":{}", name), path, rev, mailmap)
+}
+
+/// Everyone who changed the lines `range` takes up in `path` as of `rev`
+/// in any commit up to `rev`, as `git log -L <range>:<path>` follows them,
+/// most lines added first; `mailmap` has git map their identities
+pub fn range_history (repo: &Repository, range: &str, path: &Path, rev: &str, mailmap: bool) -> Result<Vec<Author>, Error>
+{
+    let format = if mailmap { "--format=%x00%aN%x1f%aE%x1f%at" } else { "--format=%x00%an%x1f%ae%x1f%at" };
+    // The path is passed on as it is, whatever its encoding
+    let mut option = OsString::from(format!("-L{}:", range));
+    option.push(path);
+    let output = git::run(repo, &["log".as_ref(), "--no-color".as_ref(), format.as_ref(), &option, rev.as_ref()])?;
+
+    let mut authors: HashMap<String, Author> = HashMap::new();
+    for commit in output.split('\0').skip(1)
+    {
+        let (header, patch) = commit.split_once('\n').unwrap_or((commit, ""));
+        let fields: Vec<&str> = header.split('\x1f').collect();
+        let (name, email, time) = match fields.as_slice() {
+            [name, email, time] => (name.trim(), normalize_email(email).into_owned(), time.parse::<i64>().unwrap_or(0)),
+            _ => continue,
+        };
+        let author = authors.entry(owner_key(name, &email).into_owned()).or_insert_with(|| Author {
+            name: name.to_string(),
+            email,
+            commits: 0,
+            added: 0,
+            removed: 0,
+            last: time,
+        });
+        author.commits += 1;
+        author.last = author.last.max(time);
+        let mut in_hunk = false;
+        for line in patch.lines()
+        {
+            if line.starts_with("@@") {
+                in_hunk = true;
+            } else if line.starts_with("diff --git") {
+                in_hunk = false;
+            } else if in_hunk && line.starts_with('+') {
+                author.added += 1;
+            } else if in_hunk && line.starts_with('-') {
+                author.removed += 1;
+            }
+        }
+    }
+
+    let mut authors: Vec<Author> = authors.into_values().collect();
+    authors.sort_by(|a, b| b.added.cmp(&a.added).then_with(|| a.email.cmp(&b.email)));
+    Ok(authors)
+}
@@ -0,0 +1,272 @@
+use std::sync::{Mutex, OnceLock};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Separator placed between columns
+const GUTTER: &str = "  ";
+
+/// Narrowest a shrinkable column is allowed to become
+const MIN_WIDTH: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// How a cell is shortened when its column has to shrink
+#[derive(Clone, Copy, PartialEq)]
+pub enum Truncate {
+    /// Never truncate, numeric columns and the like
+    Never,
+    /// Cut the end of the text
+    End,
+    /// Keep the domain of an email address and cut the local part
+    Email,
+}
+
+struct Column {
+    header: String,
+    align: Align,
+    truncate: Truncate,
+}
+
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new () -> Table
+    {
+        Table {
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn column (
+        &mut self,
+        header: &str,
+        align: Align,
+        truncate: Truncate
+    ) -> &mut Table
+    {
+        self.columns.push(Column {
+            header: header.to_string(),
+            align,
+            truncate,
+        });
+        self
+    }
+
+    pub fn row (&mut self, cells: Vec<String>)
+    {
+        debug_assert_eq!(cells.len(), self.columns.len());
+        self.rows.push(cells);
+    }
+
+    /// Render the table, shrinking truncatable columns so that no line
+    /// exceeds `max_width` when one is given
+    pub fn render (&self, max_width: Option<usize>) -> String
+    {
+        let mut widths: Vec<usize> = self.columns.iter()
+            .map(|c| c.header.width())
+            .collect();
+        for row in &self.rows
+        {
+            for (width, cell) in widths.iter_mut().zip(row)
+            {
+                *width = (*width).max(cell.width());
+            }
+        }
+
+        if let Some(max_width) = max_width {
+            self.shrink(&mut widths, max_width);
+        }
+
+        let mut out = String::new();
+        let header: Vec<String> = self.columns.iter().map(|c| c.header.clone()).collect();
+        self.render_row(&mut out, &header, &widths);
+        for row in &self.rows
+        {
+            self.render_row(&mut out, row, &widths);
+        }
+        if let Some(recorded) = RECORDED.lock().unwrap().as_mut() {
+            recorded.push((out.clone(), self.markdown()));
+        }
+        out
+    }
+
+    /// The table in GitHub flavored Markdown, never truncated
+    pub fn markdown (&self) -> String
+    {
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let header = self.columns.iter().map(|c| escape(&c.header)).collect();
+        let rule = self.columns.iter()
+            .map(|c| match c.align {
+                Align::Left => "---".to_string(),
+                Align::Right => "---:".to_string(),
+            })
+            .collect();
+        let mut out = line(header) + &line(rule);
+        for row in &self.rows
+        {
+            out.push_str(&line(row.iter().map(|cell| escape(cell)).collect()));
+        }
+        out
+    }
+
+    fn shrink (&self, widths: &mut [usize], max_width: usize)
+    {
+        let gutters = GUTTER.len() * widths.len().saturating_sub(1);
+        loop {
+            let total = widths.iter().sum::<usize>() + gutters;
+            if total <= max_width {
+                return;
+            }
+
+            // Always take from the widest column that can still give
+            let widest = self.columns.iter()
+                .enumerate()
+                .filter(|(i, c)| c.truncate != Truncate::Never
+                        && widths[*i] > MIN_WIDTH.max(c.header.width()))
+                .max_by_key(|(i, _)| widths[*i])
+                .map(|(i, _)| i);
+
+            match widest {
+                Some(i) => widths[i] -= 1,
+                None => return,
+            }
+        }
+    }
+
+    fn render_row (&self, out: &mut String, cells: &[String], widths: &[usize])
+    {
+        let mut line = String::new();
+        for (i, (column, cell)) in self.columns.iter().zip(cells).enumerate()
+        {
+            if i > 0 {
+                line.push_str(GUTTER);
+            }
+            let text = truncate(cell, widths[i], column.truncate);
+            let pad = " ".repeat(widths[i] - text.width());
+            match column.align {
+                Align::Left => {
+                    line.push_str(&text);
+                    line.push_str(&pad);
+                }
+                Align::Right => {
+                    line.push_str(&pad);
+                    line.push_str(&text);
+                }
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+}
+
+/// `cell` safe to put between the pipes of a Markdown table
+fn escape (cell: &str) -> String
+{
+    cell.replace('|', "\\|")
+}
+
+/// Shorten `text` to at most `width` display columns using an ellipsis
+fn truncate (text: &str, width: usize, style: Truncate) -> String
+{
+    if text.width() <= width || style == Truncate::Never {
+        return text.to_string();
+    }
+
+    if style == Truncate::Email {
+        if let Some(at) = text.rfind('@') {
+            let (local, domain) = text.split_at(at);
+            // Only worth it while a few characters of the local part survive
+            if domain.width() + 4 <= width {
+                let mut shortened = take_width(local, width - domain.width() - 1);
+                shortened.push('…');
+                shortened.push_str(domain);
+                return shortened;
+            }
+        }
+    }
+
+    let mut shortened = take_width(text, width.saturating_sub(1));
+    shortened.push('…');
+    shortened
+}
+
+/// Longest prefix of `text` that fits into `width` display columns
+fn take_width (text: &str, width: usize) -> String
+{
+    let mut taken = String::new();
+    let mut used = 0;
+    for c in text.chars()
+    {
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        taken.push(c);
+    }
+    taken
+}
+
+/// Every table rendered since `record`, as text and as Markdown
+static RECORDED: Mutex<Option<Vec<(String, String)>>> = Mutex::new(None);
+
+/// Keep every table rendered from now on, see `recorded`
+pub fn record ()
+{
+    *RECORDED.lock().unwrap() = Some(Vec::new());
+}
+
+/// The tables rendered since `record`, each as text and as Markdown
+pub fn recorded () -> Vec<(String, String)>
+{
+    RECORDED.lock().unwrap().take().unwrap_or_default()
+}
+
+/// Width to render at instead of that of the terminal, see `set_width`
+static WIDTH: OnceLock<Option<usize>> = OnceLock::new();
+
+/// Render every table at `width` from now on, or without truncating
+/// anything if `None`, whatever the terminal; only the first call counts
+pub fn set_width (width: Option<usize>)
+{
+    let _ = WIDTH.set(width);
+}
+
+/// Width of the terminal attached to stdout, if there is one, unless
+/// `set_width` says otherwise
+pub fn terminal_width () -> Option<usize>
+{
+    if let Some(width) = WIDTH.get() {
+        return *width;
+    }
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(columns);
+    }
+    ioctl_width()
+}
+
+#[cfg(unix)]
+fn ioctl_width () -> Option<usize>
+{
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if ret == 0 && size.ws_col > 0 {
+        Some(size.ws_col as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn ioctl_width () -> Option<usize>
+{
+    None
+}
@@ -0,0 +1,83 @@
+//! Ownership by team
+//!
+//! Teams are sets of member emails, those of the `[teams]` table or of the
+//! teams of a GitHub organization. Someone in two teams counts for both,
+//! as a CODEOWNERS entry of either team would name them; lines of people
+//! in no team are left over as unassigned.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::FileReport;
+
+/// The lines one team owns
+pub struct Team {
+    pub name: String,
+    /// Members owning any lines
+    pub owners: usize,
+    pub lines: usize,
+    /// Files the team owns lines of
+    pub files: usize,
+    /// Files the team owns more lines of than any other team
+    pub led: usize,
+}
+
+/// The ownership of every team with lines in `files`, most lines first
+pub fn ownership (files: &[FileReport], teams: &BTreeMap<String, Vec<String>>) -> Vec<Team>
+{
+    let mut result: Vec<Team> = Vec::new();
+    for (name, members) in teams
+    {
+        let mut team = Team { name: name.clone(), owners: 0, lines: 0, files: 0, led: 0 };
+        let mut owners: HashSet<&str> = HashSet::new();
+        for file in files
+        {
+            let mut lines = 0;
+            for owner in file.owners.values().filter(|owner| owner.lines() > 0 && member(members, &owner.email))
+            {
+                owners.insert(&owner.email);
+                lines += owner.lines();
+            }
+            if lines > 0 {
+                team.lines += lines;
+                team.files += 1;
+            }
+        }
+        team.owners = owners.len();
+        result.push(team);
+    }
+
+    for file in files
+    {
+        let lines: Vec<usize> = teams.values()
+            .map(|members| file.owners.values()
+                .filter(|owner| member(members, &owner.email))
+                .map(|owner| owner.lines())
+                .sum())
+            .collect();
+        let most = lines.iter().copied().max().unwrap_or(0);
+        // A tie leads for nobody
+        if most > 0 && lines.iter().filter(|&&l| l == most).count() == 1 {
+            let i = lines.iter().position(|&l| l == most).unwrap();
+            result[i].led += 1;
+        }
+    }
+
+    result.retain(|team| team.lines > 0);
+    result.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.name.cmp(&b.name)));
+    result
+}
+
+/// Lines of `files` owned by people in none of `teams`
+pub fn unassigned (files: &[FileReport], teams: &BTreeMap<String, Vec<String>>) -> usize
+{
+    files.iter()
+        .flat_map(|file| file.owners.values())
+        .filter(|owner| !teams.values().any(|members| member(members, &owner.email)))
+        .map(|owner| owner.lines())
+        .sum()
+}
+
+fn member (members: &[String], email: &str) -> bool
+{
+    members.iter().any(|m| m.eq_ignore_ascii_case(email))
+}
@@ -0,0 +1,167 @@
+//! Telling tests from the code they test
+//!
+//! Whoever only wrote the tests of a file knows it differently from whoever
+//! wrote the implementation. Files are tests by the usual conventions of
+//! their languages or by configured patterns, and the `#[cfg(test)]`
+//! modules of Rust files are tests within an otherwise ordinary file.
+
+use std::path::Path;
+
+use git2::Repository;
+
+use crate::analyze::{blame_regions, blamed_text, Options};
+use crate::blame::Hunk;
+use crate::error::Error;
+use crate::glob::Pattern;
+use crate::ownership::owner_key;
+use crate::FileReport;
+
+/// Directories and file names that hold tests in common project layouts
+const CONVENTIONS: &[&str] = &[
+    "test", "tests", "__tests__", "spec", "testdata",
+    "*_test.go", "*_test.rs", "test_*.py", "*_test.py", "conftest.py",
+    "*.test.*", "*.spec.*", "*_spec.rb",
+    "*Test.java", "*Tests.java", "*Test.kt", "*Tests.cs",
+];
+
+/// Whether `path`, relative to the repository root, holds tests by
+/// convention or by one of `patterns`
+pub fn is_test (path: &str, patterns: &[Pattern]) -> bool
+{
+    CONVENTIONS.iter().any(|convention| Pattern::new(convention).matches(path))
+        || patterns.iter().any(|pattern| pattern.matches(path))
+}
+
+/// The lines of the `#[cfg(test)]` modules of Rust `source`, each as its
+/// first line, counting from 1, and the number of lines
+///
+/// Braces are counted outside of strings, character literals and line
+/// comments, which is enough for code that compiles.
+pub fn cfg_test_ranges (source: &str) -> Vec<(usize, usize)>
+{
+    let lines: Vec<&str> = source.lines().collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].trim_start().starts_with("#[cfg(test)]") {
+            i += 1;
+            continue;
+        }
+        let first = i;
+        // Other attributes may come between the attribute and the module
+        let mut item = i + 1;
+        while item < lines.len() && (lines[item].trim().is_empty() || lines[item].trim_start().starts_with("#[")) {
+            item += 1;
+        }
+        let is_module = lines.get(item)
+            .map(|line| line.trim_start().trim_start_matches("pub ").trim_start_matches("pub(crate) "))
+            .is_some_and(|line| line.starts_with("mod ") && line.contains('{'));
+        if !is_module {
+            i = item;
+            continue;
+        }
+        let mut depth = 0;
+        let mut last = item;
+        for (n, line) in lines.iter().enumerate().skip(item)
+        {
+            depth += brace_balance(line);
+            last = n;
+            if depth <= 0 {
+                break;
+            }
+        }
+        ranges.push((first + 1, last - first + 1));
+        i = last + 1;
+    }
+    ranges
+}
+
+/// Opening minus closing braces of one line of Rust
+fn brace_balance (line: &str) -> i64
+{
+    let chars: Vec<char> = line.chars().collect();
+    let mut balance = 0;
+    let mut in_string = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if in_string => i += 1,
+            '"' => in_string = !in_string,
+            '/' if !in_string && chars.get(i + 1) == Some(&'/') => break,
+            // '{' and '}' as characters
+            '\'' if !in_string && chars.get(i + 2) == Some(&'\'') => i += 2,
+            '{' if !in_string => balance += 1,
+            '}' if !in_string => balance -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    balance
+}
+
+/// `file` split into the lines of production code and those of tests:
+/// all of it is tests when `is_test`, otherwise the `#[cfg(test)]`
+/// modules of a Rust file are; `opts` must be those it was analyzed with
+pub fn split (
+    repo: &Repository,
+    file: &FileReport,
+    is_test: bool,
+    opts: &Options
+) -> Result<(FileReport, FileReport), Error>
+{
+    let mut source = FileReport::new(&file.path);
+    let mut tests = FileReport::new(&file.path);
+    if is_test {
+        tests.merge(file);
+        return Ok((source, tests));
+    }
+    source.merge(file);
+    if !file.path.ends_with(".rs") {
+        return Ok((source, tests));
+    }
+
+    let path = Path::new(&file.path);
+    let text = blamed_text(repo, path, opts)?;
+    let ranges = cfg_test_ranges(&text);
+    if ranges.is_empty() {
+        return Ok((source, tests));
+    }
+
+    for region in blame_regions(repo, path, opts)?
+    {
+        let lines: usize = ranges.iter()
+            .map(|&(first, count)| {
+                let start = region.line.max(first);
+                let end = (region.line + region.lines).min(first + count);
+                end.saturating_sub(start)
+            })
+            .sum();
+        // Lines the analysis left out, of excluded authors for instance,
+        // stay out
+        let key = owner_key(&region.name, &region.email);
+        let owner = match source.owners.get_mut(key.as_ref()) {
+            Some(owner) => owner,
+            None => continue,
+        };
+        let moved = match owner.commits.get_mut(&region.commit) {
+            Some(contribution) => {
+                let moved = lines.min(contribution.lines);
+                contribution.lines -= moved;
+                moved
+            }
+            None => continue,
+        };
+        if moved > 0 {
+            tests.add_hunk(&Hunk {
+                commit: region.commit,
+                name: &owner.name,
+                email: &owner.email,
+                time: region.time,
+                lines: moved,
+                origin: None,
+            });
+        }
+    }
+    source.retain_commits(|_, contribution| contribution.lines > 0);
+    Ok((source, tests))
+}
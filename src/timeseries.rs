@@ -0,0 +1,137 @@
+//! Ownership over time, for dashboards
+//!
+//! Every snapshot saved in the notes is one point in time: the commit time
+//! of the commit it was saved on. Each of them is turned into rows of
+//! `time, commit, author, metric, value`, the author being empty for
+//! metrics of the whole repository, a long format that Grafana and other
+//! time-series tools ingest without reshaping:
+//!
+//! | Metric       | Author | Value                                        |
+//! |--------------|--------|----------------------------------------------|
+//! | `lines`      |        | lines of all files                           |
+//! | `files`      |        | files in the snapshot                        |
+//! | `authors`    |        | authors owning lines                         |
+//! | `bus_factor` |        | bus factor of the repository as a whole      |
+//! | `lines`      | email  | lines the author owns                        |
+//! | `share`      | email  | their share of all lines, in percent         |
+//! | `files`      | email  | files they own lines of                      |
+
+use std::collections::BTreeMap;
+
+use git2::Repository;
+
+use crate::date;
+use crate::error::Error;
+use crate::json::Value;
+use crate::snapshot::{self, Snapshot};
+use crate::{percent, FileReport};
+
+pub struct Point {
+    /// Commit time of the snapshot, seconds since the epoch
+    pub time: i64,
+    pub commit: String,
+    /// Email of the author, empty for the whole repository
+    pub author: String,
+    pub metric: &'static str,
+    pub value: f64,
+}
+
+/// Every snapshot saved in `repo`, oldest commit first
+pub fn snapshots (repo: &Repository) -> Result<Vec<(i64, Snapshot)>, Error>
+{
+    let notes = match repo.notes(Some(snapshot::NOTES_REF)) {
+        Ok(notes) => notes,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut snapshots = Vec::new();
+    for note in notes
+    {
+        let (_, commit) = note?;
+        // Notes outlive the commits they are on when history is rewritten
+        let time = match repo.find_commit(commit) {
+            Ok(commit) => commit.time().seconds(),
+            Err(_) => continue,
+        };
+        snapshots.push((time, snapshot::load(repo, commit)?));
+    }
+    snapshots.sort_by_key(|(time, _)| *time);
+    Ok(snapshots)
+}
+
+/// The points of the snapshot of a commit made at `time`
+pub fn points (time: i64, snapshot: &Snapshot) -> Vec<Point>
+{
+    let commit = snapshot.commit.to_string();
+    let point = |author: &str, metric, value| Point {
+        time,
+        commit: commit.clone(),
+        author: author.to_string(),
+        metric,
+        value,
+    };
+
+    let mut whole = FileReport::new(".");
+    let mut authors: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for file in &snapshot.files
+    {
+        whole.merge(file);
+        for owner in file.owners.values().filter(|owner| owner.lines() > 0)
+        {
+            let author = authors.entry(&owner.email).or_default();
+            author.0 += owner.lines();
+            author.1 += 1;
+        }
+    }
+    let total = whole.lines();
+    let mut points = vec![
+        point("", "lines", total as f64),
+        point("", "files", snapshot.files.len() as f64),
+        point("", "authors", authors.len() as f64),
+        point("", "bus_factor", whole.bus_factor() as f64),
+    ];
+    for (email, (lines, files)) in authors
+    {
+        points.push(point(email, "lines", lines as f64));
+        points.push(point(email, "share", (percent(lines, total) * 10.0).round() / 10.0));
+        points.push(point(email, "files", files as f64));
+    }
+    points
+}
+
+/// `points` as CSV with a header row, times in RFC 3339
+pub fn csv (points: &[Point]) -> String
+{
+    let mut out = String::from("time,commit,author,metric,value\n");
+    for point in points
+    {
+        out.push_str(&format!("{},{},{},{},{}\n",
+                              date::format_rfc3339(point.time), point.commit, field(&point.author),
+                              point.metric, point.value));
+    }
+    out
+}
+
+/// `points` as a JSON array of objects, times in RFC 3339
+pub fn json (points: &[Point]) -> Value
+{
+    Value::Array(points.iter()
+        .map(|point| Value::object(vec![
+            ("time", date::format_rfc3339(point.time).into()),
+            ("commit", point.commit.as_str().into()),
+            ("author", point.author.as_str().into()),
+            ("metric", point.metric.into()),
+            ("value", point.value.into()),
+        ]))
+        .collect())
+}
+
+/// `text` as a CSV field, quoted if it has to be
+fn field (text: &str) -> String
+{
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
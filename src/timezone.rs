@@ -0,0 +1,117 @@
+//! Where in the world the owners of a file are
+//!
+//! The timezone of an owner is the UTC offset their commits were authored
+//! in, the one most of their lines come through when they moved or
+//! switched to daylight saving time; their working hours are when the
+//! middle 80% of those commits were made, in local time. A file whose
+//! owners all share one timezone has nobody to hand it over to when that
+//! part of the world is asleep, which matters for follow-the-sun support.
+
+use std::collections::{BTreeMap, HashMap};
+
+use git2::Oid;
+
+use crate::commits::Commits;
+use crate::error::Error;
+use crate::FileReport;
+
+/// Where one owner works, over every file they own lines of
+pub struct OwnerZone {
+    pub name: String,
+    pub email: String,
+    /// Minutes east of UTC
+    pub offset: i32,
+    /// First and last local hour of the middle 80% of their commits
+    pub hours: (u32, u32),
+}
+
+/// The timezone and working hours of every owner of `files`, keyed like
+/// `FileReport::owners`
+pub fn owner_zones (files: &[FileReport], commits: &mut Commits) -> Result<HashMap<String, OwnerZone>, Error>
+{
+    let mut contributions: HashMap<&str, (&str, &str, HashMap<Oid, usize>)> = HashMap::new();
+    for file in files
+    {
+        for (key, owner) in &file.owners
+        {
+            let (_, _, lines) = contributions.entry(key).or_insert((&owner.name, &owner.email, HashMap::new()));
+            for (oid, contribution) in &owner.commits
+            {
+                *lines.entry(*oid).or_default() += contribution.lines;
+            }
+        }
+    }
+
+    let mut zones = HashMap::new();
+    for (key, (name, email, lines)) in contributions
+    {
+        let mut by_offset: BTreeMap<i32, usize> = BTreeMap::new();
+        let mut hours = Vec::new();
+        for (oid, lines) in lines
+        {
+            // Uncommitted lines have no commit to tell
+            if oid.is_zero() {
+                continue;
+            }
+            let commit = commits.get(oid)?;
+            *by_offset.entry(commit.tz).or_default() += lines;
+            let local = commit.time + i64::from(commit.tz) * 60;
+            hours.push((local.rem_euclid(86_400) / 3600) as u32);
+        }
+        let offset = match by_offset.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0))) {
+            Some((offset, _)) => offset,
+            None => continue,
+        };
+        hours.sort_unstable();
+        let at = |share: usize| hours[(hours.len() - 1) * share / 100];
+        zones.insert(key.to_string(), OwnerZone {
+            name: name.to_string(),
+            email: email.to_string(),
+            offset,
+            hours: (at(10), at(90)),
+        });
+    }
+    Ok(zones)
+}
+
+/// How the lines of one file spread over timezones
+pub struct FileZones {
+    pub path: String,
+    /// Lines by the offset of their owner, most lines first; lines of
+    /// owners without a timezone are left out
+    pub zones: Vec<(i32, usize)>,
+}
+
+impl FileZones {
+    pub fn new (file: &FileReport, owners: &HashMap<String, OwnerZone>) -> FileZones
+    {
+        let mut zones: BTreeMap<i32, usize> = BTreeMap::new();
+        for (key, owner) in &file.owners
+        {
+            if let Some(zone) = owners.get(key) {
+                *zones.entry(zone.offset).or_default() += owner.lines();
+            }
+        }
+        let mut zones: Vec<(i32, usize)> = zones.into_iter().filter(|(_, lines)| *lines > 0).collect();
+        zones.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        FileZones { path: file.path.clone(), zones }
+    }
+
+    pub fn lines (&self) -> usize
+    {
+        self.zones.iter().map(|(_, lines)| lines).sum()
+    }
+
+    /// Whether everyone who knows the file is in one timezone
+    pub fn single_zone (&self) -> bool
+    {
+        self.zones.len() == 1
+    }
+}
+
+/// `UTC+hh:mm` of an offset in minutes
+pub fn format_offset (offset: i32) -> String
+{
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("UTC{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
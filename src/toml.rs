@@ -0,0 +1,493 @@
+//! The subset of TOML used by configuration files
+//!
+//! Tables (`[a]`, `[a.b]`), `key = value` pairs with bare or quoted keys,
+//! basic and literal strings, integers, floats, booleans and arrays, which
+//! may span lines. Dates and inline tables are not supported.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+pub type Table = BTreeMap<String, Value>;
+
+impl Value {
+    pub fn as_str (&self) -> Option<&str>
+    {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Integers are numbers too
+    pub fn as_float (&self) -> Option<f64>
+    {
+        match self {
+            Value::Integer(n) => Some(*n as f64),
+            Value::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer (&self) -> Option<i64>
+    {
+        match self {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool (&self) -> Option<bool>
+    {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array (&self) -> Option<&[Value]>
+    {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_table (&self) -> Option<&Table>
+    {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a document into its root table; errors name the offending line
+pub fn parse (text: &str) -> Result<Table, String>
+{
+    let mut root = Table::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+
+    loop {
+        parser.skip_blank();
+        let c = match parser.peek() {
+            Some(c) => c,
+            None => return Ok(root),
+        };
+
+        if c == '[' {
+            parser.pos += 1;
+            current = parser.key_path(']')?;
+            parser.expect(']')?;
+            table_at(&mut root, &current).map_err(|e| parser.error(&e))?;
+        } else {
+            let path = parser.key_path('=')?;
+            parser.expect('=')?;
+            let value = parser.value()?;
+            let (last, parents) = path.split_last().unwrap();
+            let mut full = current.clone();
+            full.extend_from_slice(parents);
+            let table = table_at(&mut root, &full).map_err(|e| parser.error(&e))?;
+            if table.insert(last.clone(), value).is_some() {
+                return Err(parser.error(&format!("duplicate key '{}'", last)));
+            }
+        }
+        parser.end_of_line()?;
+    }
+}
+
+/// The dotted key at the start of `text`, as in `a."b c".d = 1` or
+/// `[a."b c"]` when `end` is `]`
+pub fn key_path (text: &str, end: char) -> Result<Vec<String>, String>
+{
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+    parser.key_path(end)
+}
+
+/// `key` as it has to be written, bare if it can be
+pub fn quote_key (key: &str) -> String
+{
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => {
+                f.write_str("\"")?;
+                for c in s.chars()
+                {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\r' => f.write_str("\\r")?,
+                        c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{:?}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Table(table) => {
+                let pairs: Vec<String> = table.iter()
+                    .map(|(key, value)| format!("{} = {}", quote_key(key), value))
+                    .collect();
+                write!(f, "{{ {} }}", pairs.join(", "))
+            }
+        }
+    }
+}
+
+/// The table at `path`, created on the way where missing
+fn table_at<'t> (root: &'t mut Table, path: &[String]) -> Result<&'t mut Table, String>
+{
+    let mut table = root;
+    for key in path
+    {
+        table = match table.entry(key.clone()).or_insert_with(|| Value::Table(Table::new())) {
+            Value::Table(t) => t,
+            _ => return Err(format!("'{}' is not a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error (&self, msg: &str) -> String
+    {
+        format!("line {}: {}", self.line, msg)
+    }
+
+    fn peek (&self) -> Option<char>
+    {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn bump (&mut self) -> Option<char>
+    {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// Spaces and tabs only
+    fn skip_spaces (&mut self)
+    {
+        while let Some(' ') | Some('\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Whitespace, newlines and comments
+    fn skip_blank (&mut self)
+    {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') | Some('\r') | Some('\n') => {
+                    self.bump();
+                }
+                Some('#') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn expect (&mut self, c: char) -> Result<(), String>
+    {
+        self.skip_spaces();
+        if self.peek() == Some(c) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn end_of_line (&mut self) -> Result<(), String>
+    {
+        self.skip_spaces();
+        match self.peek() {
+            None | Some('\n') | Some('\r') | Some('#') => Ok(()),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    /// `a.b."c d"` up to, not including, `end`
+    fn key_path (&mut self, end: char) -> Result<Vec<String>, String>
+    {
+        let mut path = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while let Some(c) = self.peek() {
+                        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            if key.is_empty() {
+                return Err(self.error("expected a key"));
+            }
+            path.push(key);
+            self.skip_spaces();
+            match self.peek() {
+                Some('.') => self.pos += 1,
+                Some(c) if c == end => return Ok(path),
+                _ => return Err(self.error(&format!("expected '{}'", end))),
+            }
+        }
+    }
+
+    fn value (&mut self) -> Result<Value, String>
+    {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some(_) => self.scalar(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn array (&mut self) -> Result<Value, String>
+    {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    /// Booleans and numbers
+    fn scalar (&mut self) -> Result<Value, String>
+    {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || "+-._".contains(c) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        let number = word.replace('_', "");
+        match word.as_str() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ => if let Ok(n) = number.parse::<i64>() {
+                Ok(Value::Integer(n))
+            } else if let Ok(n) = number.parse::<f64>() {
+                Ok(Value::Float(n))
+            } else {
+                Err(self.error(&format!("invalid value '{}'", word)))
+            },
+        }
+    }
+
+    fn basic_string (&mut self) -> Result<String, String>
+    {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            // Looked at before it is taken, as taking a newline counts it
+            let c = match self.peek() {
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => c,
+            };
+            self.bump();
+            match c {
+                '"' => return Ok(s),
+                '\\' => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok()
+                            .and_then(std::char::from_u32)
+                            .ok_or_else(|| self.error("invalid \\u escape"))?;
+                        s.push(c);
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn literal_string (&mut self) -> Result<String, String>
+    {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            let c = match self.peek() {
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => c,
+            };
+            self.bump();
+            match c {
+                '\'' => return Ok(s),
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table (pairs: Vec<(&str, Value)>) -> Value
+    {
+        Value::Table(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    #[test]
+    fn tables_and_dotted_keys ()
+    {
+        let doc = parse("top = 1\n\
+                         [a]\n\
+                         x = true\n\
+                         b.c = 'dotted'\n\
+                         [a.\"d e\"]\n\
+                         y = -2.5\n").unwrap();
+        assert_eq!(Value::Table(doc), table(vec![
+            ("top", Value::Integer(1)),
+            ("a", table(vec![
+                ("x", Value::Boolean(true)),
+                ("b", table(vec![("c", Value::String("dotted".to_string()))])),
+                ("d e", table(vec![("y", Value::Float(-2.5))])),
+            ])),
+        ]));
+    }
+
+    #[test]
+    fn strings_and_escapes ()
+    {
+        let doc = parse(r#"basic = "tab\t \"quoted\" back\\slash \u00e9\n"
+literal = 'C:\dir\"as is"'
+"#).unwrap();
+        assert_eq!(doc["basic"].as_str(), Some("tab\t \"quoted\" back\\slash \u{e9}\n"));
+        assert_eq!(doc["literal"].as_str(), Some("C:\\dir\\\"as is\""));
+    }
+
+    #[test]
+    fn numbers ()
+    {
+        let doc = parse("a = 1_000\nb = -3\nc = +0.5\nd = 1e3\n").unwrap();
+        assert_eq!(doc["a"], Value::Integer(1000));
+        assert_eq!(doc["b"], Value::Integer(-3));
+        assert_eq!(doc["c"], Value::Float(0.5));
+        assert_eq!(doc["d"], Value::Float(1000.0));
+        assert_eq!(doc["a"].as_float(), Some(1000.0));
+    }
+
+    #[test]
+    fn arrays_over_lines ()
+    {
+        let doc = parse("list = [\n\
+                         \"one\", # the first\n\
+                         \n\
+                         'two',\n\
+                         ]\n\
+                         nested = [[1, 2], []]\n\
+                         empty = []\n").unwrap();
+        assert_eq!(doc["list"], Value::Array(vec![Value::String("one".to_string()), Value::String("two".to_string())]));
+        assert_eq!(doc["nested"], Value::Array(vec![
+            Value::Array(vec![Value::Integer(1), Value::Integer(2)]),
+            Value::Array(Vec::new()),
+        ]));
+        assert_eq!(doc["empty"].as_array(), Some(&[][..]));
+    }
+
+    #[test]
+    fn comments_and_blank_lines ()
+    {
+        let doc = parse("# heading\r\n\r\n  key = 'value'  # trailing\r\n").unwrap();
+        assert_eq!(doc["key"].as_str(), Some("value"));
+    }
+
+    #[test]
+    fn errors_name_the_line ()
+    {
+        assert_eq!(parse("a = 1\na = 2\n"), Err("line 2: duplicate key 'a'".to_string()));
+        assert_eq!(parse("a = 1\nb = \"open\nc = 3\n"), Err("line 2: unterminated string".to_string()));
+        assert_eq!(parse("x = 'open\n"), Err("line 1: unterminated string".to_string()));
+        assert_eq!(parse("\n[a\n"), Err("line 2: expected ']'".to_string()));
+        assert_eq!(parse("a = yes\n"), Err("line 1: invalid value 'yes'".to_string()));
+        assert_eq!(parse("a = 1 2\n"), Err("line 1: unexpected '2'".to_string()));
+        assert_eq!(parse("a = \"\\q\"\n"), Err("line 1: invalid escape".to_string()));
+        assert_eq!(parse("a = [1\n2]\n"), Err("line 2: expected ',' or ']' in array".to_string()));
+        assert_eq!(parse("a = 1\n[a.b]\n"), Err("line 2: 'a' is not a table".to_string()));
+        assert_eq!(parse("= 1\n"), Err("line 1: expected a key".to_string()));
+    }
+
+    #[test]
+    fn written_as_parsed ()
+    {
+        let value = Value::String("quote \" tab \t bell \u{7}".to_string());
+        assert_eq!(value.to_string(), r#""quote \" tab \t bell \u0007""#);
+        assert_eq!(parse(&format!("v = {}", value)).unwrap()["v"], value);
+        assert_eq!(quote_key("bare-key_1"), "bare-key_1");
+        assert_eq!(quote_key("with space"), "\"with space\"");
+        assert_eq!(key_path("a.\"b.c\".d =", '=').unwrap(), vec!["a", "b.c", "d"]);
+    }
+}
@@ -0,0 +1,96 @@
+//! What each owner of a file worked on, in a few words
+//!
+//! The summaries of the commits an owner's lines survive from are split
+//! into words, and the words found in most of those commits are their
+//! topics. The type and scope of a conventional commit, `perf` and
+//! `parser` in `perf(parser): ...`, count as words; verbs every commit
+//! has, such as `fix` and `add`, short words, numbers and issue keys do
+//! not.
+
+use std::collections::HashMap;
+
+use crate::commits::Commits;
+use crate::error::Error;
+use crate::FileReport;
+
+/// Words that say nothing about what was worked on
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "add", "added", "adding", "adds", "after", "all", "allow", "also", "an", "and", "any", "are",
+    "as", "at", "be", "been", "before", "better", "bump", "but", "by", "can", "change", "changed", "changes",
+    "chore", "clean", "cleanup", "do", "does", "don't", "each", "else", "feat", "feature", "fix", "fixed",
+    "fixes", "fixing", "for", "from", "get", "has", "have", "improve", "in", "initial", "instead", "into", "is",
+    "it", "its", "make", "makes", "merge", "minor", "more", "move", "moved", "new", "no", "not", "now", "of",
+    "old", "on", "one", "only", "or", "other", "out", "refactor", "remove", "removed", "rename", "renamed",
+    "revert", "same", "see", "set", "should", "so", "some", "style", "support", "than", "that", "the", "them",
+    "then", "there", "this", "to", "too", "tweak", "up", "update", "updated", "updates", "use", "used", "uses",
+    "using", "via", "was", "when", "where", "which", "while", "will", "with", "without", "wip", "work",
+];
+
+/// The topics of one owner of a file
+pub struct Topics {
+    pub name: String,
+    pub email: String,
+    /// Most frequent first
+    pub words: Vec<String>,
+}
+
+/// The words of `summary` that may be a topic, lower case and each once
+pub fn words (summary: &str) -> Vec<String>
+{
+    // `type(scope): subject`, where `feat` and `fix` are stop words but
+    // `perf` and `docs` are not
+    let summary = match summary.split_once(':') {
+        Some((prefix, rest)) if !prefix.contains(' ') => {
+            format!("{} {}", prefix.replace(['(', ')', '!'], " "), rest)
+        }
+        _ => summary.to_string(),
+    };
+    let mut words: Vec<String> = Vec::new();
+    for word in summary.split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_' || c == '\''))
+    {
+        let word = word.trim_matches(['-', '_', '\'']).to_lowercase();
+        let noise = word.chars().count() < 3
+            || word.chars().any(|c| c.is_ascii_digit())
+            || STOP_WORDS.contains(&word.as_str());
+        if !noise && !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Up to `max` topics of every owner of `file`, owners ranked as by
+/// `FileReport::ranked_owners`; owners without any are left out
+pub fn topics (file: &FileReport, commits: &mut Commits, max: usize) -> Result<Vec<Topics>, Error>
+{
+    let mut topics = Vec::new();
+    for owner in file.ranked_owners()
+    {
+        // Commits a word is in, then the lines they own
+        let mut found: HashMap<String, (usize, usize)> = HashMap::new();
+        for (oid, contribution) in &owner.commits
+        {
+            // Uncommitted lines have no message
+            if oid.is_zero() {
+                continue;
+            }
+            for word in words(commits.get(*oid)?.summary())
+            {
+                let counts = found.entry(word).or_default();
+                counts.0 += 1;
+                counts.1 += contribution.lines;
+            }
+        }
+        let mut found: Vec<(String, (usize, usize))> = found.into_iter().collect();
+        found.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        if found.is_empty() {
+            continue;
+        }
+        topics.push(Topics {
+            name: owner.name.clone(),
+            email: owner.email.clone(),
+            words: found.into_iter().take(max).map(|(word, _)| word).collect(),
+        });
+    }
+    Ok(topics)
+}
@@ -0,0 +1,102 @@
+//! Where the time of a run goes
+//!
+//! Spans time one step, such as finding the repository or blaming a file,
+//! and are written to stderr when they end, as text or one JSON object per
+//! line. `Level::Debug` shows the steps of every file, `Level::Trace` also
+//! the parsing and cache lookups within them. Nothing is written unless
+//! `init` asked for it.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::json::Value;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Off,
+    Debug,
+    Trace,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}'", s)),
+        }
+    }
+}
+
+static LEVEL: AtomicUsize = AtomicUsize::new(Level::Off as usize);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Write the spans up to `level` from now on, in `format`
+pub fn init (level: Level, format: LogFormat)
+{
+    LEVEL.store(level as usize, Ordering::Relaxed);
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+pub fn enabled (level: Level) -> bool
+{
+    level != Level::Off && level as usize <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// One step being timed, written when dropped
+pub struct Span {
+    name: &'static str,
+    fields: Vec<(&'static str, String)>,
+    start: Instant,
+    enabled: bool,
+}
+
+/// Start timing `name` at `level`
+pub fn span (level: Level, name: &'static str) -> Span
+{
+    Span {
+        name,
+        fields: Vec::new(),
+        start: Instant::now(),
+        enabled: enabled(level),
+    }
+}
+
+impl Span {
+    /// Note `value` as `key`, to be written with the span
+    pub fn record<V: ToString> (&mut self, key: &'static str, value: V) -> &mut Span
+    {
+        if self.enabled {
+            self.fields.push((key, value.to_string()));
+        }
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop (&mut self)
+    {
+        if !self.enabled {
+            return;
+        }
+        let elapsed = self.start.elapsed().as_secs_f64() * 1000.0;
+        if JSON.load(Ordering::Relaxed) {
+            let mut members = vec![("span".to_string(), Value::from(self.name))];
+            members.extend(self.fields.iter().map(|(key, value)| (key.to_string(), value.as_str().into())));
+            members.push(("elapsed_ms".to_string(), elapsed.into()));
+            eprintln!("{}", Value::Object(members));
+        } else {
+            let fields: String = self.fields.iter().map(|(key, value)| format!(" {}={}", key, value)).collect();
+            eprintln!("whoknows: {}{} elapsed={:.1}ms", self.name, fields, elapsed);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Version control systems other than git
+//!
+//! Everything the analysis of ownership needs from a version control
+//! system is which files it tracks and who last changed each of their
+//! lines. `Vcs` is that much, so that a Mercurial repository can be
+//! analyzed as well as a git one; history, caching and the rest of what
+//! `Analyzer` does stay with git.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use git2::{Oid, Repository};
+
+use crate::blame::{self, Backend, Hunk};
+use crate::error::Error;
+use crate::platform;
+use crate::json::{self, Value};
+use crate::FileReport;
+
+/// A repository of some version control system
+pub trait Vcs {
+    /// Name of the system, for messages
+    fn name (&self) -> &'static str;
+
+    /// Root of the work tree
+    fn root (&self) -> &Path;
+
+    /// The tracked files named by `paths`, directories standing for every
+    /// file below them, relative to the root
+    fn files (&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error>;
+
+    /// Blame `path`, relative to the root, handing every hunk to `sink` in
+    /// the order of their lines
+    fn blame (&self, path: &Path, opts: &blame::Options, sink: &mut dyn FnMut(&Hunk)) -> Result<(), Error>;
+}
+
+/// A git repository, blamed by `backend`
+pub struct Git<'r> {
+    pub repo: &'r Repository,
+    pub backend: Backend,
+}
+
+impl Vcs for Git<'_> {
+    fn name (&self) -> &'static str
+    {
+        "git"
+    }
+
+    fn root (&self) -> &Path
+    {
+        self.repo.workdir().unwrap_or_else(|| self.repo.path())
+    }
+
+    fn files (&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error>
+    {
+        crate::tracked_files(self.repo, paths)
+    }
+
+    fn blame (&self, path: &Path, opts: &blame::Options, sink: &mut dyn FnMut(&Hunk)) -> Result<(), Error>
+    {
+        blame::blame(self.repo, path, self.backend, opts, sink)
+    }
+}
+
+/// A Mercurial repository, through the `hg` command
+pub struct Mercurial {
+    root: PathBuf,
+}
+
+impl Mercurial {
+    /// The Mercurial repository `start` is in, found by its `.hg`
+    /// directory
+    pub fn discover (start: &Path) -> Option<Mercurial>
+    {
+        let start = platform::canonicalize(start).ok()?;
+        let root = start.ancestors().find(|dir| dir.join(".hg").is_dir())?;
+        Some(Mercurial { root: root.to_path_buf() })
+    }
+
+    /// `hg` run in the root, its output left alone by user settings
+    fn command (&self) -> Command
+    {
+        let mut cmd = Command::new("hg");
+        cmd.current_dir(&self.root).env("HGPLAIN", "1");
+        cmd
+    }
+
+    fn output (&self, mut cmd: Command) -> Result<Vec<u8>, Error>
+    {
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+            .map_err(|e| Error::Blame(format!("could not run hg: {}", e)))?;
+        let mut out = Vec::new();
+        child.stdout.take().unwrap().read_to_end(&mut out)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::Blame(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        Ok(out)
+    }
+
+    /// `path` relative to the root
+    fn relative (&self, path: &Path) -> Result<PathBuf, Error>
+    {
+        let absolute = platform::canonicalize(path)
+            .map_err(|_| Error::Path(format!("{} does not exist", path.display())))?;
+        absolute.strip_prefix(&self.root)
+            .map(Path::to_path_buf)
+            .map_err(|_| Error::Path(format!("{} is outside the repository at {}", path.display(),
+                                             self.root.display())))
+    }
+}
+
+impl Vcs for Mercurial {
+    fn name (&self) -> &'static str
+    {
+        "Mercurial"
+    }
+
+    fn root (&self) -> &Path
+    {
+        &self.root
+    }
+
+    fn files (&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error>
+    {
+        let mut cmd = self.command();
+        cmd.args(["files", "-0", "--"]);
+        for path in paths
+        {
+            let relative = self.relative(path)?;
+            cmd.arg(format!("path:{}", if relative.as_os_str().is_empty() { Path::new(".") } else { &relative }.display()));
+        }
+        let out = self.output(cmd)?;
+        let files: Vec<PathBuf> = out.split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| PathBuf::from(String::from_utf8_lossy(name).as_ref()))
+            .collect();
+        if files.is_empty() {
+            if let Some(path) = paths.first() {
+                return Err(Error::Untracked(path.display().to_string()));
+            }
+        }
+        Ok(files)
+    }
+
+    fn blame (&self, path: &Path, opts: &blame::Options, sink: &mut dyn FnMut(&Hunk)) -> Result<(), Error>
+    {
+        let mut cmd = self.command();
+        cmd.args(["annotate", "--user", "--changeset", "--date", "-Tjson"]);
+        if let Some(rev) = &opts.rev {
+            cmd.args(["-r", rev]);
+        }
+        if opts.no_follow {
+            cmd.arg("--no-follow");
+        }
+        cmd.arg("--").arg(format!("path:{}", path.display()));
+        let out = self.output(cmd)?;
+        let malformed = |what: &str| Error::Blame(format!("unexpected output of hg annotate {}: {}", path.display(), what));
+        let value = json::parse(&String::from_utf8_lossy(&out)).map_err(|e| malformed(&e))?;
+        let lines = value.as_array().and_then(|files| files.first())
+            .and_then(|file| file.get("lines"))
+            .and_then(Value::as_array)
+            .ok_or_else(|| malformed("no lines"))?;
+
+        // Consecutive lines of the same changeset make one hunk
+        let mut run: Option<(&str, &str, i64, usize)> = None;
+        for line in lines
+        {
+            let node = line.get("node").and_then(Value::as_str).ok_or_else(|| malformed("a line without node"))?;
+            let user = line.get("user").and_then(Value::as_str).unwrap_or("");
+            let time = line.get("date").and_then(Value::as_array)
+                .and_then(|date| date.first())
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0) as i64;
+            match &mut run {
+                Some((last, _, _, lines)) if *last == node => *lines += 1,
+                _ => {
+                    if let Some(run) = run.take() {
+                        hunk(run, sink)?;
+                    }
+                    run = Some((node, user, time, 1));
+                }
+            }
+        }
+        if let Some(run) = run {
+            hunk(run, sink)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hand the run of `lines` lines of changeset `node` by `user` to `sink`
+fn hunk ((node, user, time, lines): (&str, &str, i64, usize), sink: &mut dyn FnMut(&Hunk)) -> Result<(), Error>
+{
+    // Changeset ids are SHA-1 hashes too
+    let commit = Oid::from_str(node).map_err(|_| Error::Blame(format!("not a changeset id: {}", node)))?;
+    let (name, email) = split_user(user);
+    sink(&Hunk { commit, name, email, time, lines, origin: None });
+    Ok(())
+}
+
+/// The name and email of a Mercurial user, given as `Name <email>`, just
+/// an email or just a name
+fn split_user (user: &str) -> (&str, &str)
+{
+    match (user.rfind('<'), user.rfind('>')) {
+        (Some(start), Some(end)) if start < end => (user[..start].trim(), user[start + 1..end].trim()),
+        _ if user.contains('@') => (user.trim(), user.trim()),
+        _ => (user.trim(), ""),
+    }
+}
+
+/// Ownership of every file named by `paths` in `vcs`, in the order `files`
+/// lists them
+pub fn analyze (vcs: &dyn Vcs, paths: &[PathBuf], opts: &blame::Options) -> Result<Vec<FileReport>, Error>
+{
+    let mut reports = Vec::new();
+    for path in vcs.files(paths)?
+    {
+        let mut report = FileReport::new(&path.to_string_lossy());
+        vcs.blame(&path, opts, &mut |hunk| report.add_blamed(hunk))?;
+        reports.push(report);
+    }
+    Ok(reports)
+}
@@ -0,0 +1,81 @@
+//! Polling for changes to the work tree and HEAD
+//!
+//! Checking modification times twice a second is cheap for the handful of
+//! files one keeps an eye on and needs no platform specific notification
+//! API.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use git2::{Oid, Repository};
+
+use whoknows::Error;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What a change is noticed by
+#[derive(PartialEq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+fn stamp (path: &Path) -> Stamp
+{
+    match fs::metadata(path) {
+        Ok(meta) => Stamp { modified: meta.modified().ok(), len: meta.len() },
+        Err(_) => Stamp { modified: None, len: 0 },
+    }
+}
+
+fn head (repo: &Repository) -> Option<Oid>
+{
+    repo.head().ok()?.target()
+}
+
+/// Call `changed` with the repository relative paths among those `files`
+/// lists that changed until interrupted; a new HEAD counts as a change to
+/// all of them
+pub fn watch<L, F> (repo: &Repository, files: L, mut changed: F) -> Result<(), Error>
+    where L: Fn() -> Result<Vec<PathBuf>, Error>,
+          F: FnMut(&[PathBuf]) -> Result<(), Error>
+{
+    let workdir = repo.workdir()
+        .ok_or_else(|| Error::Path("repository has no work tree".to_string()))?;
+    let mut paths = files()?;
+    let mut stamps: HashMap<PathBuf, Stamp> = paths.iter()
+        .map(|p| (p.clone(), stamp(&workdir.join(p))))
+        .collect();
+    let mut last_head = head(repo);
+
+    while !crate::interrupt::interrupted() {
+        thread::sleep(POLL_INTERVAL);
+
+        let current_head = head(repo);
+        let mut dirty = Vec::new();
+        if current_head != last_head {
+            // Commits may have added files below the requested directories
+            paths = files()?;
+            dirty = paths.clone();
+            last_head = current_head;
+        }
+        for path in &paths
+        {
+            let now = stamp(&workdir.join(path));
+            if stamps.get(path) != Some(&now) {
+                if !dirty.contains(path) {
+                    dirty.push(path.clone());
+                }
+                stamps.insert(path.clone(), now);
+            }
+        }
+
+        if !dirty.is_empty() {
+            changed(&dirty)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Ownership across several repositories
+//!
+//! A workspace is a set of repositories analyzed as one: each with its own
+//! configuration and `.mailmap`, then combined with the paths of its files
+//! prefixed by its name. Identities are merged across all of them by
+//! email, and by the identities of the manifest, so that someone known
+//! under another address in every repository still counts as one owner of
+//! the whole.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::platform;
+use crate::{discover, owner_key, Analyzer, FileReport, Options, RepoReport};
+
+/// One repository of a workspace
+pub struct Member {
+    /// Prefix of its paths
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// The repositories of the `[workspace]` table of the manifest at
+/// `manifest`, their paths relative to it, in the order of their names
+pub fn members (manifest: &Path, config: &Config) -> Vec<Member>
+{
+    let base = manifest.parent().unwrap_or_else(|| Path::new("."));
+    config.workspace.iter()
+        .map(|(name, path)| Member { name: name.clone(), path: base.join(path) })
+        .collect()
+}
+
+/// Members for repositories given by path, named after their directories,
+/// a number added to tell apart directories of the same name
+pub fn members_of (paths: &[PathBuf]) -> Vec<Member>
+{
+    let mut members: Vec<Member> = Vec::new();
+    for path in paths
+    {
+        let base = platform::canonicalize(path).ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| path.display().to_string());
+        let mut name = base.clone();
+        let mut n = 1;
+        while members.iter().any(|member| member.name == name) {
+            n += 1;
+            name = format!("{}-{}", base, n);
+        }
+        members.push(Member { name, path: path.clone() });
+    }
+    members
+}
+
+/// The ownership of one member
+pub struct Part {
+    pub name: String,
+    pub report: RepoReport,
+}
+
+/// Analyze every tracked file of every member with `opts`, the identities
+/// of each repository added to those of `opts`
+pub fn analyze (members: &[Member], opts: &Options) -> Result<Vec<Part>, Error>
+{
+    let mut parts = Vec::new();
+    for member in members
+    {
+        let in_member = |e: Error| Error::File(member.name.clone(), Box::new(e));
+        let repo = discover(&member.path).map_err(|e| match e.code() {
+            git2::ErrorCode::NotFound => in_member(Error::NotRepository(format!(
+                "{} is not in a git work tree", member.path.display()))),
+            _ => in_member(e.into()),
+        })?;
+        let config = Config::load(&repo).map_err(in_member)?;
+        let mut opts = opts.clone();
+        opts.identities.extend(&config.identities);
+        let mut report = Analyzer::new(&repo).options(opts).repo().map_err(in_member)?;
+        report.files.retain(|file| !config.excluded(Path::new(&file.path)));
+        for file in &mut report.files
+        {
+            file.path = format!("{}/{}", member.name, file.path);
+        }
+        parts.push(Part { name: member.name.clone(), report });
+    }
+    Ok(parts)
+}
+
+/// Every file of every part merged into one
+pub fn combined (parts: &[Part]) -> FileReport
+{
+    let mut combined = FileReport::new(".");
+    for file in parts.iter().flat_map(|part| &part.report.files)
+    {
+        combined.merge(file);
+    }
+    combined
+}
+
+/// Someone owning lines of the workspace
+pub struct Owner {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+    /// Members they own lines of, by name
+    pub repositories: BTreeSet<String>,
+}
+
+/// The owners of all `parts`, most lines first
+pub fn owners (parts: &[Part]) -> Vec<Owner>
+{
+    let mut owners: BTreeMap<String, Owner> = BTreeMap::new();
+    for part in parts
+    {
+        for owner in part.report.files.iter().flat_map(|file| file.owners.values())
+        {
+            if owner.lines() == 0 {
+                continue;
+            }
+            let entry = owners.entry(owner_key(&owner.name, &owner.email).into_owned()).or_insert_with(|| Owner {
+                name: owner.name.clone(),
+                email: owner.email.clone(),
+                lines: 0,
+                repositories: BTreeSet::new(),
+            });
+            entry.lines += owner.lines();
+            entry.repositories.insert(part.name.clone());
+        }
+    }
+    let mut owners: Vec<Owner> = owners.into_values().collect();
+    owners.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+    owners
+}
@@ -0,0 +1,149 @@
+//! Python bindings of the `whoknows` library
+//!
+//! Reports are handed to Python as plain objects copied out of the Rust
+//! ones, so that nothing Python holds on to borrows from the analysis. The
+//! analysis itself runs without the GIL, leaving other Python threads be.
+
+use std::path::{Path, PathBuf};
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(whoknows, WhoknowsError, PyException);
+
+/// Someone owning lines of a file
+#[pyclass(name = "Owner", get_all, module = "whoknows")]
+#[derive(Clone)]
+struct Owner {
+    name: String,
+    email: String,
+    lines: usize,
+    /// Commits the lines survive from
+    commits: usize,
+    /// Weighted lines used for ranking, the lines without decay
+    score: f64,
+}
+
+#[pymethods]
+impl Owner {
+    fn __repr__ (&self) -> String
+    {
+        format!("Owner({} <{}>, {} lines)", self.name, self.email, self.lines)
+    }
+}
+
+/// Who owns how many lines of one file
+#[pyclass(name = "FileReport", get_all, module = "whoknows")]
+#[derive(Clone)]
+struct FileReport {
+    path: String,
+    lines: usize,
+    bus_factor: usize,
+    /// Largest first
+    owners: Vec<Owner>,
+    /// Other paths the file had in commits its lines survive from
+    renamed_from: Vec<String>,
+}
+
+#[pymethods]
+impl FileReport {
+    fn __repr__ (&self) -> String
+    {
+        format!("FileReport({}, {} lines, bus factor {})", self.path, self.lines, self.bus_factor)
+    }
+}
+
+impl From<&whoknows::FileReport> for FileReport {
+    fn from (file: &whoknows::FileReport) -> FileReport
+    {
+        FileReport {
+            path: file.path.clone(),
+            lines: file.lines(),
+            bus_factor: file.bus_factor(),
+            owners: file.ranked_owners().into_iter()
+                .map(|owner| Owner {
+                    name: owner.name.clone(),
+                    email: owner.email.clone(),
+                    lines: owner.lines(),
+                    commits: owner.commits.len(),
+                    score: owner.score,
+                })
+                .collect(),
+            renamed_from: file.renamed_from.clone(),
+        }
+    }
+}
+
+fn error (e: whoknows::Error) -> PyErr
+{
+    WhoknowsError::new_err(e.to_string())
+}
+
+fn options (rev: Option<String>, decay: Option<f64>, mailmap: bool, cache: bool) -> whoknows::Options
+{
+    let mut opts = whoknows::Options::default();
+    opts.blame.rev = rev;
+    opts.decay = decay;
+    opts.mailmap = mailmap;
+    opts.cache = cache;
+    opts
+}
+
+/// Ownership of the file at `path`, in whichever repository it is
+#[pyfunction]
+#[pyo3(signature = (path, rev = None, decay = None, mailmap = true, cache = true))]
+fn analyze_file (
+    py: Python<'_>,
+    path: PathBuf,
+    rev: Option<String>,
+    decay: Option<f64>,
+    mailmap: bool,
+    cache: bool
+) -> PyResult<FileReport>
+{
+    let opts = options(rev, decay, mailmap, cache);
+    py.allow_threads(|| {
+        let start = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let repo = whoknows::discover(start).map_err(whoknows::Error::from)?;
+        let relative = whoknows::repo_relative(&repo, &path)?;
+        whoknows::ensure_tracked(&repo, &relative)?;
+        whoknows::analyze_file(&repo, &relative, &opts).map(|file| FileReport::from(&file))
+    })
+    .map_err(error)
+}
+
+/// Ownership of every tracked file of the repository `path` is in
+#[pyfunction]
+#[pyo3(signature = (path = PathBuf::from("."), rev = None, decay = None, mailmap = true, cache = true, jobs = None))]
+fn analyze_repo (
+    py: Python<'_>,
+    path: PathBuf,
+    rev: Option<String>,
+    decay: Option<f64>,
+    mailmap: bool,
+    cache: bool,
+    jobs: Option<usize>
+) -> PyResult<Vec<FileReport>>
+{
+    let mut opts = options(rev, decay, mailmap, cache);
+    opts.jobs = jobs;
+    py.allow_threads(|| {
+        let repo = whoknows::discover(&path).map_err(whoknows::Error::from)?;
+        let files = whoknows::analyze_repo(&repo, &opts)?;
+        Ok(files.iter().map(FileReport::from).collect())
+    })
+    .map_err(error)
+}
+
+#[pymodule]
+#[pyo3(name = "whoknows")]
+fn module (py: Python<'_>, m: &PyModule) -> PyResult<()>
+{
+    m.add_class::<Owner>()?;
+    m.add_class::<FileReport>()?;
+    m.add_function(wrap_pyfunction!(analyze_file, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze_repo, m)?)?;
+    m.add("WhoknowsError", py.get_type::<WhoknowsError>())?;
+    Ok(())
+}